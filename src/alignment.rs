@@ -0,0 +1,429 @@
+/// COURSE ALIGNMENT - Snap a recording onto a published course polyline
+/// Every recorded point is matched to a position (distance along the course) by projecting
+/// it onto the nearby course segments. The search only looks a little behind and a little
+/// ahead of the last matched position, so out-and-back sections and loops that pass the same
+/// spot twice are matched to the right leg; points too far from the course are a detour and
+/// stay unmatched until the recording rejoins. With the correspondence, recorded elevation
+/// can be compared with the course at identical course positions, or replaced by the course's
+/// smoothed elevation.
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use csv::Writer;
+use geo::point;
+use gpx::{write, Gpx, GpxVersion, Track, TrackSegment, Waypoint};
+use serde::Serialize;
+use walkdir::WalkDir;
+use crate::elevation_smoother::{ElevationSmoother, SmoothedProfile};
+use crate::track_loader::{calculate_gain_loss, is_supported_track_file, load_track, LoadedTrack};
+
+// A recorded point further than this from the course is off course
+pub const MAX_COURSE_OFFSET_M: f64 = 50.0;
+// Course distance searched behind the last match (GPS jitter, standing still)
+const BACKTRACK_M: f64 = 30.0;
+// Course distance searched beyond where the recorded distance says the athlete should be
+const LOOKAHEAD_M: f64 = 200.0;
+// Recorded distance can run long or short against the course; scales the forward search
+const DISTANCE_SLACK: f64 = 1.5;
+// Metres of offset traded per metre of disagreement with the expected course position
+const PROGRESS_WEIGHT: f64 = 0.05;
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+/// Project `point` onto the segment `a`-`b` (all `(lat, lon)`), returning the fraction of the
+/// way from `a` and the distance from the segment in metres
+pub fn project_onto_segment(point: (f64, f64), a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+    let x_scale = point.0.to_radians().cos() * METERS_PER_DEGREE;
+    let local = |(lat, lon): (f64, f64)| ((lon - point.1) * x_scale, (lat - point.0) * METERS_PER_DEGREE);
+    let ((ax, ay), (bx, by)) = (local(a), local(b));
+    let (dx, dy) = (bx - ax, by - ay);
+    let length_sq = dx * dx + dy * dy;
+    let t = if length_sq > 0.0 { (-(ax * dx + ay * dy) / length_sq).clamp(0.0, 1.0) } else { 0.0 };
+    (t, (ax + t * dx).hypot(ay + t * dy))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CourseMatch {
+    pub distance_m: f64,  // Distance along the course
+    pub offset_m: f64,    // Distance from the course
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Detour {
+    pub start_index: usize,  // First unmatched recorded point
+    pub end_index: usize,    // First point back on course
+    pub length_m: f64,       // Recorded distance off course
+}
+
+#[derive(Debug, Clone)]
+pub struct CourseAlignment {
+    pub matches: Vec<Option<CourseMatch>>,  // One per recorded point
+    pub detours: Vec<Detour>,
+    pub matched_fraction: f64,
+    pub mean_offset_m: f64,
+}
+
+impl CourseAlignment {
+    /// Course distances covered from the first to the last matched point
+    pub fn course_span_m(&self) -> Option<(f64, f64)> {
+        let first = self.matches.iter().flatten().next()?;
+        let last = self.matches.iter().flatten().last()?;
+        Some((first.distance_m, last.distance_m))
+    }
+}
+
+/// Lowest-cost course position for `point` among segments overlapping `[from_m, to_m]`,
+/// only considering positions within MAX_COURSE_OFFSET_M
+fn snap(course: &LoadedTrack, point: (f64, f64), from_m: f64, to_m: f64, expected_m: f64) -> Option<CourseMatch> {
+    let distances = &course.cumulative_distances;
+    let lo = distances.partition_point(|&d| d < from_m).saturating_sub(1);
+    let hi = distances.partition_point(|&d| d <= to_m).min(distances.len() - 1);
+
+    let mut best: Option<(f64, CourseMatch)> = None;
+    for i in lo..hi {
+        let (a, b) = (&course.points[i], &course.points[i + 1]);
+        let (t, offset_m) = project_onto_segment(point, (a.latitude, a.longitude), (b.latitude, b.longitude));
+        if offset_m > MAX_COURSE_OFFSET_M {
+            continue;
+        }
+        let distance_m = distances[i] + t * (distances[i + 1] - distances[i]);
+        let cost = offset_m + PROGRESS_WEIGHT * (distance_m - expected_m).abs();
+        if best.is_none_or(|(best_cost, _)| cost < best_cost) {
+            best = Some((cost, CourseMatch { distance_m, offset_m }));
+        }
+    }
+    best.map(|(_, matched)| matched)
+}
+
+/// Match every recorded point to a position along the course
+pub fn align_to_course(course: &LoadedTrack, recording: &LoadedTrack) -> CourseAlignment {
+    let mut matches = Vec::with_capacity(recording.points.len());
+    if course.points.len() < 2 {
+        matches.resize(recording.points.len(), None);
+        return CourseAlignment { matches, detours: Vec::new(), matched_fraction: 0.0, mean_offset_m: 0.0 };
+    }
+    let course_length = course.cumulative_distances.last().copied().unwrap_or(0.0);
+
+    let mut progress: Option<f64> = None;
+    let mut since_match = 0.0;  // Recorded distance since the last matched point
+    for (i, point) in recording.points.iter().enumerate() {
+        if i > 0 {
+            since_match += recording.cumulative_distances[i] - recording.cumulative_distances[i - 1];
+        }
+        let position = (point.latitude, point.longitude);
+        let matched = match progress {
+            // Before the first match anywhere on the course, preferring the start
+            None => snap(course, position, 0.0, course_length, 0.0),
+            Some(at) => snap(
+                course,
+                position,
+                at - BACKTRACK_M,
+                at + since_match * DISTANCE_SLACK + LOOKAHEAD_M,
+                at + since_match,
+            ),
+        };
+        if let Some(m) = matched {
+            progress = Some(m.distance_m);
+            since_match = 0.0;
+        }
+        matches.push(matched);
+    }
+
+    // Unmatched runs between matched points are detours; leading/trailing ones are warm-up
+    // and cool-down
+    let mut detours = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut seen_match = false;
+    for (i, matched) in matches.iter().enumerate() {
+        match (matched, start) {
+            (None, None) if seen_match => start = Some(i),
+            (Some(_), Some(s)) => {
+                detours.push(Detour {
+                    start_index: s,
+                    end_index: i,
+                    length_m: recording.cumulative_distances[i] - recording.cumulative_distances[s - 1],
+                });
+                start = None;
+            }
+            _ => {}
+        }
+        seen_match |= matched.is_some();
+    }
+
+    let matched: Vec<&CourseMatch> = matches.iter().flatten().collect();
+    CourseAlignment {
+        matched_fraction: matched.len() as f64 / matches.len().max(1) as f64,
+        mean_offset_m: matched.iter().map(|m| m.offset_m).sum::<f64>() / matched.len().max(1) as f64,
+        detours,
+        matches,
+    }
+}
+
+/// Elevation of a (sorted-distance) profile at `distance_m`, clamped to its ends
+fn profile_elevation_at(profile: &SmoothedProfile, distance_m: f64) -> f64 {
+    let i = profile.distances.partition_point(|&d| d < distance_m);
+    if i == 0 {
+        return profile.elevations[0];
+    }
+    if i >= profile.distances.len() {
+        return profile.elevations[profile.elevations.len() - 1];
+    }
+    let (d1, d2) = (profile.distances[i - 1], profile.distances[i]);
+    let t = if d2 > d1 { (distance_m - d1) / (d2 - d1) } else { 0.0 };
+    profile.elevations[i - 1] + t * (profile.elevations[i] - profile.elevations[i - 1])
+}
+
+/// Course elevation at every matched recorded point
+pub fn course_elevations(alignment: &CourseAlignment, course_profile: &SmoothedProfile) -> Vec<Option<f64>> {
+    alignment.matches.iter()
+        .map(|m| m.map(|m| profile_elevation_at(course_profile, m.distance_m)))
+        .collect()
+}
+
+/// The recording with the course's elevation. Off-course points keep their own shape,
+/// shifted by the course-minus-recorded offset blended between the matched points around them
+pub fn inherit_course_elevation(
+    alignment: &CourseAlignment,
+    recording: &LoadedTrack,
+    course_profile: &SmoothedProfile,
+) -> Vec<f64> {
+    let recorded = recording.elevations();
+    let course = course_elevations(alignment, course_profile);
+    let anchors: Vec<usize> = (0..course.len()).filter(|&i| course[i].is_some()).collect();
+    if anchors.is_empty() {
+        return recorded;
+    }
+    let offset_at = |i: usize| course[i].unwrap() - recorded[i];
+
+    (0..recorded.len())
+        .map(|i| {
+            if let Some(elevation) = course[i] {
+                return elevation;
+            }
+            let next = anchors.partition_point(|&a| a < i);
+            let offset = match (next.checked_sub(1).map(|p| anchors[p]), anchors.get(next)) {
+                (Some(prev), Some(&next)) => {
+                    let span = recording.cumulative_distances[next] - recording.cumulative_distances[prev];
+                    let t = if span > 0.0 {
+                        (recording.cumulative_distances[i] - recording.cumulative_distances[prev]) / span
+                    } else {
+                        0.0
+                    };
+                    offset_at(prev) + t * (offset_at(next) - offset_at(prev))
+                }
+                (Some(prev), None) => offset_at(prev),
+                (None, Some(&next)) => offset_at(next),
+                (None, None) => 0.0,
+            };
+            recorded[i] + offset
+        })
+        .collect()
+}
+
+#[derive(Debug, Serialize)]
+struct AlignedPointRow {
+    index: usize,
+    latitude: f64,
+    longitude: f64,
+    recorded_distance_m: f64,
+    course_distance_m: Option<f64>,
+    offset_m: Option<f64>,
+    recorded_elevation_m: f64,
+    course_elevation_m: Option<f64>,
+    difference_m: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AlignmentSummary {
+    pub filename: String,
+    pub points: usize,
+    pub matched_percent: f64,
+    pub mean_offset_m: f64,
+    pub course_from_km: f64,
+    pub course_to_km: f64,
+    pub detours: usize,
+    pub detour_length_m: f64,
+    pub elevation_bias_m: f64,       // Mean recorded-minus-course at matched points
+    pub elevation_rms_m: f64,        // RMS difference after removing the bias
+    pub raw_gain_m: f64,
+    pub inherited_gain_m: f64,
+}
+
+fn write_track_gpx(recording: &LoadedTrack, elevations: &[f64], path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut track = Track::new();
+    track.name = Some(recording.filename.clone());
+    let mut segment = TrackSegment::new();
+    for (point, &elevation) in recording.points.iter().zip(elevations) {
+        let mut waypoint = Waypoint::new(point!(x: point.longitude, y: point.latitude));
+        waypoint.elevation = Some(elevation);
+        segment.points.push(waypoint);
+    }
+    track.segments.push(segment);
+
+    let gpx = Gpx {
+        version: GpxVersion::Gpx11,
+        creator: Some("rust-gpx-smoother align".to_string()),
+        tracks: vec![track],
+        ..Default::default()
+    };
+    write(&gpx, File::create(path)?)?;
+    Ok(())
+}
+
+/// Align one recording, write its per-point CSV and course-elevation GPX and summarize it
+fn align_recording_file(
+    course: &LoadedTrack,
+    course_profile: &SmoothedProfile,
+    recording: &LoadedTrack,
+    output_folder: &Path,
+) -> Result<AlignmentSummary, Box<dyn std::error::Error>> {
+    let alignment = align_to_course(course, recording);
+    let course_elevation = course_elevations(&alignment, course_profile);
+    let inherited = inherit_course_elevation(&alignment, recording, course_profile);
+    let recorded = recording.elevations();
+
+    let differences: Vec<f64> = recorded.iter()
+        .zip(&course_elevation)
+        .filter_map(|(r, c)| Some(r - (*c)?))
+        .collect();
+    let bias = differences.iter().sum::<f64>() / differences.len().max(1) as f64;
+    let rms = (differences.iter().map(|d| (d - bias).powi(2)).sum::<f64>() / differences.len().max(1) as f64).sqrt();
+
+    let stem = Path::new(&recording.filename)
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| recording.filename.clone());
+    let mut wtr = Writer::from_path(output_folder.join(format!("{}_aligned.csv", stem)))?;
+    for (i, point) in recording.points.iter().enumerate() {
+        let matched = alignment.matches[i];
+        wtr.serialize(AlignedPointRow {
+            index: i,
+            latitude: point.latitude,
+            longitude: point.longitude,
+            recorded_distance_m: recording.cumulative_distances[i],
+            course_distance_m: matched.map(|m| m.distance_m),
+            offset_m: matched.map(|m| m.offset_m),
+            recorded_elevation_m: recorded[i],
+            course_elevation_m: course_elevation[i],
+            difference_m: course_elevation[i].map(|c| recorded[i] - c),
+        })?;
+    }
+    wtr.flush()?;
+    write_track_gpx(recording, &inherited, &output_folder.join(format!("{}_course_elevation.gpx", stem)))?;
+
+    let (from_m, to_m) = alignment.course_span_m().unwrap_or((0.0, 0.0));
+    Ok(AlignmentSummary {
+        filename: recording.filename.clone(),
+        points: recording.points.len(),
+        matched_percent: alignment.matched_fraction * 100.0,
+        mean_offset_m: alignment.mean_offset_m,
+        course_from_km: from_m / 1000.0,
+        course_to_km: to_m / 1000.0,
+        detours: alignment.detours.len(),
+        detour_length_m: alignment.detours.iter().map(|d| d.length_m).sum(),
+        elevation_bias_m: bias,
+        elevation_rms_m: rms,
+        raw_gain_m: calculate_gain_loss(&recorded).0,
+        inherited_gain_m: calculate_gain_loss(&inherited).0,
+    })
+}
+
+/// Align a recording (or every recording in a folder) with a course file
+pub fn run_course_alignment(
+    course_path: &Path,
+    input: &Path,
+    output_folder: &Path,
+    smoother: &dyn ElevationSmoother,
+) -> Result<Vec<AlignmentSummary>, Box<dyn std::error::Error>> {
+    println!("\n🗺️  COURSE ALIGNMENT");
+    println!("===================");
+    let course = load_track(course_path)?;
+    let course_profile = smoother.smooth_coordinates(&course.coordinates(), &course.cumulative_distances);
+    println!("Course: {} ({:.2}km, {:.0}m gain with {})",
+             course.filename, course.total_distance_km(), course_profile.gain_m, smoother.name());
+
+    let recordings: Vec<PathBuf> = if input.is_dir() {
+        WalkDir::new(input)
+            .max_depth(1)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file() && is_supported_track_file(entry.path()))
+            .map(|entry| entry.into_path())
+            .filter(|path| path.as_path() != course_path)
+            .collect()
+    } else {
+        vec![input.to_path_buf()]
+    };
+    std::fs::create_dir_all(output_folder)?;
+
+    let mut summaries = Vec::new();
+    for path in &recordings {
+        let recording = match load_track(path) {
+            Ok(track) => track,
+            Err(e) => {
+                eprintln!("⚠️  Error loading {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        let summary = align_recording_file(&course, &course_profile, &recording, output_folder)?;
+        println!("\n🔄 {}", summary.filename);
+        println!("  📍 {:.1}% of {} points on course (mean offset {:.1}m), course km {:.2}-{:.2}",
+                 summary.matched_percent, summary.points, summary.mean_offset_m, summary.course_from_km, summary.course_to_km);
+        if summary.detours > 0 {
+            println!("  ↪️  {} detour(s), {:.0}m off course", summary.detours, summary.detour_length_m);
+        }
+        println!("  📏 Recorded vs course elevation: {:+.1}m bias, {:.1}m RMS after bias",
+                 summary.elevation_bias_m, summary.elevation_rms_m);
+        println!("  📈 Gain: {:.0}m recorded, {:.0}m with the course elevation", summary.raw_gain_m, summary.inherited_gain_m);
+        summaries.push(summary);
+    }
+
+    if !summaries.is_empty() {
+        let summary_path = output_folder.join("alignment_summary.csv");
+        let mut wtr = Writer::from_path(&summary_path)?;
+        for summary in &summaries {
+            wtr.serialize(summary)?;
+        }
+        wtr.flush()?;
+        println!("\n📁 Per-point CSVs, course-elevation GPX files and {} saved to {}",
+                 summary_path.file_name().unwrap().to_string_lossy(), output_folder.display());
+    }
+
+    Ok(summaries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::track_loader::TrackPoint;
+
+    fn track(name: &str, positions: &[(f64, f64)]) -> LoadedTrack {
+        let points = positions.iter()
+            .map(|&(lat, lon)| TrackPoint { latitude: lat, longitude: lon, elevation: 100.0, time: None, distance: None })
+            .collect();
+        LoadedTrack::from_points(name.to_string(), points)
+    }
+
+    #[test]
+    fn test_out_and_back_with_detour_follows_course_order() {
+        // 1km north and back along the same line
+        let step = 1.0 / METERS_PER_DEGREE * 20.0;
+        let out: Vec<(f64, f64)> = (0..=50).map(|i| (46.0 + i as f64 * step, 7.0)).collect();
+        let course_points: Vec<(f64, f64)> = out.iter().chain(out.iter().rev().skip(1)).copied().collect();
+        let course = track("course.gpx", &course_points);
+
+        // Recorded slightly east, with a 200m-wide excursion on the way back
+        let mut recorded: Vec<(f64, f64)> = course_points.iter().map(|&(lat, lon)| (lat, lon + 0.00005)).collect();
+        for point in &mut recorded[70..75] {
+            point.1 += 0.003;
+        }
+        let alignment = align_to_course(&course, &track("run.gpx", &recorded));
+
+        assert_eq!(alignment.detours.len(), 1);
+        assert_eq!((alignment.detours[0].start_index, alignment.detours[0].end_index), (70, 75));
+        let along: Vec<f64> = alignment.matches.iter().flatten().map(|m| m.distance_m).collect();
+        assert!(along.windows(2).all(|w| w[1] >= w[0] - BACKTRACK_M));
+        // Points on the return leg map past the turnaround, not onto the outbound leg
+        let back = alignment.matches[90].unwrap();
+        assert!((back.distance_m - 1800.0).abs() < 25.0, "{}", back.distance_m);
+        assert!(back.offset_m < 5.0);
+    }
+}
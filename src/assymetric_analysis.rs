@@ -283,81 +283,40 @@ fn generate_focused_winner_grid() -> Vec<(f32, f32)> {
 }
 
 fn load_gpx_data(gpx_folder: &str) -> Result<(HashMap<String, GpxFileData>, Vec<String>), Box<dyn std::error::Error>> {
-    use std::fs::File;
-    use std::io::BufReader;
-    use gpx::read;
-    use geo::{HaversineDistance, point};
     use walkdir::WalkDir;
-    
+    use crate::track_loader::{load_track, is_supported_track_file};
+
     let mut gpx_data = HashMap::new();
     let mut valid_files = Vec::new();
-    
-    let official_data = crate::load_official_elevation_data()?;
-    
+
+    let official_data = crate::load_ground_truth_registry()?;
+
     for entry in WalkDir::new(gpx_folder) {
         let entry = entry?;
-        if entry.file_type().is_file() {
-            if let Some(extension) = entry.path().extension() {
-                if extension.to_str().unwrap_or("").to_lowercase() == "gpx" {
-                    let path = entry.path();
-                    let filename = path.file_name()
-                        .and_then(|n| n.to_str())
-                        .unwrap_or("")
-                        .to_string();
-                    
-                    match File::open(path) {
-                        Ok(file) => {
-                            let reader = BufReader::new(file);
-                            match read(reader) {
-                                Ok(gpx) => {
-                                    let mut coords: Vec<(f64, f64, f64)> = vec![];
-                                    
-                                    for track in gpx.tracks {
-                                        for segment in track.segments {
-                                            for pt in segment.points {
-                                                if let Some(ele) = pt.elevation {
-                                                    coords.push((pt.point().y(), pt.point().x(), ele));
-                                                }
-                                            }
-                                        }
-                                    }
-                                    
-                                    if !coords.is_empty() {
-                                        let mut distances = vec![0.0];
-                                        for i in 1..coords.len() {
-                                            let a = point!(x: coords[i-1].1, y: coords[i-1].0);
-                                            let b = point!(x: coords[i].1, y: coords[i].0);
-                                            let dist = a.haversine_distance(&b);
-                                            distances.push(distances[i-1] + dist);
-                                        }
-                                        
-                                        let elevations: Vec<f64> = coords.iter().map(|c| c.2).collect();
-                                        let official_gain = official_data
-                                            .get(&filename.to_lowercase())
-                                            .copied()
-                                            .unwrap_or(0);
-                                        
-                                        let file_data = GpxFileData {
-                                            filename: filename.clone(),
-                                            elevations,
-                                            distances,
-                                            official_gain,
-                                        };
-                                        
-                                        gpx_data.insert(filename.clone(), file_data);
-                                        valid_files.push(filename);
-                                    }
-                                },
-                                Err(_) => continue,
-                            }
-                        },
-                        Err(_) => continue,
-                    }
-                }
-            }
+        if entry.file_type().is_file() && is_supported_track_file(entry.path()) {
+            // GPX and FIT both land in the same point model; unreadable files are skipped
+            let track = match load_track(entry.path()) {
+                Ok(track) => track,
+                Err(_) => continue,
+            };
+            let filename = track.filename.clone();
+
+            let official_gain = official_data.official_gain(entry.path(), &track.coordinates());
+
+            let file_data = GpxFileData {
+                elevations: track.elevations(),
+                distances: track.cumulative_distances,
+                filename: filename.clone(),
+                official_gain,
+            };
+
+            gpx_data.insert(filename.clone(), file_data);
+            valid_files.push(filename);
         }
     }
-    
+
+    official_data.print_match_report();
+
     Ok((gpx_data, valid_files))
 }
 
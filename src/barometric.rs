@@ -0,0 +1,327 @@
+/// BAROMETRIC ALTITUDE - Detect barometer-sourced elevation and process it on its own path
+/// Barometric profiles are smooth but drift with the weather, the opposite of GPS altitude.
+/// GPS-tuned deadbands throw away real climbing on them, so they get lighter smoothing after
+/// the drift is removed against DEM anchors or, on loops, against the start elevation.
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use geo::{HaversineDistance, point};
+use xml::reader::{EventReader, XmlEvent};
+use crate::dem::ElevationLookup;
+use crate::error::TrackError;
+use crate::elevation_smoother::{
+    ElevationSmoother, SmoothedProfile, gaussian_smooth, resample_to_uniform_distance,
+};
+
+// ICAO standard atmosphere
+const SEA_LEVEL_PRESSURE_HPA: f64 = 1013.25;
+// A track ending this close to its start is a loop: it must finish at the start elevation
+pub const LOOP_CLOSURE_M: f64 = 200.0;
+// One DEM anchor (median barometric - DEM offset) per stretch of this length
+pub const DEM_ANCHOR_SPACING_M: f64 = 2000.0;
+const MIN_POINTS_PER_ANCHOR: usize = 10;
+// Share of points that need a reading for a track to count as barometric
+pub const MIN_BAROMETRIC_COVERAGE: f64 = 0.8;
+
+// Extension element names (lowercase local names) seen in Garmin-style and other exports
+const ALTITUDE_TAGS: [&str; 6] = ["baro_alt", "baroalt", "baroaltitude", "barometric_altitude", "altitude_baro", "baro"];
+const PRESSURE_TAGS: [&str; 6] = ["pressure", "press", "air_pressure", "airpressure", "baro_pressure", "absolute_pressure"];
+
+/// Pressure altitude in meters for a pressure in hPa (standard atmosphere)
+pub fn pressure_to_altitude(pressure_hpa: f64) -> f64 {
+    44330.0 * (1.0 - (pressure_hpa / SEA_LEVEL_PRESSURE_HPA).powf(1.0 / 5.255))
+}
+
+/// Readings above this are taken to be Pa rather than hPa
+fn pressure_in_hpa(value: f64) -> f64 {
+    if value > 2000.0 { value / 100.0 } else { value }
+}
+
+/// Barometric altitude for every `<trkpt>` in document order, from its `<extensions>`.
+/// Pressure readings are converted with the standard atmosphere; `None` when the point
+/// has neither.
+pub fn read_gpx_barometric_altitudes(path: &Path) -> Result<Vec<Option<f64>>, TrackError> {
+    let file = File::open(path).map_err(|e| TrackError::io(path, e))?;
+    let parser = EventReader::new(BufReader::new(file));
+
+    let mut altitudes = Vec::new();
+    let mut in_trackpoint = false;
+    let mut in_extensions = false;
+    let mut current_tag = String::new();
+    let mut altitude: Option<f64> = None;
+    let mut pressure_altitude: Option<f64> = None;
+
+    for event in parser {
+        match event.map_err(|e| TrackError::from_xml(path, e))? {
+            XmlEvent::StartElement { name, .. } => {
+                let local = name.local_name.to_lowercase();
+                match local.as_str() {
+                    "trkpt" => {
+                        in_trackpoint = true;
+                        altitude = None;
+                        pressure_altitude = None;
+                    }
+                    "extensions" if in_trackpoint => in_extensions = true,
+                    _ => {}
+                }
+                current_tag = local;
+            }
+            XmlEvent::Characters(text) if in_extensions => {
+                if let Ok(value) = text.trim().parse::<f64>() {
+                    if ALTITUDE_TAGS.contains(&current_tag.as_str()) {
+                        altitude = Some(value);
+                    } else if PRESSURE_TAGS.contains(&current_tag.as_str()) {
+                        pressure_altitude = Some(pressure_to_altitude(pressure_in_hpa(value)));
+                    }
+                }
+            }
+            XmlEvent::EndElement { name } => {
+                match name.local_name.to_lowercase().as_str() {
+                    "trkpt" => {
+                        altitudes.push(altitude.or(pressure_altitude));
+                        in_trackpoint = false;
+                        in_extensions = false;
+                    }
+                    "extensions" => in_extensions = false,
+                    _ => {}
+                }
+                current_tag.clear();
+            }
+            _ => {}
+        }
+    }
+
+    Ok(altitudes)
+}
+
+/// The barometric series with points lacking a reading filled in linearly (by point index)
+/// from their neighbours, and held flat before the first and after the last reading.
+/// None when fewer than MIN_BAROMETRIC_COVERAGE of the points have a reading: the track is
+/// then GPS. Barometric and GPS altitude have different datums and are never mixed.
+pub fn fill_barometric_gaps(altitudes: &[Option<f64>]) -> Option<Vec<f64>> {
+    let readings: Vec<(usize, f64)> = altitudes.iter()
+        .enumerate()
+        .filter_map(|(i, altitude)| altitude.map(|a| (i, a)))
+        .collect();
+    if readings.is_empty() || (readings.len() as f64) < MIN_BAROMETRIC_COVERAGE * altitudes.len() as f64 {
+        return None;
+    }
+
+    let mut next = 0;  // First reading at or after the current point
+    let filled = (0..altitudes.len())
+        .map(|i| {
+            while next < readings.len() && readings[next].0 < i {
+                next += 1;
+            }
+            match (next.checked_sub(1).map(|k| readings[k]), readings.get(next)) {
+                (_, Some(&(j, altitude))) if j == i => altitude,
+                (Some((i0, a0)), Some(&(i1, a1))) => a0 + (a1 - a0) * (i - i0) as f64 / (i1 - i0) as f64,
+                (Some((_, a0)), None) => a0,
+                (None, Some(&(_, a1))) => a1,
+                (None, None) => unreachable!("readings is not empty"),
+            }
+        })
+        .collect();
+    Some(filled)
+}
+
+/// Remove linear drift on a loop so the track finishes at its start elevation.
+/// Returns the drift that was removed, or None if the track is not a loop.
+pub fn correct_loop_drift(elevations: &mut [f64], coordinates: &[(f64, f64)], distances: &[f64]) -> Option<f64> {
+    let (&first, &last) = (coordinates.first()?, coordinates.last()?);
+    let gap = point!(x: first.1, y: first.0).haversine_distance(&point!(x: last.1, y: last.0));
+    let total_distance = *distances.last()?;
+    if gap > LOOP_CLOSURE_M || total_distance <= 0.0 || elevations.len() != distances.len() {
+        return None;
+    }
+
+    let drift = elevations[elevations.len() - 1] - elevations[0];
+    for (elevation, distance) in elevations.iter_mut().zip(distances) {
+        *elevation -= drift * distance / total_distance;
+    }
+    Some(drift)
+}
+
+/// Pin the profile to DEM anchors: one median offset per DEM_ANCHOR_SPACING_M stretch,
+/// interpolated linearly between stretch centres. Returns the number of anchors used.
+pub fn correct_dem_drift(
+    elevations: &mut [f64],
+    coordinates: &[(f64, f64)],
+    distances: &[f64],
+    dem: &dyn ElevationLookup,
+) -> usize {
+    let total_distance = distances.last().copied().unwrap_or(0.0);
+    let stretches = ((total_distance / DEM_ANCHOR_SPACING_M).ceil() as usize).max(1);
+
+    let mut anchors: Vec<(f64, f64)> = Vec::new();  // (distance, offset)
+    for stretch in 0..stretches {
+        let start = stretch as f64 * DEM_ANCHOR_SPACING_M;
+        let end = start + DEM_ANCHOR_SPACING_M;
+        let mut offsets: Vec<f64> = Vec::new();
+        let mut anchor_distances: Vec<f64> = Vec::new();
+        for ((&(lat, lon), &distance), &elevation) in coordinates.iter().zip(distances).zip(elevations.iter()) {
+            if distance >= start && (distance < end || stretch == stretches - 1) {
+                if let Some(terrain) = dem.elevation_at(lat, lon) {
+                    offsets.push(elevation - terrain);
+                    anchor_distances.push(distance);
+                }
+            }
+        }
+        if offsets.len() >= MIN_POINTS_PER_ANCHOR {
+            offsets.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            let centre = anchor_distances.iter().sum::<f64>() / anchor_distances.len() as f64;
+            anchors.push((centre, offsets[offsets.len() / 2]));
+        }
+    }
+
+    if anchors.is_empty() {
+        return 0;
+    }
+
+    for (elevation, &distance) in elevations.iter_mut().zip(distances) {
+        *elevation -= offset_at(&anchors, distance);
+    }
+    anchors.len()
+}
+
+fn offset_at(anchors: &[(f64, f64)], distance: f64) -> f64 {
+    if distance <= anchors[0].0 {
+        return anchors[0].1;
+    }
+    for pair in anchors.windows(2) {
+        let ((d0, o0), (d1, o1)) = (pair[0], pair[1]);
+        if distance <= d1 {
+            let t = if d1 > d0 { (distance - d0) / (d1 - d0) } else { 0.0 };
+            return o0 + t * (o1 - o0);
+        }
+    }
+    anchors[anchors.len() - 1].1
+}
+
+/// Light smoothing for barometric altitude: drift correction, 2m resampling,
+/// a short Gaussian and a small symmetric deadband
+pub struct BarometricSmoother {
+    pub deadband_m: f64,
+    pub dem: Option<Box<dyn ElevationLookup>>,
+}
+
+impl Default for BarometricSmoother {
+    fn default() -> Self {
+        BarometricSmoother {
+            deadband_m: 0.5,
+            dem: None,
+        }
+    }
+}
+
+impl BarometricSmoother {
+    fn smooth_corrected(&self, elevations: &[f64], distances: &[f64]) -> SmoothedProfile {
+        let (uniform_distances, uniform_elevations) = resample_to_uniform_distance(elevations, distances, 2.0);
+        if uniform_elevations.is_empty() {
+            return SmoothedProfile::from_elevations(distances.to_vec(), elevations.to_vec());
+        }
+
+        let smoothed = gaussian_smooth(&uniform_elevations, 5);
+
+        // Symmetric deadband: ignore wiggles smaller than the barometer's resolution
+        let mut filtered = Vec::with_capacity(smoothed.len());
+        let mut level = smoothed[0];
+        for &elevation in &smoothed {
+            if (elevation - level).abs() >= self.deadband_m {
+                level = elevation;
+            }
+            filtered.push(level);
+        }
+
+        SmoothedProfile::from_elevations(uniform_distances, filtered)
+            .with_diagnostic("deadband_m", self.deadband_m)
+    }
+}
+
+impl ElevationSmoother for BarometricSmoother {
+    fn name(&self) -> String {
+        format!("barometric-{:.2}m", self.deadband_m)
+    }
+
+    fn smooth(&self, elevations: &[f64], distances: &[f64]) -> SmoothedProfile {
+        self.smooth_corrected(elevations, distances)
+    }
+
+    fn smooth_coordinates(&self, coordinates: &[(f64, f64, f64)], distances: &[f64]) -> SmoothedProfile {
+        let mut elevations: Vec<f64> = coordinates.iter().map(|c| c.2).collect();
+        let positions: Vec<(f64, f64)> = coordinates.iter().map(|c| (c.0, c.1)).collect();
+
+        let dem_anchors = match &self.dem {
+            Some(dem) => correct_dem_drift(&mut elevations, &positions, distances, dem.as_ref()),
+            None => 0,
+        };
+        let loop_drift = if dem_anchors == 0 {
+            correct_loop_drift(&mut elevations, &positions, distances)
+        } else {
+            None
+        };
+
+        self.smooth_corrected(&elevations, distances)
+            .with_diagnostic("dem_anchors", dem_anchors as f64)
+            .with_diagnostic("loop_drift_m", loop_drift.unwrap_or(0.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loop_drift_is_removed() {
+        // Out and back on the same line; the barometer drifts +6m over the ride
+        let out_and_back = |i: i32| (50 - (i - 50).abs()) as f64;
+        let coordinates: Vec<(f64, f64)> = (0..=100)
+            .map(|i| (46.0 + 0.0001 * out_and_back(i), 7.0))
+            .collect();
+        let distances: Vec<f64> = (0..=100).map(|i| i as f64 * 11.1).collect();
+        let mut elevations: Vec<f64> = (0..=100)
+            .map(|i| 500.0 + out_and_back(i) + 0.06 * i as f64)
+            .collect();
+
+        let drift = correct_loop_drift(&mut elevations, &coordinates, &distances).unwrap();
+
+        assert!((drift - 6.0).abs() < 1e-9);
+        assert!((elevations[100] - elevations[0]).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_pressure_to_altitude() {
+        assert!(pressure_to_altitude(SEA_LEVEL_PRESSURE_HPA).abs() < 1e-9);
+        // ~900 hPa is a little under 1000m
+        let altitude = pressure_to_altitude(900.0);
+        assert!((altitude - 988.5).abs() < 5.0);
+    }
+
+    #[test]
+    fn test_points_without_a_barometric_reading_are_interpolated() {
+        let trackpoint = |i: usize, baro: Option<f64>| format!(
+            "<trkpt lat=\"{:.4}\" lon=\"7.0\"><ele>{}</ele>{}</trkpt>",
+            46.0 + i as f64 * 0.0001,
+            400 + i,
+            baro.map(|alt| format!("<extensions><baro_alt>{}</baro_alt></extensions>", alt)).unwrap_or_default(),
+        );
+        let readings: Vec<Option<f64>> = (0..10).map(|i| if i == 1 || i == 9 { None } else { Some(500.0 + i as f64) }).collect();
+        let content = format!(
+            "<?xml version=\"1.0\"?>\n<gpx version=\"1.1\" creator=\"test\"><trk><trkseg>{}</trkseg></trk></gpx>\n",
+            readings.iter().enumerate().map(|(i, &baro)| trackpoint(i, baro)).collect::<String>(),
+        );
+        let path = std::env::temp_dir().join(format!("barometric_mixed_{}.gpx", std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        let track = crate::track_loader::load_gpx_track(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        // The GPS <ele> (400m datum) never leaks into the barometric profile
+        assert_eq!(track.elevations(), vec![500.0, 501.0, 502.0, 503.0, 504.0, 505.0, 506.0, 507.0, 508.0, 508.0]);
+        assert_eq!(track.altitude_source, crate::track_loader::AltitudeSource::Barometric);
+
+        // Too few readings: the whole track is GPS
+        assert_eq!(fill_barometric_gaps(&[Some(500.0), None, None, Some(503.0)]), None);
+        assert_eq!(fill_barometric_gaps(&[None, Some(500.0), Some(501.0), Some(503.0), Some(504.0)]),
+                   Some(vec![500.0, 500.0, 501.0, 503.0, 504.0]));
+    }
+}
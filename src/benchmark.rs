@@ -0,0 +1,254 @@
+/// SMOOTHER BENCHMARK - Score a single smoothing algorithm against official elevation data
+/// Processes every GPX/FIT/TCX file in a folder with one ElevationSmoother, compares the
+/// processed gain to the official figure and writes a per-file CSV. Barometric tracks can be
+/// routed to a separate (lighter) smoother, and stationary stops collapsed before smoothing.
+use std::path::{Path, PathBuf};
+use csv::Writer;
+use serde::Serialize;
+use rayon::prelude::*;
+use walkdir::WalkDir;
+use crate::elevation_smoother::{smooth_between_gaps, ElevationSmoother};
+use crate::ground_truth::GroundTruthRecord;
+use crate::pause_detection;
+use crate::track_loader::{load_track, is_supported_track_file, calculate_gain_loss, AltitudeSource, DistanceSource, LoadedTrack};
+
+#[derive(Debug, Serialize, Clone)]
+pub struct BenchmarkResult {
+    pub filename: String,
+    pub altitude_source: String,
+    pub smoother: String,
+    pub total_points: usize,
+    pub distance_km: f64,
+    pub raw_gain_m: f64,
+    pub raw_loss_m: f64,
+    pub processed_gain_m: f64,
+    pub processed_loss_m: f64,
+    pub official_gain_m: u32,
+    pub accuracy_percent: f64,
+    pub absolute_error_m: f64,
+    pub official_loss_m: Option<u32>,
+    pub loss_accuracy_percent: Option<f64>,
+    pub official_distance_km: Option<f64>,
+    pub distance_accuracy_percent: Option<f64>,
+    pub stops: usize,
+    pub stopped_time_s: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct BenchmarkSummary {
+    pub files_found: usize,
+    pub files_processed: usize,
+    pub files_with_official_data: usize,
+    pub barometric_files: usize,
+    pub average_accuracy_percent: f64,
+    pub median_accuracy_percent: f64,
+    pub files_within_95_105: usize,
+    pub files_within_90_110: usize,
+    pub files_with_official_loss: usize,
+    pub average_loss_accuracy_percent: f64,
+    pub loss_within_90_110: usize,
+    pub files_with_official_distance: usize,
+    pub average_distance_accuracy_percent: f64,
+    pub files_with_stops: usize,
+}
+
+pub fn run_benchmark(
+    gpx_folder: &str,
+    smoother: &dyn ElevationSmoother,
+    output_folder: &str,
+    distance_source: DistanceSource,
+    barometric_smoother: Option<&dyn ElevationSmoother>,
+    collapse_stops: bool,
+) -> Result<BenchmarkSummary, Box<dyn std::error::Error>> {
+    println!("\n📏 SMOOTHER BENCHMARK");
+    println!("=====================");
+    println!("Smoother: {}", smoother.name());
+    if let Some(barometric) = barometric_smoother {
+        println!("Barometric tracks: {}", barometric.name());
+    }
+    if collapse_stops {
+        println!("Stationary stops collapsed before smoothing");
+    }
+
+    let official_data = crate::load_ground_truth_registry()?;
+
+    let gpx_files: Vec<PathBuf> = WalkDir::new(gpx_folder)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file() && is_supported_track_file(entry.path()))
+        .map(|entry| entry.path().to_path_buf())
+        .collect();
+
+    println!("📁 Found {} GPX/FIT/TCX files to benchmark", gpx_files.len());
+
+    let results: Vec<BenchmarkResult> = gpx_files
+        .par_iter()
+        .filter_map(|path| match load_track(path) {
+            Ok(track) => {
+                let track_smoother = match (track.altitude_source, barometric_smoother) {
+                    (AltitudeSource::Barometric, Some(barometric)) => barometric,
+                    _ => smoother,
+                };
+                let track = track.with_distance_source(distance_source);
+                // Matched on the file as recorded, before any stops are collapsed
+                let official = official_data.official_record(path, &track.coordinates());
+                let stops = pause_detection::detect_stops(&track);
+                let mut result = if collapse_stops {
+                    benchmark_track(&pause_detection::collapse_stops(&track, &stops).track, track_smoother, official)
+                } else {
+                    benchmark_track(&track, track_smoother, official)
+                };
+                result.stops = stops.stops.len();
+                result.stopped_time_s = stops.stopped_time_s;
+                Some(result)
+            }
+            Err(e) => {
+                eprintln!("⚠️  Error processing {}: {}", path.display(), e);
+                None
+            }
+        })
+        .collect();
+
+    let summary = summarize_results(&results, gpx_files.len());
+
+    if !results.is_empty() {
+        std::fs::create_dir_all(output_folder)?;
+        let output_path = Path::new(output_folder)
+            .join(format!("benchmark_{}.csv", smoother.name()));
+        write_results_csv(&results, &output_path)?;
+        println!("📁 Results saved to: {}", output_path.display());
+    }
+
+    print_summary(&summary);
+    official_data.print_match_report();
+
+    Ok(summary)
+}
+
+/// Smooth one loaded track and score it against its official record's gain (and loss and
+/// distance, when the record has them)
+pub fn benchmark_track(
+    track: &LoadedTrack,
+    smoother: &dyn ElevationSmoother,
+    official: Option<&GroundTruthRecord>,
+) -> BenchmarkResult {
+    let raw_elevations = track.elevations();
+    let (raw_gain, raw_loss) = calculate_gain_loss(&raw_elevations);
+
+    // No smoothing window spans a recording gap between segments
+    let profile = smooth_between_gaps(smoother, &track.coordinates(), &track.cumulative_distances, &track.gaps());
+    let processed_gain = profile.gain_m();
+    let processed_loss = profile.loss_m();
+
+    let official_gain = official.map_or(0, |r| r.gain_m);
+    let distance_km = track.total_distance_km();
+
+    let (accuracy_percent, absolute_error_m) = if official_gain > 0 {
+        (
+            (processed_gain / official_gain as f64) * 100.0,
+            (processed_gain - official_gain as f64).abs(),
+        )
+    } else {
+        (0.0, 0.0)
+    };
+
+    BenchmarkResult {
+        filename: track.filename.clone(),
+        altitude_source: track.altitude_source.name().to_string(),
+        smoother: smoother.name(),
+        total_points: track.points.len(),
+        distance_km,
+        raw_gain_m: raw_gain,
+        raw_loss_m: raw_loss,
+        processed_gain_m: processed_gain,
+        processed_loss_m: processed_loss,
+        official_gain_m: official_gain,
+        accuracy_percent,
+        absolute_error_m,
+        official_loss_m: official.and_then(|r| r.loss_m),
+        loss_accuracy_percent: official.and_then(|r| r.loss_accuracy(processed_loss)),
+        official_distance_km: official.and_then(|r| r.distance_km),
+        distance_accuracy_percent: official.and_then(|r| r.distance_accuracy(distance_km)),
+        stops: 0,
+        stopped_time_s: 0.0,
+    }
+}
+
+fn summarize_results(results: &[BenchmarkResult], files_found: usize) -> BenchmarkSummary {
+    let mut accuracies: Vec<f64> = results.iter()
+        .filter(|r| r.official_gain_m > 0)
+        .map(|r| r.accuracy_percent)
+        .collect();
+    accuracies.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    let average_accuracy_percent = if accuracies.is_empty() {
+        0.0
+    } else {
+        accuracies.iter().sum::<f64>() / accuracies.len() as f64
+    };
+    let median_accuracy_percent = if accuracies.is_empty() {
+        0.0
+    } else {
+        accuracies[accuracies.len() / 2]
+    };
+
+    let loss_accuracies: Vec<f64> = results.iter().filter_map(|r| r.loss_accuracy_percent).collect();
+    let distance_accuracies: Vec<f64> = results.iter().filter_map(|r| r.distance_accuracy_percent).collect();
+    let mean = |values: &[f64]| if values.is_empty() { 0.0 } else { values.iter().sum::<f64>() / values.len() as f64 };
+
+    BenchmarkSummary {
+        files_found,
+        files_processed: results.len(),
+        files_with_official_data: accuracies.len(),
+        barometric_files: results.iter().filter(|r| r.altitude_source == AltitudeSource::Barometric.name()).count(),
+        average_accuracy_percent,
+        median_accuracy_percent,
+        files_within_95_105: accuracies.iter().filter(|&&a| (95.0..=105.0).contains(&a)).count(),
+        files_within_90_110: accuracies.iter().filter(|&&a| (90.0..=110.0).contains(&a)).count(),
+        files_with_official_loss: loss_accuracies.len(),
+        average_loss_accuracy_percent: mean(&loss_accuracies),
+        loss_within_90_110: loss_accuracies.iter().filter(|&&a| (90.0..=110.0).contains(&a)).count(),
+        files_with_official_distance: distance_accuracies.len(),
+        average_distance_accuracy_percent: mean(&distance_accuracies),
+        files_with_stops: results.iter().filter(|r| r.stops > 0).count(),
+    }
+}
+
+fn write_results_csv(results: &[BenchmarkResult], output_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut wtr = Writer::from_path(output_path)?;
+    for result in results {
+        wtr.serialize(result)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+fn print_summary(summary: &BenchmarkSummary) {
+    println!("\n📊 BENCHMARK SUMMARY");
+    println!("====================");
+    println!("Files processed: {}/{}", summary.files_processed, summary.files_found);
+    println!("Files with official data: {}", summary.files_with_official_data);
+    if summary.barometric_files > 0 {
+        println!("Barometric altitude files: {}", summary.barometric_files);
+    }
+    if summary.files_with_stops > 0 {
+        println!("Files with stops or auto-pauses: {}", summary.files_with_stops);
+    }
+    if summary.files_with_official_data > 0 {
+        let n = summary.files_with_official_data as f64;
+        println!("Average accuracy: {:.1}%", summary.average_accuracy_percent);
+        println!("Median accuracy: {:.1}%", summary.median_accuracy_percent);
+        println!("Within ±5%: {} ({:.1}%)", summary.files_within_95_105,
+                 summary.files_within_95_105 as f64 / n * 100.0);
+        println!("Within ±10%: {} ({:.1}%)", summary.files_within_90_110,
+                 summary.files_within_90_110 as f64 / n * 100.0);
+    }
+    if summary.files_with_official_loss > 0 {
+        println!("Loss accuracy: {:.1}% average, {}/{} within ±10%", summary.average_loss_accuracy_percent,
+                 summary.loss_within_90_110, summary.files_with_official_loss);
+    }
+    if summary.files_with_official_distance > 0 {
+        println!("Distance accuracy: {:.1}% average over {} files", summary.average_distance_accuracy_percent,
+                 summary.files_with_official_distance);
+    }
+}
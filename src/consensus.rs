@@ -0,0 +1,549 @@
+/// CONSENSUS PROFILE - Reference elevation profile from many recordings of one course
+/// Every recording of a course is resampled onto a distance grid along a reference
+/// recording (the one of median length), matching grid points by position so GPS distance
+/// drift does not smear climbs. Per-recording elevation bias is removed, recordings far
+/// from the course or far noisier than the rest are rejected, and the median of the others
+/// is the consensus. Confidence bands come from the spread across recordings; the gain
+/// uncertainty from bootstrapping the recordings.
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use csv::Writer;
+use geo::point;
+use gpx::{write, Gpx, GpxVersion, Track, TrackSegment, Waypoint};
+use rayon::prelude::*;
+use serde::Serialize;
+use walkdir::WalkDir;
+use crate::alignment::project_onto_segment;
+use crate::elevation_smoother::ElevationSmoother;
+use crate::search_strategy::SearchRng;
+use crate::track_loader::{is_supported_track_file, load_track, LoadedTrack};
+
+// Recording distance searched either side of the length-scaled position of a grid point
+const ALIGN_WINDOW_M: f64 = 500.0;
+// A grid point further than this from every recording segment is a gap in that recording
+const MAX_POINT_OFFSET_M: f64 = 150.0;
+// Recordings further than this from the reference course on average are another course
+pub const MAX_MEAN_OFFSET_M: f64 = 60.0;
+// Share of the grid a recording must cover to take part
+pub const MIN_COVERAGE: f64 = 0.9;
+// Recordings whose deviation from the median exceeds the typical one by this many MADs are outliers
+pub const OUTLIER_MADS: f64 = 3.0;
+// ...and by at least this much, so a very consistent set does not reject good recordings
+const MIN_OUTLIER_MARGIN_M: f64 = 1.0;
+const MAD_TO_SIGMA: f64 = 1.4826;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ConsensusConfig {
+    pub step_m: f64,
+    pub bootstrap_samples: usize,
+    pub seed: u64,
+}
+
+impl Default for ConsensusConfig {
+    fn default() -> Self {
+        ConsensusConfig {
+            step_m: 10.0,
+            bootstrap_samples: 200,
+            seed: 42,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordingSummary {
+    pub filename: String,
+    pub distance_km: f64,
+    pub coverage_percent: f64,
+    pub mean_offset_m: f64,      // Mean distance from the reference course
+    pub elevation_bias_m: f64,   // Median elevation offset against the consensus, removed
+    pub deviation_m: f64,        // RMS deviation from the consensus after bias removal
+    pub gain_m: f64,             // This recording alone, same smoother
+    pub accepted: bool,
+    pub rejection: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConsensusProfile {
+    pub reference: String,
+    pub distances: Vec<f64>,
+    pub positions: Vec<(f64, f64)>,  // (lat, lon) on the reference course
+    pub elevations: Vec<f64>,        // Median of the accepted recordings
+    pub lower: Vec<f64>,             // 95% band of the median
+    pub upper: Vec<f64>,
+    pub spread: Vec<f64>,            // Robust standard deviation across recordings
+    pub counts: Vec<usize>,          // Recordings covering each grid point
+    pub recordings: Vec<RecordingSummary>,
+    pub gain_m: f64,
+    pub loss_m: f64,
+    pub gain_std_m: f64,             // Bootstrap over recordings
+    pub gain_low_m: f64,             // 2.5th percentile
+    pub gain_high_m: f64,            // 97.5th percentile
+}
+
+impl ConsensusProfile {
+    pub fn accepted_count(&self) -> usize {
+        self.recordings.iter().filter(|r| r.accepted).count()
+    }
+}
+
+/// The reference recording resampled every `step_m` metres
+struct CourseGrid {
+    distances: Vec<f64>,
+    positions: Vec<(f64, f64)>,
+    total_m: f64,
+}
+
+impl CourseGrid {
+    fn new(reference: &LoadedTrack, step_m: f64) -> Self {
+        let total_m = reference.cumulative_distances.last().copied().unwrap_or(0.0);
+        let count = (total_m / step_m).floor() as usize + 1;
+        let distances: Vec<f64> = (0..count).map(|i| i as f64 * step_m).collect();
+
+        let mut positions = Vec::with_capacity(count);
+        let mut segment = 0;
+        for &distance in &distances {
+            while segment + 2 < reference.points.len() && reference.cumulative_distances[segment + 1] < distance {
+                segment += 1;
+            }
+            let (a, b) = (&reference.points[segment], &reference.points[(segment + 1).min(reference.points.len() - 1)]);
+            let (da, db) = (reference.cumulative_distances[segment], reference.cumulative_distances[(segment + 1).min(reference.points.len() - 1)]);
+            let t = if db > da { ((distance - da) / (db - da)).clamp(0.0, 1.0) } else { 0.0 };
+            positions.push((a.latitude + t * (b.latitude - a.latitude), a.longitude + t * (b.longitude - a.longitude)));
+        }
+
+        CourseGrid { distances, positions, total_m }
+    }
+}
+
+/// A recording sampled at the grid points
+struct Alignment {
+    elevations: Vec<Option<f64>>,
+    mean_offset_m: f64,
+    coverage: f64,
+}
+
+/// Match every grid point to the nearest point on the recording's segments, searching
+/// only near where the recording's own distance (scaled to the reference length) puts it,
+/// so out-and-back sections are not matched to the wrong direction
+fn align_recording(grid: &CourseGrid, track: &LoadedTrack) -> Alignment {
+    let distances = &track.cumulative_distances;
+    let points = &track.points;
+    let scale = distances.last().copied().unwrap_or(0.0) / grid.total_m.max(1.0);
+
+    let mut elevations = Vec::with_capacity(grid.distances.len());
+    let mut offset_sum = 0.0;
+    let mut matched = 0;
+
+    for (&grid_distance, &(lat, lon)) in grid.distances.iter().zip(&grid.positions) {
+        let guess = grid_distance * scale;
+        let lo = distances.partition_point(|&d| d < guess - ALIGN_WINDOW_M).saturating_sub(1);
+        let hi = distances.partition_point(|&d| d <= guess + ALIGN_WINDOW_M).min(points.len().saturating_sub(1));
+
+        let mut best: Option<(f64, f64)> = None;  // (offset, elevation)
+        for i in lo..hi {
+            let (a, b) = (&points[i], &points[i + 1]);
+            let (t, offset) = project_onto_segment((lat, lon), (a.latitude, a.longitude), (b.latitude, b.longitude));
+            if best.is_none_or(|(best_offset, _)| offset < best_offset) {
+                let elevation = points[i].elevation + t * (points[i + 1].elevation - points[i].elevation);
+                best = Some((offset, elevation));
+            }
+        }
+
+        match best.filter(|&(offset, _)| offset <= MAX_POINT_OFFSET_M) {
+            Some((offset, elevation)) => {
+                offset_sum += offset;
+                matched += 1;
+                elevations.push(Some(elevation));
+            }
+            None => elevations.push(None),
+        }
+    }
+
+    Alignment {
+        mean_offset_m: if matched > 0 { offset_sum / matched as f64 } else { f64::INFINITY },
+        coverage: matched as f64 / grid.distances.len().max(1) as f64,
+        elevations,
+    }
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    if values.is_empty() {
+        return f64::NAN;
+    }
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) { (values[mid - 1] + values[mid]) / 2.0 } else { values[mid] }
+}
+
+/// Per-grid-point median over the given recordings, skipping their gaps
+fn median_profile(series: &[&Vec<Option<f64>>], length: usize) -> Vec<f64> {
+    (0..length)
+        .map(|i| {
+            let mut values: Vec<f64> = series.iter().filter_map(|s| s[i]).collect();
+            median(&mut values)
+        })
+        .collect()
+}
+
+/// Fill grid points nobody covered from their neighbours so the smoother sees a full profile
+fn fill_gaps(profile: &mut [f64]) {
+    let known: Vec<usize> = (0..profile.len()).filter(|&i| profile[i].is_finite()).collect();
+    let (Some(&first), Some(&last)) = (known.first(), known.last()) else {
+        return;
+    };
+    for i in 0..profile.len() {
+        if profile[i].is_finite() {
+            continue;
+        }
+        profile[i] = if i < first {
+            profile[first]
+        } else if i > last {
+            profile[last]
+        } else {
+            let next = known[known.partition_point(|&k| k < i)];
+            let prev = known[known.partition_point(|&k| k < i) - 1];
+            let t = (i - prev) as f64 / (next - prev) as f64;
+            profile[prev] + t * (profile[next] - profile[prev])
+        };
+    }
+}
+
+/// Build the consensus of several recordings of one course
+pub fn build_consensus(
+    tracks: &[LoadedTrack],
+    smoother: &dyn ElevationSmoother,
+    config: &ConsensusConfig,
+) -> Result<ConsensusProfile, Box<dyn std::error::Error>> {
+    let usable: Vec<&LoadedTrack> = tracks.iter().filter(|t| t.points.len() >= 2).collect();
+    if usable.len() < 2 {
+        return Err("a consensus needs at least two recordings of the course".into());
+    }
+
+    // Reference: the recording of median length
+    let mut by_length: Vec<&LoadedTrack> = usable.clone();
+    by_length.sort_by(|a, b| a.total_distance_km().partial_cmp(&b.total_distance_km()).unwrap());
+    let reference = by_length[by_length.len() / 2];
+    let grid = CourseGrid::new(reference, config.step_m);
+    let length = grid.distances.len();
+
+    let alignments: Vec<Alignment> = usable.par_iter().map(|track| align_recording(&grid, track)).collect();
+    let mut recordings: Vec<RecordingSummary> = usable.iter()
+        .zip(&alignments)
+        .map(|(track, alignment)| {
+            let rejection = if alignment.coverage < MIN_COVERAGE {
+                format!("covers {:.0}% of the course", alignment.coverage * 100.0)
+            } else if alignment.mean_offset_m > MAX_MEAN_OFFSET_M {
+                format!("{:.0}m from the course on average", alignment.mean_offset_m)
+            } else {
+                String::new()
+            };
+            RecordingSummary {
+                filename: track.filename.clone(),
+                distance_km: track.total_distance_km(),
+                coverage_percent: alignment.coverage * 100.0,
+                mean_offset_m: alignment.mean_offset_m,
+                elevation_bias_m: 0.0,
+                deviation_m: 0.0,
+                gain_m: smoother.smooth(&track.elevations(), &track.cumulative_distances).gain_m,
+                accepted: rejection.is_empty(),
+                rejection,
+            }
+        })
+        .collect();
+
+    // Bias against a first median, then deviation from the bias-free median
+    let on_course: Vec<usize> = (0..recordings.len()).filter(|&i| recordings[i].accepted).collect();
+    let first_pass = median_profile(&on_course.iter().map(|&i| &alignments[i].elevations).collect::<Vec<_>>(), length);
+    let mut adjusted: Vec<Vec<Option<f64>>> = Vec::with_capacity(alignments.len());
+    for (recording, alignment) in recordings.iter_mut().zip(&alignments) {
+        let mut offsets: Vec<f64> = alignment.elevations.iter()
+            .zip(&first_pass)
+            .filter_map(|(e, m)| Some(e.as_ref()? - m))
+            .filter(|d| d.is_finite())
+            .collect();
+        let bias = if offsets.is_empty() { 0.0 } else { median(&mut offsets) };
+        recording.elevation_bias_m = bias;
+        adjusted.push(alignment.elevations.iter().map(|e| e.map(|e| e - bias)).collect());
+    }
+
+    let debiased = median_profile(&on_course.iter().map(|&i| &adjusted[i]).collect::<Vec<_>>(), length);
+    for (recording, series) in recordings.iter_mut().zip(&adjusted) {
+        let squared: Vec<f64> = series.iter()
+            .zip(&debiased)
+            .filter_map(|(e, m)| Some((e.as_ref()? - m).powi(2)))
+            .filter(|d| d.is_finite())
+            .collect();
+        recording.deviation_m = (squared.iter().sum::<f64>() / squared.len().max(1) as f64).sqrt();
+    }
+
+    // Outliers: deviation far above the typical one among on-course recordings
+    let mut deviations: Vec<f64> = on_course.iter().map(|&i| recordings[i].deviation_m).collect();
+    let typical = median(&mut deviations);
+    let mut spreads: Vec<f64> = deviations.iter().map(|d| (d - typical).abs()).collect();
+    let limit = typical + (OUTLIER_MADS * MAD_TO_SIGMA * median(&mut spreads)).max(MIN_OUTLIER_MARGIN_M);
+    for &i in &on_course {
+        if recordings[i].deviation_m > limit {
+            recordings[i].accepted = false;
+            recordings[i].rejection = format!("{:.1}m RMS from the consensus (limit {:.1}m)", recordings[i].deviation_m, limit);
+        }
+    }
+
+    let accepted: Vec<&Vec<Option<f64>>> = (0..recordings.len())
+        .filter(|&i| recordings[i].accepted)
+        .map(|i| &adjusted[i])
+        .collect();
+    if accepted.len() < 2 {
+        return Err(format!("only {} recording(s) left after rejecting outliers", accepted.len()).into());
+    }
+
+    let mut elevations = Vec::with_capacity(length);
+    let mut spread = Vec::with_capacity(length);
+    let mut counts = Vec::with_capacity(length);
+    for i in 0..length {
+        let mut values: Vec<f64> = accepted.iter().filter_map(|s| s[i]).collect();
+        let center = median(&mut values);
+        let mut deviations: Vec<f64> = values.iter().map(|v| (v - center).abs()).collect();
+        elevations.push(center);
+        spread.push(if values.len() > 1 { MAD_TO_SIGMA * median(&mut deviations) } else { f64::NAN });
+        counts.push(values.len());
+    }
+    fill_gaps(&mut elevations);
+    fill_gaps(&mut spread);
+    // Standard error of a median is ~1.25x that of a mean
+    let (lower, upper): (Vec<f64>, Vec<f64>) = (0..length)
+        .map(|i| {
+            let half_width = 1.96 * 1.2533 * spread[i] / (counts[i].max(1) as f64).sqrt();
+            (elevations[i] - half_width, elevations[i] + half_width)
+        })
+        .unzip();
+
+    let profile = smoother.smooth(&elevations, &grid.distances);
+
+    // Bootstrap the recordings for the gain uncertainty
+    let mut gains: Vec<f64> = (0..config.bootstrap_samples)
+        .into_par_iter()
+        .map(|sample| {
+            let mut rng = SearchRng::new(config.seed.wrapping_add(sample as u64));
+            let resampled: Vec<&Vec<Option<f64>>> = (0..accepted.len())
+                .map(|_| accepted[(rng.next_u64() % accepted.len() as u64) as usize])
+                .collect();
+            let mut median_elevations = median_profile(&resampled, length);
+            fill_gaps(&mut median_elevations);
+            smoother.smooth(&median_elevations, &grid.distances).gain_m
+        })
+        .collect();
+    gains.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let (gain_std_m, gain_low_m, gain_high_m) = if gains.is_empty() {
+        (0.0, profile.gain_m, profile.gain_m)
+    } else {
+        let mean = gains.iter().sum::<f64>() / gains.len() as f64;
+        let variance = gains.iter().map(|g| (g - mean).powi(2)).sum::<f64>() / gains.len() as f64;
+        let percentile = |p: f64| gains[((gains.len() - 1) as f64 * p).round() as usize];
+        (variance.sqrt(), percentile(0.025), percentile(0.975))
+    };
+
+    Ok(ConsensusProfile {
+        reference: reference.filename.clone(),
+        distances: grid.distances,
+        positions: grid.positions,
+        elevations,
+        lower,
+        upper,
+        spread,
+        counts,
+        recordings,
+        gain_m: profile.gain_m,
+        loss_m: profile.loss_m,
+        gain_std_m,
+        gain_low_m,
+        gain_high_m,
+    })
+}
+
+fn write_consensus_gpx(consensus: &ConsensusProfile, course_name: &str, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut track = Track::new();
+    track.name = Some(format!("{} (consensus)", course_name));
+    track.description = Some(format!(
+        "Consensus of {} recordings: {:.0}m gain (95% {:.0}-{:.0}m), {:.0}m loss",
+        consensus.accepted_count(), consensus.gain_m, consensus.gain_low_m, consensus.gain_high_m, consensus.loss_m
+    ));
+
+    let mut segment = TrackSegment::new();
+    for (&(lat, lon), &elevation) in consensus.positions.iter().zip(&consensus.elevations) {
+        let mut waypoint = Waypoint::new(point!(x: lon, y: lat));
+        waypoint.elevation = Some(elevation);
+        segment.points.push(waypoint);
+    }
+    track.segments.push(segment);
+
+    let gpx = Gpx {
+        version: GpxVersion::Gpx11,
+        creator: Some("rust-gpx-smoother consensus".to_string()),
+        tracks: vec![track],
+        ..Default::default()
+    };
+    write(&gpx, File::create(path)?)?;
+    Ok(())
+}
+
+fn write_consensus_csv(consensus: &ConsensusProfile, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut wtr = Writer::from_path(path)?;
+    wtr.write_record([
+        "Distance_m",
+        "Latitude",
+        "Longitude",
+        "Elevation_m",
+        "Lower_95_m",
+        "Upper_95_m",
+        "Spread_m",
+        "Recordings",
+    ])?;
+    for i in 0..consensus.distances.len() {
+        wtr.write_record([
+            &format!("{:.1}", consensus.distances[i]),
+            &format!("{:.7}", consensus.positions[i].0),
+            &format!("{:.7}", consensus.positions[i].1),
+            &format!("{:.2}", consensus.elevations[i]),
+            &format!("{:.2}", consensus.lower[i]),
+            &format!("{:.2}", consensus.upper[i]),
+            &format!("{:.2}", consensus.spread[i]),
+            &consensus.counts[i].to_string(),
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+fn print_consensus_summary(consensus: &ConsensusProfile, course_name: &str) {
+    println!("\n📊 CONSENSUS PROFILE: {}", course_name);
+    println!("==========================================");
+    println!("Reference course: {} ({:.2}km, {} grid points)",
+             consensus.reference, consensus.distances.last().copied().unwrap_or(0.0) / 1000.0, consensus.distances.len());
+    println!("\nRecording                                  | Dist km | Offset m | Bias m | RMS m | Gain m | Status");
+    println!("-------------------------------------------|---------|----------|--------|-------|--------|-------");
+    for r in &consensus.recordings {
+        println!("{:42} | {:7.2} | {:8.1} | {:6.1} | {:5.1} | {:6.0} | {}",
+                 r.filename, r.distance_km, r.mean_offset_m, r.elevation_bias_m, r.deviation_m, r.gain_m,
+                 if r.accepted { "✅ used".to_string() } else { format!("❌ {}", r.rejection) });
+    }
+
+    let mean_spread = consensus.spread.iter().sum::<f64>() / consensus.spread.len().max(1) as f64;
+    println!("\n🎯 CONSENSUS ({} of {} recordings):", consensus.accepted_count(), consensus.recordings.len());
+    println!("  📈 Gain: {:.0}m ± {:.0}m (95% interval {:.0}-{:.0}m)",
+             consensus.gain_m, consensus.gain_std_m, consensus.gain_low_m, consensus.gain_high_m);
+    println!("  📉 Loss: {:.0}m", consensus.loss_m);
+    println!("  📏 Mean spread between recordings: {:.1}m", mean_spread);
+}
+
+/// Build a consensus from every track file in `input_folder` (one course per folder)
+pub fn run_consensus(
+    input_folder: &str,
+    output_folder: &str,
+    smoother: &dyn ElevationSmoother,
+    config: &ConsensusConfig,
+) -> Result<ConsensusProfile, Box<dyn std::error::Error>> {
+    println!("\n🧭 CONSENSUS ELEVATION PROFILE");
+    println!("==============================");
+    println!("Smoother: {} | Grid step: {:.0}m | Bootstrap: {} samples", smoother.name(), config.step_m, config.bootstrap_samples);
+
+    let files: Vec<PathBuf> = WalkDir::new(input_folder)
+        .max_depth(1)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file() && is_supported_track_file(entry.path()))
+        .map(|entry| entry.into_path())
+        .collect();
+    let mut tracks: Vec<LoadedTrack> = files.par_iter()
+        .filter_map(|path| match load_track(path) {
+            Ok(track) => Some(track),
+            Err(e) => {
+                eprintln!("⚠️  Error loading {}: {}", path.display(), e);
+                None
+            }
+        })
+        .collect();
+    tracks.sort_by(|a, b| a.filename.cmp(&b.filename));
+    println!("📁 Loaded {} recordings from {}", tracks.len(), input_folder);
+
+    let consensus = build_consensus(&tracks, smoother, config)?;
+    let course_name = Path::new(input_folder)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "course".to_string());
+    print_consensus_summary(&consensus, &course_name);
+
+    std::fs::create_dir_all(output_folder)?;
+    let gpx_path = Path::new(output_folder).join("consensus_profile.gpx");
+    let profile_path = Path::new(output_folder).join("consensus_profile.csv");
+    let recordings_path = Path::new(output_folder).join("consensus_recordings.csv");
+    write_consensus_gpx(&consensus, &course_name, &gpx_path)?;
+    write_consensus_csv(&consensus, &profile_path)?;
+    let mut wtr = Writer::from_path(&recordings_path)?;
+    for recording in &consensus.recordings {
+        wtr.serialize(recording)?;
+    }
+    wtr.flush()?;
+
+    println!("\n📋 Ground-truth registry row (no official figure for this course):");
+    println!("   consensus_profile.gpx,{:.0},{:.0},{:.2},,,consensus of {} recordings,,low,\"95% interval {:.0}-{:.0}m\"",
+             consensus.gain_m, consensus.loss_m, consensus.distances.last().copied().unwrap_or(0.0) / 1000.0,
+             consensus.accepted_count(), consensus.gain_low_m, consensus.gain_high_m);
+    println!("\n📁 Reference GPX: {}", gpx_path.display());
+    println!("📁 Profile with confidence bands: {}", profile_path.display());
+    println!("📁 Recordings: {}", recordings_path.display());
+
+    Ok(consensus)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elevation_smoother::smoother_from_name;
+    use crate::track_loader::TrackPoint;
+
+    /// A 5km loop-free course with two climbs, sampled every `spacing_m` with noise
+    fn recording(name: &str, spacing_m: f64, noise_m: f64, bias_m: f64, lateral_deg: f64, seed: u64) -> LoadedTrack {
+        let mut rng = SearchRng::new(seed);
+        let count = (5000.0 / spacing_m) as usize + 1;
+        let points = (0..count)
+            .map(|i| {
+                let d = i as f64 * spacing_m;
+                TrackPoint {
+                    latitude: 46.0 + d / 111_320.0 + lateral_deg,
+                    longitude: 7.0 + lateral_deg,
+                    elevation: 500.0 + 40.0 * (d / 5000.0 * 2.0 * std::f64::consts::PI).sin().abs() + bias_m + noise_m * rng.normal(),
+                    time: None,
+                    distance: None,
+                }
+            })
+            .collect();
+        LoadedTrack::from_points(name.to_string(), points)
+    }
+
+    #[test]
+    fn test_consensus_rejects_outliers_and_recovers_gain() {
+        let tracks = vec![
+            recording("a.gpx", 5.0, 1.0, 0.0, 0.0, 1),
+            recording("b.gpx", 8.0, 1.0, 12.0, 0.0001, 2),
+            recording("c.gpx", 3.0, 1.0, -6.0, -0.0001, 3),
+            recording("d.gpx", 6.0, 1.0, 3.0, 0.0, 4),
+            recording("noisy.gpx", 5.0, 15.0, 0.0, 0.0, 5),
+            recording("elsewhere.gpx", 5.0, 1.0, 0.0, 0.01, 6),
+        ];
+        let smoother = smoother_from_name("symmetric-fixed", None).unwrap();
+        let config = ConsensusConfig { bootstrap_samples: 50, ..ConsensusConfig::default() };
+        let consensus = build_consensus(&tracks, smoother.as_ref(), &config).unwrap();
+
+        let rejected: Vec<&str> = consensus.recordings.iter()
+            .filter(|r| !r.accepted)
+            .map(|r| r.filename.as_str())
+            .collect();
+        assert_eq!(rejected, vec!["noisy.gpx", "elsewhere.gpx"]);
+
+        let b = consensus.recordings.iter().find(|r| r.filename == "b.gpx").unwrap();
+        assert!((b.elevation_bias_m - 12.0).abs() < 3.0);
+        // Two 40m climbs
+        assert!((consensus.gain_m - 80.0).abs() < 10.0, "gain {}", consensus.gain_m);
+        assert!(consensus.gain_low_m <= consensus.gain_m && consensus.gain_m <= consensus.gain_high_m);
+    }
+}
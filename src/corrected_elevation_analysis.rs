@@ -5,8 +5,10 @@
 /// 
 /// Proper Scoring Logic:
 /// 1. PRIMARY: Gain accuracy vs official elevation gain benchmark
-/// 2. SECONDARY: Loss should be close to gain value (what goes up, comes down)
-/// 3. COMBINED: Best method = highest gain accuracy + gain/loss balance
+/// 2. SECONDARY: Loss accuracy vs official loss where the registry has one; otherwise
+///    loss should be close to gain value (what goes up, comes down)
+/// 3. DISTANCE: Track distance vs official distance where the registry has one
+/// 4. COMBINED: Best method = highest gain accuracy + loss accuracy (or balance) + distance
 
 use std::path::Path;
 use std::collections::HashMap;
@@ -19,6 +21,9 @@ use std::sync::Arc;
 pub struct CorrectedFileResult {
     filename: String,
     official_gain_m: u32,
+    official_loss_m: Option<u32>,
+    official_distance_km: Option<f64>,
+    distance_accuracy: Option<f32>,
     
     // Raw unprocessed data
     raw_gain_m: f32,
@@ -80,6 +85,7 @@ pub struct CorrectedFileResult {
     best_method_name: String,
     best_gain_accuracy: f32,
     best_gain_loss_balance: f32,
+    best_loss_accuracy: Option<f32>,
     improvement_vs_current: f32,
     symmetric_improvement: f32, // How much better symmetric is vs old asymmetric
 }
@@ -91,6 +97,7 @@ struct MethodResult {
     loss: f32,
     gain_accuracy: f32,
     gain_loss_ratio: f32,
+    loss_accuracy: Option<f32>,
     combined_score: f32,
 }
 
@@ -100,6 +107,8 @@ struct GpxFileData {
     elevations: Vec<f64>,
     distances: Vec<f64>,
     official_gain: u32,
+    official_loss: Option<u32>,         // Only non-zero official losses
+    official_distance_km: Option<f64>,
 }
 
 pub fn run_corrected_elevation_analysis(gpx_folder: &str) -> Result<(), Box<dyn std::error::Error>> {
@@ -108,9 +117,10 @@ pub fn run_corrected_elevation_analysis(gpx_folder: &str) -> Result<(), Box<dyn
     println!("FIXED: Now uses symmetric deadband filtering to eliminate loss under-estimation");
     println!("PROPER SCORING LOGIC:");
     println!("1. PRIMARY: Gain accuracy vs official elevation gain");
-    println!("2. SECONDARY: Gain/loss balance (should be close to 1.0 ratio)");
-    println!("3. COMBINED: Best = highest gain accuracy + balanced gain/loss");
-    println!("4. COMPARISON: Old asymmetric vs New symmetric methods\n");
+    println!("2. SECONDARY: Loss accuracy vs official loss, or gain/loss balance (close to 1.0) without one");
+    println!("3. DISTANCE: Track distance vs official distance, where known");
+    println!("4. COMBINED: Best = highest gain accuracy + accurate loss + accurate distance");
+    println!("5. COMPARISON: Old asymmetric vs New symmetric methods\n");
     
     let start_time = std::time::Instant::now();
     
@@ -154,67 +164,44 @@ pub fn run_corrected_elevation_analysis(gpx_folder: &str) -> Result<(), Box<dyn
 }
 
 fn load_gpx_data(gpx_folder: &str) -> Result<(HashMap<String, GpxFileData>, Vec<String>), Box<dyn std::error::Error>> {
-    use std::fs::File;
-    use std::io::BufReader;
-    use gpx::read;
-    use geo::{HaversineDistance, point};
     use walkdir::WalkDir;
-    
+    use crate::track_loader::{load_track, is_supported_track_file};
+
     let mut gpx_data = HashMap::new();
     let mut valid_files = Vec::new();
-    let official_data = crate::load_official_elevation_data()?;
-    
+
+    let official_data = crate::load_ground_truth_registry()?;
+
     for entry in WalkDir::new(gpx_folder) {
         let entry = entry?;
-        if entry.file_type().is_file() && 
-           entry.path().extension().and_then(|s| s.to_str()) == Some("gpx") {
-            
-            let filename = entry.path().file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("")
-                .to_string();
-            
-            if let Ok(file) = File::open(entry.path()) {
-                let reader = BufReader::new(file);
-                if let Ok(gpx) = read(reader) {
-                    let mut coords: Vec<(f64, f64, f64)> = vec![];
-                    
-                    for track in gpx.tracks {
-                        for segment in track.segments {
-                            for pt in segment.points {
-                                if let Some(ele) = pt.elevation {
-                                    coords.push((pt.point().y(), pt.point().x(), ele));
-                                }
-                            }
-                        }
-                    }
-                    
-                    if !coords.is_empty() {
-                        let mut distances = vec![0.0];
-                        for i in 1..coords.len() {
-                            let a = point!(x: coords[i-1].1, y: coords[i-1].0);
-                            let b = point!(x: coords[i].1, y: coords[i].0);
-                            distances.push(distances[i-1] + a.haversine_distance(&b));
-                        }
-                        
-                        let elevations: Vec<f64> = coords.iter().map(|c| c.2).collect();
-                        let official_gain = official_data.get(&filename.to_lowercase()).copied().unwrap_or(0);
-                        
-                        if official_gain > 0 {
-                            gpx_data.insert(filename.clone(), GpxFileData {
-                                filename: filename.clone(),
-                                elevations,
-                                distances,
-                                official_gain,
-                            });
-                            valid_files.push(filename);
-                        }
-                    }
-                }
+        if entry.file_type().is_file() && is_supported_track_file(entry.path()) {
+            // GPX and FIT both land in the same point model; unreadable files are skipped
+            let track = match load_track(entry.path()) {
+                Ok(track) => track,
+                Err(_) => continue,
+            };
+            let filename = track.filename.clone();
+
+            let official = official_data.official_record(entry.path(), &track.coordinates());
+
+            if let Some(official) = official.filter(|r| r.gain_m > 0) { // Only include files with official data
+                let file_data = GpxFileData {
+                    elevations: track.elevations(),
+                    distances: track.cumulative_distances,
+                    filename: filename.clone(),
+                    official_gain: official.gain_m,
+                    official_loss: official.loss_m.filter(|&loss| loss > 0),
+                    official_distance_km: official.distance_km,
+                };
+
+                gpx_data.insert(filename.clone(), file_data);
+                valid_files.push(filename);
             }
         }
     }
-    
+
+    official_data.print_match_report();
+
     Ok((gpx_data, valid_files))
 }
 
@@ -240,6 +227,9 @@ fn process_files_corrected_symmetric_scoring(
 
 fn process_single_file_corrected_symmetric(file_data: &GpxFileData) -> CorrectedFileResult {
     let official_gain = file_data.official_gain as f32;
+    let loss_accuracy_of = |loss: f32| file_data.official_loss.map(|official| loss / official as f32 * 100.0);
+    let track_distance_km = file_data.distances.last().copied().unwrap_or(0.0) / 1000.0;
+    let distance_accuracy = file_data.official_distance_km.map(|official| (track_distance_km / official * 100.0) as f32);
     
     // Raw data
     let (raw_gain, raw_loss) = calculate_raw_gain_loss(&file_data.elevations);
@@ -265,20 +255,21 @@ fn process_single_file_corrected_symmetric(file_data: &GpxFileData) -> Corrected
     let (current_gain, current_loss) = apply_distance_based(&file_data.elevations, &file_data.distances, 3.0);
     let current_gain_accuracy = (current_gain / official_gain) * 100.0;
     let current_gain_loss_ratio = current_gain / current_loss.max(1.0);
-    let current_combined_score = calculate_combined_score(current_gain_accuracy, current_gain_loss_ratio);
+    let current_combined_score = calculate_combined_score(current_gain_accuracy, current_gain_loss_ratio, loss_accuracy_of(current_loss), distance_accuracy);
     
     // OLD asymmetric method (to demonstrate the problem)
     let (old_asym_gain, old_asym_loss) = apply_old_asymmetric_method(&file_data.elevations, &file_data.distances, 3.0);
     let old_asym_gain_accuracy = (old_asym_gain / official_gain) * 100.0;
     let old_asym_gain_loss_ratio = old_asym_gain / old_asym_loss.max(1.0);
-    let old_asym_combined_score = calculate_combined_score(old_asym_gain_accuracy, old_asym_gain_loss_ratio);
+    let old_asym_combined_score = calculate_combined_score(old_asym_gain_accuracy, old_asym_gain_loss_ratio, loss_accuracy_of(old_asym_loss), distance_accuracy);
     
     // Calculate scores for all methods
     let mut method_results: Vec<MethodResult> = methods.into_iter()
         .map(|(name, (gain, loss))| {
             let gain_accuracy = (gain / official_gain) * 100.0;
             let gain_loss_ratio = gain / loss.max(1.0);
-            let combined_score = calculate_combined_score(gain_accuracy, gain_loss_ratio);
+            let loss_accuracy = loss_accuracy_of(loss);
+            let combined_score = calculate_combined_score(gain_accuracy, gain_loss_ratio, loss_accuracy, distance_accuracy);
             
             MethodResult {
                 name: name.to_string(),
@@ -286,6 +277,7 @@ fn process_single_file_corrected_symmetric(file_data: &GpxFileData) -> Corrected
                 loss,
                 gain_accuracy,
                 gain_loss_ratio,
+                loss_accuracy,
                 combined_score,
             }
         })
@@ -302,6 +294,7 @@ fn process_single_file_corrected_symmetric(file_data: &GpxFileData) -> Corrected
             loss: 0.0,
             gain_accuracy: 0.0,
             gain_loss_ratio: 0.0,
+            loss_accuracy: None,
             combined_score: 0.0,
         });
     }
@@ -314,6 +307,9 @@ fn process_single_file_corrected_symmetric(file_data: &GpxFileData) -> Corrected
     CorrectedFileResult {
         filename: file_data.filename.clone(),
         official_gain_m: file_data.official_gain,
+        official_loss_m: file_data.official_loss,
+        official_distance_km: file_data.official_distance_km,
+        distance_accuracy,
         
         raw_gain_m: raw_gain,
         raw_loss_m: raw_loss,
@@ -370,6 +366,7 @@ fn process_single_file_corrected_symmetric(file_data: &GpxFileData) -> Corrected
         best_method_name: best_method.name.clone(),
         best_gain_accuracy: best_method.gain_accuracy,
         best_gain_loss_balance: best_method.gain_loss_ratio,
+        best_loss_accuracy: best_method.loss_accuracy,
         improvement_vs_current: improvement_vs_current,
         symmetric_improvement: symmetric_improvement,
     }
@@ -438,17 +435,35 @@ fn apply_distance_based(elevations: &[f64], distances: &[f64], interval: f64) ->
     (gain, loss)
 }
 
-fn calculate_combined_score(gain_accuracy: f32, gain_loss_ratio: f32) -> f32 {
-    // PRIMARY: Gain accuracy (weight: 70%) - closer to 100% is better
+fn calculate_combined_score(
+    gain_accuracy: f32,
+    gain_loss_ratio: f32,
+    loss_accuracy: Option<f32>,
+    distance_accuracy: Option<f32>
+) -> f32 {
+    // PRIMARY: Gain accuracy (weight: 70%, 60% with a distance target) - closer to 100% is better
     let gain_score = 100.0 - (gain_accuracy - 100.0).abs();
     
-    // SECONDARY: Gain/loss balance (weight: 30%) - closer to 1.0 ratio is better
-    let ideal_ratio = 1.0;
-    let ratio_distance = (gain_loss_ratio - ideal_ratio).abs();
-    let balance_score = (100.0 - ratio_distance * 20.0).max(0.0); // 20% penalty per 0.1 deviation
+    // SECONDARY (weight: 30%): Loss accuracy against the official loss when known. Without one,
+    // gain/loss balance stands in - closer to 1.0 ratio is better, which is wrong for
+    // point-to-point races, so it is only a proxy.
+    let loss_score = match loss_accuracy {
+        Some(accuracy) => (100.0 - (accuracy - 100.0).abs()).max(0.0),
+        None => {
+            let ideal_ratio = 1.0;
+            let ratio_distance = (gain_loss_ratio - ideal_ratio).abs();
+            (100.0 - ratio_distance * 20.0).max(0.0) // 20% penalty per 0.1 deviation
+        }
+    };
     
-    // Combined score (0-100 scale)
-    (gain_score * 0.7 + balance_score * 0.3).max(0.0)
+    // Combined score (0-100 scale); distance accuracy takes 10% from gain when known
+    match distance_accuracy {
+        Some(accuracy) => {
+            let distance_score = (100.0 - (accuracy - 100.0).abs()).max(0.0);
+            (gain_score * 0.6 + loss_score * 0.3 + distance_score * 0.1).max(0.0)
+        }
+        None => (gain_score * 0.7 + loss_score * 0.3).max(0.0),
+    }
 }
 
 fn calculate_raw_gain_loss(elevations: &[f64]) -> (f32, f32) {
@@ -593,9 +608,9 @@ fn write_detailed_results(results: &[CorrectedFileResult], output_path: &Path) -
     
     // Write header
     wtr.write_record(&[
-        "Filename", "Official_Gain_m",
+        "Filename", "Official_Gain_m", "Official_Loss_m", "Official_Distance_km", "Distance_Acc_%",
         "Raw_Gain_m", "Raw_Loss_m", "Raw_Gain_Acc_%", "Raw_Gain_Loss_Ratio",
-        "Best_Method", "Best_Gain_m", "Best_Loss_m", "Best_Gain_Acc_%", "Best_Ratio", "Best_Score",
+        "Best_Method", "Best_Gain_m", "Best_Loss_m", "Best_Gain_Acc_%", "Best_Ratio", "Best_Loss_Acc_%", "Best_Score",
         "Method2", "M2_Gain_m", "M2_Loss_m", "M2_Gain_Acc_%", "M2_Ratio", "M2_Score",
         "Method3", "M3_Gain_m", "M3_Loss_m", "M3_Gain_Acc_%", "M3_Ratio", "M3_Score",
         "Current_3m_Gain_m", "Current_3m_Loss_m", "Current_3m_Gain_Acc_%", "Current_3m_Ratio", "Current_3m_Score",
@@ -608,6 +623,9 @@ fn write_detailed_results(results: &[CorrectedFileResult], output_path: &Path) -
         wtr.write_record(&[
             &result.filename,
             &result.official_gain_m.to_string(),
+            &result.official_loss_m.map(|l| l.to_string()).unwrap_or_default(),
+            &result.official_distance_km.map(|d| format!("{:.2}", d)).unwrap_or_default(),
+            &result.distance_accuracy.map(|a| format!("{:.1}", a)).unwrap_or_default(),
             &format!("{:.1}", result.raw_gain_m),
             &format!("{:.1}", result.raw_loss_m),
             &format!("{:.1}", result.raw_gain_accuracy),
@@ -617,6 +635,7 @@ fn write_detailed_results(results: &[CorrectedFileResult], output_path: &Path) -
             &format!("{:.1}", result.method_1_loss_m),
             &format!("{:.1}", result.method_1_gain_accuracy),
             &format!("{:.2}", result.method_1_gain_loss_ratio),
+            &result.best_loss_accuracy.map(|a| format!("{:.1}", a)).unwrap_or_default(),
             &format!("{:.1}", result.method_1_combined_score),
             &result.method_2_name,
             &format!("{:.1}", result.method_2_gain_m),
@@ -784,4 +803,21 @@ fn print_corrected_analysis(results: &[CorrectedFileResult], summary_stats: &[Su
             println!("• Symmetric methods show realistic gain/loss ratios! 🎉");
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_official_loss_replaces_balance_proxy() {
+        // Point-to-point race: 500m up, 1500m down, processed exactly
+        let ratio = 500.0 / 1500.0;
+        let proxy = calculate_combined_score(100.0, ratio, None, None);
+        let direct = calculate_combined_score(100.0, ratio, Some(100.0), None);
+        assert!((proxy - 96.0).abs() < 1e-3);
+        assert!((direct - 100.0).abs() < 1e-4);
+
+        let off_course = calculate_combined_score(100.0, ratio, Some(100.0), Some(80.0));
+        assert!((off_course - 98.0).abs() < 1e-4);
+    }
+}
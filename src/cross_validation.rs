@@ -0,0 +1,393 @@
+/// CROSS-VALIDATION - Held-out accuracy for tuned smoothing parameters
+/// Parameters picked on the same files they are scored on look better than they are.
+/// Routes are split into folds stratified by terrain class; each fold's parameters are
+/// tuned on the other folds and scored on the held-out fold, so every file gets exactly
+/// one out-of-fold result. The gap between in-fold and out-of-fold error is the optimism
+/// of the tuning.
+use std::collections::HashMap;
+use std::path::Path;
+use csv::Writer;
+use serde::Serialize;
+use crate::elevation_smoother::{smoother_from_name, smoother_from_parameters, smoother_parameter_ranges};
+use crate::parameter_optimizer::{
+    score_route_results, ElevationOptimizer, OptimizationParameters, RouteResult, TerrainType, TestRoute,
+};
+use crate::search_strategy::{SearchBudget, SearchRng, SearchStrategy};
+
+const TERRAIN_ORDER: [TerrainType; 4] = [TerrainType::Flat, TerrainType::Rolling, TerrainType::Hilly, TerrainType::Mountainous];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FoldScheme {
+    KFold(usize),
+    LeaveOneOut,
+}
+
+impl FoldScheme {
+    pub fn fold_count(&self, route_count: usize) -> usize {
+        match self {
+            FoldScheme::KFold(k) => (*k).clamp(2, route_count.max(2)),
+            FoldScheme::LeaveOneOut => route_count,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            FoldScheme::KFold(k) => format!("{}-fold", k),
+            FoldScheme::LeaveOneOut => "leave-one-out".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct FoldResult {
+    pub fold: usize,
+    pub train_files: usize,
+    pub test_files: usize,
+    pub parameters: String,
+    pub in_fold_mean_error: f64,
+    pub in_fold_within_5_percent: f64,  // Share of training files, %
+    pub out_of_fold_mean_error: f64,
+    pub out_of_fold_max_error: f64,
+    pub out_of_fold_within_5_percent: f64,  // Share of held-out files, %
+}
+
+#[derive(Debug, Clone)]
+pub struct CrossValidationResult {
+    pub method: String,
+    pub scheme: FoldScheme,
+    pub folds: Vec<FoldResult>,
+    pub out_of_fold: Vec<(usize, RouteResult)>,  // (fold, held-out result), one per route
+    pub in_fold_mean_error: f64,       // Training error averaged over folds
+    pub out_of_fold_mean_error: f64,   // Pooled over every held-out file
+}
+
+impl CrossValidationResult {
+    pub fn optimism_gap(&self) -> f64 {
+        self.out_of_fold_mean_error - self.in_fold_mean_error
+    }
+
+    pub fn out_of_fold_within_5_percent(&self) -> usize {
+        self.out_of_fold.iter().filter(|(_, r)| r.error <= 5.0).count()
+    }
+}
+
+/// Fold index for every route: routes of each terrain class are shuffled (seeded) and
+/// dealt round-robin, continuing across classes, so every fold gets its share of each
+pub fn stratified_folds(routes: &[TestRoute], scheme: FoldScheme, seed: u64) -> Vec<usize> {
+    let fold_count = scheme.fold_count(routes.len());
+    let mut rng = SearchRng::new(seed);
+    let mut assignment = vec![0; routes.len()];
+    let mut dealt = 0;
+
+    for terrain in &TERRAIN_ORDER {
+        let mut indices: Vec<usize> = (0..routes.len())
+            .filter(|&i| routes[i].terrain_type == *terrain)
+            .collect();
+        // Fisher-Yates
+        for i in (1..indices.len()).rev() {
+            let j = (rng.next_u64() % (i as u64 + 1)) as usize;
+            indices.swap(i, j);
+        }
+        for index in indices {
+            assignment[index] = dealt % fold_count;
+            dealt += 1;
+        }
+    }
+
+    assignment
+}
+
+/// Tune on each fold's training routes, score on its held-out routes. `smoother` picks
+/// a smoother to tune (or just evaluate, if it has no parameters); None tunes the
+/// terrain-adaptive spike/deadzone parameters.
+pub fn cross_validate(
+    routes: &[TestRoute],
+    smoother: Option<&str>,
+    scheme: FoldScheme,
+    strategy: SearchStrategy,
+    budget: SearchBudget,
+    seed: u64,
+) -> Result<CrossValidationResult, Box<dyn std::error::Error>> {
+    if routes.len() < 2 {
+        return Err("cross-validation needs at least two routes with official data".into());
+    }
+
+    let assignment = stratified_folds(routes, scheme, seed);
+    let fold_count = scheme.fold_count(routes.len());
+    let method = smoother.unwrap_or("spike-deadzone (terrain-adaptive)").to_string();
+
+    let mut folds = Vec::new();
+    let mut out_of_fold = Vec::new();
+
+    for fold in 0..fold_count {
+        let routes_where = |held_out: bool| -> Vec<TestRoute> {
+            routes.iter()
+                .zip(&assignment)
+                .filter(|(_, &route_fold)| (route_fold == fold) == held_out)
+                .map(|(route, _)| route.clone())
+                .collect()
+        };
+        let (train, test) = (routes_where(false), routes_where(true));
+        if test.is_empty() || train.is_empty() {
+            continue;
+        }
+        println!("📂 Fold {}/{}: tuning on {} files, holding out {}", fold + 1, fold_count, train.len(), test.len());
+
+        let train_optimizer = ElevationOptimizer::new(train);
+        let test_optimizer = ElevationOptimizer::new(test);
+        let fold_seed = seed.wrapping_add(fold as u64);
+
+        let (parameters, in_fold, held_out) = match smoother {
+            Some(name) => match smoother_parameter_ranges(name) {
+                Some(ranges) => {
+                    let (search, _) = train_optimizer.search_smoother(name, strategy, budget, fold_seed)?;
+                    let values = search.best().map(|e| e.values.clone()).ok_or("search made no evaluations")?;
+                    let tuned = smoother_from_parameters(name, &values)?;
+                    let parameters = ranges.iter()
+                        .zip(&values)
+                        .map(|(r, v)| format!("{}={:.4}", r.name, v))
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    (parameters, train_optimizer.smoother_route_results(tuned.as_ref()), test_optimizer.smoother_route_results(tuned.as_ref()))
+                }
+                None => {
+                    let fixed = smoother_from_name(name, None)?;
+                    (fixed.name(), train_optimizer.smoother_route_results(fixed.as_ref()), test_optimizer.smoother_route_results(fixed.as_ref()))
+                }
+            },
+            None => {
+                let (_, best) = train_optimizer.search(strategy, budget, fold_seed);
+                let held_out = test_optimizer.evaluate_parameters(&best.params).route_results;
+                (describe_parameters(&best.params), best.route_results, held_out)
+            }
+        };
+
+        let (in_fold_mean_error, _, in_fold_within_5, _, _) = score_route_results(&in_fold);
+        let (out_of_fold_mean_error, out_of_fold_max_error, out_of_fold_within_5, _, _) = score_route_results(&held_out);
+        folds.push(FoldResult {
+            fold: fold + 1,
+            train_files: in_fold.len(),
+            test_files: held_out.len(),
+            parameters,
+            in_fold_mean_error,
+            in_fold_within_5_percent: in_fold_within_5 as f64 / in_fold.len() as f64 * 100.0,
+            out_of_fold_mean_error,
+            out_of_fold_max_error,
+            out_of_fold_within_5_percent: out_of_fold_within_5 as f64 / held_out.len() as f64 * 100.0,
+        });
+        out_of_fold.extend(held_out.into_iter().map(|r| (fold + 1, r)));
+    }
+
+    let in_fold_mean_error = folds.iter().map(|f| f.in_fold_mean_error).sum::<f64>() / folds.len().max(1) as f64;
+    let out_of_fold_results: Vec<RouteResult> = out_of_fold.iter().map(|(_, r)| r.clone()).collect();
+    let (out_of_fold_mean_error, _, _, _, _) = score_route_results(&out_of_fold_results);
+
+    Ok(CrossValidationResult {
+        method,
+        scheme,
+        folds,
+        out_of_fold,
+        in_fold_mean_error,
+        out_of_fold_mean_error,
+    })
+}
+
+fn describe_parameters(params: &OptimizationParameters) -> String {
+    format!(
+        "spike={:.2}/{:.2}/{:.2}/{:.2} gain={:.3} loss={:.3} cap={:.1}",
+        params.flat_spike_threshold,
+        params.rolling_spike_threshold,
+        params.hilly_spike_threshold,
+        params.mountainous_spike_threshold,
+        params.gain_threshold,
+        params.loss_threshold,
+        params.gradient_cap
+    )
+}
+
+fn print_cross_validation_summary(result: &CrossValidationResult) {
+    println!("\n📊 CROSS-VALIDATION RESULTS: {} ({})", result.method, result.scheme.name());
+    println!("==========================================");
+    println!("Fold | Train | Test | In-fold Err | Out-of-fold Err | OOF ±5%  | Parameters");
+    println!("-----|-------|------|-------------|-----------------|----------|-----------");
+    for fold in &result.folds {
+        println!("{:4} | {:5} | {:4} | {:10.2}% | {:14.2}% | {:7.1}% | {}",
+                 fold.fold, fold.train_files, fold.test_files,
+                 fold.in_fold_mean_error, fold.out_of_fold_mean_error,
+                 fold.out_of_fold_within_5_percent, fold.parameters);
+    }
+
+    println!("\n🎯 SUMMARY:");
+    println!("  📈 In-fold mean error:      {:.2}%", result.in_fold_mean_error);
+    println!("  📉 Out-of-fold mean error:  {:.2}%", result.out_of_fold_mean_error);
+    println!("  ⚠️  Optimism gap:            {:+.2} percentage points", result.optimism_gap());
+    println!("  ✅ Out-of-fold within ±5%:  {}/{} files",
+             result.out_of_fold_within_5_percent(), result.out_of_fold.len());
+
+    println!("\n📈 OUT-OF-FOLD ERROR BY TERRAIN TYPE:");
+    let mut by_terrain: HashMap<&'static str, Vec<f64>> = HashMap::new();
+    for (_, route) in &result.out_of_fold {
+        by_terrain.entry(route.terrain.as_str()).or_default().push(route.error);
+    }
+    for terrain in &TERRAIN_ORDER {
+        if let Some(errors) = by_terrain.get(terrain.as_str()) {
+            println!("  {:12}: {:.2}% avg error ({} files)",
+                     terrain.as_str(), errors.iter().sum::<f64>() / errors.len() as f64, errors.len());
+        }
+    }
+}
+
+fn write_cross_validation_results(result: &CrossValidationResult, output_folder: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let folds_path = output_folder.join("cross_validation_folds.csv");
+    let mut wtr = Writer::from_path(&folds_path)?;
+    for fold in &result.folds {
+        wtr.serialize(fold)?;
+    }
+    wtr.flush()?;
+
+    let files_path = output_folder.join("cross_validation_files.csv");
+    let mut wtr = Writer::from_path(&files_path)?;
+    wtr.write_record([
+        "Filename",
+        "Terrain",
+        "Fold",
+        "Official_Gain_m",
+        "Processed_Gain_m",
+        "Accuracy_%",
+        "Absolute_Error_m",
+    ])?;
+    for (fold, route) in &result.out_of_fold {
+        wtr.write_record([
+            &route.filename,
+            route.terrain.as_str(),
+            &fold.to_string(),
+            &route.official.to_string(),
+            &format!("{:.1}", route.processed),
+            &format!("{:.2}", route.accuracy),
+            &format!("{:.1}", (route.processed - route.official as f64).abs()),
+        ])?;
+    }
+    wtr.flush()?;
+
+    println!("\n💾 Fold results saved to: {}", folds_path.display());
+    println!("💾 Out-of-fold file results saved to: {}", files_path.display());
+    Ok(())
+}
+
+pub fn run_cross_validation(
+    gpx_folder: &str,
+    smoother: Option<&str>,
+    scheme: FoldScheme,
+    strategy: SearchStrategy,
+    budget: SearchBudget,
+    seed: u64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!("\n🧪 CROSS-VALIDATION OF TUNED PARAMETERS");
+    println!("=======================================");
+    println!("🔬 Scheme: {} (stratified by terrain) | Tuning: {} with {} evaluations | Seed: {}",
+             scheme.name(), strategy.name(), budget.max_evaluations, seed);
+
+    let official_data = crate::load_ground_truth_registry()?;
+    let optimizer = ElevationOptimizer::from_folder(gpx_folder, &official_data)?;
+
+    let result = cross_validate(optimizer.test_routes(), smoother, scheme, strategy, budget, seed)?;
+
+    print_cross_validation_summary(&result);
+    write_cross_validation_results(&result, Path::new(gpx_folder))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(index: usize, terrain_type: TerrainType) -> TestRoute {
+        TestRoute {
+            filename: format!("route_{}.gpx", index),
+            distance_km: 10.0,
+            official_gain: 100,
+            terrain_type,
+            raw_gain: 0.0,
+            elevations: vec![],
+            distances: vec![],
+        }
+    }
+
+    fn noisy_route(index: usize, official_gain: u32) -> TestRoute {
+        let distances: Vec<f64> = (0..2000).map(|i| i as f64 * 5.0).collect();
+        // 100m climb and descent with +-0.1m alternating GPS noise, so the deadzone
+        // thresholds decide how much of the noise counts as gain
+        let elevations: Vec<f64> = distances.iter()
+            .enumerate()
+            .map(|(i, d)| 200.0 + 100.0 * (d / 10000.0 * std::f64::consts::PI).sin() + if i % 2 == 0 { 0.1 } else { -0.1 })
+            .collect();
+        let (raw_gain, _) = crate::track_loader::calculate_gain_loss(&elevations);
+        TestRoute {
+            filename: format!("route_{}.gpx", index),
+            distance_km: 10.0,
+            official_gain,
+            terrain_type: TerrainType::from_gain_per_km(raw_gain / 10.0),
+            raw_gain,
+            elevations,
+            distances,
+        }
+    }
+
+    #[test]
+    fn test_cross_validate_holds_out_every_route_once() {
+        // Identical tracks with scattered official gains: whatever the tuning fits on the
+        // training routes, the held-out route's gain was not part of it
+        let routes: Vec<TestRoute> = [110, 140, 170, 200, 230, 260]
+            .iter()
+            .enumerate()
+            .map(|(i, &gain)| noisy_route(i, gain))
+            .collect();
+        let (scheme, budget, seed) = (FoldScheme::KFold(3), SearchBudget { max_evaluations: 30, max_duration: None }, 7);
+
+        let result = cross_validate(&routes, None, scheme, SearchStrategy::Random, budget, seed).unwrap();
+
+        // Every route is held out exactly once, in the fold it was assigned to
+        let assignment = stratified_folds(&routes, scheme, seed);
+        let mut held_out: Vec<(String, usize)> = result.out_of_fold.iter().map(|(fold, r)| (r.filename.clone(), *fold)).collect();
+        held_out.sort();
+        let expected: Vec<(String, usize)> = routes.iter().zip(&assignment).map(|(r, &fold)| (r.filename.clone(), fold + 1)).collect();
+        assert_eq!(held_out, expected);
+
+        // Each fold's parameters are what tuning on its training routes alone picks
+        assert_eq!(result.folds.len(), 3);
+        for fold in &result.folds {
+            let train: Vec<TestRoute> = routes.iter()
+                .zip(&assignment)
+                .filter(|(_, &route_fold)| route_fold + 1 != fold.fold)
+                .map(|(route, _)| route.clone())
+                .collect();
+            assert_eq!((fold.train_files, fold.test_files), (4, 2));
+            let (_, best) = ElevationOptimizer::new(train).search(SearchStrategy::Random, budget, seed.wrapping_add(fold.fold as u64 - 1));
+            assert_eq!(fold.parameters, describe_parameters(&best.params));
+        }
+
+        assert!(result.out_of_fold_mean_error >= result.in_fold_mean_error);
+        assert!(result.optimism_gap() >= 0.0);
+    }
+
+    #[test]
+    fn test_folds_are_stratified_by_terrain() {
+        let routes: Vec<TestRoute> = (0..12)
+            .map(|i| route(i, if i < 8 { TerrainType::Flat } else { TerrainType::Hilly }))
+            .collect();
+
+        let folds = stratified_folds(&routes, FoldScheme::KFold(4), 3);
+
+        for fold in 0..4 {
+            let flat = (0..12).filter(|&i| folds[i] == fold && routes[i].terrain_type == TerrainType::Flat).count();
+            let hilly = (0..12).filter(|&i| folds[i] == fold && routes[i].terrain_type == TerrainType::Hilly).count();
+            assert_eq!((flat, hilly), (2, 1));
+        }
+
+        let loo = stratified_folds(&routes, FoldScheme::LeaveOneOut, 3);
+        let mut sorted = loo.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..12).collect::<Vec<_>>());
+    }
+}
@@ -22,6 +22,50 @@ pub enum SmoothingVariant {
     PostCap,    // 5-point + capping + 83-point post-capping smoothing
     DistBased,  // Distance-based uniform resampling + distance-aware processing
     SymmetricFixed, // NEW: Distance-based with symmetric deadband filtering (FIXED VERSION)
+    DemFusion,  // SymmetricFixed processing of a GPS/DEM fused profile (see dem_fusion)
+    Kalman,     // Elevation + grade state-space model: Kalman filter + RTS smoother, noise estimated from the data
+}
+
+impl SmoothingVariant {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SmoothingVariant::Original => "original",
+            SmoothingVariant::Capping => "capping",
+            SmoothingVariant::Flat21 => "flat21",
+            SmoothingVariant::PostCap => "postcap",
+            SmoothingVariant::DistBased => "distbased",
+            SmoothingVariant::SymmetricFixed => "symmetric-fixed",
+            SmoothingVariant::DemFusion => "dem-fusion",
+            SmoothingVariant::Kalman => "kalman",
+        }
+    }
+}
+
+impl std::str::FromStr for SmoothingVariant {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Accept "SymmetricFixed", "symmetric-fixed", "symmetric_fixed", ...
+        let key: String = s.chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_lowercase();
+
+        match key.as_str() {
+            "original" => Ok(SmoothingVariant::Original),
+            "capping" => Ok(SmoothingVariant::Capping),
+            "flat21" => Ok(SmoothingVariant::Flat21),
+            "postcap" => Ok(SmoothingVariant::PostCap),
+            "distbased" => Ok(SmoothingVariant::DistBased),
+            "symmetricfixed" | "symmetric" => Ok(SmoothingVariant::SymmetricFixed),
+            "demfusion" | "fusion" => Ok(SmoothingVariant::DemFusion),
+            "kalman" | "kalmanrts" | "rts" => Ok(SmoothingVariant::Kalman),
+            _ => Err(format!(
+                "unknown smoothing variant '{}' (expected original, capping, flat21, postcap, distbased, symmetric-fixed, dem-fusion or kalman)",
+                s
+            )),
+        }
+    }
 }
 
 impl ElevationData {
@@ -411,6 +455,17 @@ impl ElevationData {
                 self.apply_distance_based_processing_symmetric();
                 return; // Skip the normal smoothing path
             },
+            
+            SmoothingVariant::DemFusion => {
+                // Elevations were already fused with the DEM; smooth them like SymmetricFixed
+                self.apply_distance_based_processing_symmetric();
+                return; // Skip the normal smoothing path
+            },
+            
+            SmoothingVariant::Kalman => {
+                self.apply_kalman_rts_processing();
+                return; // Skip the normal smoothing path
+            },
         }
         
         self.calculate_gradients();
@@ -419,6 +474,21 @@ impl ElevationData {
         let _smoothed_gain = self.accumulated_ascent.last().unwrap_or(&0.0).clone();
     }
     
+    /// Kalman filter + RTS smoother over the raw elevations; no windows or deadband
+    fn apply_kalman_rts_processing(&mut self) {
+        let result = crate::kalman_smoother::kalman_rts_smooth(
+            &self.enhanced_altitude,
+            &self.cumulative_distance,
+            None
+        );
+        
+        self.altitude_change = std::iter::once(0.0)
+            .chain(result.elevations.windows(2).map(|w| w[1] - w[0]))
+            .collect();
+        
+        self.recalculate_derived_values();
+    }
+    
     /// NEW: Distance-based processing with symmetric deadband filtering
     fn apply_distance_based_processing_symmetric(&mut self) {
         let original_gain = self.accumulated_ascent.last().unwrap_or(&0.0).clone();
@@ -537,7 +607,7 @@ impl ElevationData {
     
     fn apply_gradient_capping_variant(&mut self, variant: SmoothingVariant) {
         // Distance-based variants handle their own processing
-        if matches!(variant, SmoothingVariant::DistBased | SmoothingVariant::SymmetricFixed) {
+        if matches!(variant, SmoothingVariant::DistBased | SmoothingVariant::SymmetricFixed | SmoothingVariant::DemFusion | SmoothingVariant::Kalman) {
             return;
         }
         
@@ -672,7 +742,7 @@ impl ElevationData {
         self.apply_smoothing_variant(variant);
         
         // For distance-based variants, processing is complete at this point
-        if matches!(variant, SmoothingVariant::DistBased | SmoothingVariant::SymmetricFixed) {
+        if matches!(variant, SmoothingVariant::DistBased | SmoothingVariant::SymmetricFixed | SmoothingVariant::DemFusion | SmoothingVariant::Kalman) {
             let _final_gain = self.accumulated_ascent.last().unwrap_or(&0.0);
             return;
         }
@@ -0,0 +1,361 @@
+/// DEM ELEVATION - Terrain elevations for track points from local SRTM .hgt tiles
+/// Tiles are loaded lazily from one directory and sampled bilinearly, either to fill
+/// points the GPS left without elevation or to replace GPS elevations entirely.
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use gpx::Gpx;
+use serde::Serialize;
+use crate::error::TrackError;
+use crate::track_loader::{gpx_course_segments, gpx_course_segments_mut};
+
+// SRTM marks voids (no radar return) with the most negative 16-bit value
+const HGT_VOID: i16 = -32768;
+
+/// Anything that can answer "what is the terrain elevation here?"
+pub trait ElevationLookup: Send + Sync {
+    fn elevation_at(&self, latitude: f64, longitude: f64) -> Option<f64>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ElevationSource {
+    Gps,
+    Dem,
+    Missing,
+}
+
+impl ElevationSource {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ElevationSource::Gps => "gps",
+            ElevationSource::Dem => "dem",
+            ElevationSource::Missing => "missing",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DemMode {
+    #[default]
+    Fill,     // Only points without a GPS elevation
+    Replace,  // Every point the DEM covers
+}
+
+impl FromStr for DemMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fill" => Ok(DemMode::Fill),
+            "replace" => Ok(DemMode::Replace),
+            other => Err(format!("Unknown DEM mode '{}' (expected fill or replace)", other)),
+        }
+    }
+}
+
+/// Per-file tally of where each point's elevation came from
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct ElevationSourceCounts {
+    pub gps: usize,
+    pub dem: usize,
+    pub missing: usize,
+}
+
+impl ElevationSourceCounts {
+    pub fn record(&mut self, source: ElevationSource) {
+        match source {
+            ElevationSource::Gps => self.gps += 1,
+            ElevationSource::Dem => self.dem += 1,
+            ElevationSource::Missing => self.missing += 1,
+        }
+    }
+}
+
+/// A DEM plus how it should be applied
+pub struct DemCorrection {
+    pub lookup: Box<dyn ElevationLookup>,
+    pub mode: DemMode,
+}
+
+impl DemCorrection {
+    pub fn new(lookup: Box<dyn ElevationLookup>, mode: DemMode) -> Self {
+        DemCorrection { lookup, mode }
+    }
+
+    /// Set DEM elevations on every course point (track, else route or waypoints) and mark
+    /// them with `<src>DEM</src>`
+    pub fn apply_to_gpx(&self, gpx: &mut Gpx) -> ElevationSourceCounts {
+        let mut counts = ElevationSourceCounts::default();
+        for segment in gpx_course_segments_mut(gpx) {
+            for point in segment.iter_mut() {
+                let (lat, lon) = (point.point().y(), point.point().x());
+                let (elevation, source) = resolve_elevation(Some(self), lat, lon, point.elevation);
+                point.elevation = elevation;
+                if source == ElevationSource::Dem {
+                    point.source = Some("DEM".to_string());
+                }
+                counts.record(source);
+            }
+        }
+        counts
+    }
+}
+
+/// Several DEMs queried in order; the first one covering a point answers
+pub struct LayeredElevationLookup {
+    layers: Vec<Box<dyn ElevationLookup>>,
+}
+
+impl LayeredElevationLookup {
+    pub fn new(layers: Vec<Box<dyn ElevationLookup>>) -> Self {
+        LayeredElevationLookup { layers }
+    }
+}
+
+impl ElevationLookup for LayeredElevationLookup {
+    fn elevation_at(&self, latitude: f64, longitude: f64) -> Option<f64> {
+        self.layers.iter().find_map(|layer| layer.elevation_at(latitude, longitude))
+    }
+}
+
+/// Open a DEM directory: GeoTIFF rasters (high-resolution) first, SRTM .hgt tiles as fallback
+pub fn open_dem_directory(directory: impl Into<PathBuf>) -> Result<Box<dyn ElevationLookup>, Box<dyn std::error::Error>> {
+    let directory = directory.into();
+    let srtm = SrtmTileSet::new(&directory)?;
+    let geotiff = crate::geotiff_dem::GeoTiffDemSet::new(&directory)?;
+
+    if geotiff.raster_count() == 0 {
+        return Ok(Box::new(srtm));
+    }
+
+    println!("🗺️  Indexed {} GeoTIFF raster(s) in {}", geotiff.raster_count(), directory.display());
+    Ok(Box::new(LayeredElevationLookup::new(vec![Box::new(geotiff), Box::new(srtm)])))
+}
+
+/// Pick the elevation for one point: GPS, DEM (per the mode), or nothing
+pub fn resolve_elevation(
+    dem: Option<&DemCorrection>,
+    latitude: f64,
+    longitude: f64,
+    gps_elevation: Option<f64>,
+) -> (Option<f64>, ElevationSource) {
+    let wants_dem = match dem {
+        Some(dem) => dem.mode == DemMode::Replace || gps_elevation.is_none(),
+        None => false,
+    };
+
+    if wants_dem {
+        if let Some(elevation) = dem.and_then(|d| d.lookup.elevation_at(latitude, longitude)) {
+            return (Some(elevation), ElevationSource::Dem);
+        }
+    }
+
+    match gps_elevation {
+        Some(elevation) => (Some(elevation), ElevationSource::Gps),
+        None => (None, ElevationSource::Missing),
+    }
+}
+
+/// Tally sources for a GPX file that had no DEM applied
+pub fn count_gpx_sources(gpx: &Gpx) -> ElevationSourceCounts {
+    let mut counts = ElevationSourceCounts::default();
+    for point in gpx_course_segments(gpx).into_iter().flatten() {
+        if point.source.as_deref() == Some("DEM") && point.elevation.is_some() {
+            counts.record(ElevationSource::Dem);
+        } else if point.elevation.is_some() {
+            counts.record(ElevationSource::Gps);
+        } else {
+            counts.record(ElevationSource::Missing);
+        }
+    }
+    counts
+}
+
+/// One 1°x1° SRTM tile: a square grid of big-endian i16 meters, north row first
+struct HgtTile {
+    samples: usize,  // 1201 for 3 arc-second, 3601 for 1 arc-second
+    data: Vec<i16>,
+}
+
+impl HgtTile {
+    fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let values = bytes.len() / 2;
+        let samples = (values as f64).sqrt().round() as usize;
+        if !bytes.len().is_multiple_of(2) || samples < 2 || samples * samples != values {
+            return Err(format!("Not a square HGT grid ({} bytes)", bytes.len()));
+        }
+
+        let data = bytes.chunks_exact(2)
+            .map(|b| i16::from_be_bytes([b[0], b[1]]))
+            .collect();
+
+        Ok(HgtTile { samples, data })
+    }
+
+    fn sample(&self, row: usize, col: usize) -> Option<f64> {
+        let value = self.data[row * self.samples + col];
+        if value == HGT_VOID {
+            None
+        } else {
+            Some(value as f64)
+        }
+    }
+
+    /// Bilinear interpolation inside the tile whose south-west corner is (south, west)
+    fn elevation_at(&self, latitude: f64, longitude: f64, south: f64, west: f64) -> Option<f64> {
+        let last = (self.samples - 1) as f64;
+        let row = ((south + 1.0 - latitude) * last).clamp(0.0, last);
+        let col = ((longitude - west) * last).clamp(0.0, last);
+
+        let row0 = (row.floor() as usize).min(self.samples - 2);
+        let col0 = (col.floor() as usize).min(self.samples - 2);
+        let dr = row - row0 as f64;
+        let dc = col - col0 as f64;
+
+        let corners = [
+            (self.sample(row0, col0), (1.0 - dr) * (1.0 - dc)),
+            (self.sample(row0, col0 + 1), (1.0 - dr) * dc),
+            (self.sample(row0 + 1, col0), dr * (1.0 - dc)),
+            (self.sample(row0 + 1, col0 + 1), dr * dc),
+        ];
+
+        // Voids drop out and the remaining weights are renormalized
+        let mut weighted = 0.0;
+        let mut weight_sum = 0.0;
+        for (value, weight) in corners {
+            if let Some(v) = value {
+                weighted += v * weight;
+                weight_sum += weight;
+            }
+        }
+
+        if weight_sum > 1e-9 {
+            Some(weighted / weight_sum)
+        } else {
+            None
+        }
+    }
+}
+
+// Keyed by the tile's south-west corner; None remembers tiles that are absent or unreadable
+type TileCache = HashMap<(i32, i32), Option<Arc<HgtTile>>>;
+
+/// Directory of SRTM tiles named like N46E007.hgt
+pub struct SrtmTileSet {
+    directory: PathBuf,
+    tiles: Mutex<TileCache>,
+}
+
+impl SrtmTileSet {
+    pub fn new(directory: impl Into<PathBuf>) -> Result<Self, Box<dyn std::error::Error>> {
+        let directory = directory.into();
+        if !directory.is_dir() {
+            return Err(TrackError::io(&directory, io::Error::new(io::ErrorKind::NotFound, "DEM directory not found")).into());
+        }
+        Ok(SrtmTileSet {
+            directory,
+            tiles: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Standard SRTM name for the tile whose south-west corner is (lat, lon)
+    pub fn tile_name(lat: i32, lon: i32) -> String {
+        format!(
+            "{}{:02}{}{:03}.hgt",
+            if lat >= 0 { 'N' } else { 'S' },
+            lat.abs(),
+            if lon >= 0 { 'E' } else { 'W' },
+            lon.abs()
+        )
+    }
+
+    fn tile(&self, lat: i32, lon: i32) -> Option<Arc<HgtTile>> {
+        let mut tiles = self.tiles.lock().unwrap();
+        tiles.entry((lat, lon))
+            .or_insert_with(|| {
+                let name = Self::tile_name(lat, lon);
+                // Tile archives are distributed with both upper- and lower-case names
+                [name.clone(), name.to_lowercase()].iter()
+                    .map(|n| self.directory.join(n))
+                    .find(|p| p.is_file())
+                    .and_then(|path| match fs::read(&path) {
+                        Ok(bytes) => match HgtTile::from_bytes(&bytes) {
+                            Ok(tile) => Some(Arc::new(tile)),
+                            Err(e) => {
+                                eprintln!("⚠️  Skipping DEM tile {}: {}", path.display(), e);
+                                None
+                            }
+                        },
+                        Err(e) => {
+                            eprintln!("⚠️  Could not read DEM tile {}: {}", path.display(), e);
+                            None
+                        }
+                    })
+            })
+            .clone()
+    }
+}
+
+impl ElevationLookup for SrtmTileSet {
+    fn elevation_at(&self, latitude: f64, longitude: f64) -> Option<f64> {
+        if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+            return None;
+        }
+        let south = latitude.floor();
+        let west = longitude.floor();
+        let tile = self.tile(south as i32, west as i32)?;
+        tile.elevation_at(latitude, longitude, south, west)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tile_from(values: &[i16]) -> HgtTile {
+        let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_be_bytes()).collect();
+        HgtTile::from_bytes(&bytes).unwrap()
+    }
+
+    #[test]
+    fn test_bilinear_sampling_and_voids() {
+        // 3x3 grid over N46E007: north row first
+        let tile = tile_from(&[
+            100, 200, 300,
+            100, 200, 300,
+            100, 200, HGT_VOID,
+        ]);
+
+        // North-west corner and the centre of the tile
+        assert_eq!(tile.elevation_at(47.0, 7.0, 46.0, 7.0), Some(100.0));
+        assert_eq!(tile.elevation_at(46.5, 7.5, 46.0, 7.0), Some(200.0));
+        // Halfway between the 100m and 200m columns
+        assert!((tile.elevation_at(46.75, 7.25, 46.0, 7.0).unwrap() - 150.0).abs() < 1e-9);
+        // The void corner is ignored rather than pulling the value towards -32768
+        let near_void = tile.elevation_at(46.1, 7.9, 46.0, 7.0).unwrap();
+        assert!((200.0..=300.0).contains(&near_void));
+    }
+
+    #[test]
+    fn test_fill_keeps_gps_and_replace_overrides() {
+        struct Flat;
+        impl ElevationLookup for Flat {
+            fn elevation_at(&self, _: f64, _: f64) -> Option<f64> {
+                Some(42.0)
+            }
+        }
+
+        let fill = DemCorrection::new(Box::new(Flat), DemMode::Fill);
+        assert_eq!(resolve_elevation(Some(&fill), 0.0, 0.0, Some(10.0)), (Some(10.0), ElevationSource::Gps));
+        assert_eq!(resolve_elevation(Some(&fill), 0.0, 0.0, None), (Some(42.0), ElevationSource::Dem));
+
+        let replace = DemCorrection::new(Box::new(Flat), DemMode::Replace);
+        assert_eq!(resolve_elevation(Some(&replace), 0.0, 0.0, Some(10.0)), (Some(42.0), ElevationSource::Dem));
+
+        assert_eq!(resolve_elevation(None, 0.0, 0.0, None), (None, ElevationSource::Missing));
+        assert_eq!(SrtmTileSet::tile_name(-12, -77), "S12W077.hgt");
+    }
+}
@@ -0,0 +1,290 @@
+/// DEM FUSION - Blend recorded GPS elevation with a DEM-sampled profile
+/// Neither source is right on its own: GPS is noisy and biased, the DEM sees tree canopy
+/// and the valley under a bridge. After removing the GPS bias against the DEM, each point
+/// is an inverse-variance blend of the two, weighted by their local noise. Short stretches
+/// where the DEM departs sharply from the GPS are treated as bridges or tunnels and crossed
+/// on a straight grade instead of following the terrain.
+use serde::Serialize;
+
+// Points either side of a point used for its local noise estimate
+const NOISE_HALF_WINDOW: usize = 12;
+// Noise floor so a perfectly smooth source never takes all the weight
+const MIN_NOISE_M: f64 = 0.1;
+// GPS/DEM divergence (after bias removal) that marks a possible structure
+pub const STRUCTURE_DIVERGENCE_M: f64 = 8.0;
+// Shorter runs are GPS spikes, longer ones are not a bridge or tunnel we can straighten
+pub const MIN_STRUCTURE_LENGTH_M: f64 = 15.0;
+pub const MAX_STRUCTURE_LENGTH_M: f64 = 2000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum StructureKind {
+    Bridge,  // GPS above the DEM: the terrain model drops into the valley below
+    Tunnel,  // GPS below the DEM: the terrain model climbs over the hill above
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StructureSegment {
+    pub kind: StructureKind,
+    pub start_index: usize,
+    pub end_index: usize,
+    pub start_distance_m: f64,
+    pub end_distance_m: f64,
+    pub max_divergence_m: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct FusionResult {
+    pub elevations: Vec<f64>,
+    pub gps_weights: Vec<f64>,  // 1.0 = pure GPS, 0.0 = pure DEM
+    pub gps_bias_m: f64,        // Median GPS - DEM offset removed before blending
+    pub dem_points: usize,
+    pub structures: Vec<StructureSegment>,
+}
+
+impl FusionResult {
+    pub fn mean_gps_weight(&self) -> f64 {
+        if self.gps_weights.is_empty() {
+            return 1.0;
+        }
+        self.gps_weights.iter().sum::<f64>() / self.gps_weights.len() as f64
+    }
+
+    pub fn structure_count(&self, kind: StructureKind) -> usize {
+        self.structures.iter().filter(|s| s.kind == kind).count()
+    }
+}
+
+/// Fraction of the total elevation variation that a 5-point moving average removes
+pub fn calculate_elevation_noise_ratio(elevations: &[f64]) -> f64 {
+    if elevations.len() < 10 {
+        return 0.0;
+    }
+
+    let total_variation: f64 = elevations.windows(2)
+        .map(|w| (w[1] - w[0]).abs())
+        .sum();
+
+    let window_size = 5;
+    let smoothed: Vec<f64> = (0..elevations.len())
+        .map(|i| {
+            let start = i.saturating_sub(window_size / 2);
+            let end = (i + window_size / 2 + 1).min(elevations.len());
+            elevations[start..end].iter().sum::<f64>() / (end - start) as f64
+        })
+        .collect();
+
+    let smooth_variation: f64 = smoothed.windows(2)
+        .map(|w| (w[1] - w[0]).abs())
+        .sum();
+
+    if total_variation > 0.0 {
+        (total_variation - smooth_variation) / total_variation
+    } else {
+        0.0
+    }
+}
+
+/// Local noise in meters per point: the noisy share of the variation around each point
+pub fn local_noise_levels(elevations: &[f64]) -> Vec<f64> {
+    (0..elevations.len())
+        .map(|i| {
+            let start = i.saturating_sub(NOISE_HALF_WINDOW);
+            let end = (i + NOISE_HALF_WINDOW + 1).min(elevations.len());
+            let window = &elevations[start..end];
+            if window.len() < 2 {
+                return MIN_NOISE_M;
+            }
+            let variation: f64 = window.windows(2).map(|w| (w[1] - w[0]).abs()).sum();
+            let per_point = variation / (window.len() - 1) as f64;
+            (calculate_elevation_noise_ratio(window) * per_point).max(MIN_NOISE_M)
+        })
+        .collect()
+}
+
+/// Fuse GPS elevations with DEM samples (None where the DEM has no coverage)
+pub fn fuse_gps_with_dem(gps: &[f64], dem: &[Option<f64>], distances: &[f64]) -> FusionResult {
+    let dem_points = dem.iter().filter(|d| d.is_some()).count();
+
+    if dem_points == 0 || gps.len() != dem.len() || gps.len() != distances.len() {
+        return FusionResult {
+            elevations: gps.to_vec(),
+            gps_weights: vec![1.0; gps.len()],
+            gps_bias_m: 0.0,
+            dem_points: 0,
+            structures: Vec::new(),
+        };
+    }
+
+    // Step 1: remove the GPS bias so both sources sit on the DEM datum
+    let mut offsets: Vec<f64> = gps.iter().zip(dem)
+        .filter_map(|(g, d)| d.map(|d| g - d))
+        .filter(|offset| offset.is_finite())
+        .collect();
+    offsets.sort_by(|a, b| a.total_cmp(b));
+    let gps_bias_m = offsets.get(offsets.len() / 2).copied().unwrap_or(0.0);
+    let corrected_gps: Vec<f64> = gps.iter().map(|g| g - gps_bias_m).collect();
+
+    // Step 2: inverse-variance blend from local noise (DEM gaps are filled from GPS for the estimate)
+    let dem_filled: Vec<f64> = dem.iter().zip(&corrected_gps)
+        .map(|(d, g)| d.unwrap_or(*g))
+        .collect();
+    let gps_noise = local_noise_levels(&corrected_gps);
+    let dem_noise = local_noise_levels(&dem_filled);
+
+    let mut gps_weights = Vec::with_capacity(gps.len());
+    let mut elevations = Vec::with_capacity(gps.len());
+    for i in 0..gps.len() {
+        match dem[i] {
+            Some(dem_elevation) => {
+                let gps_variance = gps_noise[i].powi(2);
+                let dem_variance = dem_noise[i].powi(2);
+                let weight = dem_variance / (gps_variance + dem_variance);
+                gps_weights.push(weight);
+                elevations.push(weight * corrected_gps[i] + (1.0 - weight) * dem_elevation);
+            }
+            None => {
+                gps_weights.push(1.0);
+                elevations.push(corrected_gps[i]);
+            }
+        }
+    }
+
+    // Step 3: straighten bridges and tunnels. Back-to-back structures (a bridge straight
+    // into a tunnel) share one grade, since each one's anchor point lies in the other.
+    let structures = detect_structures(&corrected_gps, dem, distances);
+    let mut first = 0;
+    while first < structures.len() {
+        let mut last = first;
+        while last + 1 < structures.len() && structures[last + 1].start_index == structures[last].end_index + 1 {
+            last += 1;
+        }
+        let before = structures[first].start_index - 1;
+        let after = structures[last].end_index + 1;
+        let span = distances[after] - distances[before];
+        for i in structures[first].start_index..=structures[last].end_index {
+            let t = if span > 0.0 { (distances[i] - distances[before]) / span } else { 0.0 };
+            elevations[i] = elevations[before] + t * (elevations[after] - elevations[before]);
+            gps_weights[i] = 1.0;
+        }
+        first = last + 1;
+    }
+
+    FusionResult {
+        elevations,
+        gps_weights,
+        gps_bias_m,
+        dem_points,
+        structures,
+    }
+}
+
+/// Runs where the bias-corrected GPS and the DEM disagree by more than
+/// STRUCTURE_DIVERGENCE_M in one direction, with a usable point on either side
+pub fn detect_structures(corrected_gps: &[f64], dem: &[Option<f64>], distances: &[f64]) -> Vec<StructureSegment> {
+    let divergence: Vec<Option<f64>> = corrected_gps.iter().zip(dem)
+        .map(|(g, d)| d.map(|d| g - d))
+        .collect();
+
+    let mut structures = Vec::new();
+    let mut i = 1;
+    while i + 1 < divergence.len() {
+        let Some(first) = divergence[i].filter(|d| d.abs() > STRUCTURE_DIVERGENCE_M) else {
+            i += 1;
+            continue;
+        };
+
+        let sign = first.signum();
+        let mut end = i;
+        let mut max_divergence = first.abs();
+        while end + 1 < divergence.len() {
+            match divergence[end + 1] {
+                Some(d) if d * sign > STRUCTURE_DIVERGENCE_M => {
+                    max_divergence = max_divergence.max(d.abs());
+                    end += 1;
+                }
+                _ => break,
+            }
+        }
+
+        let length = distances[end] - distances[i];
+        // A run reaching the last point has nothing to interpolate towards
+        if end + 1 < divergence.len() && (MIN_STRUCTURE_LENGTH_M..=MAX_STRUCTURE_LENGTH_M).contains(&length) {
+            structures.push(StructureSegment {
+                kind: if sign > 0.0 { StructureKind::Bridge } else { StructureKind::Tunnel },
+                start_index: i,
+                end_index: end,
+                start_distance_m: distances[i],
+                end_distance_m: distances[end],
+                max_divergence_m: max_divergence,
+            });
+        }
+        i = end + 1;
+    }
+
+    structures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fusion_removes_bias_and_straightens_bridge() {
+        let distances: Vec<f64> = (0..200).map(|i| i as f64 * 5.0).collect();
+        let terrain: Vec<f64> = distances.iter().map(|d| 300.0 + d * 0.02).collect();
+        // GPS reads 12m high with alternating noise; the DEM drops 30m into a valley at 400-500m
+        let gps: Vec<f64> = terrain.iter().enumerate()
+            .map(|(i, t)| t + 12.0 + if i % 2 == 0 { 1.5 } else { -1.5 })
+            .collect();
+        let dem: Vec<Option<f64>> = terrain.iter().zip(&distances)
+            .map(|(t, d)| Some(if (400.0..=500.0).contains(d) { t - 30.0 } else { *t }))
+            .collect();
+
+        let result = fuse_gps_with_dem(&gps, &dem, &distances);
+
+        assert!((result.gps_bias_m - 12.0).abs() < 2.0);
+        assert_eq!(result.structure_count(StructureKind::Bridge), 1);
+        assert_eq!(result.structure_count(StructureKind::Tunnel), 0);
+        // The deck follows the approach grade instead of dropping into the valley
+        let mid = 90;
+        assert!((result.elevations[mid] - terrain[mid]).abs() < 2.0);
+        // Noisy GPS defers to the clean DEM away from the structure
+        assert!(result.gps_weights[20] < 0.5);
+    }
+
+    #[test]
+    fn test_bridge_straight_into_tunnel_shares_one_grade() {
+        let distances: Vec<f64> = (0..200).map(|i| i as f64 * 5.0).collect();
+        let terrain: Vec<f64> = distances.iter().map(|d| 300.0 + d * 0.02).collect();
+        // The DEM drops into a valley at 400-500m, then climbs over a hill from 505m to 600m
+        let dem: Vec<Option<f64>> = terrain.iter().zip(&distances)
+            .map(|(t, d)| Some(match d {
+                d if (400.0..=500.0).contains(d) => t - 30.0,
+                d if (505.0..=600.0).contains(d) => t + 30.0,
+                _ => *t,
+            }))
+            .collect();
+
+        let result = fuse_gps_with_dem(&terrain, &dem, &distances);
+
+        assert_eq!(result.structure_count(StructureKind::Bridge), 1);
+        assert_eq!(result.structure_count(StructureKind::Tunnel), 1);
+        assert_eq!(result.structures[1].start_index, result.structures[0].end_index + 1);
+        for (i, (fused, expected)) in result.elevations.iter().zip(&terrain).enumerate().take(126).skip(75) {
+            assert!((fused - expected).abs() < 1.0, "point {}: {} vs {}", i, fused, expected);
+        }
+
+        // A NaN elevation must not panic the bias estimate
+        let mut with_nan = terrain.clone();
+        with_nan[10] = f64::NAN;
+        assert!(fuse_gps_with_dem(&with_nan, &dem, &distances).gps_bias_m.abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_fusion_without_dem_keeps_gps() {
+        let gps = vec![100.0, 101.0, 102.0];
+        let result = fuse_gps_with_dem(&gps, &[None, None, None], &[0.0, 5.0, 10.0]);
+        assert_eq!(result.elevations, gps);
+        assert_eq!(result.dem_points, 0);
+    }
+}
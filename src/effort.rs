@@ -0,0 +1,325 @@
+/// EFFORT METRICS - Pace, moving time and grade-adjusted pace from timed tracks
+/// Uses the smoothed gradient from ElevationData and the Minetti et al. (2002) energy cost of
+/// running on a slope: a step at grade i costs C(i)/C(0) times a flat step, so its
+/// grade-adjusted distance is that much longer. Normalized effort is the 4th-power mean of the
+/// 30-second rolling metabolic power (W/kg), the running counterpart of normalized power.
+/// Everything is reported for the whole activity and for every incline/decline segment
+/// found by incline_analyzer.
+use std::path::Path;
+use csv::Writer;
+use crate::custom_smoother::{ElevationData, SmoothingVariant};
+use crate::incline_analyzer::{analyze_inclines, InclineAnalysisConfig};
+use crate::pause_detection::{detect_stops, format_duration};
+use crate::track_loader::LoadedTrack;
+
+// Minetti's polynomial was fitted between -45% and +45%
+const MAX_MODEL_GRADE: f64 = 0.45;
+// Energy cost of running on the flat, J/kg/m
+pub const FLAT_COST_J_PER_KG_M: f64 = 3.6;
+
+#[derive(Debug, Clone, Copy)]
+pub struct EffortConfig {
+    pub moving_speed_mps: f64,    // Slower steps count as stopped, as do steps inside detected stops
+    pub effort_window_s: f64,     // Rolling window for normalized effort
+    pub smoothing_variant: SmoothingVariant,
+}
+
+impl Default for EffortConfig {
+    fn default() -> Self {
+        EffortConfig {
+            moving_speed_mps: 0.5,
+            effort_window_s: 30.0,
+            smoothing_variant: SmoothingVariant::DistBased,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EffortSummary {
+    pub distance_km: f64,
+    pub elapsed_time_s: f64,
+    pub moving_time_s: f64,
+    pub average_pace_s_per_km: f64,         // Elapsed time over distance
+    pub moving_pace_s_per_km: f64,
+    pub grade_adjusted_pace_s_per_km: f64,  // Moving time over grade-adjusted distance
+    pub normalized_effort_w_per_kg: f64,
+    pub normalized_graded_pace_s_per_km: f64,  // Flat pace with the normalized effort's cost
+    pub energy_kj_per_kg: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct SegmentEffort {
+    pub kind: &'static str,  // "incline" or "decline"
+    pub start_km: f64,
+    pub end_km: f64,
+    pub elevation_change_m: f64,
+    pub average_grade_percent: f64,
+    pub moving_time_s: f64,
+    pub pace_s_per_km: f64,
+    pub grade_adjusted_pace_s_per_km: f64,
+    pub vertical_speed_m_per_h: f64,  // Signed: negative on declines
+}
+
+#[derive(Debug, Clone)]
+pub struct EffortAnalysis {
+    pub summary: EffortSummary,
+    pub segments: Vec<SegmentEffort>,
+}
+
+/// Energy cost of running at `grade` (rise over run), J/kg/m (Minetti et al., 2002)
+pub fn minetti_cost(grade: f64) -> f64 {
+    let i = grade.clamp(-MAX_MODEL_GRADE, MAX_MODEL_GRADE);
+    155.4 * i.powi(5) - 30.4 * i.powi(4) - 43.3 * i.powi(3) + 46.3 * i.powi(2) + 19.5 * i + FLAT_COST_J_PER_KG_M
+}
+
+/// "m:ss" for a pace in seconds per km, "-" when undefined
+pub fn format_pace(seconds_per_km: f64) -> String {
+    if !seconds_per_km.is_finite() || seconds_per_km <= 0.0 {
+        return "-".to_string();
+    }
+    let total = seconds_per_km.round() as u64;
+    format!("{}:{:02}", total / 60, total % 60)
+}
+
+/// One step between consecutive points
+struct Step {
+    distance_m: f64,
+    time_s: f64,
+    moving: bool,
+    cost: f64,  // J/kg/m at the smoothed grade
+}
+
+fn pace(time_s: f64, distance_m: f64) -> f64 {
+    if distance_m > 0.0 { time_s / distance_m * 1000.0 } else { f64::NAN }
+}
+
+/// Moving time, moving distance and grade-adjusted distance over `steps`
+fn moving_totals(steps: &[Step]) -> (f64, f64, f64) {
+    steps.iter()
+        .filter(|s| s.moving)
+        .fold((0.0, 0.0, 0.0), |(time, distance, adjusted), s| {
+            (time + s.time_s, distance + s.distance_m, adjusted + s.distance_m * s.cost / FLAT_COST_J_PER_KG_M)
+        })
+}
+
+/// 4th-power mean of the rolling metabolic power over moving time
+fn normalized_effort(steps: &[Step], window_s: f64) -> f64 {
+    let moving: Vec<&Step> = steps.iter().filter(|s| s.moving).collect();
+    let mut times = vec![0.0];
+    let mut energies = vec![0.0];
+    for s in &moving {
+        times.push(times.last().unwrap() + s.time_s);
+        energies.push(energies.last().unwrap() + s.cost * s.distance_m);
+    }
+    let total_time = *times.last().unwrap();
+    if total_time <= 0.0 {
+        return 0.0;
+    }
+    if total_time < window_s {
+        return energies.last().unwrap() / total_time;
+    }
+
+    let mut weighted = 0.0;
+    let mut weight = 0.0;
+    let mut start = 0;
+    for k in 1..times.len() {
+        if times[k] < window_s {
+            continue;
+        }
+        while times[start + 1] <= times[k] - window_s {
+            start += 1;
+        }
+        let rolling = (energies[k] - energies[start]) / (times[k] - times[start]);
+        weighted += rolling.powi(4) * moving[k - 1].time_s;
+        weight += moving[k - 1].time_s;
+    }
+    (weighted / weight).powf(0.25)
+}
+
+/// Pace and effort metrics of a timed track, overall and per incline/decline segment
+pub fn analyze_effort(track: &LoadedTrack, config: &EffortConfig) -> Result<EffortAnalysis, Box<dyn std::error::Error>> {
+    let timed = track.points.iter().filter(|p| p.time.is_some()).count();
+    if timed < 2 {
+        return Err(format!("{} has no timestamps; pace and effort need a timed recording", track.filename).into());
+    }
+
+    let elevation_data = ElevationData::new_with_variant(
+        track.elevations(),
+        track.cumulative_distances.clone(),
+        config.smoothing_variant,
+    );
+    let stops = detect_stops(track);
+    let steps: Vec<Step> = (1..track.points.len())
+        .map(|i| {
+            let distance_m = track.cumulative_distances[i] - track.cumulative_distances[i - 1];
+            let time_s = match (track.points[i - 1].time, track.points[i].time) {
+                (Some(a), Some(b)) => (b - a).num_milliseconds().max(0) as f64 / 1000.0,
+                _ => 0.0,
+            };
+            let grade = elevation_data.gradient_percent.get(i).copied().unwrap_or(0.0) / 100.0;
+            Step {
+                distance_m,
+                time_s,
+                moving: time_s > 0.0 && distance_m / time_s >= config.moving_speed_mps && !stops.is_stopped_step(i - 1),
+                cost: minetti_cost(grade),
+            }
+        })
+        .collect();
+
+    let first_time = track.points.iter().find_map(|p| p.time).unwrap();
+    let last_time = track.points.iter().rev().find_map(|p| p.time).unwrap();
+    let elapsed_time_s = (last_time - first_time).num_milliseconds() as f64 / 1000.0;
+    let distance_m = track.cumulative_distances.last().copied().unwrap_or(0.0);
+    let (moving_time_s, moving_distance_m, adjusted_distance_m) = moving_totals(&steps);
+    let normalized_effort_w_per_kg = normalized_effort(&steps, config.effort_window_s);
+
+    let summary = EffortSummary {
+        distance_km: distance_m / 1000.0,
+        elapsed_time_s,
+        moving_time_s,
+        average_pace_s_per_km: pace(elapsed_time_s, distance_m),
+        moving_pace_s_per_km: pace(moving_time_s, moving_distance_m),
+        grade_adjusted_pace_s_per_km: pace(moving_time_s, adjusted_distance_m),
+        normalized_effort_w_per_kg,
+        normalized_graded_pace_s_per_km: if normalized_effort_w_per_kg > 0.0 {
+            1000.0 * FLAT_COST_J_PER_KG_M / normalized_effort_w_per_kg
+        } else {
+            f64::NAN
+        },
+        energy_kj_per_kg: steps.iter().filter(|s| s.moving).map(|s| s.cost * s.distance_m).sum::<f64>() / 1000.0,
+    };
+
+    let incline_config = InclineAnalysisConfig {
+        smoothing_variant: config.smoothing_variant,
+        ..InclineAnalysisConfig::default()
+    };
+    let inclines = analyze_inclines(track.elevations(), track.cumulative_distances.clone(), &incline_config);
+    let segment_effort = |kind: &'static str, start: usize, end: usize, change: f64, grade: f64, start_km: f64, end_km: f64| {
+        // steps[k] joins point k to point k + 1
+        let (time, distance, adjusted) = moving_totals(&steps[start..end]);
+        SegmentEffort {
+            kind,
+            start_km,
+            end_km,
+            elevation_change_m: change,
+            average_grade_percent: grade,
+            moving_time_s: time,
+            pace_s_per_km: pace(time, distance),
+            grade_adjusted_pace_s_per_km: pace(time, adjusted),
+            vertical_speed_m_per_h: if time > 0.0 { change / time * 3600.0 } else { 0.0 },
+        }
+    };
+    let mut segments: Vec<SegmentEffort> = inclines.all_inclines.iter()
+        .map(|s| segment_effort("incline", s.start_index, s.end_index, s.elevation_gain_m,
+                                s.average_grade_percent, s.start_distance_km, s.end_distance_km))
+        .chain(inclines.all_declines.iter()
+            .map(|s| segment_effort("decline", s.start_index, s.end_index, -s.elevation_loss_m,
+                                    s.average_grade_percent, s.start_distance_km, s.end_distance_km)))
+        .collect();
+    segments.sort_by(|a, b| a.start_km.partial_cmp(&b.start_km).unwrap());
+
+    Ok(EffortAnalysis { summary, segments })
+}
+
+pub fn print_effort_summary(analysis: &EffortAnalysis) {
+    let s = &analysis.summary;
+    println!("\n🏃 EFFORT");
+    println!("=========");
+    println!("Distance: {:.2}km | Elapsed: {} | Moving: {}", s.distance_km, format_duration(s.elapsed_time_s), format_duration(s.moving_time_s));
+    println!("Pace: {}/km average, {}/km moving", format_pace(s.average_pace_s_per_km), format_pace(s.moving_pace_s_per_km));
+    println!("Grade-adjusted pace: {}/km", format_pace(s.grade_adjusted_pace_s_per_km));
+    println!("Normalized effort: {:.1} W/kg (flat-equivalent {}/km)",
+             s.normalized_effort_w_per_kg, format_pace(s.normalized_graded_pace_s_per_km));
+    println!("Energy: {:.1} kJ/kg", s.energy_kj_per_kg);
+
+    if !analysis.segments.is_empty() {
+        println!("\nSegment  |  Start km |  End km | Elev m | Grade % |  Time   | Pace/km | GAP/km | Vert m/h");
+        println!("---------|-----------|---------|--------|---------|---------|---------|--------|---------");
+        for seg in &analysis.segments {
+            println!("{:8} | {:9.2} | {:7.2} | {:6.0} | {:7.1} | {:>7} | {:>7} | {:>6} | {:8.0}",
+                     seg.kind, seg.start_km, seg.end_km, seg.elevation_change_m, seg.average_grade_percent,
+                     format_duration(seg.moving_time_s), format_pace(seg.pace_s_per_km),
+                     format_pace(seg.grade_adjusted_pace_s_per_km), seg.vertical_speed_m_per_h);
+        }
+    }
+}
+
+/// One "activity" row followed by one row per incline/decline segment
+pub fn write_effort_csv(analysis: &EffortAnalysis, output_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut wtr = Writer::from_path(output_path)?;
+    wtr.write_record([
+        "Type", "Start_km", "End_km", "Elevation_Change_m", "Average_Grade_%", "Moving_Time_s",
+        "Pace_s_per_km", "Grade_Adjusted_Pace_s_per_km", "Vertical_Speed_m_per_h",
+    ])?;
+
+    let s = &analysis.summary;
+    wtr.write_record([
+        "activity".to_string(),
+        "0.000".to_string(),
+        format!("{:.3}", s.distance_km),
+        String::new(),
+        String::new(),
+        format!("{:.0}", s.moving_time_s),
+        format!("{:.1}", s.moving_pace_s_per_km),
+        format!("{:.1}", s.grade_adjusted_pace_s_per_km),
+        String::new(),
+    ])?;
+    for seg in &analysis.segments {
+        wtr.write_record([
+            seg.kind.to_string(),
+            format!("{:.3}", seg.start_km),
+            format!("{:.3}", seg.end_km),
+            format!("{:.1}", seg.elevation_change_m),
+            format!("{:.1}", seg.average_grade_percent),
+            format!("{:.0}", seg.moving_time_s),
+            format!("{:.1}", seg.pace_s_per_km),
+            format!("{:.1}", seg.grade_adjusted_pace_s_per_km),
+            format!("{:.0}", seg.vertical_speed_m_per_h),
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone, Utc};
+    use crate::track_loader::TrackPoint;
+
+    #[test]
+    fn test_grade_adjusted_pace_and_moving_time() {
+        assert_eq!(minetti_cost(0.0), FLAT_COST_J_PER_KG_M);
+        assert!(minetti_cost(0.1) > minetti_cost(0.0) && minetti_cost(-0.1) < minetti_cost(0.0));
+
+        // 2km at 3 m/s: 1km flat then 1km at 10%, with a 2-minute stop in the middle
+        let start = Utc.with_ymd_and_hms(2024, 6, 1, 8, 0, 0).unwrap();
+        let mut points = Vec::new();
+        let mut seconds = 0.0;
+        for i in 0..=200 {
+            let d = i as f64 * 10.0;
+            if i == 101 {
+                seconds += 120.0;
+            }
+            points.push(TrackPoint {
+                latitude: 46.0 + d / 111_320.0,
+                longitude: 7.0,
+                elevation: 500.0 + (d - 1000.0).max(0.0) * 0.1,
+                time: Some(start + Duration::milliseconds((seconds * 1000.0) as i64)),
+                distance: None,
+            });
+            seconds += 10.0 / 3.0;
+        }
+        let track = LoadedTrack::from_points("timed.gpx".to_string(), points);
+        let analysis = analyze_effort(&track, &EffortConfig::default()).unwrap();
+        let s = &analysis.summary;
+
+        assert!((s.elapsed_time_s - s.moving_time_s - 120.0).abs() < 5.0);
+        assert!((s.moving_pace_s_per_km - 333.3).abs() < 5.0);
+        // The climb makes the same speed worth a faster flat pace
+        assert!(s.grade_adjusted_pace_s_per_km < s.moving_pace_s_per_km - 30.0);
+        let climb = analysis.segments.iter().find(|seg| seg.kind == "incline").unwrap();
+        assert!(climb.grade_adjusted_pace_s_per_km < climb.pace_s_per_km);
+        assert!(climb.vertical_speed_m_per_h > 800.0);
+    }
+}
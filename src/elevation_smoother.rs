@@ -0,0 +1,902 @@
+/// ELEVATION SMOOTHER - One pluggable interface for every smoothing algorithm
+///
+/// Input is always raw elevations + cumulative distances; output is the smoothed
+/// profile, its gain/loss and algorithm-specific diagnostics. The shared signal
+/// primitives (resampling, median, Gaussian, Savitzky-Golay, Butterworth) live here
+/// so analyses stop carrying their own slightly different copies.
+use std::collections::BTreeMap;
+use biquad::{Biquad, DirectForm1, ToHertz, Coefficients, Q_BUTTERWORTH_F64};
+use crate::custom_smoother::{ElevationData, SmoothingVariant};
+use crate::dem::ElevationLookup;
+use crate::dem_fusion::{fuse_gps_with_dem, StructureKind};
+use crate::kalman_smoother::kalman_rts_smooth;
+use crate::barometric::BarometricSmoother;
+use crate::distbased_elevation_processor::DistBasedElevationProcessor;
+use crate::gpx_processor::{filter_elevation_spikes_with_limit, apply_directional_deadzone_with_thresholds};
+use crate::search_strategy::ParameterRange;
+use crate::track_loader::{calculate_gain_loss, gap_runs};
+
+#[derive(Debug, Clone)]
+pub struct SmoothedProfile {
+    pub distances: Vec<f64>,
+    pub elevations: Vec<f64>,
+    pub gain_m: f64,
+    pub loss_m: f64,
+    pub diagnostics: BTreeMap<String, f64>,
+}
+
+impl SmoothedProfile {
+    /// Profile whose gain/loss are the plain deltas of the smoothed elevations
+    pub fn from_elevations(distances: Vec<f64>, elevations: Vec<f64>) -> Self {
+        let (gain_m, loss_m) = calculate_gain_loss(&elevations);
+        SmoothedProfile {
+            distances,
+            elevations,
+            gain_m,
+            loss_m,
+            diagnostics: BTreeMap::new(),
+        }
+    }
+
+    /// Rebuild a profile from a start elevation and (deadband-filtered) altitude changes
+    pub fn from_altitude_changes(distances: Vec<f64>, start_elevation: f64, altitude_changes: &[f64]) -> Self {
+        let mut elevations = Vec::with_capacity(altitude_changes.len());
+        let mut current = start_elevation;
+        for (i, &change) in altitude_changes.iter().enumerate() {
+            if i > 0 {
+                current += change;
+            }
+            elevations.push(current);
+        }
+        Self::from_elevations(distances, elevations)
+    }
+
+    pub fn with_diagnostic(mut self, key: &str, value: f64) -> Self {
+        self.diagnostics.insert(key.to_string(), value);
+        self
+    }
+
+    pub fn diagnostic(&self, key: &str) -> Option<f64> {
+        self.diagnostics.get(key).copied()
+    }
+
+    /// Smoothed elevation at each requested distance (linear interpolation), so
+    /// resampled profiles can be written back onto the original track points
+    pub fn elevations_at(&self, distances: &[f64]) -> Vec<f64> {
+        if self.elevations.is_empty() {
+            return vec![];
+        }
+        distances.iter()
+            .map(|&d| interpolate_elevation_at_distance(&self.elevations, &self.distances, d))
+            .collect()
+    }
+}
+
+pub trait ElevationSmoother: Send + Sync {
+    /// Short identifier used in reports and output filenames
+    fn name(&self) -> String;
+
+    fn smooth(&self, elevations: &[f64], distances: &[f64]) -> SmoothedProfile;
+
+    /// Smooth (latitude, longitude, elevation) points; only position-aware smoothers
+    /// (DEM fusion) look past the elevations
+    fn smooth_coordinates(&self, coordinates: &[(f64, f64, f64)], distances: &[f64]) -> SmoothedProfile {
+        let elevations: Vec<f64> = coordinates.iter().map(|c| c.2).collect();
+        self.smooth(&elevations, distances)
+    }
+}
+
+/// A track smoothed run by run between recording gaps, so no smoothing window spans a gap
+#[derive(Debug, Clone)]
+pub struct SegmentedProfile {
+    pub run_starts: Vec<usize>,     // First point of each run in the smoothed points
+    pub offsets: Vec<f64>,          // Cumulative distance at the start of each run
+    pub runs: Vec<SmoothedProfile>, // Each run's profile, its distances starting at 0
+}
+
+impl SegmentedProfile {
+    pub fn gain_m(&self) -> f64 {
+        self.runs.iter().map(|run| run.gain_m).sum()
+    }
+
+    pub fn loss_m(&self) -> f64 {
+        self.runs.iter().map(|run| run.loss_m).sum()
+    }
+
+    /// Diagnostic summed over the runs (counts, gains), if any run reports it
+    pub fn diagnostic_total(&self, key: &str) -> Option<f64> {
+        self.runs.iter().filter_map(|run| run.diagnostic(key)).reduce(|a, b| a + b)
+    }
+
+    /// Largest value of a diagnostic over the runs
+    pub fn diagnostic_max(&self, key: &str) -> Option<f64> {
+        self.runs.iter().filter_map(|run| run.diagnostic(key)).reduce(f64::max)
+    }
+
+    /// Smoothed elevation at each distance, each one read from its own run. `gaps` splits
+    /// `distances` the same way the smoothed points were split.
+    pub fn elevations_at(&self, distances: &[f64], gaps: &[usize]) -> Vec<f64> {
+        let mut elevations = Vec::with_capacity(distances.len());
+        for (range, (run, offset)) in gap_runs(distances.len(), gaps).into_iter().zip(self.runs.iter().zip(&self.offsets)) {
+            let run_distances: Vec<f64> = distances[range].iter().map(|d| d - offset).collect();
+            elevations.extend(run.elevations_at(&run_distances));
+        }
+        elevations
+    }
+}
+
+// Runs shorter than this are kept as recorded; there is nothing to smooth
+const MIN_SMOOTHED_RUN_POINTS: usize = 5;
+
+/// Smooth each run between `gaps` (indices of the first point after a recording gap) on its own
+pub fn smooth_between_gaps(
+    smoother: &dyn ElevationSmoother,
+    coordinates: &[(f64, f64, f64)],
+    distances: &[f64],
+    gaps: &[usize],
+) -> SegmentedProfile {
+    let mut profile = SegmentedProfile { run_starts: Vec::new(), offsets: Vec::new(), runs: Vec::new() };
+    for range in gap_runs(coordinates.len(), gaps) {
+        let offset = distances[range.start];
+        let run_distances: Vec<f64> = distances[range.clone()].iter().map(|d| d - offset).collect();
+        let run_coordinates = &coordinates[range.clone()];
+        let run = if run_coordinates.len() < MIN_SMOOTHED_RUN_POINTS {
+            SmoothedProfile::from_elevations(run_distances, run_coordinates.iter().map(|c| c.2).collect())
+        } else {
+            smoother.smooth_coordinates(run_coordinates, &run_distances)
+        };
+        profile.run_starts.push(range.start);
+        profile.offsets.push(offset);
+        profile.runs.push(run);
+    }
+    profile
+}
+
+/// Build a smoother from its CLI name. `interval_m` overrides the default interval
+/// (or window, for Savitzky-Golay) where the algorithm has one.
+pub fn smoother_from_name(name: &str, interval_m: Option<f64>) -> Result<Box<dyn ElevationSmoother>, String> {
+    smoother_from_name_with_dem(name, interval_m, None)
+}
+
+/// Smoothers that sample a DEM: dem-fusion (required) and barometric (drift anchors)
+pub fn smoother_uses_dem(name: &str) -> bool {
+    matches!(smoother_key(name).as_str(), "demfusion" | "fusion" | "barometric" | "baro")
+}
+
+fn smoother_key(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// As smoother_from_name, with the DEM that dem-fusion and barometric sample
+pub fn smoother_from_name_with_dem(
+    name: &str,
+    interval_m: Option<f64>,
+    dem: Option<Box<dyn ElevationLookup>>,
+) -> Result<Box<dyn ElevationSmoother>, String> {
+    let key = smoother_key(name);
+
+    let smoother: Box<dyn ElevationSmoother> = match key.as_str() {
+        "symmetricfixed" | "symmetric" => Box::new(SymmetricFixedSmoother {
+            interval_m: interval_m.unwrap_or(1.9),
+        }),
+        "distbased" => Box::new(DistBasedSmoother),
+        "butterworth" => Box::new(ButterworthSmoother {
+            interval_m: interval_m.unwrap_or(6.0),
+        }),
+        "savitzkygolay" | "savgol" => Box::new(SavitzkyGolaySmoother {
+            window: interval_m.map(|w| w.round() as usize).unwrap_or(15),
+        }),
+        "twopass" => Box::new(TwoPassSmoother {
+            loss_interval_m: interval_m.unwrap_or(15.0),
+        }),
+        "spikedeadzone" | "spikefiltered" => Box::new(SpikeDeadzoneSmoother::default()),
+        "kalman" | "kalmanrts" | "rts" => Box::new(KalmanSmoother),
+        "barometric" | "baro" => Box::new(BarometricSmoother {
+            deadband_m: interval_m.unwrap_or(0.5),
+            dem,
+        }),
+        "demfusion" | "fusion" => match dem {
+            Some(dem) => Box::new(DemFusionSmoother {
+                dem,
+                interval_m: interval_m.unwrap_or(1.9),
+            }),
+            None => return Err("dem-fusion needs a DEM directory (--dem-dir)".to_string()),
+        },
+        _ => match name.parse::<SmoothingVariant>() {
+            Ok(variant) => Box::new(VariantSmoother { variant, interval_m }),
+            Err(_) => return Err(format!(
+                "unknown smoother '{}' (expected symmetric-fixed, distbased, butterworth, savitzky-golay, two-pass, spike-deadzone, kalman, barometric, dem-fusion or a smoothing variant)",
+                name
+            )),
+        },
+    };
+
+    Ok(smoother)
+}
+
+/// Continuous parameters a search strategy can tune for a smoother, in the order
+/// smoother_from_parameters takes them. None for smoothers with nothing to tune.
+pub fn smoother_parameter_ranges(name: &str) -> Option<Vec<ParameterRange>> {
+    let ranges = match smoother_key(name).as_str() {
+        "symmetricfixed" | "symmetric" => vec![ParameterRange::new("interval_m", 0.5, 10.0)],
+        "butterworth" => vec![ParameterRange::new("interval_m", 1.0, 30.0)],
+        "savitzkygolay" | "savgol" => vec![ParameterRange::new("window", 5.0, 61.0)],
+        "twopass" => vec![ParameterRange::new("loss_interval_m", 5.0, 40.0)],
+        "barometric" | "baro" => vec![ParameterRange::new("deadband_m", 0.0, 3.0)],
+        "spikedeadzone" | "spikefiltered" => vec![
+            ParameterRange::new("max_change_per_point_m", 0.5, 12.0),
+            ParameterRange::new("gain_threshold_m", 0.0, 0.5),
+            ParameterRange::new("loss_threshold_m", 0.0, 0.5),
+        ],
+        _ => return None,
+    };
+    Some(ranges)
+}
+
+/// Build a smoother from values matching smoother_parameter_ranges
+pub fn smoother_from_parameters(name: &str, values: &[f64]) -> Result<Box<dyn ElevationSmoother>, String> {
+    let ranges = smoother_parameter_ranges(name)
+        .ok_or_else(|| format!("smoother '{}' has no tunable parameters", name))?;
+    if values.len() != ranges.len() {
+        return Err(format!("smoother '{}' takes {} parameters, got {}", name, ranges.len(), values.len()));
+    }
+
+    match smoother_key(name).as_str() {
+        "spikedeadzone" | "spikefiltered" => Ok(Box::new(SpikeDeadzoneSmoother {
+            max_change_per_point_m: values[0],
+            gain_threshold_m: values[1],
+            loss_threshold_m: values[2],
+        })),
+        _ => smoother_from_name(name, Some(values[0])),
+    }
+}
+
+/// ElevationData SymmetricFixed variant with custom-interval resampling (the 1.9m winner)
+#[derive(Debug, Clone)]
+pub struct SymmetricFixedSmoother {
+    pub interval_m: f64,
+}
+
+impl ElevationSmoother for SymmetricFixedSmoother {
+    fn name(&self) -> String {
+        format!("symmetric-fixed-{:.2}m", self.interval_m)
+    }
+
+    fn smooth(&self, elevations: &[f64], distances: &[f64]) -> SmoothedProfile {
+        let mut data = ElevationData::new_with_variant(
+            elevations.to_vec(),
+            distances.to_vec(),
+            SmoothingVariant::SymmetricFixed
+        );
+        data.apply_custom_interval_processing_symmetric(self.interval_m);
+        profile_from_elevation_data(&data)
+            .with_diagnostic("interval_m", self.interval_m)
+    }
+}
+
+/// Any legacy ElevationData variant, optionally with custom-interval processing
+#[derive(Debug, Clone)]
+pub struct VariantSmoother {
+    pub variant: SmoothingVariant,
+    pub interval_m: Option<f64>,
+}
+
+impl ElevationSmoother for VariantSmoother {
+    fn name(&self) -> String {
+        match self.interval_m {
+            Some(interval) => format!("{}-{:.2}m", self.variant.name(), interval),
+            None => self.variant.name().to_string(),
+        }
+    }
+
+    fn smooth(&self, elevations: &[f64], distances: &[f64]) -> SmoothedProfile {
+        let mut data = ElevationData::new_with_variant(
+            elevations.to_vec(),
+            distances.to_vec(),
+            self.variant
+        );
+        if let Some(interval) = self.interval_m {
+            match self.variant {
+                SmoothingVariant::SymmetricFixed | SmoothingVariant::DemFusion => {
+                    data.apply_custom_interval_processing_symmetric(interval)
+                }
+                _ => data.apply_custom_interval_processing(interval),
+            }
+        }
+        profile_from_elevation_data(&data)
+    }
+}
+
+/// GPS elevations fused with DEM samples (noise-weighted, bridges/tunnels straightened),
+/// then SymmetricFixed custom-interval processing
+pub struct DemFusionSmoother {
+    pub dem: Box<dyn ElevationLookup>,
+    pub interval_m: f64,
+}
+
+impl DemFusionSmoother {
+    fn smooth_with_dem_samples(&self, elevations: &[f64], dem: &[Option<f64>], distances: &[f64]) -> SmoothedProfile {
+        let fusion = fuse_gps_with_dem(elevations, dem, distances);
+        let mut data = ElevationData::new_with_variant(
+            fusion.elevations.clone(),
+            distances.to_vec(),
+            SmoothingVariant::DemFusion
+        );
+        data.apply_custom_interval_processing_symmetric(self.interval_m);
+        profile_from_elevation_data(&data)
+            .with_diagnostic("interval_m", self.interval_m)
+            .with_diagnostic("dem_points", fusion.dem_points as f64)
+            .with_diagnostic("gps_bias_m", fusion.gps_bias_m)
+            .with_diagnostic("mean_gps_weight", fusion.mean_gps_weight())
+            .with_diagnostic("bridges_detected", fusion.structure_count(StructureKind::Bridge) as f64)
+            .with_diagnostic("tunnels_detected", fusion.structure_count(StructureKind::Tunnel) as f64)
+    }
+}
+
+impl ElevationSmoother for DemFusionSmoother {
+    fn name(&self) -> String {
+        format!("dem-fusion-{:.2}m", self.interval_m)
+    }
+
+    /// Without positions there is nothing to sample, so this is plain SymmetricFixed
+    fn smooth(&self, elevations: &[f64], distances: &[f64]) -> SmoothedProfile {
+        self.smooth_with_dem_samples(elevations, &vec![None; elevations.len()], distances)
+    }
+
+    fn smooth_coordinates(&self, coordinates: &[(f64, f64, f64)], distances: &[f64]) -> SmoothedProfile {
+        let elevations: Vec<f64> = coordinates.iter().map(|c| c.2).collect();
+        let dem: Vec<Option<f64>> = coordinates.iter()
+            .map(|&(lat, lon, _)| self.dem.elevation_at(lat, lon))
+            .collect();
+        self.smooth_with_dem_samples(&elevations, &dem, distances)
+    }
+}
+
+/// Kalman filter + RTS smoother on an [elevation, grade] state (the Kalman variant)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KalmanSmoother;
+
+impl ElevationSmoother for KalmanSmoother {
+    fn name(&self) -> String {
+        "kalman".to_string()
+    }
+
+    fn smooth(&self, elevations: &[f64], distances: &[f64]) -> SmoothedProfile {
+        let result = kalman_rts_smooth(elevations, distances, None);
+        SmoothedProfile::from_elevations(distances.to_vec(), result.elevations)
+            .with_diagnostic("measurement_noise_m", result.noise.measurement_variance.sqrt())
+            .with_diagnostic("process_noise", result.noise.process_noise)
+    }
+}
+
+/// Terrain-adaptive DistBasedElevationProcessor (10m grid)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DistBasedSmoother;
+
+impl ElevationSmoother for DistBasedSmoother {
+    fn name(&self) -> String {
+        "distbased".to_string()
+    }
+
+    fn smooth(&self, elevations: &[f64], distances: &[f64]) -> SmoothedProfile {
+        let processor = DistBasedElevationProcessor::new(elevations.to_vec(), distances.to_vec());
+        let start = processor.enhanced_altitude.first().copied().unwrap_or(0.0);
+        let mut profile = SmoothedProfile::from_altitude_changes(
+            processor.cumulative_distance.clone(),
+            start,
+            &processor.altitude_change
+        );
+        let stats = processor.get_processing_stats();
+        profile.gain_m = processor.get_total_elevation_gain();
+        profile.loss_m = processor.get_total_elevation_loss();
+        profile
+            .with_diagnostic("resampled_points", stats.resampled_points as f64)
+            .with_diagnostic("smoothing_window", stats.smoothing_window_size as f64)
+            .with_diagnostic("deadband_threshold_m", stats.deadband_threshold_m)
+    }
+}
+
+/// Zero-phase (forward-backward) Butterworth low-pass over distance
+#[derive(Debug, Clone)]
+pub struct ButterworthSmoother {
+    pub interval_m: f64,
+}
+
+impl ElevationSmoother for ButterworthSmoother {
+    fn name(&self) -> String {
+        format!("butterworth-{:.2}m", self.interval_m)
+    }
+
+    fn smooth(&self, elevations: &[f64], distances: &[f64]) -> SmoothedProfile {
+        let filtered = butterworth_filter(elevations, distances, self.interval_m as f32);
+        SmoothedProfile::from_elevations(distances.to_vec(), filtered)
+            .with_diagnostic("interval_m", self.interval_m)
+    }
+}
+
+/// Point-count Savitzky-Golay style smoothing (triangular weights)
+#[derive(Debug, Clone)]
+pub struct SavitzkyGolaySmoother {
+    pub window: usize,
+}
+
+impl ElevationSmoother for SavitzkyGolaySmoother {
+    fn name(&self) -> String {
+        format!("savitzky-golay-{}", self.window)
+    }
+
+    fn smooth(&self, elevations: &[f64], distances: &[f64]) -> SmoothedProfile {
+        let window = self.window.max(5).min(elevations.len() / 4);
+        let smoothed = savitzky_golay_smooth(elevations, window);
+        SmoothedProfile::from_elevations(distances.to_vec(), smoothed)
+            .with_diagnostic("window", window as f64)
+    }
+}
+
+/// DistBased profile for gain, separate coarse pass (median + 15-point Gaussian) for loss
+#[derive(Debug, Clone)]
+pub struct TwoPassSmoother {
+    pub loss_interval_m: f64,
+}
+
+impl ElevationSmoother for TwoPassSmoother {
+    fn name(&self) -> String {
+        format!("two-pass-{:.1}m", self.loss_interval_m)
+    }
+
+    fn smooth(&self, elevations: &[f64], distances: &[f64]) -> SmoothedProfile {
+        // PASS 1: gain (and the returned profile) from the standard distance-based approach
+        let mut profile = DistBasedSmoother.smooth(elevations, distances);
+
+        // PASS 2: loss from a coarser resampling
+        let (_uniform_distances, uniform_elevations) = resample_to_uniform_distance(
+            elevations, distances, self.loss_interval_m
+        );
+        let median_smoothed = median_filter(&uniform_elevations, 3);
+        let gaussian_smoothed = gaussian_smooth(&median_smoothed, 15);
+        let (_, pass_two_loss) = calculate_gain_loss(&gaussian_smoothed);
+
+        profile.diagnostics.insert("pass_one_loss_m".to_string(), profile.loss_m);
+        profile.loss_m = pass_two_loss;
+        profile.with_diagnostic("loss_interval_m", self.loss_interval_m)
+    }
+}
+
+/// Stage 1 spike filter + stage 2 directional deadzone (the GPX processor defaults)
+#[derive(Debug, Clone)]
+pub struct SpikeDeadzoneSmoother {
+    pub max_change_per_point_m: f64,
+    pub gain_threshold_m: f64,
+    pub loss_threshold_m: f64,
+}
+
+impl Default for SpikeDeadzoneSmoother {
+    fn default() -> Self {
+        SpikeDeadzoneSmoother {
+            max_change_per_point_m: crate::gpx_processor::MAX_ELEVATION_CHANGE_PER_POINT,
+            gain_threshold_m: crate::gpx_processor::GAIN_THRESHOLD,
+            loss_threshold_m: crate::gpx_processor::LOSS_THRESHOLD,
+        }
+    }
+}
+
+impl ElevationSmoother for SpikeDeadzoneSmoother {
+    fn name(&self) -> String {
+        "spike-filtered".to_string()
+    }
+
+    fn smooth(&self, elevations: &[f64], distances: &[f64]) -> SmoothedProfile {
+        let (spike_filtered, stats) = filter_elevation_spikes_with_limit(elevations, self.max_change_per_point_m);
+        let (spike_filtered_gain, spike_filtered_loss) = calculate_gain_loss(&spike_filtered);
+        let deadzoned = apply_directional_deadzone_with_thresholds(
+            &spike_filtered,
+            self.gain_threshold_m,
+            self.loss_threshold_m
+        );
+
+        SmoothedProfile::from_elevations(distances.to_vec(), deadzoned)
+            .with_diagnostic("spikes_detected", stats.spikes_detected as f64)
+            .with_diagnostic("spikes_filtered", stats.spikes_filtered as f64)
+            .with_diagnostic("max_spike_magnitude_m", stats.max_spike_magnitude)
+            .with_diagnostic("spike_filtered_gain_m", spike_filtered_gain)
+            .with_diagnostic("spike_filtered_loss_m", spike_filtered_loss)
+    }
+}
+
+fn profile_from_elevation_data(data: &ElevationData) -> SmoothedProfile {
+    let start = data.enhanced_altitude.first().copied().unwrap_or(0.0);
+    let mut profile = SmoothedProfile::from_altitude_changes(
+        data.cumulative_distance.clone(),
+        start,
+        &data.altitude_change
+    );
+    // Keep the variant's own accounting (some variants cap or split ascent/descent)
+    profile.gain_m = data.get_total_elevation_gain();
+    profile.loss_m = data.get_total_elevation_loss();
+    profile
+}
+
+// SHARED SIGNAL PRIMITIVES
+
+/// Resample elevations onto a uniform distance grid starting at 0m
+pub fn resample_to_uniform_distance(
+    elevations: &[f64],
+    distances: &[f64],
+    interval: f64
+) -> (Vec<f64>, Vec<f64>) {
+    if elevations.is_empty() || distances.is_empty() {
+        return (vec![], vec![]);
+    }
+
+    let total_distance = *distances.last().unwrap();
+    let num_points = (total_distance / interval).ceil() as usize + 1;
+
+    let mut uniform_distances = Vec::with_capacity(num_points);
+    let mut uniform_elevations = Vec::with_capacity(num_points);
+
+    for i in 0..num_points {
+        let target_distance = i as f64 * interval;
+        if target_distance > total_distance {
+            break;
+        }
+        uniform_distances.push(target_distance);
+        uniform_elevations.push(interpolate_elevation_at_distance(elevations, distances, target_distance));
+    }
+
+    (uniform_distances, uniform_elevations)
+}
+
+/// Linear interpolation of elevation at a distance along the track (`distances` sorted)
+pub fn interpolate_elevation_at_distance(
+    elevations: &[f64],
+    distances: &[f64],
+    target_distance: f64
+) -> f64 {
+    if target_distance <= 0.0 {
+        return elevations[0];
+    }
+
+    // First point at or past the target, bracketed with the one before it
+    let i = distances.partition_point(|&d| d < target_distance).max(1);
+    if i >= distances.len() {
+        return *elevations.last().unwrap();
+    }
+
+    let d1 = distances[i - 1];
+    let d2 = distances[i];
+    let e1 = elevations[i - 1];
+    let e2 = elevations[i];
+
+    if (d2 - d1).abs() < 1e-10 {
+        return e1;
+    }
+
+    let t = (target_distance - d1) / (d2 - d1);
+    e1 + t * (e2 - e1)
+}
+
+pub fn median_filter(data: &[f64], window: usize) -> Vec<f64> {
+    let mut result = Vec::with_capacity(data.len());
+
+    for i in 0..data.len() {
+        let start = i.saturating_sub(window / 2);
+        let end = (i + window / 2).min(data.len() - 1);
+
+        let mut window_data: Vec<f64> = data[start..=end].to_vec();
+        window_data.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let median = if window_data.len().is_multiple_of(2) {
+            (window_data[window_data.len() / 2 - 1] + window_data[window_data.len() / 2]) / 2.0
+        } else {
+            window_data[window_data.len() / 2]
+        };
+
+        result.push(median);
+    }
+
+    result
+}
+
+pub fn gaussian_smooth(data: &[f64], window: usize) -> Vec<f64> {
+    let mut result = Vec::with_capacity(data.len());
+    let sigma = window as f64 / 6.0;
+
+    for i in 0..data.len() {
+        let start = i.saturating_sub(window / 2);
+        let end = (i + window / 2).min(data.len() - 1);
+
+        let mut weighted_sum = 0.0;
+        let mut weight_sum = 0.0;
+
+        for (j, &value) in data.iter().enumerate().take(end + 1).skip(start) {
+            let distance = (j as f64 - i as f64).abs();
+            let weight = (-0.5 * (distance / sigma).powi(2)).exp();
+
+            weighted_sum += value * weight;
+            weight_sum += weight;
+        }
+
+        result.push(weighted_sum / weight_sum);
+    }
+
+    result
+}
+
+pub fn savitzky_golay_smooth(data: &[f64], window: usize) -> Vec<f64> {
+    if window < 5 || window >= data.len() {
+        return data.to_vec();
+    }
+    weighted_window_smooth(data, window, &generate_savgol_coefficients(window))
+}
+
+/// Savitzky-Golay with weights shaped by the polynomial order: 1 - d^order across the
+/// window for orders 2 to 4, the triangular weights of `savitzky_golay_smooth` otherwise
+pub fn savitzky_golay_smooth_with_order(data: &[f64], window: usize, poly_order: usize) -> Vec<f64> {
+    if window < 5 || window >= data.len() || poly_order >= window {
+        return data.to_vec();
+    }
+    let coeffs = match poly_order {
+        2..=4 => {
+            let center = (window / 2) as f64;
+            (0..window)
+                .map(|i| 1.0 - ((i as f64 - center).abs() / center).powi(poly_order as i32))
+                .collect()
+        }
+        _ => generate_savgol_coefficients(window),
+    };
+    weighted_window_smooth(data, window, &coeffs)
+}
+
+/// Weighted average over a centred window, renormalized where the window is cut at the ends
+fn weighted_window_smooth(data: &[f64], window: usize, coeffs: &[f64]) -> Vec<f64> {
+    let mut result = Vec::with_capacity(data.len());
+    let half_window = window / 2;
+
+    for i in 0..data.len() {
+        let start = i.saturating_sub(half_window);
+        let end = (i + half_window).min(data.len() - 1);
+
+        let mut smoothed_value = 0.0;
+        let mut weight_sum = 0.0;
+
+        for (j, &value) in data[start..=end].iter().enumerate() {
+            let coeff = coeffs.get(j).copied().unwrap_or(1.0);
+            smoothed_value += value * coeff;
+            weight_sum += coeff;
+        }
+
+        result.push(smoothed_value / weight_sum);
+    }
+
+    result
+}
+
+fn generate_savgol_coefficients(window: usize) -> Vec<f64> {
+    // Simplified Savitzky-Golay coefficients: triangular weighting approximation
+    let center = window / 2;
+    (0..window)
+        .map(|i| {
+            let distance = (i as f64 - center as f64).abs();
+            (window as f64 - distance) / window as f64
+        })
+        .collect()
+}
+
+/// Forward-backward Butterworth low-pass keeping wavelengths longer than 2 x interval,
+/// returned at the original distances
+pub fn butterworth_filter(
+    elevations: &[f64],
+    distances: &[f64],
+    interval_m: f32
+) -> Vec<f64> {
+    // Adaptive resampling for Butterworth
+    let sample_spacing = (interval_m / 3.0).max(0.5) as f64;
+
+    let resampled_elevations = resample_to_uniform_spacing(elevations, distances, sample_spacing);
+
+    if resampled_elevations.len() < 10 {
+        return elevations.to_vec();
+    }
+
+    // Calculate cutoff frequency
+    let wavelength_to_keep = interval_m as f64 * 2.0;
+    let cutoff_cycles_per_meter = 1.0 / wavelength_to_keep;
+    let normalized_cutoff = cutoff_cycles_per_meter * sample_spacing;
+    let sample_rate_hz = 1.0 / sample_spacing;
+    let cutoff_hz = normalized_cutoff * sample_rate_hz;
+    let nyquist = sample_rate_hz / 2.0;
+    let cutoff_hz = cutoff_hz.clamp(0.01 * nyquist, 0.45 * nyquist);
+
+    let coeffs = match Coefficients::<f64>::from_params(
+        biquad::Type::LowPass,
+        sample_rate_hz.hz(),
+        cutoff_hz.hz(),
+        Q_BUTTERWORTH_F64
+    ) {
+        Ok(c) => c,
+        Err(_) => return elevations.to_vec(),
+    };
+
+    // Forward pass
+    let mut df_forward = DirectForm1::<f64>::new(coeffs);
+    let mut elev_fwd: Vec<f64> = resampled_elevations
+        .iter()
+        .map(|&x| df_forward.run(x))
+        .collect();
+
+    // Backward pass
+    elev_fwd.reverse();
+    let mut df_backward = DirectForm1::<f64>::new(coeffs);
+    let mut elev_smooth: Vec<f64> = elev_fwd
+        .iter()
+        .map(|&x| df_backward.run(x))
+        .collect();
+    elev_smooth.reverse();
+
+    interpolate_to_original_distances(&elev_smooth, sample_spacing, distances)
+}
+
+fn resample_to_uniform_spacing(
+    elevations: &[f64],
+    distances: &[f64],
+    spacing_m: f64
+) -> Vec<f64> {
+    if elevations.is_empty() || distances.is_empty() {
+        return vec![];
+    }
+
+    let total_distance = distances.last().unwrap();
+    let num_samples = (total_distance / spacing_m).ceil() as usize + 1;
+    let mut resampled = Vec::with_capacity(num_samples);
+
+    for i in 0..num_samples {
+        let target_distance = i as f64 * spacing_m;
+
+        let idx = match distances.binary_search_by(|d| d.partial_cmp(&target_distance).unwrap()) {
+            Ok(i) => i,
+            Err(i) => i.saturating_sub(1),
+        };
+
+        if idx >= distances.len() - 1 {
+            resampled.push(*elevations.last().unwrap());
+        } else {
+            let d0 = distances[idx];
+            let d1 = distances[idx + 1];
+            let e0 = elevations[idx];
+            let e1 = elevations[idx + 1];
+
+            let t = (target_distance - d0) / (d1 - d0);
+            resampled.push(e0 + t * (e1 - e0));
+        }
+    }
+
+    resampled
+}
+
+fn interpolate_to_original_distances(
+    smooth_elevations: &[f64],
+    sample_spacing: f64,
+    original_distances: &[f64]
+) -> Vec<f64> {
+    let mut result = Vec::with_capacity(original_distances.len());
+
+    for &dist in original_distances {
+        let sample_idx = dist / sample_spacing;
+        let idx = sample_idx.floor() as usize;
+
+        if idx >= smooth_elevations.len() - 1 {
+            result.push(*smooth_elevations.last().unwrap());
+        } else {
+            let t = sample_idx - idx as f64;
+            result.push(smooth_elevations[idx] * (1.0 - t) + smooth_elevations[idx + 1] * t);
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rolling_route() -> (Vec<f64>, Vec<f64>) {
+        let distances: Vec<f64> = (0..500).map(|i| i as f64 * 5.0).collect();
+        let elevations: Vec<f64> = distances.iter()
+            .enumerate()
+            .map(|(i, d)| 100.0 + 20.0 * (d / 400.0).sin() + if i % 2 == 0 { 0.3 } else { -0.3 })
+            .collect();
+        (elevations, distances)
+    }
+
+    #[test]
+    fn test_every_smoother_produces_consistent_profile() {
+        let (elevations, distances) = rolling_route();
+        let (raw_gain, _) = calculate_gain_loss(&elevations);
+
+        for name in ["symmetric-fixed", "distbased", "butterworth", "savitzky-golay", "two-pass", "spike-deadzone", "kalman"] {
+            let smoother = smoother_from_name(name, None).unwrap();
+            let profile = smoother.smooth(&elevations, &distances);
+            assert!(profile.gain_m > 0.0, "{} produced no gain", name);
+            assert!(profile.gain_m <= raw_gain, "{} added elevation gain", name);
+            assert_eq!(profile.elevations.len(), profile.distances.len());
+        }
+    }
+
+    #[test]
+    fn test_runs_between_gaps_are_smoothed_separately() {
+        use crate::track_loader::{LoadedTrack, TrackPoint};
+        let point = |north_m: f64, east_m: f64, elevation: f64| TrackPoint {
+            latitude: 46.0 + north_m / 111_320.0,
+            longitude: 7.0 + east_m / 77_300.0,
+            elevation,
+            time: None,
+            distance: None,
+        };
+        // 1km climbing 50m, a new segment 20m on (bridged), then one resuming 2km away and
+        // 200m higher, and a short last segment
+        let mut points: Vec<TrackPoint> = (0..200).map(|i| point(i as f64 * 5.0, 0.0, 100.0 + i as f64 * 0.25)).collect();
+        points.extend((0..100).map(|i| point(1015.0 + i as f64 * 5.0, 0.0, 150.0)));
+        points.extend((0..200).map(|i| point(1500.0, 2000.0 + i as f64 * 5.0, 350.0 + i as f64 * 0.25)));
+        points.extend((0..6).map(|i| point(1500.0, 4000.0 + i as f64 * 5.0, 400.0)));
+        let track = LoadedTrack::from_segments("gaps.gpx".to_string(), points, vec![0, 200, 300, 500]);
+
+        assert_eq!(track.gaps(), vec![300, 500]);
+        assert!((track.total_distance_km() - 2.53).abs() < 0.01, "{}", track.total_distance_km());
+
+        // Butterworth starts every run from a zero filter state, so its gain is not comparable here
+        for name in ["symmetric-fixed", "distbased", "savitzky-golay", "two-pass", "spike-deadzone", "kalman"] {
+            let smoother = smoother_from_name(name, None).unwrap();
+            let profile = smooth_between_gaps(smoother.as_ref(), &track.coordinates(), &track.cumulative_distances, &track.gaps());
+            assert_eq!(profile.runs.len(), 3);
+            // Neither gap's 200m step counts as gain
+            assert!(profile.gain_m() < 110.0, "{}: {}", name, profile.gain_m());
+            let mapped = profile.elevations_at(&track.cumulative_distances, &track.gaps());
+            assert_eq!(mapped.len(), track.points.len());
+            assert!((mapped[300] - 350.0).abs() < 5.0, "{}: {}", name, mapped[300]);
+            assert!((mapped[299] - 150.0).abs() < 5.0, "{}: {}", name, mapped[299]);
+        }
+    }
+
+    #[test]
+    fn test_elevations_at_maps_back_to_original_points() {
+        let (elevations, distances) = rolling_route();
+        let profile = SymmetricFixedSmoother { interval_m: 1.9 }.smooth(&elevations, &distances);
+        let mapped = profile.elevations_at(&distances);
+        assert_eq!(mapped.len(), distances.len());
+    }
+
+    #[test]
+    fn test_savitzky_golay_orders_keep_ramps_and_flatten_spikes() {
+        let ramp: Vec<f64> = (0..50).map(|i| i as f64).collect();
+        let mut spiky = vec![100.0; 50];
+        spiky[25] = 110.0;
+        for order in [0, 2, 3, 4] {
+            let smoothed = savitzky_golay_smooth_with_order(&ramp, 9, order);
+            assert!(smoothed[4..46].iter().zip(&ramp[4..46]).all(|(s, r)| (s - r).abs() < 1e-9), "order {}", order);
+            assert!(savitzky_golay_smooth_with_order(&spiky, 9, order)[25] < 105.0, "order {}", order);
+        }
+        assert_eq!(savitzky_golay_smooth_with_order(&ramp, 9, 0), savitzky_golay_smooth(&ramp, 9));
+        // An order the window cannot fit leaves the data alone
+        assert_eq!(savitzky_golay_smooth_with_order(&spiky, 5, 5), spiky);
+    }
+
+    #[test]
+    fn test_tunable_smoothers_build_from_their_ranges() {
+        for name in ["symmetric-fixed", "butterworth", "savitzky-golay", "two-pass", "barometric", "spike-deadzone"] {
+            let ranges = smoother_parameter_ranges(name).unwrap();
+            let midpoint: Vec<f64> = ranges.iter().map(|r| (r.min + r.max) / 2.0).collect();
+            assert!(smoother_from_parameters(name, &midpoint).is_ok(), "{} rejected its own range", name);
+        }
+        assert!(smoother_parameter_ranges("kalman").is_none());
+    }
+
+    #[test]
+    fn test_unknown_smoother_name() {
+        assert!(smoother_from_name("does-not-exist", None).is_err());
+        // dem-fusion cannot be built without a DEM to sample
+        assert!(smoother_from_name("dem-fusion", None).is_err());
+    }
+}
@@ -0,0 +1,216 @@
+/// ERROR TYPES - What went wrong loading or processing a file, and where
+/// Loaders and processors return TrackError so callers can branch on the kind of failure
+/// (and reports can group by it) without parsing messages. Every variant names the file,
+/// and parse and coordinate errors carry the line or point index when the reader knows it.
+/// TrackError converts into Box<dyn Error> with `?` like any other error.
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use xml::common::Position;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    Parse,               // Malformed XML / GPX / TCX / FIT
+    NoElevation,         // Readable, but no point carries an elevation
+    NoTracks,            // No track (or route / waypoints) to process
+    InvalidCoordinates,  // Latitude / longitude out of range
+    RepairFailed,        // Unreadable even after the repair passes
+    Io,                  // Missing file, permissions, ...
+    GroundTruthMissing,  // No official elevation CSV to compare against
+    UnsupportedFormat,   // Not a .gpx, .fit or .tcx file
+    Other,
+}
+
+impl ErrorKind {
+    /// Stable code for CSV reports
+    pub fn code(self) -> &'static str {
+        match self {
+            ErrorKind::Parse => "XML_PARSE_ERROR",
+            ErrorKind::NoElevation => "NO_ELEVATION_DATA",
+            ErrorKind::NoTracks => "EMPTY_OR_NO_TRACKS",
+            ErrorKind::InvalidCoordinates => "INVALID_COORDINATES",
+            ErrorKind::RepairFailed => "REPAIR_FAILED",
+            ErrorKind::Io => "FILE_ACCESS_ERROR",
+            ErrorKind::GroundTruthMissing => "GROUND_TRUTH_MISSING",
+            ErrorKind::UnsupportedFormat => "UNSUPPORTED_FORMAT",
+            ErrorKind::Other => "UNKNOWN_ERROR",
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+#[derive(Debug)]
+pub enum TrackError {
+    Io { file: PathBuf, source: io::Error },
+    Parse { file: PathBuf, line: Option<u64>, message: String },
+    UnsupportedFormat { file: PathBuf },
+    NoTracks { file: PathBuf },
+    NoElevation { file: PathBuf },
+    InvalidCoordinates { file: PathBuf, point_index: Option<usize>, message: String },
+    RepairFailed { file: PathBuf, original: Box<TrackError>, attempts: Vec<String> },
+    GroundTruthMissing { searched: Vec<PathBuf> },
+    GroundTruthPathAlreadySet { file: PathBuf, current: PathBuf },
+}
+
+impl TrackError {
+    pub fn io(file: &Path, source: io::Error) -> Self {
+        TrackError::Io { file: file.to_path_buf(), source }
+    }
+
+    pub fn parse(file: &Path, message: impl Into<String>) -> Self {
+        TrackError::Parse { file: file.to_path_buf(), line: None, message: message.into() }
+    }
+
+    /// An error from the gpx crate, with the line for XML-level failures
+    pub fn from_gpx(file: &Path, error: gpx::errors::GpxError) -> Self {
+        match error {
+            gpx::errors::GpxError::XmlParseError(xml_error) => TrackError::from_xml(file, xml_error),
+            gpx::errors::GpxError::LonLatOutOfBoundsError(..) => TrackError::InvalidCoordinates {
+                file: file.to_path_buf(),
+                point_index: None,
+                message: error.to_string(),
+            },
+            other => TrackError::parse(file, other.to_string()),
+        }
+    }
+
+    /// A csv crate error, with the line when the reader knows it
+    pub fn from_csv(file: &Path, error: csv::Error) -> Self {
+        TrackError::Parse {
+            file: file.to_path_buf(),
+            line: error.position().map(|p| p.line()),
+            message: format!("CSV error: {}", error),
+        }
+    }
+
+    /// An xml-rs reader error; its position is 0-based
+    pub fn from_xml(file: &Path, error: xml::reader::Error) -> Self {
+        TrackError::Parse {
+            file: file.to_path_buf(),
+            line: Some(error.position().row + 1),
+            message: format!("XML parse error: {}", error.msg()),
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            TrackError::Io { .. } => ErrorKind::Io,
+            TrackError::Parse { .. } => ErrorKind::Parse,
+            TrackError::UnsupportedFormat { .. } => ErrorKind::UnsupportedFormat,
+            TrackError::NoTracks { .. } => ErrorKind::NoTracks,
+            TrackError::NoElevation { .. } => ErrorKind::NoElevation,
+            TrackError::InvalidCoordinates { .. } => ErrorKind::InvalidCoordinates,
+            TrackError::RepairFailed { .. } => ErrorKind::RepairFailed,
+            TrackError::GroundTruthMissing { .. } => ErrorKind::GroundTruthMissing,
+            TrackError::GroundTruthPathAlreadySet { .. } => ErrorKind::Other,
+        }
+    }
+
+    /// The file the error is about
+    pub fn file(&self) -> Option<&Path> {
+        match self {
+            TrackError::Io { file, .. }
+            | TrackError::Parse { file, .. }
+            | TrackError::UnsupportedFormat { file }
+            | TrackError::NoTracks { file }
+            | TrackError::NoElevation { file }
+            | TrackError::InvalidCoordinates { file, .. }
+            | TrackError::RepairFailed { file, .. }
+            | TrackError::GroundTruthPathAlreadySet { file, .. } => Some(file),
+            TrackError::GroundTruthMissing { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for TrackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrackError::Io { file, source } => write!(f, "{}: {}", file.display(), source),
+            TrackError::Parse { file, line: Some(line), message } => write!(f, "{}:{}: {}", file.display(), line, message),
+            TrackError::Parse { file, line: None, message } => write!(f, "{}: {}", file.display(), message),
+            TrackError::UnsupportedFormat { file } => write!(f, "Unsupported track file format: {}", file.display()),
+            TrackError::NoTracks { file } => write!(f, "{}: no tracks, routes or waypoints found", file.display()),
+            TrackError::NoElevation { file } => write!(f, "{}: no elevation data found", file.display()),
+            TrackError::InvalidCoordinates { file, point_index: Some(index), message } => {
+                write!(f, "{}: invalid coordinates at point {}: {}", file.display(), index, message)
+            }
+            TrackError::InvalidCoordinates { file, point_index: None, message } => {
+                write!(f, "{}: invalid coordinates: {}", file.display(), message)
+            }
+            TrackError::RepairFailed { file, original, attempts } => {
+                write!(f, "{}: all repair attempts failed. Original: {}", file.display(), original)?;
+                for attempt in attempts {
+                    write!(f, ". {}", attempt)?;
+                }
+                Ok(())
+            }
+            TrackError::GroundTruthMissing { searched } => {
+                let paths: Vec<String> = searched.iter().map(|p| p.display().to_string()).collect();
+                write!(f, "No official elevation data CSV found ({}); pass --official-data", paths.join(" or "))
+            }
+            TrackError::GroundTruthPathAlreadySet { file, current } => {
+                write!(f, "{}: official elevation data path already set to {}", file.display(), current.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for TrackError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TrackError::Io { source, .. } => Some(source),
+            TrackError::RepairFailed { original, .. } => Some(original.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// Kind of any error a loader or processor returned, looking through the boxing
+pub fn error_kind(error: &(dyn std::error::Error + 'static)) -> ErrorKind {
+    if let Some(track_error) = error.downcast_ref::<TrackError>() {
+        track_error.kind()
+    } else if error.is::<io::Error>() {
+        ErrorKind::Io
+    } else if let Some(gpx_error) = error.downcast_ref::<gpx::errors::GpxError>() {
+        match gpx_error {
+            gpx::errors::GpxError::LonLatOutOfBoundsError(..) => ErrorKind::InvalidCoordinates,
+            _ => ErrorKind::Parse,
+        }
+    } else if error.is::<xml::reader::Error>() {
+        ErrorKind::Parse
+    } else {
+        ErrorKind::Other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gpx_errors_keep_file_line_and_kind() {
+        let file = Path::new("broken.gpx");
+        let source = "<?xml version=\"1.0\"?>\n<gpx version=\"1.1\" creator=\"test\">\n<trk><trkseg>\n<trkpt lat=\"46\" lon=\"7\"><ele>1 & 2</ele></trkpt>\n</trkseg></trk>\n</gpx>\n";
+        let error = TrackError::from_gpx(file, gpx::read(source.as_bytes()).unwrap_err());
+        assert_eq!(error.kind(), ErrorKind::Parse);
+        assert!(matches!(error, TrackError::Parse { line: Some(4), .. }), "{:?}", error);
+        assert!(error.to_string().starts_with("broken.gpx:4: "), "{}", error);
+
+        let out_of_range = "<gpx version=\"1.1\" creator=\"test\"><trk><trkseg><trkpt lat=\"95\" lon=\"7\"/></trkseg></trk></gpx>";
+        let error = TrackError::from_gpx(file, gpx::read(out_of_range.as_bytes()).unwrap_err());
+        assert_eq!(error.kind(), ErrorKind::InvalidCoordinates);
+
+        // Through a Box<dyn Error>, as the processors return it
+        let boxed: Box<dyn std::error::Error> = TrackError::NoElevation { file: file.to_path_buf() }.into();
+        assert_eq!(error_kind(boxed.as_ref()).code(), "NO_ELEVATION_DATA");
+        let boxed: Box<dyn std::error::Error> = io::Error::new(io::ErrorKind::NotFound, "gone").into();
+        assert_eq!(error_kind(boxed.as_ref()), ErrorKind::Io);
+        let boxed: Box<dyn std::error::Error> = "something else".into();
+        assert_eq!(error_kind(boxed.as_ref()), ErrorKind::Other);
+    }
+}
@@ -0,0 +1,260 @@
+/// FINE-GRAINED INTERVAL ANALYSIS - DistBased processing swept from 0.05m to 8.0m
+use std::path::Path;
+use std::collections::HashMap;
+use geo::{HaversineDistance, point};
+use csv::Writer;
+use rayon::prelude::*;
+use walkdir::WalkDir;
+use crate::custom_smoother::{ElevationData, SmoothingVariant};
+use crate::error::TrackError;
+use crate::track_loader::{calculate_gain_loss, gpx_course_segments, gpx_geometry, read_gpx_file};
+use crate::ground_truth::GroundTruthRegistry;
+
+// Separate struct for fine-grained analysis
+#[derive(Debug, Clone)]
+struct FineGrainedResult {
+    filename: String,
+    raw_distance_km: f32,
+    raw_elevation_gain_m: u32,
+    official_elevation_gain_m: u32,
+    interval_gains: Vec<(f32, u32)>, // (interval_m, gain_m)
+    interval_accuracies: Vec<(f32, f32)>, // (interval_m, accuracy_percent)
+}
+
+// Fine-grained analysis function (existing functionality)
+pub fn run_fine_grained_analysis(gpx_folder: &str) -> Result<(), Box<dyn std::error::Error>> {
+    println!("\n📊 FINE-GRAINED INTERVAL ANALYSIS");
+    println!("==================================");
+    println!("Testing elevation processing with intervals from 0.05m to 8.0m");
+    
+    let official_data = crate::load_ground_truth_registry()?;
+    let mut all_results = Vec::new();
+    
+    let mut file_count = 0;
+    let mut processed_count = 0;
+    
+    for entry in WalkDir::new(gpx_folder) {
+        let entry = entry?;
+        if entry.file_type().is_file() {
+            if let Some(extension) = entry.path().extension() {
+                if extension.to_str().unwrap_or("").to_lowercase() == "gpx" {
+                    file_count += 1;
+                    match process_gpx_file_fine_grained(entry.path(), &official_data) {
+                        Ok(result) => {
+                            all_results.push(result);
+                            processed_count += 1;
+                        },
+                        Err(e) => {
+                            eprintln!("⚠️  Error processing {}: {}", entry.path().display(), e);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    
+    println!("\n✅ Processed {} out of {} GPX files", processed_count, file_count);
+    official_data.print_match_report();
+    
+    if !all_results.is_empty() {
+        let output_path = Path::new(gpx_folder).join("fine_grained_analysis_0.05_to_8m.csv");
+        write_fine_grained_csv(&all_results, &output_path)?;
+        print_fine_grained_summary(&all_results);
+        println!("📁 Results saved to: {}", output_path.display());
+    } else {
+        println!("⚠️  No valid results to save");
+    }
+    
+    Ok(())
+}
+
+fn process_gpx_file_fine_grained(
+    path: &Path, 
+    official_data: &GroundTruthRegistry
+) -> Result<FineGrainedResult, Box<dyn std::error::Error>> {
+    let gpx = read_gpx_file(path)?;
+    if gpx_geometry(&gpx).is_none() {
+        return Err(TrackError::NoTracks { file: path.to_path_buf() }.into());
+    }
+    
+    let mut coords: Vec<(f64, f64, f64)> = vec![];
+    
+    for segment in gpx_course_segments(&gpx) {
+        for pt in segment {
+            if let Some(ele) = pt.elevation {
+                let lat = pt.point().y();
+                let lon = pt.point().x();
+                coords.push((lat, lon, ele));
+            }
+        }
+    }
+    
+    if coords.is_empty() {
+        return Err(TrackError::NoElevation { file: path.to_path_buf() }.into());
+    }
+    
+    // Calculate distances
+    let mut distances = vec![0.0];
+    for i in 1..coords.len() {
+        let a = point!(x: coords[i-1].1, y: coords[i-1].0);
+        let b = point!(x: coords[i].1, y: coords[i].0);
+        let dist = a.haversine_distance(&b);
+        distances.push(distances[i-1] + dist);
+    }
+    
+    let raw_elevations: Vec<f64> = coords.iter().map(|x| x.2).collect();
+    let total_distance_km = distances.last().unwrap() / 1000.0;
+    let (raw_gain, _) = calculate_gain_loss(&raw_elevations);
+    
+    let filename = path.file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("unknown")
+        .to_string();
+    
+    // Matched by content hash, then by name (original or cleaned), then by course fingerprint
+    let official_gain = official_data.official_gain(path, &coords);
+    
+    println!("🔄 Processing: {} ({:.1}km, official: {}m)", filename, total_distance_km, official_gain);
+    
+    // Generate intervals from 0.05m to 8.0m in 0.05m increments
+    let intervals: Vec<f64> = (1..=160).map(|i| i as f64 * 0.05).collect();
+    
+    // Process all intervals in parallel for this file
+    let interval_results: Vec<(f32, u32, f32)> = intervals
+        .par_iter()
+        .map(|&interval| {
+            let gain = distbased_with_interval(&raw_elevations, &distances, interval);
+            let gain_u32 = gain.round() as u32;
+            let accuracy = if official_gain > 0 {
+                (gain_u32 as f32 / official_gain as f32) * 100.0
+            } else {
+                0.0
+            };
+            (interval as f32, gain_u32, accuracy)
+        })
+        .collect();
+    
+    let mut interval_gains = Vec::new();
+    let mut interval_accuracies = Vec::new();
+    
+    for (interval, gain, accuracy) in interval_results {
+        interval_gains.push((interval, gain));
+        interval_accuracies.push((interval, accuracy));
+    }
+    
+    Ok(FineGrainedResult {
+        filename,
+        raw_distance_km: total_distance_km as f32,
+        raw_elevation_gain_m: raw_gain.round() as u32,
+        official_elevation_gain_m: official_gain,
+        interval_gains,
+        interval_accuracies,
+    })
+}
+
+// Optimized distance-based processing with custom intervals
+fn distbased_with_interval(raw_elevations: &[f64], distances: &[f64], interval_meters: f64) -> f64 {
+    let mut elevation_data = ElevationData::new_with_variant(
+        raw_elevations.to_vec(), 
+        distances.to_vec(), 
+        SmoothingVariant::DistBased
+    );
+    
+    elevation_data.apply_custom_interval_processing(interval_meters);
+    elevation_data.get_total_elevation_gain()
+}
+
+fn write_fine_grained_csv(results: &[FineGrainedResult], output_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut wtr = Writer::from_path(output_path)?;
+    
+    // Build header
+    let mut header = vec![
+        "Filename".to_string(),
+        "Raw Distance (km)".to_string(),
+        "Raw Elevation Gain (m)".to_string(),
+        "Official Elevation Gain (m)".to_string(),
+    ];
+    
+    // Add columns for each interval
+    for i in 1..=160 {
+        let interval = i as f32 * 0.05;
+        header.push(format!("{:.2}m Gain", interval));
+        header.push(format!("{:.2}m Accuracy %", interval));
+    }
+    
+    wtr.write_record(&header)?;
+    
+    // Write data rows
+    for result in results {
+        let mut row = vec![
+            result.filename.clone(),
+            result.raw_distance_km.to_string(),
+            result.raw_elevation_gain_m.to_string(),
+            result.official_elevation_gain_m.to_string(),
+        ];
+        
+        // Add interval data
+        for i in 0..result.interval_gains.len() {
+            row.push(result.interval_gains[i].1.to_string());
+            row.push(format!("{:.1}", result.interval_accuracies[i].1));
+        }
+        
+        wtr.write_record(&row)?;
+    }
+    
+    wtr.flush()?;
+    Ok(())
+}
+
+fn print_fine_grained_summary(results: &[FineGrainedResult]) {
+    println!("\n📊 FINE-GRAINED ANALYSIS SUMMARY");
+    println!("================================");
+    
+    // Find best interval for each file
+    let mut best_intervals: Vec<f32> = Vec::new();
+    
+    for result in results {
+        if result.official_elevation_gain_m > 0 {
+            // Find interval with accuracy closest to 100%
+            let best_idx = result.interval_accuracies
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, (_, acc))| ((acc - 100.0).abs() * 100.0) as i32)
+                .map(|(idx, _)| idx)
+                .unwrap_or(0);
+            
+            if best_idx < result.interval_gains.len() {
+                best_intervals.push(result.interval_gains[best_idx].0);
+            }
+        }
+    }
+    
+    if !best_intervals.is_empty() {
+        best_intervals.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let median_interval = best_intervals[best_intervals.len() / 2];
+        let avg_interval = best_intervals.iter().sum::<f32>() / best_intervals.len() as f32;
+        
+        println!("🎯 Optimal interval statistics:");
+        println!("  Average optimal interval: {:.2}m", avg_interval);
+        println!("  Median optimal interval: {:.2}m", median_interval);
+        println!("  Min optimal interval: {:.2}m", best_intervals.first().unwrap());
+        println!("  Max optimal interval: {:.2}m", best_intervals.last().unwrap());
+        
+        // Count distribution
+        println!("\n📈 Optimal interval distribution:");
+        let mut distribution: HashMap<i32, i32> = HashMap::new();
+        for &interval in &best_intervals {
+            // Convert to integer key (multiply by 10 to preserve one decimal place)
+            let bucket_key = ((interval / 0.5).round() * 5.0) as i32;
+            *distribution.entry(bucket_key).or_insert(0) += 1;
+        }
+        
+        let mut buckets: Vec<_> = distribution.into_iter().collect();
+        buckets.sort_by_key(|&(k, _)| k);
+        
+        for (bucket_key, count) in buckets {
+            let bucket_value = bucket_key as f32 / 10.0;
+            println!("  {:.1}m ± 0.25m: {} files", bucket_value, count);
+        }
+    }
+}
\ No newline at end of file
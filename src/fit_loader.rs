@@ -0,0 +1,216 @@
+/// FIT LOADER - Garmin/Wahoo .fit activity files into the shared point model
+/// Reads `record` messages (position, enhanced_altitude, distance, timestamp) so FIT
+/// data goes straight into ElevationData without a GPX conversion step. Records carrying
+/// `absolute_pressure` come from a barometric altimeter, and every timer start after a
+/// pause begins a new segment.
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use chrono::Utc;
+use fitparser::profile::MesgNum;
+use fitparser::{FitDataRecord, Value};
+use crate::error::TrackError;
+use crate::track_loader::{check_coordinates, AltitudeSource, LoadedTrack, TrackPoint};
+
+// FIT stores positions as semicircles: 2^31 semicircles = 180 degrees
+const SEMICIRCLES_TO_DEGREES: f64 = 180.0 / 2_147_483_648.0;
+
+/// Load every record message that has a GPS fix and an altitude
+pub fn load_fit_track(path: &Path) -> Result<LoadedTrack, TrackError> {
+    let file = File::open(path).map_err(|e| TrackError::io(path, e))?;
+    let mut reader = BufReader::new(file);
+    let records = fitparser::from_reader(&mut reader).map_err(|e| TrackError::parse(path, e.to_string()))?;
+
+    let (points, segment_starts, barometric_points) = records_to_points(&records);
+
+    if points.is_empty() {
+        return Err(TrackError::NoElevation { file: path.to_path_buf() });
+    }
+    check_coordinates(path, &points)?;
+
+    let filename = path.file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("unknown")
+        .to_string();
+
+    let altitude_source = AltitudeSource::from_point_counts(barometric_points, points.len());
+    Ok(LoadedTrack::from_segments(filename, points, segment_starts).with_altitude_source(altitude_source))
+}
+
+/// Points from the record messages, the first point of each timer-delimited segment, and
+/// how many of them carried an air pressure reading
+fn records_to_points(records: &[FitDataRecord]) -> (Vec<TrackPoint>, Vec<usize>, usize) {
+    let mut points = Vec::new();
+    let mut segment_starts = vec![0];
+    let mut timer_restarted = false;
+    let mut barometric_points = 0;
+
+    for record in records {
+        match record.kind() {
+            MesgNum::Event if is_timer_start(record) => timer_restarted = true,
+            MesgNum::Record => {
+                if let Some((point, has_pressure)) = record_to_point(record) {
+                    // The first fix after a timer start opens a segment
+                    if timer_restarted && !points.is_empty() {
+                        segment_starts.push(points.len());
+                    }
+                    timer_restarted = false;
+                    barometric_points += usize::from(has_pressure);
+                    points.push(point);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    (points, segment_starts, barometric_points)
+}
+
+/// Whether an event message is the timer (re)starting
+fn is_timer_start(record: &FitDataRecord) -> bool {
+    let field = |name: &str| record.fields().iter().find(|f| f.name() == name).map(|f| f.value().to_string());
+    field("event").as_deref() == Some("timer") && field("event_type").as_deref() == Some("start")
+}
+
+/// The point, and whether the record carried an air pressure reading
+fn record_to_point(record: &FitDataRecord) -> Option<(TrackPoint, bool)> {
+    let mut latitude = None;
+    let mut longitude = None;
+    let mut enhanced_altitude = None;
+    let mut altitude = None;
+    let mut distance = None;
+    let mut pressure = None;
+    let mut time = None;
+
+    for field in record.fields() {
+        match field.name() {
+            "position_lat" => latitude = semicircles_to_degrees(field.value()),
+            "position_long" => longitude = semicircles_to_degrees(field.value()),
+            "enhanced_altitude" => enhanced_altitude = value_as_f64(field.value()),
+            "altitude" => altitude = value_as_f64(field.value()),
+            "distance" => distance = value_as_f64(field.value()),
+            "absolute_pressure" => pressure = value_as_f64(field.value()),
+            "timestamp" => {
+                if let Value::Timestamp(ts) = field.value() {
+                    time = Some(ts.with_timezone(&Utc));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Prefer enhanced_altitude (32-bit, used by newer devices) over the legacy 16-bit field,
+    // falling back to the pressure altitude (absolute_pressure is in Pa)
+    let elevation = enhanced_altitude
+        .or(altitude)
+        .or_else(|| pressure.map(|pa| crate::barometric::pressure_to_altitude(pa / 100.0)))?;
+
+    let point = TrackPoint {
+        latitude: latitude?,
+        longitude: longitude?,
+        elevation,
+        time,
+        distance,
+    };
+    Some((point, pressure.is_some()))
+}
+
+fn semicircles_to_degrees(value: &Value) -> Option<f64> {
+    let degrees = match value {
+        Value::SInt32(v) => *v as f64 * SEMICIRCLES_TO_DEGREES,
+        other => value_as_f64(other)? * SEMICIRCLES_TO_DEGREES,
+    };
+    // Devices write 0x7FFFFFFF (decoded as invalid) or garbage before a fix
+    if degrees.abs() <= 180.0 {
+        Some(degrees)
+    } else {
+        None
+    }
+}
+
+fn value_as_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Invalid | Value::String(_) | Value::Array(_) | Value::Timestamp(_) => None,
+        other => other.clone().try_into().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fitparser::FitDataField;
+
+    fn record(fields: &[(&str, Value)]) -> FitDataRecord {
+        message(MesgNum::Record, fields)
+    }
+
+    fn message(kind: MesgNum, fields: &[(&str, Value)]) -> FitDataRecord {
+        let mut record = FitDataRecord::new(kind);
+        for (number, (name, value)) in fields.iter().enumerate() {
+            record.push(FitDataField::new(name.to_string(), number as u8, None, value.clone(), String::new()));
+        }
+        record
+    }
+
+    #[test]
+    fn test_records_become_points_in_degrees() {
+        // 2^30 semicircles is 90 degrees; fitparser decodes the "no fix" marker as Invalid
+        assert_eq!(semicircles_to_degrees(&Value::SInt32(1 << 30)), Some(90.0));
+        assert_eq!(semicircles_to_degrees(&Value::SInt32(-(1 << 29))), Some(-45.0));
+        assert_eq!(semicircles_to_degrees(&Value::Float64(3.0e9)), None);
+        assert_eq!(semicircles_to_degrees(&Value::Invalid), None);
+
+        let semicircles = |degrees: f64| Value::SInt32((degrees / SEMICIRCLES_TO_DEGREES).round() as i32);
+        let position = [("position_lat", semicircles(46.0)), ("position_long", semicircles(7.0))];
+        let (point, barometric) = record_to_point(&record(&[
+            position[0].clone(),
+            position[1].clone(),
+            ("altitude", Value::Float64(480.0)),
+            ("enhanced_altitude", Value::Float64(512.4)),
+            ("distance", Value::Float64(1234.5)),
+        ])).unwrap();
+        assert!((point.latitude - 46.0).abs() < 1e-6 && (point.longitude - 7.0).abs() < 1e-6);
+        assert_eq!(point.elevation, 512.4);
+        assert_eq!(point.distance, Some(1234.5));
+        assert!(!barometric);
+
+        // Legacy altitude only, then pressure only (101325 Pa is sea level)
+        let legacy = record_to_point(&record(&[position[0].clone(), position[1].clone(), ("altitude", Value::Float64(480.0))])).unwrap();
+        assert_eq!(legacy.0.elevation, 480.0);
+        let (pressure, barometric) = record_to_point(&record(&[
+            position[0].clone(),
+            position[1].clone(),
+            ("absolute_pressure", Value::Float64(101_325.0)),
+        ])).unwrap();
+        assert!(pressure.elevation.abs() < 1.0 && barometric);
+
+        // Records before the first fix, or without any altitude, are skipped
+        assert!(record_to_point(&record(&[("altitude", Value::Float64(480.0))])).is_none());
+        assert!(record_to_point(&record(&[position[0].clone(), ("position_long", Value::Invalid), ("altitude", Value::Float64(480.0))])).is_none());
+        assert!(record_to_point(&record(&position)).is_none());
+    }
+
+    #[test]
+    fn test_timer_restarts_start_segments() {
+        let semicircles = |degrees: f64| Value::SInt32((degrees / SEMICIRCLES_TO_DEGREES).round() as i32);
+        let fix = |lat: f64| record(&[("position_lat", semicircles(lat)), ("position_long", semicircles(7.0)), ("altitude", Value::Float64(500.0))]);
+        let timer = |event_type: &str| message(MesgNum::Event, &[
+            ("event", Value::String("timer".to_string())),
+            ("event_type", Value::String(event_type.to_string())),
+        ]);
+
+        // Paused and resumed 1km further on; the opening start and a start without a fix add nothing
+        let records = vec![
+            timer("start"), fix(46.0), fix(46.0001), timer("stop_all"),
+            timer("start"), record(&[("altitude", Value::Float64(500.0))]), fix(46.0091), fix(46.0092),
+        ];
+        let (points, segment_starts, barometric_points) = records_to_points(&records);
+        assert_eq!(points.len(), 4);
+        assert_eq!(segment_starts, vec![0, 2]);
+        assert_eq!(barometric_points, 0);
+
+        let track = LoadedTrack::from_segments("paused.fit".to_string(), points, segment_starts);
+        assert_eq!(track.gaps(), vec![2]);
+        assert!(track.total_distance_km() < 0.05);
+    }
+}
@@ -3,7 +3,8 @@
 /// Ultra-comprehensive search for the optimal SymmetricFixed interval
 /// Goal: Find the best balance between:
 /// 1. Elevation gain accuracy (closest to 100%)
-/// 2. Gain/loss balance (ratio closest to 1.0)
+/// 2. Loss accuracy against the official loss where known, gain/loss balance (ratio
+///    closest to 1.0) as a proxy elsewhere
 /// 3. Maximum files in 90-110% and 80-120% accuracy ranges
 
 use std::path::Path;
@@ -38,6 +39,13 @@ pub struct FocusedSymmetricResult {
     files_perfect_095_105: u32,    // Ratio between 0.95-1.05
     ratio_std_deviation: f32,
     
+    // Direct loss and distance accuracy, over files whose record has the official figure
+    files_with_official_loss: u32,
+    mean_loss_accuracy: f32,
+    files_loss_in_90_110_percent: u32,
+    files_with_official_distance: u32,
+    mean_distance_accuracy: f32,   // Same for every interval; reported, not scored
+    
     // Combined optimization score
     optimization_score: f32,       // Higher = better overall performance
     
@@ -51,6 +59,8 @@ struct GpxFileData {
     elevations: Vec<f64>,
     distances: Vec<f64>,
     official_gain: u32,
+    official_loss: Option<u32>,         // Only non-zero official losses
+    official_distance_km: Option<f64>,
 }
 
 #[derive(Debug, Clone)]
@@ -61,6 +71,8 @@ struct SingleFileResult {
     processed_loss: f32,
     gain_accuracy: f32,
     gain_loss_ratio: f32,
+    loss_accuracy: Option<f32>,
+    distance_accuracy: Option<f32>,
 }
 
 pub fn run_focused_symmetric_analysis(gpx_folder: &str) -> Result<(), Box<dyn std::error::Error>> {
@@ -151,91 +163,45 @@ pub fn run_focused_symmetric_analysis(gpx_folder: &str) -> Result<(), Box<dyn st
 }
 
 fn load_gpx_data(gpx_folder: &str) -> Result<(HashMap<String, GpxFileData>, Vec<String>), Box<dyn std::error::Error>> {
-    use std::fs::File;
-    use std::io::BufReader;
-    use gpx::read;
-    use geo::{HaversineDistance, point};
     use walkdir::WalkDir;
-    
+    use crate::track_loader::{load_track, is_supported_track_file};
+
     let mut gpx_data = HashMap::new();
     let mut valid_files = Vec::new();
-    
-    let official_data = crate::load_official_elevation_data()?;
-    
+
+    let official_data = crate::load_ground_truth_registry()?;
+
     for entry in WalkDir::new(gpx_folder) {
         let entry = entry?;
-        if entry.file_type().is_file() {
-            if let Some(extension) = entry.path().extension() {
-                if extension.to_str().unwrap_or("").to_lowercase() == "gpx" {
-                    let path = entry.path();
-                    let filename = path.file_name()
-                        .and_then(|n| n.to_str())
-                        .unwrap_or("")
-                        .to_string();
-                    
-                    match File::open(path) {
-                        Ok(file) => {
-                            let reader = BufReader::new(file);
-                            match read(reader) {
-                                Ok(gpx) => {
-                                    let mut coords: Vec<(f64, f64, f64)> = vec![];
-                                    
-                                    for track in gpx.tracks {
-                                        for segment in track.segments {
-                                            for pt in segment.points {
-                                                if let Some(ele) = pt.elevation {
-                                                    coords.push((pt.point().y(), pt.point().x(), ele));
-                                                }
-                                            }
-                                        }
-                                    }
-                                    
-                                    if !coords.is_empty() {
-                                        let mut distances = vec![0.0];
-                                        for i in 1..coords.len() {
-                                            let a = point!(x: coords[i-1].1, y: coords[i-1].0);
-                                            let b = point!(x: coords[i].1, y: coords[i].0);
-                                            let dist = a.haversine_distance(&b);
-                                            distances.push(distances[i-1] + dist);
-                                        }
-                                        
-                                        let elevations: Vec<f64> = coords.iter().map(|c| c.2).collect();
-                                        
-                                        // Handle both original and cleaned filenames
-                                        let clean_filename = if filename.starts_with("cleaned_") {
-                                            filename.strip_prefix("cleaned_").unwrap_or(&filename)
-                                        } else {
-                                            &filename
-                                        };
-                                        
-                                        let official_gain = official_data
-                                            .get(&clean_filename.to_lowercase())
-                                            .copied()
-                                            .unwrap_or(0);
-                                        
-                                        if official_gain > 0 {
-                                            let file_data = GpxFileData {
-                                                filename: filename.clone(),
-                                                elevations,
-                                                distances,
-                                                official_gain,
-                                            };
-                                            
-                                            gpx_data.insert(filename.clone(), file_data);
-                                            valid_files.push(filename);
-                                        }
-                                    }
-                                },
-                                Err(_) => continue,
-                            }
-                        },
-                        Err(_) => continue,
-                    }
-                }
+        if entry.file_type().is_file() && is_supported_track_file(entry.path()) {
+            // GPX and FIT both land in the same point model; unreadable files are skipped
+            let track = match load_track(entry.path()) {
+                Ok(track) => track,
+                Err(_) => continue,
+            };
+            let filename = track.filename.clone();
+
+            // Matched by content hash, then by name (original or cleaned), then by course fingerprint
+            let official = official_data.official_record(entry.path(), &track.coordinates());
+
+            if let Some(official) = official.filter(|r| r.gain_m > 0) { // Only include files with official data
+                let file_data = GpxFileData {
+                    elevations: track.elevations(),
+                    distances: track.cumulative_distances,
+                    filename: filename.clone(),
+                    official_gain: official.gain_m,
+                    official_loss: official.loss_m.filter(|&loss| loss > 0),
+                    official_distance_km: official.distance_km,
+                };
+
+                gpx_data.insert(filename.clone(), file_data);
+                valid_files.push(filename);
             }
         }
     }
-    
+
+    official_data.print_match_report();
+
     Ok((gpx_data, valid_files))
 }
 
@@ -303,6 +269,9 @@ fn process_single_file_symmetric(
     let official_gain = file_data.official_gain as f32;
     let gain_accuracy = (gain / official_gain) * 100.0;
     let gain_loss_ratio = gain / loss.max(1.0); // Avoid division by zero
+    let loss_accuracy = file_data.official_loss.map(|official| loss / official as f32 * 100.0);
+    let track_distance_km = file_data.distances.last().copied().unwrap_or(0.0) / 1000.0;
+    let distance_accuracy = file_data.official_distance_km.map(|official| (track_distance_km / official * 100.0) as f32);
     
     SingleFileResult {
         filename: file_data.filename.clone(),
@@ -311,6 +280,8 @@ fn process_single_file_symmetric(
         processed_loss: loss,
         gain_accuracy,
         gain_loss_ratio,
+        loss_accuracy,
+        distance_accuracy,
     }
 }
 
@@ -359,6 +330,11 @@ fn calculate_focused_metrics(
             files_excellent_09_11: 0,
             files_perfect_095_105: 0,
             ratio_std_deviation: 0.0,
+            files_with_official_loss: 0,
+            mean_loss_accuracy: 0.0,
+            files_loss_in_90_110_percent: 0,
+            files_with_official_distance: 0,
+            mean_distance_accuracy: 0.0,
             optimization_score: 0.0,
             total_files: 0,
         };
@@ -397,6 +373,21 @@ fn calculate_focused_metrics(
     let median_gain_loss_ratio = calculate_median(&ratios);
     let ratio_std_deviation = calculate_std_deviation(&ratios);
     
+    // Direct loss and distance accuracy where the official figures are known
+    let loss_accuracies: Vec<f32> = file_results.iter().filter_map(|r| r.loss_accuracy).collect();
+    let files_with_official_loss = loss_accuracies.len() as u32;
+    let mean_loss_accuracy = loss_accuracies.iter().sum::<f32>() / files_with_official_loss.max(1) as f32;
+    let files_loss_in_90_110_percent = loss_accuracies.iter().filter(|&&acc| (90.0..=110.0).contains(&acc)).count() as u32;
+    let distance_accuracies: Vec<f32> = file_results.iter().filter_map(|r| r.distance_accuracy).collect();
+    let files_with_official_distance = distance_accuracies.len() as u32;
+    let mean_distance_accuracy = distance_accuracies.iter().sum::<f32>() / files_with_official_distance.max(1) as f32;
+    
+    // The gain/loss ratio proxy only scores the files without an official loss
+    let proxy_ratios: Vec<f32> = file_results.iter().filter(|r| r.loss_accuracy.is_none()).map(|r| r.gain_loss_ratio).collect();
+    let proxy_files_excellent = proxy_ratios.iter().filter(|&&r| (0.9..=1.1).contains(&r)).count() as u32;
+    let proxy_median_ratio = calculate_median(&proxy_ratios);
+    let proxy_ratio_std = calculate_std_deviation(&proxy_ratios);
+    
     // Calculate comprehensive optimization score
     let optimization_score = calculate_optimization_score(
         files_in_90_110_percent,
@@ -404,12 +395,15 @@ fn calculate_focused_metrics(
         files_in_95_105_percent,
         files_in_98_102_percent,
         files_balanced_08_12,
-        files_excellent_09_11,
+        proxy_files_excellent,
         files_perfect_095_105,
         mean_gain_accuracy,
-        median_gain_loss_ratio,
+        proxy_median_ratio,
         accuracy_std_deviation,
-        ratio_std_deviation,
+        proxy_ratio_std,
+        files_with_official_loss,
+        files_loss_in_90_110_percent,
+        mean_loss_accuracy,
         total_files
     );
     
@@ -430,6 +424,11 @@ fn calculate_focused_metrics(
         files_excellent_09_11,
         files_perfect_095_105,
         ratio_std_deviation,
+        files_with_official_loss,
+        mean_loss_accuracy,
+        files_loss_in_90_110_percent,
+        files_with_official_distance,
+        mean_distance_accuracy,
         optimization_score,
         total_files,
     }
@@ -441,12 +440,15 @@ fn calculate_optimization_score(
     _files_95_105: u32,
     files_98_102: u32,
     _files_balanced: u32,
-    files_excellent: u32,
+    proxy_files_excellent: u32,
     _files_perfect: u32,
     mean_accuracy: f32,
-    median_ratio: f32,
+    proxy_median_ratio: f32,
     accuracy_std: f32,
-    ratio_std: f32,
+    proxy_ratio_std: f32,
+    files_with_loss: u32,
+    files_loss_90_110: u32,
+    mean_loss_accuracy: f32,
     total_files: u32
 ) -> f32 {
     let total_f = total_files as f32;
@@ -454,15 +456,28 @@ fn calculate_optimization_score(
     // Primary metrics (70% of score)
     let accuracy_coverage = (files_90_110 as f32 / total_f) * 100.0 * 0.30; // 30% weight on 90-110% coverage
     let precision_coverage = (files_98_102 as f32 / total_f) * 100.0 * 0.20; // 20% weight on 98-102% precision
-    let balance_quality = (files_excellent as f32 / total_f) * 100.0 * 0.20; // 20% weight on excellent balance
+    let proxy_files = total_files.saturating_sub(files_with_loss).max(1) as f32;
+    let proxy_balance = (proxy_files_excellent as f32 / proxy_files) * 100.0 * 0.20; // 20% weight on excellent balance
     
     // Accuracy quality (20% of score)
     let accuracy_quality = (100.0 - (mean_accuracy - 100.0).abs()) * 0.10; // 10% weight on mean accuracy
-    let ratio_quality = (100.0 - (median_ratio - 1.0).abs() * 50.0).max(0.0) * 0.10; // 10% weight on ratio balance
+    let proxy_ratio = (100.0 - (proxy_median_ratio - 1.0).abs() * 50.0).max(0.0) * 0.10; // 10% weight on ratio balance
+    
+    // Files with an official loss are scored on loss accuracy instead of the gain/loss ratio
+    // proxy (wrong for point-to-point races); the two share the balance weight by file count
+    let (balance_quality, ratio_quality) = if files_with_loss > 0 {
+        let loss_share = files_with_loss as f32 / total_f;
+        let loss_coverage = (files_loss_90_110 as f32 / files_with_loss as f32) * 100.0 * 0.20;
+        let loss_quality = (100.0 - (mean_loss_accuracy - 100.0).abs()).max(0.0) * 0.10;
+        (proxy_balance * (1.0 - loss_share) + loss_coverage * loss_share,
+         proxy_ratio * (1.0 - loss_share) + loss_quality * loss_share)
+    } else {
+        (proxy_balance, proxy_ratio)
+    };
     
     // Consistency bonus (10% of score)
     let consistency_bonus = ((20.0 - accuracy_std.min(20.0)) / 20.0 * 50.0 + 
-                            (2.0 - ratio_std.min(2.0)) / 2.0 * 50.0) * 0.10;
+                            (2.0 - proxy_ratio_std.min(2.0)) / 2.0 * 50.0) * 0.10;
     
     accuracy_coverage + precision_coverage + balance_quality + accuracy_quality + ratio_quality + consistency_bonus
 }
@@ -502,7 +517,9 @@ fn write_focused_results(
         "Files_90-110%", "Files_80-120%", "Files_95-105%", "Files_98-102%",
         "Mean_Accuracy_%", "Median_Accuracy_%", "Best_Accuracy_%", "Worst_Accuracy_%", "Accuracy_StdDev",
         "Mean_Ratio", "Median_Ratio", "Files_Balanced_0.8-1.2", "Files_Excellent_0.9-1.1", 
-        "Files_Perfect_0.95-1.05", "Ratio_StdDev", "Total_Files"
+        "Files_Perfect_0.95-1.05", "Ratio_StdDev",
+        "Files_With_Official_Loss", "Mean_Loss_Accuracy_%", "Files_Loss_90-110%",
+        "Files_With_Official_Distance", "Mean_Distance_Accuracy_%", "Total_Files"
     ])?;
     
     // Sort by optimization score (highest first)
@@ -529,6 +546,11 @@ fn write_focused_results(
             &result.files_excellent_09_11.to_string(),
             &result.files_perfect_095_105.to_string(),
             &format!("{:.3}", result.ratio_std_deviation),
+            &result.files_with_official_loss.to_string(),
+            &format!("{:.2}", result.mean_loss_accuracy),
+            &result.files_loss_in_90_110_percent.to_string(),
+            &result.files_with_official_distance.to_string(),
+            &format!("{:.2}", result.mean_distance_accuracy),
             &result.total_files.to_string(),
         ])?;
     }
@@ -578,6 +600,16 @@ fn print_focused_analysis(results: &[FocusedSymmetricResult]) {
              best_result.files_perfect_095_105, total_files,
              (best_result.files_perfect_095_105 as f32 / total_files as f32) * 100.0);
     
+    if best_result.files_with_official_loss > 0 {
+        println!("• Loss accuracy (official loss known): {:.2}% mean, {}/{} files in 90-110%",
+                 best_result.mean_loss_accuracy, best_result.files_loss_in_90_110_percent,
+                 best_result.files_with_official_loss);
+    }
+    if best_result.files_with_official_distance > 0 {
+        println!("• Distance accuracy (official distance known): {:.2}% mean over {} files",
+                 best_result.mean_distance_accuracy, best_result.files_with_official_distance);
+    }
+    
     println!("\n📈 STATISTICAL QUALITY:");
     println!("• Mean accuracy: {:.2}%", best_result.mean_gain_accuracy);
     println!("• Median accuracy: {:.2}%", best_result.median_gain_accuracy);
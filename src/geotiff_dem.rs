@@ -0,0 +1,520 @@
+/// GEOTIFF DEM - High-resolution single-band GeoTIFF elevation rasters (national LiDAR surveys)
+/// Rasters in a directory are indexed once from their headers; pixel data is decoded on first
+/// use and kept in a byte-bounded cache so a batch run doesn't reopen a raster for every track point.
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tiff::decoder::{Decoder, DecodingResult, Limits};
+use tiff::tags::Tag;
+use walkdir::WalkDir;
+use crate::dem::ElevationLookup;
+use crate::error::TrackError;
+
+// Largest raster decoded (the decoder's own buffer, before conversion to f32)
+const MAX_RASTER_BYTES: usize = 512 * 1024 * 1024;
+// Decoded f32 samples kept in memory at once
+const MAX_CACHE_BYTES: usize = 1024 * 1024 * 1024;
+
+// GeoKey IDs (GeoTIFF 1.0 spec, section 6.2)
+const GT_RASTER_TYPE_GEO_KEY: u16 = 1025;
+const GEOGRAPHIC_TYPE_GEO_KEY: u16 = 2048;
+const PROJECTED_CS_TYPE_GEO_KEY: u16 = 3072;
+const RASTER_PIXEL_IS_POINT: u16 = 2;
+
+// WGS84 ellipsoid; ETRS89/NAD83 (GRS80) differ by well under a millimetre at DEM scale
+const WGS84_A: f64 = 6_378_137.0;
+const WGS84_F: f64 = 1.0 / 298.257_223_563;
+const UTM_K0: f64 = 0.9996;
+// Meters per degree of latitude (and of longitude at the equator)
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RasterCrs {
+    Geographic,  // EPSG:4326 and other lat/lon datums treated as WGS84
+    Utm { zone: u8, north: bool },
+}
+
+impl RasterCrs {
+    /// Supported EPSG codes: 4326/4258/4269, WGS84 UTM (326xx/327xx), ETRS89 UTM (258xx), NAD83 UTM (269xx)
+    pub fn from_epsg(code: u16) -> Option<Self> {
+        match code {
+            4326 | 4258 | 4269 => Some(RasterCrs::Geographic),
+            32601..=32660 => Some(RasterCrs::Utm { zone: (code - 32600) as u8, north: true }),
+            32701..=32760 => Some(RasterCrs::Utm { zone: (code - 32700) as u8, north: false }),
+            25828..=25838 => Some(RasterCrs::Utm { zone: (code - 25800) as u8, north: true }),
+            26901..=26923 => Some(RasterCrs::Utm { zone: (code - 26900) as u8, north: true }),
+            _ => None,
+        }
+    }
+
+    /// Project latitude/longitude into this CRS as (x, y)
+    pub fn project(&self, latitude: f64, longitude: f64) -> (f64, f64) {
+        match *self {
+            RasterCrs::Geographic => (longitude, latitude),
+            RasterCrs::Utm { zone, north } => utm_forward(latitude, longitude, zone, north),
+        }
+    }
+}
+
+/// Transverse Mercator forward projection for a UTM zone (Snyder, USGS PP 1395, eq. 8-9..8-10)
+pub fn utm_forward(latitude: f64, longitude: f64, zone: u8, north: bool) -> (f64, f64) {
+    let e2 = WGS84_F * (2.0 - WGS84_F);
+    let ep2 = e2 / (1.0 - e2);
+    let lon0 = ((zone as f64 - 1.0) * 6.0 - 180.0 + 3.0).to_radians();
+
+    let phi = latitude.to_radians();
+    let (sin_phi, cos_phi) = phi.sin_cos();
+    let n = WGS84_A / (1.0 - e2 * sin_phi * sin_phi).sqrt();
+    let t = phi.tan().powi(2);
+    let c = ep2 * cos_phi * cos_phi;
+    let a = cos_phi * (longitude.to_radians() - lon0);
+
+    let e4 = e2 * e2;
+    let e6 = e4 * e2;
+    let m = WGS84_A * (
+        (1.0 - e2 / 4.0 - 3.0 * e4 / 64.0 - 5.0 * e6 / 256.0) * phi
+        - (3.0 * e2 / 8.0 + 3.0 * e4 / 32.0 + 45.0 * e6 / 1024.0) * (2.0 * phi).sin()
+        + (15.0 * e4 / 256.0 + 45.0 * e6 / 1024.0) * (4.0 * phi).sin()
+        - (35.0 * e6 / 3072.0) * (6.0 * phi).sin()
+    );
+
+    let easting = UTM_K0 * n * (
+        a
+        + (1.0 - t + c) * a.powi(3) / 6.0
+        + (5.0 - 18.0 * t + t * t + 72.0 * c - 58.0 * ep2) * a.powi(5) / 120.0
+    ) + 500_000.0;
+
+    let mut northing = UTM_K0 * (
+        m + n * phi.tan() * (
+            a * a / 2.0
+            + (5.0 - t + 9.0 * c + 4.0 * c * c) * a.powi(4) / 24.0
+            + (61.0 - 58.0 * t + t * t + 600.0 * c - 330.0 * ep2) * a.powi(6) / 720.0
+        )
+    );
+    if !north {
+        northing += 10_000_000.0;
+    }
+
+    (easting, northing)
+}
+
+/// Georeferencing read from a raster's tags, without the pixel data
+#[derive(Debug, Clone)]
+struct RasterHeader {
+    path: PathBuf,
+    width: usize,
+    height: usize,
+    // GDAL-style affine: x = t0 + t1*col + t2*row, y = t3 + t4*col + t5*row (pixel corners)
+    transform: [f64; 6],
+    crs: RasterCrs,
+    pixel_is_point: bool,
+    nodata: Option<f64>,
+}
+
+impl RasterHeader {
+    fn read(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut decoder = Decoder::new(BufReader::new(File::open(path)?))?;
+        let (width, height) = decoder.dimensions()?;
+
+        let transform = match decoder.find_tag(Tag::ModelTransformationTag)? {
+            Some(value) => {
+                let m = value.into_f64_vec()?;
+                if m.len() < 8 {
+                    return Err(TrackError::parse(path, "ModelTransformationTag has fewer than 8 values").into());
+                }
+                [m[3], m[0], m[1], m[7], m[4], m[5]]
+            }
+            None => {
+                let scale = decoder.get_tag_f64_vec(Tag::ModelPixelScaleTag)?;
+                let tiepoint = decoder.get_tag_f64_vec(Tag::ModelTiepointTag)?;
+                if scale.len() < 2 || tiepoint.len() < 6 {
+                    return Err(TrackError::parse(path, "Incomplete ModelPixelScale/ModelTiepoint tags").into());
+                }
+                [
+                    tiepoint[3] - tiepoint[0] * scale[0], scale[0], 0.0,
+                    tiepoint[4] + tiepoint[1] * scale[1], 0.0, -scale[1],
+                ]
+            }
+        };
+
+        let geo_keys = parse_geo_keys(&decoder.get_tag_u16_vec(Tag::GeoKeyDirectoryTag)?);
+        let epsg = geo_keys.get(&PROJECTED_CS_TYPE_GEO_KEY)
+            .or_else(|| geo_keys.get(&GEOGRAPHIC_TYPE_GEO_KEY))
+            .copied()
+            .ok_or_else(|| TrackError::parse(path, "GeoTIFF has no EPSG code in its GeoKeys"))?;
+        let crs = RasterCrs::from_epsg(epsg)
+            .ok_or_else(|| TrackError::parse(path, format!("Unsupported raster CRS EPSG:{}", epsg)))?;
+
+        let nodata = decoder.find_tag(Tag::GdalNodata)?
+            .and_then(|v| v.into_string().ok())
+            .and_then(|s| s.trim_matches(char::from(0)).trim().parse().ok());
+
+        Ok(RasterHeader {
+            path: path.to_path_buf(),
+            width: width as usize,
+            height: height as usize,
+            transform,
+            crs,
+            pixel_is_point: geo_keys.get(&GT_RASTER_TYPE_GEO_KEY) == Some(&RASTER_PIXEL_IS_POINT),
+            nodata,
+        })
+    }
+
+    /// Ground size of a pixel in meters; geographic pixel sizes are in degrees, scaled at
+    /// the raster centre so they compare with projected rasters
+    fn pixel_size_m(&self) -> f64 {
+        let (x_size, y_size) = (self.transform[1].abs(), self.transform[5].abs());
+        match self.crs {
+            RasterCrs::Geographic => {
+                let [_, _, _, t3, t4, t5] = self.transform;
+                let centre_latitude = t3 + t4 * self.width as f64 / 2.0 + t5 * self.height as f64 / 2.0;
+                let x_m = x_size * METERS_PER_DEGREE * centre_latitude.to_radians().cos();
+                (x_m * y_size * METERS_PER_DEGREE).sqrt()
+            }
+            RasterCrs::Utm { .. } => (x_size * y_size).sqrt(),
+        }
+    }
+
+    /// Fractional (col, row) of the sample grid for a point in the raster's CRS
+    fn sample_position(&self, x: f64, y: f64) -> Option<(f64, f64)> {
+        let [t0, t1, t2, t3, t4, t5] = self.transform;
+        let det = t1 * t5 - t2 * t4;
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        let dx = x - t0;
+        let dy = y - t3;
+        let mut col = (t5 * dx - t2 * dy) / det;
+        let mut row = (t1 * dy - t4 * dx) / det;
+
+        // PixelIsArea samples sit at pixel centres
+        if !self.pixel_is_point {
+            col -= 0.5;
+            row -= 0.5;
+        }
+
+        let max_col = (self.width - 1) as f64;
+        let max_row = (self.height - 1) as f64;
+        // Allow the outer half pixel, clamped onto the edge samples
+        if col < -0.5 || row < -0.5 || col > max_col + 0.5 || row > max_row + 0.5 {
+            return None;
+        }
+        Some((col.clamp(0.0, max_col), row.clamp(0.0, max_row)))
+    }
+
+    fn sample(&self, data: &[f32], col: usize, row: usize) -> Option<f64> {
+        let value = data[row * self.width + col] as f64;
+        let is_nodata = self.nodata.map(|nd| (value - nd).abs() < 1e-6).unwrap_or(false);
+        if is_nodata || !value.is_finite() {
+            None
+        } else {
+            Some(value)
+        }
+    }
+
+    /// Bilinear interpolation; nodata corners drop out and the weights are renormalized
+    fn interpolate(&self, data: &[f32], col: f64, row: f64) -> Option<f64> {
+        let col0 = (col.floor() as usize).min(self.width.saturating_sub(2));
+        let row0 = (row.floor() as usize).min(self.height.saturating_sub(2));
+        let col1 = (col0 + 1).min(self.width - 1);
+        let row1 = (row0 + 1).min(self.height - 1);
+        let dc = (col - col0 as f64).clamp(0.0, 1.0);
+        let dr = (row - row0 as f64).clamp(0.0, 1.0);
+
+        let corners = [
+            (self.sample(data, col0, row0), (1.0 - dc) * (1.0 - dr)),
+            (self.sample(data, col1, row0), dc * (1.0 - dr)),
+            (self.sample(data, col0, row1), (1.0 - dc) * dr),
+            (self.sample(data, col1, row1), dc * dr),
+        ];
+
+        let mut weighted = 0.0;
+        let mut weight_sum = 0.0;
+        for (value, weight) in corners {
+            if let Some(v) = value {
+                weighted += v * weight;
+                weight_sum += weight;
+            }
+        }
+
+        if weight_sum > 1e-9 {
+            Some(weighted / weight_sum)
+        } else {
+            None
+        }
+    }
+}
+
+/// GeoKeyDirectory entries stored inline (TIFFTagLocation 0): key id -> value
+fn parse_geo_keys(directory: &[u16]) -> HashMap<u16, u16> {
+    let mut keys = HashMap::new();
+    if directory.len() < 4 {
+        return keys;
+    }
+    for entry in directory[4..].chunks_exact(4).take(directory[3] as usize) {
+        if entry[1] == 0 {
+            keys.insert(entry[0], entry[3]);
+        }
+    }
+    keys
+}
+
+fn read_raster_data(path: &Path) -> Result<Vec<f32>, Box<dyn std::error::Error>> {
+    let mut limits = Limits::default();
+    limits.decoding_buffer_size = MAX_RASTER_BYTES;
+    let mut decoder = Decoder::new(BufReader::new(File::open(path)?))?
+        .with_limits(limits);
+
+    match decoder.colortype()? {
+        tiff::ColorType::Gray(_) => {}
+        other => return Err(TrackError::parse(path, format!("Expected a single-band raster, found {:?}", other)).into()),
+    }
+
+    Ok(match decoder.read_image()? {
+        DecodingResult::U8(v) => v.into_iter().map(|x| x as f32).collect(),
+        DecodingResult::U16(v) => v.into_iter().map(|x| x as f32).collect(),
+        DecodingResult::U32(v) => v.into_iter().map(|x| x as f32).collect(),
+        DecodingResult::U64(v) => v.into_iter().map(|x| x as f32).collect(),
+        DecodingResult::I8(v) => v.into_iter().map(|x| x as f32).collect(),
+        DecodingResult::I16(v) => v.into_iter().map(|x| x as f32).collect(),
+        DecodingResult::I32(v) => v.into_iter().map(|x| x as f32).collect(),
+        DecodingResult::I64(v) => v.into_iter().map(|x| x as f32).collect(),
+        DecodingResult::F16(v) => v.into_iter().map(|x| x.to_f32()).collect(),
+        DecodingResult::F32(v) => v,
+        DecodingResult::F64(v) => v.into_iter().map(|x| x as f32).collect(),
+    })
+}
+
+#[derive(Default)]
+struct RasterCache {
+    rasters: HashMap<usize, Arc<Vec<f32>>>,
+    order: VecDeque<usize>,  // Least recently used at the front
+    bytes: usize,            // Size of the cached samples
+    failed: HashSet<usize>,  // Rasters that could not be decoded; never retried
+}
+
+/// Every GeoTIFF under a directory, finest resolution first
+pub struct GeoTiffDemSet {
+    headers: Vec<RasterHeader>,
+    cache: Mutex<RasterCache>,
+}
+
+impl GeoTiffDemSet {
+    pub fn new(directory: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        let directory = directory.as_ref();
+        if !directory.is_dir() {
+            return Err(TrackError::io(directory, io::Error::new(io::ErrorKind::NotFound, "DEM directory not found")).into());
+        }
+
+        let mut headers = Vec::new();
+        for entry in WalkDir::new(directory).into_iter().filter_map(|e| e.ok()) {
+            if entry.file_type().is_file() && is_geotiff_file(entry.path()) {
+                match RasterHeader::read(entry.path()) {
+                    Ok(header) => headers.push(header),
+                    Err(e) => eprintln!("⚠️  Skipping raster {}: {}", entry.path().display(), e),
+                }
+            }
+        }
+
+        // Where rasters overlap, the finest one wins
+        headers.sort_by(|a, b| a.pixel_size_m().total_cmp(&b.pixel_size_m()));
+
+        Ok(GeoTiffDemSet {
+            headers,
+            cache: Mutex::new(RasterCache::default()),
+        })
+    }
+
+    pub fn raster_count(&self) -> usize {
+        self.headers.len()
+    }
+
+    /// Pixel data of a raster, decoded on first use; None (warned about once) for a raster
+    /// that cannot be decoded
+    fn raster_data(&self, index: usize) -> Option<Arc<Vec<f32>>> {
+        {
+            let mut cache = self.cache.lock().unwrap();
+            if let Some(data) = cache.rasters.get(&index).cloned() {
+                cache.order.retain(|&i| i != index);
+                cache.order.push_back(index);
+                return Some(data);
+            }
+            if cache.failed.contains(&index) {
+                return None;
+            }
+        }
+
+        // Decode without holding the lock so lookups in other rasters aren't blocked
+        let header = &self.headers[index];
+        let decoded = match read_raster_data(&header.path) {
+            Ok(data) if data.len() == header.width * header.height => Ok(Arc::new(data)),
+            Ok(_) => Err("unexpected sample count".to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+
+        let mut cache = self.cache.lock().unwrap();
+        let data = match decoded {
+            Ok(data) => data,
+            Err(e) => {
+                if cache.failed.insert(index) {
+                    eprintln!("⚠️  Could not decode raster {}: {}", header.path.display(), e);
+                }
+                return None;
+            }
+        };
+        // Another thread may have decoded it meanwhile
+        if let Some(existing) = cache.rasters.get(&index).cloned() {
+            return Some(existing);
+        }
+
+        // Evict least recently used rasters until the new one fits (it is kept even if it
+        // alone exceeds the budget)
+        let size = data.len() * std::mem::size_of::<f32>();
+        while cache.bytes + size > MAX_CACHE_BYTES {
+            let Some(evicted) = cache.order.pop_front() else {
+                break;
+            };
+            if let Some(raster) = cache.rasters.remove(&evicted) {
+                cache.bytes -= raster.len() * std::mem::size_of::<f32>();
+            }
+        }
+        cache.rasters.insert(index, data.clone());
+        cache.order.push_back(index);
+        cache.bytes += size;
+        Some(data)
+    }
+}
+
+impl ElevationLookup for GeoTiffDemSet {
+    fn elevation_at(&self, latitude: f64, longitude: f64) -> Option<f64> {
+        for (index, header) in self.headers.iter().enumerate() {
+            let (x, y) = header.crs.project(latitude, longitude);
+            if let Some((col, row)) = header.sample_position(x, y) {
+                // A raster that cannot be decoded leaves the point to the others
+                let Some(data) = self.raster_data(index) else {
+                    continue;
+                };
+                if let Some(elevation) = header.interpolate(&data, col, row) {
+                    return Some(elevation);
+                }
+            }
+        }
+        None
+    }
+}
+
+pub fn is_geotiff_file(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|s| s.to_str()).map(|s| s.to_lowercase()).as_deref(),
+        Some("tif") | Some("tiff")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tiff::encoder::{colortype, TiffEncoder};
+
+    #[test]
+    fn test_utm_forward_reference_points() {
+        // On the central meridian at the equator: exactly the false easting/northing
+        let (e, n) = utm_forward(0.0, 9.0, 32, true);
+        assert!((e - 500_000.0).abs() < 1e-6 && n.abs() < 1e-6);
+
+        // Southern hemisphere gets the 10,000km false northing
+        let (_, n_south) = utm_forward(-0.000001, 9.0, 32, false);
+        assert!((n_south - 10_000_000.0).abs() < 1.0);
+
+        // Symmetric about the central meridian
+        let (east, _) = utm_forward(46.5, 10.0, 32, true);
+        let (west, _) = utm_forward(46.5, 8.0, 32, true);
+        assert!(((east - 500_000.0) + (west - 500_000.0)).abs() < 1e-6);
+    }
+
+    /// A 4x4 float raster with its top-left corner at `origin` (in the CRS) and square pixels
+    fn write_raster(path: &Path, origin: (f64, f64), pixel_size: f64, geo_key: u16, epsg: u16, data: &[f32]) {
+        let mut encoder = TiffEncoder::new(File::create(path).unwrap()).unwrap();
+        let mut image = encoder.new_image::<colortype::Gray32Float>(4, 4).unwrap();
+        image.encoder().write_tag(Tag::ModelPixelScaleTag, &[pixel_size, pixel_size, 0.0][..]).unwrap();
+        image.encoder().write_tag(Tag::ModelTiepointTag, &[0.0f64, 0.0, 0.0, origin.0, origin.1, 0.0][..]).unwrap();
+        // Version header, then the CRS GeoKey
+        image.encoder().write_tag(Tag::GeoKeyDirectoryTag, &[1u16, 1, 0, 1, geo_key, 0, 1, epsg][..]).unwrap();
+        image.write_data(data).unwrap();
+    }
+
+    #[test]
+    fn test_geotiff_lookup_in_utm() {
+        // 4x4 LiDAR-style raster, 1m pixels in UTM 32N, elevation = 100 + column
+        let (origin_e, origin_n) = utm_forward(46.5, 7.5, 32, true);
+        let dir = std::env::temp_dir().join(format!("geotiff_dem_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let data: Vec<f32> = (0..16).map(|i| 100.0 + (i % 4) as f32).collect();
+        write_raster(&dir.join("lidar.tif"), (origin_e, origin_n), 1.0, PROJECTED_CS_TYPE_GEO_KEY, 32632, &data);
+
+        let dem = GeoTiffDemSet::new(&dir).unwrap();
+        assert_eq!(dem.raster_count(), 1);
+
+        // A hair inside the first pixel's centre column (~0.5m east of the corner)
+        let lon_half_metre = 0.5 / (111_320.0 * 46.5f64.to_radians().cos());
+        let elevation = dem.elevation_at(46.5 - 0.00001, 7.5 + lon_half_metre);
+        // Far outside the raster
+        let outside = dem.elevation_at(47.5, 7.5);
+        std::fs::remove_dir_all(&dir).ok();
+
+        let elevation = elevation.unwrap();
+        assert!((elevation - 100.0).abs() < 0.1, "got {}", elevation);
+        assert_eq!(outside, None);
+
+        // The directory is gone now
+        let missing = GeoTiffDemSet::new(&dir).err().unwrap();
+        assert_eq!(crate::error::error_kind(missing.as_ref()), crate::error::ErrorKind::Io);
+    }
+
+    #[test]
+    fn test_finest_raster_wins_across_crss() {
+        // A ~30m EPSG:4326 raster (0.00028 degrees) over a 1m UTM tile: the tile is finer
+        // even though its pixel size is the larger number
+        let (origin_e, origin_n) = utm_forward(46.5, 7.5, 32, true);
+        let dir = std::env::temp_dir().join(format!("geotiff_dem_mixed_crs_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        write_raster(&dir.join("srtm.tif"), (7.4995, 46.5005), 0.00028, GEOGRAPHIC_TYPE_GEO_KEY, 4326, &[500.0; 16]);
+        write_raster(&dir.join("lidar.tif"), (origin_e, origin_n), 1.0, PROJECTED_CS_TYPE_GEO_KEY, 32632, &[100.0; 16]);
+
+        let dem = GeoTiffDemSet::new(&dir).unwrap();
+        let lon_half_metre = 0.5 / (111_320.0 * 46.5f64.to_radians().cos());
+        let elevation = dem.elevation_at(46.5 - 0.00001, 7.5 + lon_half_metre);
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(dem.raster_count(), 2);
+        assert!(dem.headers[0].pixel_size_m() < dem.headers[1].pixel_size_m());
+        assert_eq!(elevation, Some(100.0));
+    }
+
+    #[test]
+    fn test_undecodable_raster_falls_through_to_the_next() {
+        let (origin_e, origin_n) = utm_forward(46.5, 7.5, 32, true);
+        let dir = std::env::temp_dir().join(format!("geotiff_dem_broken_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        write_raster(&dir.join("lidar.tif"), (origin_e, origin_n), 1.0, PROJECTED_CS_TYPE_GEO_KEY, 32632, &[100.0; 16]);
+        write_raster(&dir.join("coarse.tif"), (origin_e - 8.0, origin_n + 8.0), 5.0, PROJECTED_CS_TYPE_GEO_KEY, 32632, &[200.0; 16]);
+
+        let dem = GeoTiffDemSet::new(&dir).unwrap();
+        // Cut the pixel data off the finest raster after its header was indexed
+        let finest = &dem.headers[0].path;
+        let length = std::fs::metadata(finest).unwrap().len();
+        std::fs::OpenOptions::new().write(true).open(finest).unwrap().set_len(length - 40).unwrap();
+
+        let lon_half_metre = 0.5 / (111_320.0 * 46.5f64.to_radians().cos());
+        let first = dem.elevation_at(46.5 - 0.00001, 7.5 + lon_half_metre);
+        let second = dem.elevation_at(46.5 - 0.00002, 7.5 + lon_half_metre);
+        std::fs::remove_dir_all(&dir).ok();
+
+        for elevation in [first, second] {
+            assert!((elevation.unwrap() - 200.0).abs() < 1e-6, "got {:?}", elevation);
+        }
+        assert!(dem.cache.lock().unwrap().failed.contains(&0));
+    }
+}
@@ -0,0 +1,225 @@
+/// GPS JUMP DETECTION - Horizontal outliers (teleports) in recorded positions
+/// The elevation spike filter only looks up and down; a single sample placed 400m off the
+/// route adds ~800m of Haversine distance and shifts every resampling interval after it.
+/// A jump is a run of up to MAX_JUMP_POINTS samples that leaves the route and comes straight
+/// back: far from both neighbours while the neighbours are close to each other, with the
+/// heading reversing and/or an implied speed nobody on foot (or bike) reaches.
+use chrono::{DateTime, Utc};
+use geo::{HaversineDistance, point};
+
+// Longest run of consecutive samples treated as one jump
+const MAX_JUMP_POINTS: usize = 3;
+// Excursions shorter than this are ordinary GPS noise
+const MIN_JUMP_M: f64 = 100.0;
+// ...and must also be this many typical steps long, so sparse course files keep sharp turnarounds
+const JUMP_STEP_RATIO: f64 = 5.0;
+// The way back may land at most this share of the excursion from where the jump left
+const MAX_RETURN_RATIO: f64 = 0.5;
+// Heading change between leaving and returning that counts as a reversal
+const REVERSAL_DEGREES: f64 = 120.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JumpRepair {
+    None,         // Detect and report only
+    Drop,         // Remove the jumped points
+    Interpolate,  // Move them back onto the line between their neighbours
+}
+
+impl std::str::FromStr for JumpRepair {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" | "off" | "report" => Ok(JumpRepair::None),
+            "drop" | "remove" => Ok(JumpRepair::Drop),
+            "interpolate" | "interp" => Ok(JumpRepair::Interpolate),
+            _ => Err(format!("unknown GPS jump repair '{}' (expected none, drop or interpolate)", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct JumpDetectionConfig {
+    pub max_speed_mps: f64,
+    pub repair: JumpRepair,
+}
+
+impl Default for JumpDetectionConfig {
+    fn default() -> Self {
+        JumpDetectionConfig {
+            max_speed_mps: 15.0,
+            repair: JumpRepair::None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GpsJump {
+    pub start_index: usize,  // First jumped point
+    pub end_index: usize,    // Last jumped point (inclusive)
+    pub excursion_m: f64,    // Extra distance the jump added
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct JumpStats {
+    pub jumps_detected: usize,
+    pub points_affected: usize,
+    pub max_excursion_m: f64,
+    pub total_excursion_m: f64,  // Distance the jumps add to the track
+}
+
+fn ground_distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    point!(x: a.1, y: a.0).haversine_distance(&point!(x: b.1, y: b.0))
+}
+
+/// Initial bearing from `a` to `b` in degrees
+fn bearing(a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (lat1, lat2) = (a.0.to_radians(), b.0.to_radians());
+    let dlon = (b.1 - a.1).to_radians();
+    let y = dlon.sin() * lat2.cos();
+    let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * dlon.cos();
+    y.atan2(x).to_degrees()
+}
+
+fn heading_change(a: f64, b: f64) -> f64 {
+    let diff = (b - a).rem_euclid(360.0);
+    diff.min(360.0 - diff)
+}
+
+fn implied_speed(distance_m: f64, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Option<f64> {
+    let seconds = (to? - from?).num_milliseconds() as f64 / 1000.0;
+    (seconds > 0.0).then(|| distance_m / seconds)
+}
+
+/// Find jumps in (lat, lon, ele) points. `times` is parallel to `coords` or empty.
+pub fn detect_gps_jumps(coords: &[(f64, f64, f64)], times: &[Option<DateTime<Utc>>], config: &JumpDetectionConfig) -> Vec<GpsJump> {
+    let position = |i: usize| (coords[i].0, coords[i].1);
+    let time = |i: usize| times.get(i).copied().flatten();
+    if coords.len() < 3 {
+        return Vec::new();
+    }
+
+    let mut steps: Vec<f64> = coords.windows(2).map(|w| ground_distance((w[0].0, w[0].1), (w[1].0, w[1].1))).collect();
+    steps.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let min_jump_m = MIN_JUMP_M.max(JUMP_STEP_RATIO * steps[steps.len() / 2]);
+
+    let mut jumps = Vec::new();
+    let mut i = 1;
+    while i + 1 < coords.len() {
+        let before = i - 1;
+        let out = ground_distance(position(before), position(i));
+        if out < min_jump_m {
+            i += 1;
+            continue;
+        }
+
+        // The first point after the excursion that lands back near where it left
+        let found = (i + 1..=(i + MAX_JUMP_POINTS).min(coords.len() - 1)).find_map(|after| {
+            let back = ground_distance(position(after - 1), position(after));
+            let direct = ground_distance(position(before), position(after));
+            if back < min_jump_m || direct > MAX_RETURN_RATIO * out.min(back) {
+                return None;
+            }
+            let reversed = heading_change(bearing(position(before), position(i)), bearing(position(after - 1), position(after))) >= REVERSAL_DEGREES;
+            let too_fast = implied_speed(out, time(before), time(i)).is_some_and(|v| v > config.max_speed_mps)
+                || implied_speed(back, time(after - 1), time(after)).is_some_and(|v| v > config.max_speed_mps);
+            (reversed || too_fast).then_some((after, out + back - direct))
+        });
+
+        match found {
+            Some((after, excursion_m)) => {
+                jumps.push(GpsJump { start_index: i, end_index: after - 1, excursion_m });
+                i = after;
+            }
+            None => i += 1,
+        }
+    }
+    jumps
+}
+
+/// Apply the configured repair. Returns the indices of the points to keep (all of them unless
+/// dropping) and the repaired points, in the same order.
+pub fn repair_gps_jumps(
+    coords: &[(f64, f64, f64)],
+    times: &[Option<DateTime<Utc>>],
+    jumps: &[GpsJump],
+    repair: JumpRepair,
+) -> (Vec<usize>, Vec<(f64, f64, f64)>) {
+    let mut repaired = coords.to_vec();
+    let mut jumped = vec![false; coords.len()];
+    for jump in jumps {
+        let (before, after) = (jump.start_index - 1, jump.end_index + 1);
+        for i in jump.start_index..=jump.end_index {
+            jumped[i] = true;
+            if repair == JumpRepair::Interpolate {
+                // By time where both ends have one, otherwise evenly by sample
+                let by_time = match (times.get(before).copied().flatten(), times.get(i).copied().flatten(), times.get(after).copied().flatten()) {
+                    (Some(t0), Some(t), Some(t1)) if t1 > t0 => {
+                        Some((t - t0).num_milliseconds() as f64 / (t1 - t0).num_milliseconds() as f64)
+                    }
+                    _ => None,
+                };
+                let f = by_time.unwrap_or((i - before) as f64 / (after - before) as f64).clamp(0.0, 1.0);
+                repaired[i].0 = coords[before].0 + f * (coords[after].0 - coords[before].0);
+                repaired[i].1 = coords[before].1 + f * (coords[after].1 - coords[before].1);
+            }
+        }
+    }
+
+    let keep: Vec<usize> = (0..coords.len())
+        .filter(|&i| repair != JumpRepair::Drop || !jumped[i])
+        .collect();
+    let points = keep.iter().map(|&i| repaired[i]).collect();
+    (keep, points)
+}
+
+/// Detect and repair in one go
+pub fn filter_gps_jumps(
+    coords: &[(f64, f64, f64)],
+    times: &[Option<DateTime<Utc>>],
+    config: &JumpDetectionConfig,
+) -> (Vec<usize>, Vec<(f64, f64, f64)>, JumpStats) {
+    let jumps = detect_gps_jumps(coords, times, config);
+    let (keep, repaired) = repair_gps_jumps(coords, times, &jumps, config.repair);
+    let stats = JumpStats {
+        jumps_detected: jumps.len(),
+        points_affected: jumps.iter().map(|j| j.end_index - j.start_index + 1).sum(),
+        max_excursion_m: jumps.iter().map(|j| j.excursion_m).fold(0.0, f64::max),
+        total_excursion_m: jumps.iter().map(|j| j.excursion_m).sum(),
+    };
+    (keep, repaired, stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_and_double_point_jumps_are_found_and_repaired() {
+        // 2km north in 5m steps, one sample 400m east, two samples 300m west
+        let mut coords: Vec<(f64, f64, f64)> = (0..400)
+            .map(|i| (46.0 + i as f64 * 5.0 / 111_320.0, 7.0, 500.0))
+            .collect();
+        coords[100].1 += 400.0 / 77_300.0;
+        coords[250].1 -= 300.0 / 77_300.0;
+        coords[251].1 -= 300.0 / 77_300.0;
+        // A sharp but real turn is left alone
+        for point in coords.iter_mut().skip(350) {
+            point.1 += 0.0002;
+        }
+
+        let config = JumpDetectionConfig { repair: JumpRepair::Interpolate, ..JumpDetectionConfig::default() };
+        let jumps = detect_gps_jumps(&coords, &[], &config);
+        assert_eq!(jumps.iter().map(|j| (j.start_index, j.end_index)).collect::<Vec<_>>(), vec![(100, 100), (250, 251)]);
+
+        let (keep, repaired, stats) = filter_gps_jumps(&coords, &[], &config);
+        assert_eq!(keep.len(), coords.len());
+        assert_eq!(stats.points_affected, 3);
+        assert!((repaired[100].1 - 7.0).abs() < 1e-9 && (repaired[251].1 - 7.0).abs() < 1e-9);
+
+        let dropping = JumpDetectionConfig { repair: JumpRepair::Drop, ..config };
+        let (keep, _, _) = filter_gps_jumps(&coords, &[], &dropping);
+        assert_eq!(keep.len(), coords.len() - 3);
+        assert!(!keep.contains(&100) && !keep.contains(&251));
+    }
+}
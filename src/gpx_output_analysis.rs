@@ -1,6 +1,6 @@
 use std::{fs::{File, create_dir_all}, path::{Path, PathBuf}};
-use std::io::{BufReader, BufWriter};
-use gpx::{read, write, Gpx, Track, TrackSegment, Waypoint, Time};
+use std::io::BufWriter;
+use gpx::{write, Time};
 use geo::{HaversineDistance, point};
 use walkdir::WalkDir;
 use csv::Writer;
@@ -8,6 +8,8 @@ use serde::Serialize;
 use rayon::prelude::*;
 use std::sync::{Arc, Mutex};
 use crate::custom_smoother::{ElevationData, SmoothingVariant};
+use crate::error::TrackError;
+use crate::track_loader::{gpx_course_name, gpx_course_segments, gpx_course_segments_mut, gpx_geometry, read_gpx_file};
 
 #[derive(Debug, Serialize, Clone)]
 struct ProcessingResult {
@@ -23,9 +25,8 @@ struct ProcessingResult {
     distance_km: f32,
 }
 
-pub fn run_gpx_output_analysis(gpx_folder: &str) -> Result<(), Box<dyn std::error::Error>> {
+pub fn run_gpx_output_analysis(gpx_folder: &str, output_dir: &str) -> Result<(), Box<dyn std::error::Error>> {
     let interval_m = 6.1;
-    let output_dir = r"C:\Users\Dzhu\Documents\GPX Files\GPX Analysis";
     
     println!("\n🔧 GPX PROCESSING AND OUTPUT ANALYSIS");
     println!("=====================================");
@@ -93,27 +94,26 @@ fn process_single_gpx(
     output_dir: &str
 ) -> Result<ProcessingResult, Box<dyn std::error::Error>> {
     // Read GPX file
-    let file = File::open(gpx_path)?;
-    let reader = BufReader::new(file);
-    let gpx = read(reader)?;
+    let gpx = read_gpx_file(gpx_path)?;
+    if gpx_geometry(&gpx).is_none() {
+        return Err(TrackError::NoTracks { file: gpx_path.to_path_buf() }.into());
+    }
     
     // Extract coordinates and elevations
     let mut coords: Vec<(f64, f64, f64)> = vec![];
     let mut timestamps: Vec<Option<Time>> = vec![];
     
-    for track in &gpx.tracks {
-        for segment in &track.segments {
-            for point in &segment.points {
-                if let Some(ele) = point.elevation {
-                    coords.push((point.point().y(), point.point().x(), ele));
-                    timestamps.push(point.time.clone());
-                }
+    for segment in gpx_course_segments(&gpx) {
+        for point in segment {
+            if let Some(ele) = point.elevation {
+                coords.push((point.point().y(), point.point().x(), ele));
+                timestamps.push(point.time.clone());
             }
         }
     }
     
     if coords.is_empty() {
-        return Err("No valid coordinates with elevation found".into());
+        return Err(TrackError::NoElevation { file: gpx_path.to_path_buf() }.into());
     }
     
     // Calculate distances
@@ -146,82 +146,33 @@ fn process_single_gpx(
     // Get the smoothed elevations from the elevation data
     let processed_elevations = elevation_data.enhanced_altitude.clone();
     
-    // Create new GPX with processed elevations
-    let mut new_gpx = Gpx {
-        version: gpx.version.clone(),
-        creator: Some(format!("{} - Processed at {}m intervals", 
-            gpx.creator.as_ref().unwrap_or(&"Unknown".to_string()), interval_m)),
-        metadata: gpx.metadata.clone(),
-        waypoints: gpx.waypoints.clone(),
-        tracks: vec![],
-        routes: gpx.routes.clone(),
-    };
-    
-    // Build new track with processed elevations
-    let mut point_idx = 0;
-    for track in &gpx.tracks {
-        let mut new_track = Track {
-            name: track.name.clone(),
-            comment: track.comment.clone(),
-            description: track.description.clone(),
-            source: track.source.clone(),
-            links: track.links.clone(),
-            type_: track.type_.clone(),
-            number: track.number,
-            segments: vec![],
-        };
-        
-        for segment in &track.segments {
-            let mut new_segment = TrackSegment { points: vec![] };
-            
-            for point in &segment.points {
-                if point.elevation.is_some() && point_idx < processed_elevations.len() {
-                    let mut new_point = Waypoint::new(point.point());
-                    new_point.elevation = Some(processed_elevations[point_idx]);
-                    new_point.time = point.time.clone();
-                    new_point.speed = point.speed;
-                    new_point.name = point.name.clone();
-                    new_point.comment = point.comment.clone();
-                    new_point.description = point.description.clone();
-                    new_point.source = point.source.clone();
-                    new_point.links = point.links.clone();
-                    new_point.symbol = point.symbol.clone();
-                    new_point.type_ = point.type_.clone();
-                    
-                    new_segment.points.push(new_point);
-                    point_idx += 1;
-                }
-            }
-            
-            if !new_segment.points.is_empty() {
-                new_track.segments.push(new_segment);
+    // Copy the GPX with the processed elevations on its course points (track or route),
+    // dropping the points that had no elevation
+    let mut new_gpx = gpx.clone();
+    new_gpx.creator = Some(format!("{} - Processed at {}m intervals", 
+        gpx.creator.as_ref().unwrap_or(&"Unknown".to_string()), interval_m));
+    let mut processed = processed_elevations.iter();
+    for segment in gpx_course_segments_mut(&mut new_gpx) {
+        segment.retain(|point| point.elevation.is_some());
+        for point in segment.iter_mut() {
+            if let Some(&elevation) = processed.next() {
+                point.elevation = Some(elevation);
             }
         }
-        
-        if !new_track.segments.is_empty() {
-            new_gpx.tracks.push(new_track);
-        }
     }
     
     // Write processed GPX file
-    // Try to get track name from GPX data, fallback to original filename
-    let output_filename = if let Some(track) = new_gpx.tracks.first() {
-        if let Some(name) = &track.name {
-            // Clean the track name to make it filesystem-safe
-            let clean_name = name
-                .chars()
-                .map(|c| match c {
-                    '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
-                    _ => c
-                })
-                .collect::<String>();
-            format!("{}.gpx", clean_name.trim())
-        } else {
-            gpx_path.file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("unknown.gpx")
-                .to_string()
-        }
+    // Try to get the course name from GPX data, fallback to original filename
+    let output_filename = if let Some(name) = gpx_course_name(&gpx) {
+        // Clean the course name to make it filesystem-safe
+        let clean_name = name
+            .chars()
+            .map(|c| match c {
+                '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+                _ => c
+            })
+            .collect::<String>();
+        format!("{}.gpx", clean_name.trim())
     } else {
         gpx_path.file_name()
             .and_then(|n| n.to_str())
@@ -266,10 +217,7 @@ fn process_single_gpx(
     Ok(ProcessingResult {
         filename: output_filename,
         original_points: coords.len(),
-        processed_points: new_gpx.tracks.iter()
-            .flat_map(|t| &t.segments)
-            .flat_map(|s| &s.points)
-            .count(),
+        processed_points: gpx_course_segments(&new_gpx).iter().map(|s| s.len()).sum(),
         raw_elevation_gain_m: raw_gain as f32,
         raw_elevation_loss_m: raw_loss as f32,
         processed_elevation_gain_m: processed_gain as f32,
@@ -11,6 +11,9 @@ use csv::Writer;
 use serde::Serialize;
 use gpx::{read, write, Gpx};
 use walkdir::WalkDir;
+use crate::dem::{count_gpx_sources, DemCorrection, ElevationSourceCounts};
+use crate::error::TrackError;
+use crate::track_loader::{gpx_course_segments, gpx_geometry, GpxGeometry};
 
 #[derive(Debug, Serialize)]
 pub struct PreprocessingResult {
@@ -42,12 +45,26 @@ pub struct PreprocessingResult {
     structure_validation: String,
     elevation_validation: String,
     
+    // Where each point's elevation came from
+    elevation_source_gps: usize,
+    elevation_source_dem: usize,
+    elevation_source_missing: usize,
+    
     error_message: String,
 }
 
 pub fn run_gpx_preprocessing(
     input_folder: &str,
     output_folder: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    run_gpx_preprocessing_with_dem(input_folder, output_folder, None)
+}
+
+/// Preprocess with optional DEM elevations (filling gaps or replacing GPS elevations)
+pub fn run_gpx_preprocessing_with_dem(
+    input_folder: &str,
+    output_folder: &str,
+    dem: Option<&DemCorrection>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let total_start = std::time::Instant::now();
     
@@ -59,6 +76,9 @@ pub fn run_gpx_preprocessing(
     println!("🎯 PREPROCESSING GOALS:");
     println!("   • Repair corrupted/malformed GPX files");
     println!("   • Ensure consistent elevation data");
+    if let Some(dem) = dem {
+        println!("   • DEM elevations: {:?} mode", dem.mode);
+    }
     println!("   • Validate coordinate ranges");
     println!("   • Create clean files for elevation analysis");
     println!("   • Generate detailed preprocessing report\n");
@@ -74,7 +94,7 @@ pub fn run_gpx_preprocessing(
     
     // Process each file
     let processing_start = std::time::Instant::now();
-    let results = process_all_gpx_files(&gpx_files, input_folder, output_folder);
+    let results = process_all_gpx_files(&gpx_files, input_folder, output_folder, dem);
     println!("✅ Preprocessing complete in {:.2}s", processing_start.elapsed().as_secs_f64());
     
     // Write preprocessing report
@@ -114,6 +134,7 @@ fn process_all_gpx_files(
     gpx_files: &[PathBuf],
     input_folder: &str,
     output_folder: &str,
+    dem: Option<&DemCorrection>,
 ) -> Vec<PreprocessingResult> {
     let mut results = Vec::new();
     
@@ -127,7 +148,7 @@ fn process_all_gpx_files(
         
         println!("🔄 Processing {}/{}: {}", index + 1, gpx_files.len(), filename);
         
-        let result = process_single_gpx_file(gpx_path, input_folder, output_folder);
+        let result = process_single_gpx_file(gpx_path, input_folder, output_folder, dem);
         
         match &result.processing_status[..] {
             "SUCCESS" => {
@@ -144,6 +165,13 @@ fn process_all_gpx_files(
             }
         }
         
+        if result.elevation_source_dem > 0 || result.elevation_source_missing > 0 {
+            println!("   🗺️  Elevation sources: {} GPS, {} DEM, {} missing",
+                     result.elevation_source_gps,
+                     result.elevation_source_dem,
+                     result.elevation_source_missing);
+        }
+        
         results.push(result);
     }
     
@@ -154,6 +182,7 @@ fn process_single_gpx_file(
     input_path: &Path,
     _input_folder: &str,
     output_folder: &str,
+    dem: Option<&DemCorrection>,
 ) -> PreprocessingResult {
     let filename = input_path.file_name()
         .and_then(|n| n.to_str())
@@ -168,7 +197,7 @@ fn process_single_gpx_file(
         .unwrap_or(0);
     
     // Try to read and repair the GPX file
-    let (gpx, repairs_applied, repair_details) = match read_and_repair_gpx(input_path) {
+    let (mut gpx, repairs_applied, repair_details) = match read_and_repair_gpx(input_path) {
         Ok(data) => data,
         Err(e) => {
             return create_error_result(
@@ -181,8 +210,15 @@ fn process_single_gpx_file(
         }
     };
     
+    // Terrain elevations from the DEM instead of inventing them
+    let elevation_sources = match dem {
+        Some(dem) => dem.apply_to_gpx(&mut gpx),
+        None => count_gpx_sources(&gpx),
+    };
+    
     // Analyze the GPX data
-    let analysis = analyze_gpx_data(&gpx);
+    let mut analysis = analyze_gpx_data(&gpx);
+    analysis.elevation_sources = elevation_sources;
     
     // Validate the processed data
     let validation = validate_gpx_data(&gpx);
@@ -295,13 +331,6 @@ fn apply_comprehensive_gpx_repair(
     repairs_applied.push("STRUCTURE_VALIDATION");
     repair_details.push("Validated coordinates and ensured proper track structure");
     
-    // Check if we need to add elevation data
-    if !repaired_content.contains("<ele>") {
-        repaired_content = add_estimated_elevations(&repaired_content);
-        repairs_applied.push("ELEVATION_ESTIMATION");
-        repair_details.push("Added estimated elevation data");
-    }
-    
     // Try to parse the repaired content
     match try_parse_repaired_content(&repaired_content) {
         Ok(gpx) => {
@@ -338,13 +367,13 @@ fn try_aggressive_gpx_repair(
     let track_points = extract_track_points_manually_preprocessor(content)?;
     
     if track_points.is_empty() {
-        return Err("No valid track points found even with aggressive parsing".into());
+        return Err(TrackError::NoTracks { file: input_path.to_path_buf() }.into());
     }
     
     println!("   📍 Extracted {} track points manually", track_points.len());
     
     // Create a minimal valid GPX structure
-    let repaired_gpx_content = create_minimal_gpx_from_points_preprocessor(&track_points)?;
+    let repaired_gpx_content = create_minimal_gpx_from_points_preprocessor(input_path, &track_points)?;
     
     // Try to parse the manually created GPX
     let cursor = std::io::Cursor::new(repaired_gpx_content.as_bytes());
@@ -565,54 +594,8 @@ fn ensure_valid_track_structure(content: &str) -> String {
     repaired
 }
 
-fn add_estimated_elevations(content: &str) -> String {
-    if content.contains("<ele>") {
-        return content.to_string();
-    }
-    
-    let lines: Vec<&str> = content.lines().collect();
-    let mut new_lines = Vec::new();
-    let mut elevation_counter = 100.0;
-    
-    for line in lines {
-        new_lines.push(line.to_string());
-        
-        if line.trim().starts_with("<trkpt ") && line.contains("lat=") && line.contains("lon=") {
-            if let Some(lat_start) = line.find("lat=\"") {
-                if let Some(lat_end) = line[lat_start + 5..].find("\"") {
-                    if let Ok(lat) = line[lat_start + 5..lat_start + 5 + lat_end].parse::<f64>() {
-                        elevation_counter = (lat.abs() * 50.0).max(0.0).min(4000.0);
-                    }
-                }
-            }
-            
-            let indent = "        ";
-            new_lines.push(format!("{}  <ele>{:.1}</ele>", indent, elevation_counter));
-            elevation_counter += (pseudo_random() - 0.5) * 10.0;
-        }
-    }
-    
-    new_lines.join("\n")
-}
-
-/// Simple pseudo-random number generator for elevation estimation
-fn pseudo_random() -> f64 {
-    use std::collections::hash_map::DefaultHasher;
-    use std::hash::{Hash, Hasher};
-    use std::time::{SystemTime, UNIX_EPOCH};
-    
-    let mut hasher = DefaultHasher::new();
-    let nanos = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .subsec_nanos();
-    nanos.hash(&mut hasher);
-    let hash = hasher.finish();
-    (hash as f64) / (u64::MAX as f64)
-}
-
 /// Extract track points manually using string parsing (for severely corrupted files)
-fn extract_track_points_manually_preprocessor(content: &str) -> Result<Vec<(f64, f64, f64)>, Box<dyn std::error::Error>> {
+fn extract_track_points_manually_preprocessor(content: &str) -> Result<Vec<(f64, f64, Option<f64>)>, Box<dyn std::error::Error>> {
     let mut points = Vec::new();
     
     // Look for patterns that might contain coordinates
@@ -622,11 +605,8 @@ fn extract_track_points_manually_preprocessor(content: &str) -> Result<Vec<(f64,
         // Try to extract lat/lon from trkpt tags
         if line.contains("trkpt") || (line.contains("lat=") && line.contains("lon=")) {
             if let Some((lat, lon)) = extract_lat_lon_from_line_preprocessor(line) {
-                // Look for elevation in the same line or next few lines
-                let elevation = find_elevation_near_line_preprocessor(&lines, i).unwrap_or_else(|| {
-                    // If no elevation found, estimate based on latitude
-                    estimate_elevation_from_latitude_preprocessor(lat)
-                });
+                // Look for elevation in the same line or next few lines (left empty for the DEM otherwise)
+                let elevation = find_elevation_near_line_preprocessor(&lines, i);
                 points.push((lat, lon, elevation));
             }
         }
@@ -634,9 +614,7 @@ fn extract_track_points_manually_preprocessor(content: &str) -> Result<Vec<(f64,
         // Also try to extract from any line that has decimal coordinates
         else if line.contains('.') && (line.contains('-') || line.matches(char::is_numeric).count() > 5) {
             if let Some((lat, lon)) = extract_coordinates_from_any_line_preprocessor(line) {
-                let elevation = find_elevation_near_line_preprocessor(&lines, i).unwrap_or_else(|| {
-                    estimate_elevation_from_latitude_preprocessor(lat)
-                });
+                let elevation = find_elevation_near_line_preprocessor(&lines, i);
                 points.push((lat, lon, elevation));
             }
         }
@@ -680,24 +658,6 @@ fn find_elevation_near_line_preprocessor(lines: &[&str], current_index: usize) -
     None
 }
 
-/// Estimate elevation based on latitude (very rough approximation)
-fn estimate_elevation_from_latitude_preprocessor(lat: f64) -> f64 {
-    let abs_lat = lat.abs();
-    
-    // Rough approximation based on latitude zones
-    if abs_lat < 10.0 {
-        50.0
-    } else if abs_lat < 30.0 {
-        200.0
-    } else if abs_lat < 45.0 {
-        400.0
-    } else if abs_lat < 60.0 {
-        600.0
-    } else {
-        100.0
-    }
-}
-
 fn extract_lat_lon_from_line_preprocessor(line: &str) -> Option<(f64, f64)> {
     let mut lat = None;
     let mut lon = None;
@@ -799,9 +759,9 @@ fn extract_elevation_from_line_preprocessor(line: &str) -> Option<f64> {
 }
 
 /// Create a minimal valid GPX structure from extracted points
-fn create_minimal_gpx_from_points_preprocessor(points: &[(f64, f64, f64)]) -> Result<String, Box<dyn std::error::Error>> {
+fn create_minimal_gpx_from_points_preprocessor(path: &Path, points: &[(f64, f64, Option<f64>)]) -> Result<String, TrackError> {
     if points.is_empty() {
-        return Err("No points to create GPX from".into());
+        return Err(TrackError::NoTracks { file: path.to_path_buf() });
     }
     
     let mut gpx_content = String::new();
@@ -816,10 +776,16 @@ fn create_minimal_gpx_from_points_preprocessor(points: &[(f64, f64, f64)]) -> Re
     
     // Add track points
     for (lat, lon, ele) in points {
-        gpx_content.push_str(&format!(
-            "      <trkpt lat=\"{:.6}\" lon=\"{:.6}\">\n        <ele>{:.1}</ele>\n      </trkpt>\n",
-            lat, lon, ele
-        ));
+        match ele {
+            Some(ele) => gpx_content.push_str(&format!(
+                "      <trkpt lat=\"{:.6}\" lon=\"{:.6}\">\n        <ele>{:.1}</ele>\n      </trkpt>\n",
+                lat, lon, ele
+            )),
+            None => gpx_content.push_str(&format!(
+                "      <trkpt lat=\"{:.6}\" lon=\"{:.6}\"/>\n",
+                lat, lon
+            )),
+        }
     }
     
     // GPX footer
@@ -840,10 +806,17 @@ struct GpxAnalysis {
     elevation_min: f64,
     elevation_max: f64,
     has_elevation_data: bool,
+    elevation_sources: ElevationSourceCounts,
 }
 
 fn analyze_gpx_data(gpx: &Gpx) -> GpxAnalysis {
-    let mut total_tracks = 0;
+    // Routes (or a waypoint sequence) count as the course when there is no track
+    let total_tracks = match gpx_geometry(gpx) {
+        Some(GpxGeometry::Track) => gpx.tracks.len() as u32,
+        Some(GpxGeometry::Route) => gpx.routes.len() as u32,
+        Some(GpxGeometry::Waypoints) => 1,
+        None => 0,
+    };
     let mut total_segments = 0;
     let mut total_points = 0;
     let mut points_with_elevation = 0;
@@ -851,22 +824,18 @@ fn analyze_gpx_data(gpx: &Gpx) -> GpxAnalysis {
     let mut elevation_min = f64::INFINITY;
     let mut elevation_max = f64::NEG_INFINITY;
     
-    for track in &gpx.tracks {
-        total_tracks += 1;
+    for segment in gpx_course_segments(gpx) {
+        total_segments += 1;
         
-        for segment in &track.segments {
-            total_segments += 1;
+        for point in segment {
+            total_points += 1;
             
-            for point in &segment.points {
-                total_points += 1;
-                
-                if let Some(elevation) = point.elevation {
-                    points_with_elevation += 1;
-                    elevation_min = elevation_min.min(elevation);
-                    elevation_max = elevation_max.max(elevation);
-                } else {
-                    points_without_elevation += 1;
-                }
+            if let Some(elevation) = point.elevation {
+                points_with_elevation += 1;
+                elevation_min = elevation_min.min(elevation);
+                elevation_max = elevation_max.max(elevation);
+            } else {
+                points_without_elevation += 1;
             }
         }
     }
@@ -887,6 +856,7 @@ fn analyze_gpx_data(gpx: &Gpx) -> GpxAnalysis {
         elevation_min,
         elevation_max,
         has_elevation_data,
+        elevation_sources: ElevationSourceCounts::default(),
     }
 }
 
@@ -903,20 +873,14 @@ fn validate_gpx_data(gpx: &Gpx) -> GpxValidation {
     let mut elevation_issues = Vec::new();
     
     // Validate coordinates
-    let mut coord_count = 0;
     let mut invalid_coords = 0;
     
-    for track in &gpx.tracks {
-        for segment in &track.segments {
-            for point in &segment.points {
-                coord_count += 1;
-                let lat = point.point().y();
-                let lon = point.point().x();
-                
-                if lat < -90.0 || lat > 90.0 || lon < -180.0 || lon > 180.0 {
-                    invalid_coords += 1;
-                }
-            }
+    for point in gpx_course_segments(gpx).into_iter().flatten() {
+        let lat = point.point().y();
+        let lon = point.point().x();
+        
+        if lat < -90.0 || lat > 90.0 || lon < -180.0 || lon > 180.0 {
+            invalid_coords += 1;
         }
     }
     
@@ -925,8 +889,8 @@ fn validate_gpx_data(gpx: &Gpx) -> GpxValidation {
     }
     
     // Validate structure
-    if gpx.tracks.is_empty() {
-        structure_issues.push("No tracks found".to_string());
+    if gpx_geometry(gpx).is_none() {
+        structure_issues.push("No tracks, routes or waypoints found".to_string());
     }
     
     let empty_segments = gpx.tracks.iter()
@@ -939,14 +903,12 @@ fn validate_gpx_data(gpx: &Gpx) -> GpxValidation {
     }
     
     // Validate elevation
-    let total_points = gpx.tracks.iter()
-        .flat_map(|t| &t.segments)
-        .flat_map(|s| &s.points)
+    let total_points = gpx_course_segments(gpx).into_iter()
+        .flatten()
         .count();
     
-    let points_with_elevation = gpx.tracks.iter()
-        .flat_map(|t| &t.segments)
-        .flat_map(|s| &s.points)
+    let points_with_elevation = gpx_course_segments(gpx).into_iter()
+        .flatten()
         .filter(|p| p.elevation.is_some())
         .count();
     
@@ -1006,6 +968,9 @@ fn create_success_result(
         coordinate_validation: validation.coordinate_validation,
         structure_validation: validation.structure_validation,
         elevation_validation: validation.elevation_validation,
+        elevation_source_gps: analysis.elevation_sources.gps,
+        elevation_source_dem: analysis.elevation_sources.dem,
+        elevation_source_missing: analysis.elevation_sources.missing,
         error_message: String::new(),
     }
 }
@@ -1037,6 +1002,9 @@ fn create_error_result(
         coordinate_validation: "FAILED".to_string(),
         structure_validation: "FAILED".to_string(),
         elevation_validation: "FAILED".to_string(),
+        elevation_source_gps: 0,
+        elevation_source_dem: 0,
+        elevation_source_missing: 0,
         error_message: error.to_string(),
     }
 }
@@ -1068,6 +1036,9 @@ fn write_preprocessing_report(
         "Coordinate_Validation",
         "Structure_Validation",
         "Elevation_Validation",
+        "Elevation_Source_GPS",
+        "Elevation_Source_DEM",
+        "Elevation_Source_Missing",
         "Error_Message",
     ])?;
     
@@ -1093,6 +1064,9 @@ fn write_preprocessing_report(
             &result.coordinate_validation,
             &result.structure_validation,
             &result.elevation_validation,
+            &result.elevation_source_gps.to_string(),
+            &result.elevation_source_dem.to_string(),
+            &result.elevation_source_missing.to_string(),
             &result.error_message,
         ])?;
     }
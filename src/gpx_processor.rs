@@ -4,21 +4,32 @@
 /// This version adds spike filtering BEFORE applying directional deadzone thresholds.
 
 use std::path::Path;
-use std::fs::{self, File};
-use std::io::BufReader;
-use gpx::{read, write, Track, TrackSegment, Waypoint};
+use std::fs;
+use std::collections::HashSet;
+use gpx::Gpx;
 use geo::{HaversineDistance, point};
 use walkdir::WalkDir;
 use serde::Serialize;
 use csv::Writer;
-use crate::incline_analyzer::analyze_inclines_default;
+use crate::elevation_smoother::{smooth_between_gaps, ElevationSmoother, SpikeDeadzoneSmoother};
+use crate::barometric::{fill_barometric_gaps, read_gpx_barometric_altitudes, BarometricSmoother};
+use crate::tcx::{read_tcx, write_tcx, TcxTrack};
+use crate::track_loader::{
+    cumulative_distances_from, gap_runs, gpx_course_name, gpx_course_segments, gpx_geometry, gpx_time_to_utc,
+    read_gpx_file, recording_gaps, AltitudeSource, DistanceSource, GpxGeometry, LoadedTrack, TrackPoint,
+};
+use crate::error::{error_kind, TrackError};
+use crate::pause_detection::{self, detect_stops, format_duration, print_stop_summary};
+use crate::gps_jumps::{filter_gps_jumps, JumpDetectionConfig, JumpRepair};
+use crate::gpx_rewrite::{write_processed_gpx, GpxWriteOptions, TrackpointEdit};
+use crate::dem::{resolve_elevation, DemCorrection, ElevationSource, ElevationSourceCounts};
 
 // PROVEN THRESHOLDS (these work fine)
-const GAIN_THRESHOLD: f64 = 0.10;  // 10cm for elevation gains
-const LOSS_THRESHOLD: f64 = 0.05;  // 5cm for elevation losses
+pub const GAIN_THRESHOLD: f64 = 0.10;  // 10cm for elevation gains
+pub const LOSS_THRESHOLD: f64 = 0.05;  // 5cm for elevation losses
 
 // NEW: SPIKE FILTERING THRESHOLDS
-const MAX_ELEVATION_CHANGE_PER_POINT: f64 = 2.0;  // 2m max change between consecutive points
+pub const MAX_ELEVATION_CHANGE_PER_POINT: f64 = 2.0;  // 2m max change between consecutive points
 const SPIKE_DETECTION_WINDOW: usize = 3;           // Look at 3-point windows for spike detection
 
 #[derive(Debug, Serialize)]
@@ -42,12 +53,27 @@ pub struct ProcessingResult {
     official_gain_m: u32,
     accuracy_percent: f64,
     gain_loss_ratio_percent: f64,
+    altitude_source: String,
     
     // Spike filtering stats
     spikes_detected: usize,
     max_spike_magnitude: f64,
     spikes_filtered: usize,
     
+    // Where each point's elevation came from
+    elevation_source_gps: usize,
+    elevation_source_dem: usize,
+    elevation_source_missing: usize,
+    
+    // Horizontal GPS jumps
+    gps_jumps_detected: usize,
+    gps_jump_excursion_m: f64,
+    
+    // Stops and auto-pauses (timed tracks only)
+    stops: usize,
+    elapsed_time_s: f64,
+    moving_time_s: f64,
+    
     processing_status: String,
 }
 
@@ -63,27 +89,94 @@ pub fn process_and_save_gpx_files(
              GAIN_THRESHOLD * 100.0, LOSS_THRESHOLD * 100.0);
     println!("   Expected: Dramatic noise reduction and accurate results!\n");
     
+    process_and_save_gpx_files_with_smoother(input_folder, output_folder, &ProcessingSetup {
+        smoother: &SpikeDeadzoneSmoother::default(),
+        barometric_smoother: Some(&BarometricSmoother::default()),
+        distance_source: DistanceSource::Haversine,
+        dem: None,
+        cleanup: &PointCleanup::default(),
+        write_options: &GpxWriteOptions::default(),
+    })
+}
+
+/// Point-level cleanup applied before smoothing
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PointCleanup {
+    pub collapse_stops: bool,
+    pub jumps: JumpDetectionConfig,
+}
+
+/// Settings shared by every file in a run
+pub struct ProcessingSetup<'a> {
+    pub smoother: &'a dyn ElevationSmoother,
+    pub barometric_smoother: Option<&'a dyn ElevationSmoother>,  // For tracks whose every elevation is barometric
+    pub distance_source: DistanceSource,
+    pub dem: Option<&'a DemCorrection>,
+    pub cleanup: &'a PointCleanup,
+    pub write_options: &'a GpxWriteOptions,
+}
+
+/// Process and save every GPX/TCX file in a folder using any smoothing algorithm.
+/// Each file is written back in the format it came in; with a DEM, terrain elevations
+/// fill or replace the GPS ones before smoothing. With `collapse_stops`, each stationary
+/// stop is smoothed as a single point and all of its points get that point's elevation.
+/// Horizontal GPS jumps are always counted, and repaired as `cleanup.jumps` says.
+/// Tracks whose every elevation is barometric (GPX extensions) go to the setup's
+/// `barometric_smoother` when it has one; tracks mixing barometric and GPS points are
+/// smoothed as GPS.
+/// GPX files without a track are processed along their route (or waypoint sequence). GPX
+/// output is the input file with only the course point elevations (and repaired or dropped
+/// jump points) changed, so tracks, segments, metadata, waypoints, routes and extensions
+/// carry over. Segments that resume more than MAX_BRIDGED_GAP_M away are smoothed
+/// separately and no distance is counted across the gap.
+pub fn process_and_save_gpx_files_with_smoother(
+    input_folder: &str,
+    output_folder: &str,
+    setup: &ProcessingSetup,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let ProcessingSetup { smoother, barometric_smoother, distance_source, dem, cleanup, write_options } = *setup;
+    println!("🔧 Smoother: {}", smoother.name());
+    if let Some(barometric) = barometric_smoother {
+        println!("🌡️  Barometric tracks: {}", barometric.name());
+    }
+    if cleanup.jumps.repair != JumpRepair::None {
+        println!("📍 GPS jumps: {:?} (above {:.0} m/s)", cleanup.jumps.repair, cleanup.jumps.max_speed_mps);
+    }
+    if cleanup.collapse_stops {
+        println!("⏸️  Stationary stops collapsed before smoothing");
+    }
+    if distance_source == DistanceSource::Device {
+        println!("📏 Distance: device-reported where available");
+    }
+    if let Some(dem) = dem {
+        println!("🗺️  DEM elevations: {:?} mode", dem.mode);
+    }
+    if write_options.keep_original_elevation {
+        println!("💾 Original GPX elevations kept in the point extensions");
+    }
+    
     // Create output directory
     fs::create_dir_all(output_folder)?;
     println!("📁 Output folder: {}", output_folder);
     
     // Load official elevation data
-    let official_data = crate::load_official_elevation_data()?;
+    let official_data = crate::load_ground_truth_registry()?;
     
-    // Collect all GPX files
+    // Collect all GPX and TCX files
     let mut gpx_files = Vec::new();
     for entry in WalkDir::new(input_folder) {
         let entry = entry?;
         if entry.file_type().is_file() {
             if let Some(extension) = entry.path().extension() {
-                if extension.to_str().unwrap_or("").to_lowercase() == "gpx" {
+                let extension = extension.to_str().unwrap_or("").to_lowercase();
+                if extension == "gpx" || extension == "tcx" {
                     gpx_files.push(entry.path().to_path_buf());
                 }
             }
         }
     }
     
-    println!("🔍 Found {} GPX files to process\n", gpx_files.len());
+    println!("🔍 Found {} GPX/TCX files to process\n", gpx_files.len());
     
     let mut results = Vec::new();
     let mut processed_count = 0;
@@ -96,7 +189,7 @@ pub fn process_and_save_gpx_files(
                      index + 1, gpx_files.len(), filename.to_string_lossy());
         }
         
-        match process_single_gpx_file_with_spike_filtering(gpx_path, output_folder, &official_data) {
+        match process_single_gpx_file(gpx_path, output_folder, &official_data, setup) {
             Ok(result) => {
                 results.push(result);
                 processed_count += 1;
@@ -107,7 +200,7 @@ pub fn process_and_save_gpx_files(
                 error_count += 1;
                 
                 // Create error result for CSV
-                let error_result = create_error_result(gpx_path, &format!("{}", e));
+                let error_result = create_error_result(gpx_path, e.as_ref());
                 results.push(error_result);
             }
         }
@@ -119,76 +212,225 @@ pub fn process_and_save_gpx_files(
     
     // Print summary
     print_processing_summary(&results, processed_count, error_count);
+    official_data.print_match_report();
     
     Ok(())
 }
 
-fn process_single_gpx_file_with_spike_filtering(
+/// The parsed input file, kept so the output can be written in the same format
+enum SourceDocument {
+    Gpx(Box<Gpx>),
+    Tcx(TcxTrack),
+}
+
+fn process_single_gpx_file(
     input_path: &Path,
     output_folder: &str,
-    official_data: &std::collections::HashMap<String, u32>,
+    official_data: &crate::ground_truth::GroundTruthRegistry,
+    setup: &ProcessingSetup,
 ) -> Result<ProcessingResult, Box<dyn std::error::Error>> {
-    
-    // Read the original GPX file
-    let file = File::open(input_path)?;
-    let reader = BufReader::new(file);
-    let gpx = read(reader)?;
+    let ProcessingSetup { smoother, barometric_smoother, distance_source, dem, cleanup, write_options } = *setup;
+    let jumps = &cleanup.jumps;
     
     let original_filename = input_path.file_name().unwrap().to_string_lossy().to_string();
+    let is_tcx = original_filename.to_lowercase().ends_with(".tcx");
     
-    // Extract track name
-    let track_name = if let Some(track) = gpx.tracks.first() {
-        track.name.clone().unwrap_or_else(|| {
-            clean_filename(&original_filename)
-        })
+    // Read the original file
+    let mut source = if is_tcx {
+        SourceDocument::Tcx(read_tcx(input_path)?)
     } else {
-        return Err("No tracks found in GPX file".into());
+        SourceDocument::Gpx(Box::new(read_gpx_file(input_path)?))
+    };
+    
+    // Tracks, or for course files without one their route / waypoint sequence
+    let geometry = match &source {
+        SourceDocument::Gpx(gpx) => gpx_geometry(gpx).ok_or_else(|| TrackError::NoTracks { file: input_path.to_path_buf() })?,
+        SourceDocument::Tcx(_) => GpxGeometry::Track,
+    };
+    if geometry != GpxGeometry::Track {
+        println!("   🧭 No track: using the {} as the course", if geometry == GpxGeometry::Route { "route" } else { "waypoints" });
+    }
+    
+    // Extract track name
+    let track_name = match &source {
+        SourceDocument::Gpx(gpx) => gpx_course_name(gpx).unwrap_or_else(|| clean_filename(&original_filename)),
+        SourceDocument::Tcx(tcx) => tcx.name.clone().unwrap_or_else(|| clean_filename(&original_filename)),
     };
     
     // Clean track name for use as filename
     let clean_track_name = clean_filename(&track_name);
-    let output_filename = format!("{}_spike_filtered.gpx", clean_track_name);
+    let output_filename = format!("{}_{}.{}",
+                                  clean_track_name,
+                                  clean_filename(&smoother.name()).replace('-', "_"),
+                                  if is_tcx { "tcx" } else { "gpx" });
     let output_path = Path::new(output_folder).join(&output_filename);
     
-    // Extract coordinates and calculate original metrics
+    // Extract coordinates and calculate original metrics, taking DEM elevations where configured
     let mut original_coords = Vec::new();
-    
-    for track in &gpx.tracks {
-        for segment in &track.segments {
-            for point in &segment.points {
-                if let Some(elevation) = point.elevation {
-                    original_coords.push((point.point().y(), point.point().x(), elevation));
+    let mut original_times = Vec::new();
+    let mut point_sources = Vec::new();
+    let mut point_segments = Vec::new();  // Track segment (or route) each point came from
+    let mut point_trkpts = Vec::new();    // Each point's element in document order
+    let mut trackpoint_count = 0;
+    let mut barometric_points = 0;
+    let mut elevation_sources = ElevationSourceCounts::default();
+    
+    match &mut source {
+        SourceDocument::Gpx(gpx) => {
+            // Barometric altitude from the track point extensions replaces <ele> on the whole
+            // track (gaps interpolated) when enough points carry it
+            let readings = if geometry == GpxGeometry::Track { read_gpx_barometric_altitudes(input_path)? } else { Vec::new() };
+            let barometric = fill_barometric_gaps(&readings);
+            if barometric.is_none() && readings.iter().any(|r| r.is_some()) {
+                println!("   🌡️  Barometric altitude on too few points: using the GPS elevation");
+            }
+            for (segment_index, segment) in gpx_course_segments(gpx).into_iter().enumerate() {
+                for point in segment {
+                    trackpoint_count += 1;
+                    let (lat, lon) = (point.point().y(), point.point().x());
+                    let barometric_altitude = barometric.as_ref().and_then(|b| b.get(trackpoint_count - 1).copied());
+                    let measured = if barometric.is_some() { barometric_altitude } else { point.elevation };
+                    let (elevation, mut elevation_source) = resolve_elevation(dem, lat, lon, measured);
+                    // Files preprocessed with a DEM carry the marker on their points
+                    if elevation_source == ElevationSource::Gps && point.source.as_deref() == Some("DEM") {
+                        elevation_source = ElevationSource::Dem;
+                    }
+                    elevation_sources.record(elevation_source);
+                    if let Some(elevation) = elevation {
+                        barometric_points += usize::from(barometric_altitude.is_some() && elevation_source == ElevationSource::Gps);
+                        original_coords.push((lat, lon, elevation));
+                        original_times.push(point.time.and_then(gpx_time_to_utc));
+                        point_sources.push(elevation_source);
+                        point_segments.push(segment_index);
+                        point_trkpts.push(trackpoint_count - 1);
+                    }
                 }
             }
         }
+        SourceDocument::Tcx(tcx) => {
+            let tcx_segments: Vec<usize> = (0..tcx.points.len()).map(|i| tcx.segment_of(i)).collect();
+            for (point, segment_index) in tcx.points.iter_mut().zip(tcx_segments) {
+                let (elevation, elevation_source) = resolve_elevation(dem, point.latitude, point.longitude, Some(point.elevation));
+                elevation_sources.record(elevation_source);
+                point.elevation = elevation.unwrap_or(point.elevation);
+                original_coords.push((point.latitude, point.longitude, point.elevation));
+                original_times.push(point.time);
+                point_sources.push(elevation_source);
+                point_segments.push(segment_index);
+            }
+        }
     }
     
     if original_coords.is_empty() {
-        return Err("No elevation data found in GPX file".into());
+        return Err(TrackError::NoElevation { file: input_path.to_path_buf() }.into());
+    }
+    let original_point_count = original_coords.len();
+    let altitude_source = AltitudeSource::from_point_counts(barometric_points, original_point_count);
+    let smoother = match (altitude_source, barometric_smoother) {
+        (AltitudeSource::Barometric, Some(barometric)) => barometric,
+        _ => smoother,
+    };
+    match altitude_source {
+        AltitudeSource::Barometric => println!("   🌡️  Barometric altitude: smoothed with {}", smoother.name()),
+        AltitudeSource::Gps => {}
     }
     
-    // Calculate original distances and metrics
-    let original_distances = calculate_distances(&original_coords);
+    // Horizontal GPS jumps, before they inflate the distances and shift the resampling grid
+    let (keep, repaired_coords, jump_stats) = filter_gps_jumps(&original_coords, &original_times, jumps);
+    if jump_stats.jumps_detected > 0 {
+        println!("   📍 {} GPS jump(s) over {} point(s) adding {:.0}m of distance{}",
+                 jump_stats.jumps_detected, jump_stats.points_affected, jump_stats.total_excursion_m,
+                 match jumps.repair {
+                     JumpRepair::None => " (not repaired)",
+                     JumpRepair::Drop => ", dropped",
+                     JumpRepair::Interpolate => ", interpolated",
+                 });
+    }
+    // Trackpoints the repair moved or dropped, for the output file
+    let moved: Vec<bool> = keep.iter()
+        .zip(&repaired_coords)
+        .map(|(&i, repaired)| (repaired.0, repaired.1) != (original_coords[i].0, original_coords[i].1))
+        .collect();
+    let mut dropped_trkpts = Vec::new();
+    if keep.len() != original_coords.len() {
+        let mut kept = vec![false; original_coords.len()];
+        keep.iter().for_each(|&i| kept[i] = true);
+        dropped_trkpts = point_trkpts.iter().zip(&kept).filter(|(_, &k)| !k).map(|(&t, _)| t).collect();
+        point_trkpts = keep.iter().filter_map(|&i| point_trkpts.get(i).copied()).collect();
+        original_times = keep.iter().map(|&i| original_times[i]).collect();
+        point_sources = keep.iter().map(|&i| point_sources[i]).collect();
+        point_segments = keep.iter().map(|&i| point_segments[i]).collect();
+        if let SourceDocument::Tcx(tcx) = &mut source {
+            tcx.retain_points(&keep);
+        }
+    }
+    original_coords = repaired_coords;
+    if let SourceDocument::Tcx(tcx) = &mut source {
+        for (point, &(lat, lon, _)) in tcx.points.iter_mut().zip(&original_coords) {
+            point.latitude = lat;
+            point.longitude = lon;
+        }
+    }
+    
+    // Segment boundaries, and the ones that are recording gaps rather than a quick pause
+    let segment_starts: Vec<usize> = (0..point_segments.len())
+        .filter(|&i| i == 0 || point_segments[i] != point_segments[i - 1])
+        .collect();
+    let points: Vec<TrackPoint> = original_coords.iter()
+        .zip(&original_times)
+        .map(|(&(latitude, longitude, elevation), &time)| TrackPoint { latitude, longitude, elevation, time, distance: None })
+        .collect();
+    let gaps = recording_gaps(&points, &segment_starts);
+    if !gaps.is_empty() {
+        println!("   ✂️  {} recording gap(s) between segments: not bridged, smoothed separately", gaps.len());
+    }
+    
+    // Calculate original distances and metrics (GPX carries no device distance)
+    let original_distances = match &source {
+        SourceDocument::Tcx(tcx) => cumulative_distances_from(&tcx.points, distance_source, &gaps),
+        SourceDocument::Gpx(_) => calculate_distances(&original_coords, &gaps),
+    };
     let original_distance_km = original_distances.last().unwrap() / 1000.0;
     let original_elevations: Vec<f64> = original_coords.iter().map(|c| c.2).collect();
-    let (original_raw_gain, original_raw_loss) = calculate_raw_gain_loss(&original_elevations);
-    
-    // STAGE 1: Apply spike filtering
-    let (spike_filtered_elevations, spike_stats) = filter_elevation_spikes(&original_elevations);
-    let (spike_filtered_gain, spike_filtered_loss) = calculate_raw_gain_loss(&spike_filtered_elevations);
+    let (original_raw_gain, original_raw_loss) = gap_runs(original_elevations.len(), &gaps).into_iter()
+        .map(|run| calculate_raw_gain_loss(&original_elevations[run]))
+        .fold((0.0, 0.0), |(gain, loss), (g, l)| (gain + g, loss + l));
+    
+    // Stops are found on the original points
+    let mut timed_track = LoadedTrack::from_segments(original_filename.clone(), points, segment_starts);
+    timed_track.cumulative_distances = original_distances.clone();
+    let stops = detect_stops(&timed_track);
+    print_stop_summary(&stops);
+    
+    // Smooth each run between gaps, then map the profile back onto the original track points
+    let (profile, processed_elevations) = if cleanup.collapse_stops {
+        let collapsed = pause_detection::collapse_stops(&timed_track, &stops);
+        let collapsed_gaps: Vec<usize> = gaps.iter().map(|&g| collapsed.point_map[g]).collect();
+        let profile = smooth_between_gaps(smoother, &collapsed.track.coordinates(), &collapsed.track.cumulative_distances, &collapsed_gaps);
+        let elevations = profile.elevations_at(&collapsed.original_point_distances(), &gaps);
+        (profile, elevations)
+    } else {
+        let profile = smooth_between_gaps(smoother, &original_coords, &original_distances, &gaps);
+        let elevations = profile.elevations_at(&original_distances, &gaps);
+        (profile, elevations)
+    };
+    let (processed_gain, processed_loss) = (profile.gain_m(), profile.loss_m());
     
-    // STAGE 2: Apply directional deadzone to spike-filtered data
-    let deadzone_filtered_elevations = apply_directional_deadzone(&spike_filtered_elevations);
-    let (processed_gain, processed_loss) = calculate_raw_gain_loss(&deadzone_filtered_elevations);
+    // Spike stage figures only exist for smoothers that report them
+    let spike_filtered_gain = profile.diagnostic_total("spike_filtered_gain_m").unwrap_or(original_raw_gain);
+    let spike_filtered_loss = profile.diagnostic_total("spike_filtered_loss_m").unwrap_or(original_raw_loss);
     
     // Create processed coordinates with final filtered elevations
     let processed_coords: Vec<_> = original_coords.iter()
-        .zip(deadzone_filtered_elevations.iter())
+        .zip(processed_elevations.iter())
         .map(|((lat, lon, _), &new_ele)| (*lat, *lon, new_ele))
         .collect();
     
     let processed_points = processed_coords.len();
-    let processed_distances = calculate_distances(&processed_coords);
+    let processed_distances = match distance_source {
+        DistanceSource::Device => original_distances.clone(),
+        DistanceSource::Haversine => calculate_distances(&processed_coords, &gaps),
+    };
     let processed_distance_km = processed_distances.last().unwrap_or(&0.0) / 1000.0;
     
     // Calculate metrics
@@ -205,7 +447,7 @@ fn process_single_gpx_file_with_spike_filtering(
     } else { 0.0 };
     
     // Look up official data for accuracy calculation
-    let official_gain = official_data.get(&original_filename.to_lowercase()).copied().unwrap_or(0);
+    let official_gain = official_data.official_gain(input_path, &original_coords);
     let accuracy_percent = if official_gain > 0 {
         (processed_gain / official_gain as f64) * 100.0
     } else { 0.0 };
@@ -214,45 +456,37 @@ fn process_single_gpx_file_with_spike_filtering(
         (processed_loss / processed_gain) * 100.0
     } else { 0.0 };
     
-    // Perform incline analysis on processed data
-    let incline_analysis = analyze_inclines_default(deadzone_filtered_elevations.clone(), processed_distances.clone());
-    
-    // Create new GPX with processed data
-    let mut new_gpx = gpx.clone();
-    new_gpx.tracks.clear();
-    
-    // Create new track with processed points
-    let mut new_track = Track::new();
-    new_track.name = Some(format!("{} (Spike Filtered)", track_name));
-    new_track.description = Some(format!(
-        "Two-stage filtered: {} spikes removed, then directional deadzone applied. Original: {:.0}m gain, Final: {:.0}m gain ({:.1}% total reduction), Accuracy: {:.1}%",
-        spike_stats.spikes_filtered,
-        original_raw_gain,
-        processed_gain,
-        gain_reduction_percent,
-        accuracy_percent
-    ));
-    
-    let mut new_segment = TrackSegment::new();
-    
-    for &(lat, lon, ele) in &processed_coords {
-        let mut waypoint = Waypoint::new(point!(x: lon, y: lat));
-        waypoint.elevation = Some(ele);
-        new_segment.points.push(waypoint);
+    match source {
+        SourceDocument::Gpx(_) => {
+            // Copy the input, replacing only what processing changed in each course point
+            let mut edits = vec![TrackpointEdit::default(); trackpoint_count];
+            for (k, &(lat, lon, ele)) in processed_coords.iter().enumerate() {
+                edits[point_trkpts[k]] = TrackpointEdit {
+                    elevation: Some(ele),
+                    position: moved[k].then_some((lat, lon)),
+                    dem_source: point_sources[k] == ElevationSource::Dem,
+                    remove: false,
+                };
+            }
+            for &t in &dropped_trkpts {
+                edits[t].remove = true;
+            }
+            write_processed_gpx(input_path, &output_path, geometry, &edits, write_options)?;
+        }
+        SourceDocument::Tcx(mut tcx) => {
+            // Same trackpoints (time, distance) with the processed elevations
+            for (point, &ele) in tcx.points.iter_mut().zip(processed_elevations.iter()) {
+                point.elevation = ele;
+            }
+            write_tcx(&tcx, &output_path)?;
+        }
     }
     
-    new_track.segments.push(new_segment);
-    new_gpx.tracks.push(new_track);
-    
-    // Save the processed GPX file
-    let output_file = File::create(&output_path)?;
-    write(&new_gpx, output_file)?;
-    
     let result = ProcessingResult {
         original_filename,
         track_name,
         output_filename,
-        original_points: original_coords.len(),
+        original_points: original_point_count,
         processed_points,
         original_distance_km,
         processed_distance_km,
@@ -268,11 +502,23 @@ fn process_single_gpx_file_with_spike_filtering(
         official_gain_m: official_gain,
         accuracy_percent,
         gain_loss_ratio_percent,
+        altitude_source: altitude_source.name().to_string(),
         
         // Spike filtering stats
-        spikes_detected: spike_stats.spikes_detected,
-        max_spike_magnitude: spike_stats.max_spike_magnitude,
-        spikes_filtered: spike_stats.spikes_filtered,
+        spikes_detected: profile.diagnostic_total("spikes_detected").unwrap_or(0.0) as usize,
+        max_spike_magnitude: profile.diagnostic_max("max_spike_magnitude_m").unwrap_or(0.0),
+        spikes_filtered: profile.diagnostic_total("spikes_filtered").unwrap_or(0.0) as usize,
+        
+        elevation_source_gps: elevation_sources.gps,
+        elevation_source_dem: elevation_sources.dem,
+        elevation_source_missing: elevation_sources.missing,
+        
+        gps_jumps_detected: jump_stats.jumps_detected,
+        gps_jump_excursion_m: jump_stats.total_excursion_m,
+        
+        stops: stops.stops.len(),
+        elapsed_time_s: stops.elapsed_time_s,
+        moving_time_s: stops.moving_time_s,
         
         processing_status: "SUCCESS".to_string(),
     };
@@ -281,14 +527,17 @@ fn process_single_gpx_file_with_spike_filtering(
 }
 
 #[derive(Debug)]
-struct SpikeFilteringStats {
-    spikes_detected: usize,
-    spikes_filtered: usize,
-    max_spike_magnitude: f64,
+pub struct SpikeFilteringStats {
+    pub spikes_detected: usize,
+    pub spikes_filtered: usize,
+    pub max_spike_magnitude: f64,
 }
 
-/// STAGE 1: Filter out GPS elevation spikes
-fn filter_elevation_spikes(elevations: &[f64]) -> (Vec<f64>, SpikeFilteringStats) {
+/// STAGE 1: Filter out GPS elevation spikes larger than `max_change_per_point` meters
+pub fn filter_elevation_spikes_with_limit(
+    elevations: &[f64],
+    max_change_per_point: f64
+) -> (Vec<f64>, SpikeFilteringStats) {
     if elevations.len() < 3 {
         return (elevations.to_vec(), SpikeFilteringStats {
             spikes_detected: 0,
@@ -315,7 +564,7 @@ fn filter_elevation_spikes(elevations: &[f64]) -> (Vec<f64>, SpikeFilteringStats
         }
         
         // Detect spikes
-        if elevation_change > MAX_ELEVATION_CHANGE_PER_POINT {
+        if elevation_change > max_change_per_point {
             spikes_detected += 1;
             
             // For massive spikes, use a smoothed value instead of raw data
@@ -331,7 +580,7 @@ fn filter_elevation_spikes(elevations: &[f64]) -> (Vec<f64>, SpikeFilteringStats
                 
                 // Only use smoothed value if it's reasonable
                 let smoothed_change = (smoothed_elevation - prev_elevation).abs();
-                if smoothed_change < MAX_ELEVATION_CHANGE_PER_POINT {
+                if smoothed_change < max_change_per_point {
                     filtered_elevations.push(smoothed_elevation);
                     spikes_filtered += 1;
                 } else {
@@ -360,7 +609,11 @@ fn filter_elevation_spikes(elevations: &[f64]) -> (Vec<f64>, SpikeFilteringStats
 }
 
 /// STAGE 2: Apply directional deadzone to spike-filtered data
-fn apply_directional_deadzone(elevations: &[f64]) -> Vec<f64> {
+pub fn apply_directional_deadzone_with_thresholds(
+    elevations: &[f64],
+    gain_threshold: f64,
+    loss_threshold: f64
+) -> Vec<f64> {
     if elevations.len() < 2 {
         return elevations.to_vec();
     }
@@ -374,10 +627,10 @@ fn apply_directional_deadzone(elevations: &[f64]) -> Vec<f64> {
         let elevation_change = elevation - current_elevation;
         
         // Apply directional deadzone thresholds
-        if elevation_change > GAIN_THRESHOLD {
+        if elevation_change > gain_threshold {
             // Significant elevation gain - keep the change
             current_elevation = elevation;
-        } else if elevation_change < -LOSS_THRESHOLD {
+        } else if elevation_change < -loss_threshold {
             // Significant elevation loss - keep the change
             current_elevation = elevation;
         }
@@ -389,10 +642,15 @@ fn apply_directional_deadzone(elevations: &[f64]) -> Vec<f64> {
     filtered_elevations
 }
 
-fn calculate_distances(coords: &[(f64, f64, f64)]) -> Vec<f64> {
+fn calculate_distances(coords: &[(f64, f64, f64)], gaps: &[usize]) -> Vec<f64> {
+    let gaps: HashSet<usize> = gaps.iter().copied().collect();
     let mut distances = vec![0.0];
     
     for i in 1..coords.len() {
+        if gaps.contains(&i) {
+            distances.push(distances[i-1]);
+            continue;
+        }
         let a = point!(x: coords[i-1].1, y: coords[i-1].0);
         let b = point!(x: coords[i].1, y: coords[i].0);
         let dist = a.haversine_distance(&b);
@@ -420,7 +678,7 @@ fn calculate_raw_gain_loss(elevations: &[f64]) -> (f64, f64) {
 
 fn clean_filename(name: &str) -> String {
     // Remove file extension if present
-    let name = if name.to_lowercase().ends_with(".gpx") {
+    let name = if name.to_lowercase().ends_with(".gpx") || name.to_lowercase().ends_with(".tcx") {
         &name[..name.len()-4]
     } else {
         name
@@ -438,7 +696,7 @@ fn clean_filename(name: &str) -> String {
         .to_string()
 }
 
-fn create_error_result(gpx_path: &Path, error_msg: &str) -> ProcessingResult {
+fn create_error_result(gpx_path: &Path, error: &(dyn std::error::Error + 'static)) -> ProcessingResult {
     ProcessingResult {
         original_filename: gpx_path.file_name().unwrap().to_string_lossy().to_string(),
         track_name: "ERROR".to_string(),
@@ -459,10 +717,19 @@ fn create_error_result(gpx_path: &Path, error_msg: &str) -> ProcessingResult {
         official_gain_m: 0,
         accuracy_percent: 0.0,
         gain_loss_ratio_percent: 0.0,
+        altitude_source: String::new(),
         spikes_detected: 0,
         max_spike_magnitude: 0.0,
         spikes_filtered: 0,
-        processing_status: format!("ERROR: {}", error_msg),
+        elevation_source_gps: 0,
+        elevation_source_dem: 0,
+        elevation_source_missing: 0,
+        gps_jumps_detected: 0,
+        gps_jump_excursion_m: 0.0,
+        stops: 0,
+        elapsed_time_s: 0.0,
+        moving_time_s: 0.0,
+        processing_status: format!("ERROR {}: {}", error_kind(error).code(), error),
     }
 }
 
@@ -493,9 +760,18 @@ fn save_results_to_csv(
         "Official_Gain_m",
         "Accuracy_%",
         "Gain_Loss_Ratio_%",
+        "Altitude_Source",
         "Spikes_Detected",
         "Spikes_Filtered",
         "Max_Spike_Magnitude_m",
+        "Elevation_Source_GPS",
+        "Elevation_Source_DEM",
+        "Elevation_Source_Missing",
+        "GPS_Jumps",
+        "GPS_Jump_Excursion_m",
+        "Stops",
+        "Elapsed_Time",
+        "Moving_Time",
         "Processing_Status",
     ])?;
     
@@ -521,9 +797,18 @@ fn save_results_to_csv(
             &result.official_gain_m.to_string(),
             &format!("{:.1}", result.accuracy_percent),
             &format!("{:.1}", result.gain_loss_ratio_percent),
+            &result.altitude_source,
             &result.spikes_detected.to_string(),
             &result.spikes_filtered.to_string(),
             &format!("{:.1}", result.max_spike_magnitude),
+            &result.elevation_source_gps.to_string(),
+            &result.elevation_source_dem.to_string(),
+            &result.elevation_source_missing.to_string(),
+            &result.gps_jumps_detected.to_string(),
+            &format!("{:.0}", result.gps_jump_excursion_m),
+            &result.stops.to_string(),
+            &format_duration(result.elapsed_time_s),
+            &format_duration(result.moving_time_s),
             &result.processing_status,
         ])?;
     }
@@ -587,4 +872,58 @@ fn print_processing_summary(results: &[ProcessingResult], processed_count: usize
     println!("✅ Stage 2: Directional deadzone ({:.1}cm gain, {:.1}cm loss)", 
              GAIN_THRESHOLD * 100.0, LOSS_THRESHOLD * 100.0);
     println!("✅ Should finally achieve the promised elevation accuracy!");
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elevation_smoother::SmoothedProfile;
+
+    /// Flattens every profile, so a track it smoothed has no gain
+    struct FlatSmoother;
+
+    impl ElevationSmoother for FlatSmoother {
+        fn name(&self) -> String {
+            "flat".to_string()
+        }
+
+        fn smooth(&self, elevations: &[f64], distances: &[f64]) -> SmoothedProfile {
+            SmoothedProfile::from_elevations(distances.to_vec(), vec![elevations[0]; elevations.len()])
+        }
+    }
+
+    #[test]
+    fn test_barometric_tracks_go_to_the_barometric_smoother() {
+        let dir = std::env::temp_dir().join(format!("gpx_processor_barometric_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        // A steady 60m climb on the barometer; <ele> is flat
+        let trackpoints: String = (0..200)
+            .map(|i| format!(
+                "<trkpt lat=\"{:.5}\" lon=\"7.0\"><ele>400</ele><extensions><baro_alt>{:.1}</baro_alt></extensions></trkpt>",
+                46.0 + i as f64 * 0.0001, 500.0 + i as f64 * 0.3,
+            ))
+            .collect();
+        let input = dir.join("climb.gpx");
+        fs::write(&input, format!("<?xml version=\"1.0\"?>\n<gpx version=\"1.1\" creator=\"test\"><trk><trkseg>{}</trkseg></trk></gpx>\n", trackpoints)).unwrap();
+        let registry = crate::ground_truth::GroundTruthRegistry::from_csv_str("filename,official_elevation_gain_m\n", Path::new("empty.csv")).unwrap();
+        let barometric = BarometricSmoother::default();
+
+        let mut results = Vec::new();
+        for barometric_smoother in [Some(&barometric as &dyn ElevationSmoother), None] {
+            let setup = ProcessingSetup {
+                smoother: &FlatSmoother,
+                barometric_smoother,
+                distance_source: DistanceSource::Haversine,
+                dem: None,
+                cleanup: &PointCleanup::default(),
+                write_options: &GpxWriteOptions::default(),
+            };
+            results.push(process_single_gpx_file(&input, dir.to_str().unwrap(), &registry, &setup).unwrap());
+        }
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(results[0].altitude_source, "barometric");
+        assert!((results[0].processed_gain_m - 59.7).abs() < 3.0, "{}", results[0].processed_gain_m);
+        // Ignoring the barometer smooths the same barometric elevations with --smoother
+        assert_eq!(results[1].processed_gain_m, 0.0);
+    }
+}
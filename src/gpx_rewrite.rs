@@ -0,0 +1,416 @@
+/// GPX REWRITE - Write processed elevations back into the source document text
+/// Round-tripping through the gpx crate loses everything it does not model: heart rate and
+/// cadence extensions, unknown elements, formatting and (on some paths) timestamps, metadata,
+/// waypoints and routes. Here only the course points (`<trkpt>`, or `<rtept>`/`<wpt>` for
+/// route files) that change are touched: their `<ele>` text, lat/lon of repaired GPS jumps,
+/// a DEM `<src>` marker, and dropped points.
+/// Every other byte of the input is copied through unchanged.
+use std::fs;
+use std::path::Path;
+use crate::track_loader::GpxGeometry;
+
+// Namespace for the optional original-elevation extension element
+pub const ORIGINAL_ELE_NAMESPACE: &str = "urn:rust-gpx-smoother:1";
+const ORIGINAL_ELE_PREFIX: &str = "smoother";
+// Waypoint children that come after <src> in the GPX 1.1 schema
+const AFTER_SRC: [&str; 11] = ["link", "sym", "type", "fix", "sat", "hdop", "vdop", "pdop", "ageofdgpsdata", "dgpsid", "extensions"];
+
+/// What to change in one course point. The default leaves it as it is.
+#[derive(Debug, Clone, Default)]
+pub struct TrackpointEdit {
+    pub elevation: Option<f64>,
+    pub position: Option<(f64, f64)>,  // New (latitude, longitude)
+    pub dem_source: bool,              // Mark the elevation as coming from a DEM
+    pub remove: bool,
+}
+
+impl TrackpointEdit {
+    fn is_noop(&self) -> bool {
+        self.elevation.is_none() && self.position.is_none() && !self.dem_source && !self.remove
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GpxWriteOptions {
+    pub keep_original_elevation: bool,  // Keep the replaced <ele> in the point's <extensions>
+}
+
+/// A tag found while scanning: byte range, local name and kind
+#[derive(Debug, Clone)]
+struct Tag {
+    start: usize,
+    end: usize,  // One past the closing '>'
+    name: String,
+    closing: bool,
+    self_closing: bool,
+}
+
+impl Tag {
+    fn local_name(&self) -> &str {
+        self.name.rsplit(':').next().unwrap_or(&self.name)
+    }
+}
+
+/// Next tag at or after `from`, skipping comments, CDATA, processing instructions and DOCTYPE
+fn next_tag(text: &str, mut from: usize) -> Result<Option<Tag>, Box<dyn std::error::Error>> {
+    while let Some(offset) = text[from..].find('<') {
+        let start = from + offset;
+        let rest = &text[start..];
+        let skip_to = |terminator: &str| rest.find(terminator).map(|i| start + i + terminator.len())
+            .ok_or_else(|| format!("Unterminated markup at byte {}", start));
+        if rest.starts_with("<!--") {
+            from = skip_to("-->")?;
+            continue;
+        }
+        if rest.starts_with("<![CDATA[") {
+            from = skip_to("]]>")?;
+            continue;
+        }
+        if rest.starts_with("<?") || rest.starts_with("<!") {
+            from = skip_to(">")?;
+            continue;
+        }
+
+        // '>' may appear inside quoted attribute values
+        let mut quote = None;
+        let mut end = None;
+        for (i, c) in rest.char_indices().skip(1) {
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (None, '"') | (None, '\'') => quote = Some(c),
+                (None, '>') => {
+                    end = Some(start + i + 1);
+                    break;
+                }
+                _ => {}
+            }
+        }
+        let end = end.ok_or_else(|| format!("Unterminated tag at byte {}", start))?;
+        let inner = &text[start + 1..end - 1];
+        let closing = inner.starts_with('/');
+        let name = inner.trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or("")
+            .to_string();
+        return Ok(Some(Tag { start, end, name, closing, self_closing: inner.ends_with('/') }));
+    }
+    Ok(None)
+}
+
+/// A child element's open tag and, unless self-closing, its close tag
+type Child = (Tag, Option<Tag>);
+
+/// Direct children of an element body
+fn direct_children(body: &str) -> Result<Vec<Child>, Box<dyn std::error::Error>> {
+    let mut children = Vec::new();
+    let mut open: Option<Tag> = None;
+    let mut depth = 0usize;
+    let mut from = 0;
+    while let Some(tag) = next_tag(body, from)? {
+        from = tag.end;
+        if tag.closing {
+            depth = depth.saturating_sub(1);
+            if depth == 0 {
+                if let Some(start) = open.take() {
+                    children.push((start, Some(tag)));
+                }
+            }
+        } else if tag.self_closing {
+            if depth == 0 {
+                children.push((tag, None));
+            }
+        } else {
+            if depth == 0 {
+                open = Some(tag);
+            }
+            depth += 1;
+        }
+    }
+    Ok(children)
+}
+
+/// Replace the value of attribute `name` in a start tag
+fn set_attribute(start_tag: &str, name: &str, value: &str) -> String {
+    for quote in ['"', '\''] {
+        for separator in [" ", "\t", "\n", "\r"] {
+            let pattern = format!("{}{}={}", separator, name, quote);
+            if let Some(position) = start_tag.find(&pattern) {
+                let value_start = position + pattern.len();
+                if let Some(length) = start_tag[value_start..].find(quote) {
+                    return format!("{}{}{}", &start_tag[..value_start], value, &start_tag[value_start + length..]);
+                }
+            }
+        }
+    }
+    start_tag.to_string()
+}
+
+/// Prefix (with colon) the document uses for GPX elements, taken from the point tag
+fn element_prefix(name: &str) -> &str {
+    name.rfind(':').map_or("", |i| &name[..=i])
+}
+
+/// Rewrite one point element. Returns the new text and whether the original-elevation
+/// extension was written.
+fn rewrite_point(element: &str, open: &Tag, edit: &TrackpointEdit, options: &GpxWriteOptions) -> Result<(String, bool), Box<dyn std::error::Error>> {
+    let prefix = element_prefix(&open.name);
+    // <trkpt .../> becomes an open/close pair so children can be added
+    let (mut start_tag, body, close_tag) = if open.self_closing {
+        let tag = &element[..open.end - open.start];
+        (format!("{}>", tag.trim_end_matches('>').trim_end_matches('/').trim_end()), String::new(), format!("</{}>", open.name))
+    } else {
+        let close_start = element.rfind("</").ok_or("Point without closing tag")?;
+        (element[..open.end - open.start].to_string(), element[open.end - open.start..close_start].to_string(), element[close_start..].to_string())
+    };
+
+    if let Some((latitude, longitude)) = edit.position {
+        start_tag = set_attribute(&start_tag, "lat", &format!("{:.7}", latitude));
+        start_tag = set_attribute(&start_tag, "lon", &format!("{:.7}", longitude));
+    }
+
+    let mut body = body;
+    let mut wrote_original = false;
+    if let Some(elevation) = edit.elevation {
+        let children = direct_children(&body)?;
+        let ele = children.iter().find(|(tag, _)| tag.local_name() == "ele");
+        let original = ele.and_then(|(open, close)| close.as_ref().map(|close| body[open.end..close.start].trim().to_string()));
+        let new_ele = format!("{:.2}", elevation);
+
+        if options.keep_original_elevation {
+            if let Some(original) = original.as_deref().filter(|o| !o.is_empty()) {
+                let element = format!("<{}:original_ele>{}</{}:original_ele>", ORIGINAL_ELE_PREFIX, original, ORIGINAL_ELE_PREFIX);
+                match children.iter().find(|(tag, _)| tag.local_name() == "extensions") {
+                    Some((_, Some(close))) => body.insert_str(close.start, &element),
+                    Some((open, None)) => body.replace_range(open.start..open.end, &format!("<{}extensions>{}</{}extensions>", prefix, element, prefix)),
+                    None => body.push_str(&format!("<{}extensions>{}</{}extensions>", prefix, element, prefix)),
+                }
+                wrote_original = true;
+            }
+        }
+
+        let children = direct_children(&body)?;
+        match children.iter().find(|(tag, _)| tag.local_name() == "ele") {
+            Some((open, Some(close))) => body.replace_range(open.end..close.start, &new_ele),
+            Some((open, None)) => body.replace_range(open.start..open.end, &format!("<{}ele>{}</{}ele>", prefix, new_ele, prefix)),
+            // <ele> is the first child in the schema
+            None => body.insert_str(0, &format!("<{}ele>{}</{}ele>", prefix, new_ele, prefix)),
+        }
+    }
+
+    if edit.dem_source {
+        let children = direct_children(&body)?;
+        match children.iter().find(|(tag, _)| tag.local_name() == "src") {
+            Some((open, Some(close))) => body.replace_range(open.end..close.start, "DEM"),
+            Some((open, None)) => body.replace_range(open.start..open.end, &format!("<{}src>DEM</{}src>", prefix, prefix)),
+            None => {
+                let position = children.iter()
+                    .find(|(tag, _)| AFTER_SRC.contains(&tag.local_name()))
+                    .map_or(body.len(), |(tag, _)| tag.start);
+                body.insert_str(position, &format!("<{}src>DEM</{}src>", prefix, prefix));
+            }
+        }
+    }
+
+    Ok((format!("{}{}{}", start_tag, body, close_tag), wrote_original))
+}
+
+/// Apply `edits` (one per point element of `geometry`, in document order) to GPX text
+pub fn rewrite_points(
+    source: &str,
+    geometry: GpxGeometry,
+    edits: &[TrackpointEdit],
+    options: &GpxWriteOptions,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let point_element = geometry.element();
+    let mut output = String::with_capacity(source.len());
+    let mut copied = 0;
+    let mut from = 0;
+    let mut index = 0;
+    let mut root: Option<Tag> = None;
+    let mut any_original = false;
+
+    while let Some(tag) = next_tag(source, from)? {
+        from = tag.end;
+        if tag.closing {
+            continue;
+        }
+        if tag.local_name() == "gpx" && root.is_none() {
+            root = Some(tag.clone());
+        }
+        if tag.local_name() != point_element {
+            continue;
+        }
+
+        let element_end = if tag.self_closing {
+            tag.end
+        } else {
+            let mut search = tag.end;
+            loop {
+                let next = next_tag(source, search)?.ok_or("Point without closing tag")?;
+                search = next.end;
+                if next.closing && next.local_name() == point_element {
+                    break next.end;
+                }
+            }
+        };
+        let edit = edits.get(index)
+            .ok_or_else(|| format!("Document has more <{}> points than the {} edits", point_element, edits.len()))?;
+        index += 1;
+
+        if edit.is_noop() {
+            continue;
+        }
+        output.push_str(&source[copied..tag.start]);
+        copied = element_end;
+        from = element_end;
+        if edit.remove {
+            // Take the point's line with it
+            let line_start = output.trim_end_matches([' ', '\t']).len();
+            if output[..line_start].ends_with('\n') {
+                output.truncate(line_start);
+                if source[copied..].starts_with("\r\n") {
+                    copied += 2;
+                } else if source[copied..].starts_with('\n') {
+                    copied += 1;
+                }
+                from = copied;
+            }
+            continue;
+        }
+
+        let (element, wrote_original) = rewrite_point(&source[tag.start..element_end], &tag, edit, options)?;
+        any_original |= wrote_original;
+        output.push_str(&element);
+    }
+    output.push_str(&source[copied..]);
+
+    if index != edits.len() {
+        return Err(format!("Document has {} <{}> points but {} edits were given", index, point_element, edits.len()).into());
+    }
+
+    // Declare the extension namespace on the root element if it was used
+    let declaration = format!("xmlns:{}=", ORIGINAL_ELE_PREFIX);
+    if let Some(root) = root.filter(|_| any_original) {
+        let root_tag = &source[root.start..root.end];
+        if !root_tag.contains(&declaration) {
+            let insert_at = root.start + root_tag.trim_end_matches('>').trim_end_matches('/').len();
+            // Nothing before the root element is ever rewritten, so its offset is unchanged
+            output.insert_str(insert_at, &format!(" xmlns:{}=\"{}\"", ORIGINAL_ELE_PREFIX, ORIGINAL_ELE_NAMESPACE));
+        }
+    }
+
+    Ok(output)
+}
+
+/// Number of point elements of `geometry` in GPX text
+pub fn count_points(source: &str, geometry: GpxGeometry) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut count = 0;
+    let mut from = 0;
+    while let Some(tag) = next_tag(source, from)? {
+        from = tag.end;
+        if !tag.closing && tag.local_name() == geometry.element() {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Copy a GPX file with the edits applied
+pub fn write_processed_gpx(
+    input_path: &Path,
+    output_path: &Path,
+    geometry: GpxGeometry,
+    edits: &[TrackpointEdit],
+    options: &GpxWriteOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let source = fs::read_to_string(input_path)?;
+    let rewritten = rewrite_points(&source, geometry, edits, options)?;
+    fs::write(output_path, rewritten)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="Garmin" xmlns="http://www.topografix.com/GPX/1/1" xmlns:gpxtpx="http://www.garmin.com/xmlschemas/TrackPointExtension/v1">
+  <metadata><name>Morning Run</name><time>2024-06-01T08:00:00Z</time></metadata>
+  <wpt lat="46.1" lon="7.1"><ele>900.0</ele><name>Summit</name></wpt>
+  <!-- <trkpt lat="0" lon="0"><ele>0</ele></trkpt> -->
+  <trk>
+    <name>Run</name>
+    <trkseg>
+      <trkpt lat="46.0000000" lon="7.0000000">
+        <ele>500.4</ele>
+        <time>2024-06-01T08:00:00Z</time>
+        <extensions><gpxtpx:TrackPointExtension><gpxtpx:hr>120</gpxtpx:hr></gpxtpx:TrackPointExtension></extensions>
+      </trkpt>
+      <trkpt lat="46.0010000" lon="7.0500000">
+        <ele>650.0</ele>
+        <time>2024-06-01T08:00:05Z</time>
+      </trkpt>
+      <trkpt lat="46.0020000" lon="7.0000000"><time>2024-06-01T08:00:10Z</time><sym>Dot</sym></trkpt>
+      <trkpt lat="46.0030000" lon="7.0000000"/>
+    </trkseg>
+  </trk>
+</gpx>
+"#;
+
+    #[test]
+    fn test_only_edited_trackpoints_change() {
+        assert_eq!(count_points(SOURCE, GpxGeometry::Track).unwrap(), 4);
+        let edits = vec![
+            TrackpointEdit { elevation: Some(501.0), ..Default::default() },
+            TrackpointEdit { remove: true, ..Default::default() },
+            TrackpointEdit { elevation: Some(502.5), dem_source: true, ..Default::default() },
+            TrackpointEdit { elevation: Some(503.0), position: Some((46.003, 7.0001)), ..Default::default() },
+        ];
+        let options = GpxWriteOptions { keep_original_elevation: true };
+        let output = rewrite_points(SOURCE, GpxGeometry::Track, &edits, &options).unwrap();
+
+        // Metadata, waypoints, comments, timestamps and heart rate survive untouched
+        for kept in ["<metadata><name>Morning Run</name>", "<wpt lat=\"46.1\" lon=\"7.1\"><ele>900.0</ele>", "<!-- <trkpt lat=\"0\"",
+                     "<time>2024-06-01T08:00:00Z</time>", "<gpxtpx:hr>120</gpxtpx:hr>"] {
+            assert!(output.contains(kept), "lost {}", kept);
+        }
+        assert!(output.contains("<ele>501.00</ele>"));
+        assert!(output.contains("<smoother:original_ele>500.4</smoother:original_ele></extensions>"));
+        assert!(output.contains(&format!("xmlns:smoother=\"{}\"", ORIGINAL_ELE_NAMESPACE)));
+        assert!(!output.contains("46.0010000") && !output.contains("650.0"));
+        assert!(output.contains("<trkpt lat=\"46.0020000\" lon=\"7.0000000\"><ele>502.50</ele><time>2024-06-01T08:00:10Z</time><src>DEM</src><sym>Dot</sym></trkpt>"));
+        assert!(output.contains("<trkpt lat=\"46.0030000\" lon=\"7.0001000\"><ele>503.00</ele></trkpt>"));
+
+        // Still a readable GPX with the same structure
+        let gpx = gpx::read(output.as_bytes()).unwrap();
+        assert_eq!(gpx.waypoints.len(), 1);
+        assert_eq!(gpx.tracks[0].segments[0].points.len(), 3);
+
+        // No edits is a byte-for-byte copy
+        let unchanged = rewrite_points(SOURCE, GpxGeometry::Track, &vec![TrackpointEdit::default(); 4], &options).unwrap();
+        assert_eq!(unchanged, SOURCE);
+    }
+
+    #[test]
+    fn test_route_files_are_course_geometry() {
+        let source = r#"<?xml version="1.0"?>
+<gpx version="1.1" creator="race" xmlns="http://www.topografix.com/GPX/1/1">
+  <wpt lat="46.0" lon="7.0"><ele>500</ele><name>Start</name></wpt>
+  <rte><name>Race Course</name>
+    <rtept lat="46.0" lon="7.0"><ele>500</ele></rtept>
+    <rtept lat="46.01" lon="7.0"><ele>560</ele></rtept>
+  </rte>
+</gpx>
+"#;
+        let gpx = gpx::read(source.as_bytes()).unwrap();
+        assert_eq!(crate::track_loader::gpx_geometry(&gpx), Some(GpxGeometry::Route));
+        assert_eq!(crate::track_loader::gpx_course_name(&gpx).as_deref(), Some("Race Course"));
+        assert_eq!(crate::track_loader::gpx_course_segments(&gpx)[0].len(), 2);
+
+        let edits = vec![TrackpointEdit { elevation: Some(505.0), ..Default::default() }, TrackpointEdit::default()];
+        let output = rewrite_points(source, GpxGeometry::Route, &edits, &GpxWriteOptions::default()).unwrap();
+        assert!(output.contains("<rtept lat=\"46.0\" lon=\"7.0\"><ele>505.00</ele></rtept>"));
+        assert!(output.contains("<wpt lat=\"46.0\" lon=\"7.0\"><ele>500</ele>"));
+    }
+}
@@ -0,0 +1,1004 @@
+/// GROUND TRUTH REGISTRY - One versioned source of official route figures
+/// A CSV registry (format line `# ground-truth-registry v2`, `#` comment lines allowed) holds
+/// official gain plus optional loss, distance, content hash, course fingerprint, source URL,
+/// confidence and notes per route; legacy two-column files still load as version 1.
+/// Records are keyed by lowercase filename and content hash.
+/// Course fingerprints (the distance and positions sampled along the course) match within a
+/// tolerance, so a renamed, cleaned, trimmed or re-exported track is still found.
+/// Loading reports duplicates, conflicting sources and invalid values; validating against a
+/// track folder also reports missing and unmatched files.
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use csv::{ReaderBuilder, Writer};
+use rayon::prelude::*;
+use serde::Deserialize;
+use walkdir::WalkDir;
+use geo::{HaversineDistance, point};
+use crate::error::TrackError;
+use crate::track_loader::{is_supported_track_file, load_track};
+
+pub const REGISTRY_FORMAT_VERSION: u32 = 2;
+const FORMAT_LINE_PREFIX: &str = "# ground-truth-registry v";
+const FINGERPRINT_SAMPLES: usize = 16;              // Positions at 0, 1/16, ..., 16/16 of the distance
+const FINGERPRINT_TOLERANCE_M: f64 = 75.0;          // Sample to track: GPS jitter, re-exports, point cleaning
+const FINGERPRINT_DISTANCE_TOLERANCE: f64 = 0.05;   // Course distance, and how much may be trimmed off an end
+const METERS_PER_DEGREE: f64 = 111_320.0;
+const REGISTRY_HEADER: [&str; 10] = [
+    "filename",
+    "official_elevation_gain_m",
+    "official_elevation_loss_m",
+    "official_distance_km",
+    "content_hash",
+    "fingerprint",
+    "source",
+    "source_url",
+    "confidence",
+    "notes",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Confidence {
+    #[default]
+    Unknown,
+    Low,
+    Medium,
+    High,
+}
+
+impl Confidence {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Confidence::Unknown => "",
+            Confidence::Low => "low",
+            Confidence::Medium => "medium",
+            Confidence::High => "high",
+        }
+    }
+}
+
+impl std::str::FromStr for Confidence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "unknown" => Ok(Confidence::Unknown),
+            "low" => Ok(Confidence::Low),
+            "medium" => Ok(Confidence::Medium),
+            "high" => Ok(Confidence::High),
+            _ => Err(format!("unknown confidence '{}' (expected low, medium or high)", s)),
+        }
+    }
+}
+
+/// One row as written in the CSV; every column but the first two is optional
+#[derive(Debug, Deserialize)]
+struct RegistryRow {
+    filename: String,
+    official_elevation_gain_m: u32,
+    #[serde(default)]
+    official_elevation_loss_m: Option<u32>,
+    #[serde(default)]
+    official_distance_km: Option<f64>,
+    #[serde(default)]
+    content_hash: Option<String>,
+    #[serde(default)]
+    fingerprint: Option<String>,
+    #[serde(default)]
+    source: String,
+    #[serde(default)]
+    source_url: String,
+    #[serde(default)]
+    confidence: String,
+    #[serde(default)]
+    notes: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct GroundTruthRecord {
+    pub filename: String,
+    pub content_hash: Option<String>,
+    pub fingerprint: Option<CourseFingerprint>,
+    pub gain_m: u32,
+    pub loss_m: Option<u32>,
+    pub distance_km: Option<f64>,
+    pub source: String,
+    pub source_url: String,
+    pub confidence: Confidence,
+    pub notes: String,
+    pub line: usize,  // 1-based line in the registry file, for reports
+}
+
+impl GroundTruthRecord {
+    /// Processed loss as % of the official loss, when the record has a non-zero one
+    pub fn loss_accuracy(&self, processed_loss_m: f64) -> Option<f64> {
+        self.loss_m.filter(|&loss| loss > 0).map(|loss| processed_loss_m / loss as f64 * 100.0)
+    }
+
+    /// Track distance as % of the official distance, when the record has one
+    pub fn distance_accuracy(&self, track_distance_km: f64) -> Option<f64> {
+        self.distance_km.map(|distance| track_distance_km / distance * 100.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IssueSeverity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueKind {
+    Duplicate,           // Same route listed twice with the same figures
+    ConflictingSources,  // Same route listed twice with different figures
+    InvalidValue,
+    MissingFile,         // No track in the folder matches the record
+    HashMismatch,        // A file with the record's name has different content
+    SharedFingerprint,   // Two routes on the same course; neither is matched by fingerprint
+}
+
+#[derive(Debug, Clone)]
+pub struct RegistryIssue {
+    pub severity: IssueSeverity,
+    pub kind: IssueKind,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchKind {
+    ContentHash,
+    Fingerprint,
+    Filename,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TrackMatch<'a> {
+    pub record: &'a GroundTruthRecord,
+    pub kind: MatchKind,
+}
+
+/// Lookups made through `official_gain` that did not match by name, for the match report
+#[derive(Debug, Default)]
+struct MatchLog {
+    renamed: Vec<(String, String)>,  // (file, registry filename) matched by fingerprint
+    unmatched: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct GroundTruthRegistry {
+    pub path: PathBuf,
+    pub format_version: u32,
+    records: Vec<GroundTruthRecord>,
+    by_filename: HashMap<String, usize>,
+    by_hash: HashMap<String, usize>,
+    pub issues: Vec<RegistryIssue>,
+    match_log: Mutex<MatchLog>,
+}
+
+impl GroundTruthRegistry {
+    pub fn from_path(path: &Path) -> Result<Self, TrackError> {
+        let content = fs::read_to_string(path).map_err(|e| TrackError::io(path, e))?;
+        Self::from_csv_str(&content, path)
+    }
+
+    pub fn from_csv_str(content: &str, path: &Path) -> Result<Self, TrackError> {
+        // The format line, when there is one, is line 1
+        let format_error = |message: String| TrackError::Parse { file: path.to_path_buf(), line: Some(1), message };
+        let format_version = content.lines()
+            .next()
+            .and_then(|line| line.trim().strip_prefix(FORMAT_LINE_PREFIX))
+            .map(|version| version.trim().parse::<u32>())
+            .transpose()
+            .map_err(|e| format_error(format!("invalid registry format line: {}", e)))?
+            .unwrap_or(1);
+        if format_version > REGISTRY_FORMAT_VERSION {
+            return Err(format_error(format!(
+                "ground-truth registry format v{}, this build reads up to v{}",
+                format_version, REGISTRY_FORMAT_VERSION
+            )));
+        }
+
+        let mut registry = GroundTruthRegistry {
+            path: path.to_path_buf(),
+            format_version,
+            records: Vec::new(),
+            by_filename: HashMap::new(),
+            by_hash: HashMap::new(),
+            issues: Vec::new(),
+            match_log: Mutex::new(MatchLog::default()),
+        };
+
+        let mut rdr = ReaderBuilder::new()
+            .comment(Some(b'#'))
+            .trim(csv::Trim::All)
+            .from_reader(content.as_bytes());
+
+        let headers = rdr.headers().map_err(|e| TrackError::from_csv(path, e))?.clone();
+        for result in rdr.records() {
+            let parsed = result.and_then(|row| {
+                let line = row.position().map(|p| p.line() as usize).unwrap_or(0);
+                row.deserialize::<RegistryRow>(Some(&headers)).map(|row| (row, line))
+            });
+            match parsed {
+                Ok((row, line)) => registry.add_row(row, line),
+                Err(e) => registry.issues.push(RegistryIssue {
+                    severity: IssueSeverity::Error,
+                    kind: IssueKind::InvalidValue,
+                    line: e.position().map(|p| p.line() as usize),
+                    message: format!("unreadable record: {}", e),
+                }),
+            }
+        }
+
+        Ok(registry)
+    }
+
+    fn add_row(&mut self, row: RegistryRow, line: usize) {
+        let confidence = match row.confidence.parse::<Confidence>() {
+            Ok(confidence) => confidence,
+            Err(e) => {
+                self.issue(IssueSeverity::Warning, IssueKind::InvalidValue, line, format!("{}: {}", row.filename, e));
+                Confidence::Unknown
+            }
+        };
+        let fingerprint = match row.fingerprint.filter(|f| !f.is_empty()).map(|f| f.parse::<CourseFingerprint>()) {
+            Some(Ok(fingerprint)) => Some(fingerprint),
+            Some(Err(e)) => {
+                self.issue(IssueSeverity::Warning, IssueKind::InvalidValue, line, format!("{}: {}", row.filename, e));
+                None
+            }
+            None => None,
+        };
+        let record = GroundTruthRecord {
+            filename: row.filename,
+            content_hash: row.content_hash.filter(|h| !h.is_empty()).map(|h| h.to_lowercase()),
+            fingerprint,
+            gain_m: row.official_elevation_gain_m,
+            loss_m: row.official_elevation_loss_m,
+            distance_km: row.official_distance_km,
+            source: row.source,
+            source_url: row.source_url,
+            confidence,
+            notes: row.notes,
+            line,
+        };
+
+        if record.filename.is_empty() {
+            self.issue(IssueSeverity::Error, IssueKind::InvalidValue, line, "record without a filename".to_string());
+            return;
+        }
+        if record.gain_m == 0 {
+            self.issue(IssueSeverity::Warning, IssueKind::InvalidValue, line,
+                       format!("{}: official gain is 0 and will be ignored by accuracy statistics", record.filename));
+        }
+        if record.distance_km.is_some_and(|d| d <= 0.0 || !d.is_finite()) {
+            self.issue(IssueSeverity::Error, IssueKind::InvalidValue, line,
+                       format!("{}: official distance must be positive", record.filename));
+            return;
+        }
+
+        // A route seen before, by name or by content. Courses can be shared by different
+        // races (same loop, other distance), so a shared fingerprint is not a duplicate.
+        let filename_key = record.filename.to_lowercase();
+        let existing = self.by_filename.get(&filename_key).copied()
+            .or_else(|| record.content_hash.as_ref().and_then(|h| self.by_hash.get(h).copied()));
+
+        match existing {
+            Some(index) => self.merge_duplicate(index, record),
+            None => {
+                let index = self.records.len();
+                self.by_filename.insert(filename_key, index);
+                self.index_keys(&record, index);
+                self.records.push(record);
+            }
+        }
+    }
+
+    /// Keep one record per route: the higher-confidence one wins a conflict, the first a tie
+    fn merge_duplicate(&mut self, index: usize, record: GroundTruthRecord) {
+        // Either name finds the route from now on
+        self.by_filename.entry(record.filename.to_lowercase()).or_insert(index);
+        let existing = &self.records[index];
+        let same_figures = existing.gain_m == record.gain_m
+            && existing.loss_m == record.loss_m
+            && existing.distance_km == record.distance_km;
+        let description = format!("{} duplicates {} (line {})", record.filename, existing.filename, existing.line);
+
+        if same_figures {
+            self.issue(IssueSeverity::Warning, IssueKind::Duplicate, record.line, description);
+            return;
+        }
+
+        let replace = record.confidence > existing.confidence;
+        let message = format!(
+            "{}: {}m from {} vs {}m from {}; keeping the {}",
+            description,
+            existing.gain_m, source_label(&existing.source),
+            record.gain_m, source_label(&record.source),
+            if replace { "later, higher-confidence record" } else { "first record" }
+        );
+        self.issue(IssueSeverity::Error, IssueKind::ConflictingSources, record.line, message);
+
+        if replace {
+            self.index_keys(&record, index);
+            self.records[index] = record;
+        }
+    }
+
+    fn index_keys(&mut self, record: &GroundTruthRecord, index: usize) {
+        if let Some(hash) = &record.content_hash {
+            self.by_hash.insert(hash.clone(), index);
+        }
+        // Tracks of a shared course match both records, so `lookup_by_course` finds neither
+        let Some(fingerprint) = &record.fingerprint else {
+            return;
+        };
+        let shared = self.records.iter()
+            .enumerate()
+            .find(|(other, r)| *other != index && r.fingerprint.as_ref().is_some_and(|f| f.same_course(fingerprint)));
+        if let Some((_, other)) = shared {
+            let message = format!("{} and {} share a course fingerprint; both are matched by name only",
+                                  other.filename, record.filename);
+            self.issue(IssueSeverity::Warning, IssueKind::SharedFingerprint, record.line, message);
+        }
+    }
+
+    fn issue(&mut self, severity: IssueSeverity, kind: IssueKind, line: usize, message: String) {
+        self.issues.push(RegistryIssue {
+            severity,
+            kind,
+            line: Some(line),
+            message,
+        });
+    }
+
+    pub fn records(&self) -> &[GroundTruthRecord] {
+        &self.records
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn lookup(&self, filename: &str) -> Option<&GroundTruthRecord> {
+        self.by_filename.get(&filename.to_lowercase()).map(|&i| &self.records[i])
+    }
+
+    pub fn lookup_by_hash(&self, content_hash: &str) -> Option<&GroundTruthRecord> {
+        self.by_hash.get(&content_hash.to_lowercase()).map(|&i| &self.records[i])
+    }
+
+    /// The one record whose course fingerprint the track follows; None when several do
+    pub fn lookup_by_course(&self, course: &CoursePath) -> Option<&GroundTruthRecord> {
+        let mut matching = self.records.iter().filter(|r| r.fingerprint.as_ref().is_some_and(|f| f.matches(course)));
+        let first = matching.next()?;
+        matching.next().is_none().then_some(first)
+    }
+
+    /// Record for a track file: its content hash first, then its name (without "cleaned_")
+    pub fn lookup_file(&self, path: &Path) -> Option<&GroundTruthRecord> {
+        let by_hash = file_content_hash(path).ok().and_then(|hash| self.lookup_by_hash(&hash));
+        by_hash.or_else(|| {
+            let filename = path.file_name()?.to_string_lossy();
+            self.lookup(filename.strip_prefix("cleaned_").unwrap_or(&filename))
+        })
+    }
+
+    /// Record for a loaded track file: its content hash first, then its exact name (without
+    /// "cleaned_"), then its course fingerprint. Recordings of one loop course can share a
+    /// fingerprint, so it only decides when nothing more specific matches.
+    pub fn match_track(&self, path: &Path, coordinates: &[(f64, f64, f64)]) -> Option<TrackMatch<'_>> {
+        let by_hash = file_content_hash(path).ok()
+            .and_then(|hash| self.lookup_by_hash(&hash))
+            .map(|record| TrackMatch { record, kind: MatchKind::ContentHash });
+        by_hash
+            .or_else(|| {
+                let filename = path.file_name()?.to_string_lossy();
+                self.lookup(filename.strip_prefix("cleaned_").unwrap_or(&filename))
+                    .map(|record| TrackMatch { record, kind: MatchKind::Filename })
+            })
+            .or_else(|| {
+                CoursePath::new(coordinates)
+                    .and_then(|course| self.lookup_by_course(&course))
+                    .map(|record| TrackMatch { record, kind: MatchKind::Fingerprint })
+            })
+    }
+
+    /// Official record for a loaded track file. Renamed and unmatched tracks are remembered
+    /// for `print_match_report`.
+    pub fn official_record(&self, path: &Path, coordinates: &[(f64, f64, f64)]) -> Option<&GroundTruthRecord> {
+        let found = self.match_track(path, coordinates);
+        let filename = path.file_name().map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().into_owned());
+        let mut log = self.match_log.lock().unwrap_or_else(|e| e.into_inner());
+        match found {
+            Some(found) => {
+                let name = filename.strip_prefix("cleaned_").unwrap_or(&filename);
+                if !found.record.filename.eq_ignore_ascii_case(name) {
+                    log.renamed.push((filename.clone(), found.record.filename.clone()));
+                }
+                Some(found.record)
+            }
+            None => {
+                log.unmatched.push(filename);
+                None
+            }
+        }
+    }
+
+    /// Official gain for a loaded track, 0 when nothing matches
+    pub fn official_gain(&self, path: &Path, coordinates: &[(f64, f64, f64)]) -> u32 {
+        self.official_record(path, coordinates).map_or(0, |record| record.gain_m)
+    }
+
+    /// Print (and forget) the renamed and unmatched tracks seen by `official_gain`
+    pub fn print_match_report(&self) {
+        let mut log = self.match_log.lock().unwrap_or_else(|e| e.into_inner());
+        let MatchLog { mut renamed, mut unmatched } = std::mem::take(&mut *log);
+        renamed.sort();
+        unmatched.sort();
+        if !renamed.is_empty() {
+            println!("🔗 {} tracks matched to official data by fingerprint under another name:", renamed.len());
+            for (file, record) in &renamed {
+                println!("   {} -> {}", file, record);
+            }
+        }
+        if !unmatched.is_empty() {
+            println!("⚠️  {} tracks have no official data and are left out of accuracy statistics:", unmatched.len());
+            for file in &unmatched {
+                println!("   {}", file);
+            }
+        }
+    }
+
+    /// Lowercase filename -> official gain, the shape every analysis consumes
+    pub fn gain_map(&self) -> HashMap<String, u32> {
+        self.by_filename.iter()
+            .map(|(filename, &i)| (filename.clone(), self.records[i].gain_m))
+            .collect()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.issues.iter().any(|i| i.severity == IssueSeverity::Error)
+    }
+
+    /// Check every record against the tracks in a folder (recursively)
+    pub fn validate_against_folder(&self, folder: &Path) -> Vec<RegistryIssue> {
+        self.validate_against_tracks(&scan_folder(folder), folder)
+    }
+
+    fn validate_against_tracks(&self, tracks: &[FolderTrack], folder: &Path) -> Vec<RegistryIssue> {
+        let hashes: HashMap<&str, &FolderTrack> = tracks.iter().map(|t| (t.content_hash.as_str(), t)).collect();
+        let names: HashMap<String, &FolderTrack> = tracks.iter()
+            .map(|t| (t.filename.strip_prefix("cleaned_").unwrap_or(&t.filename).to_lowercase(), t))
+            .collect();
+
+        let mut issues = Vec::new();
+        for record in &self.records {
+            let hash_match = record.content_hash.as_deref().and_then(|h| hashes.get(h));
+            let fingerprint_match = record.fingerprint.as_ref()
+                .and_then(|f| tracks.iter().find(|t| t.course.as_ref().is_some_and(|course| f.matches(course))));
+            let hash_match = hash_match.copied();
+            let name_match = names.get(&record.filename.to_lowercase());
+
+            match (hash_match.or(fingerprint_match), name_match, &record.content_hash) {
+                (Some(_), _, _) => {}
+                (None, Some(file), Some(expected)) => issues.push(RegistryIssue {
+                    severity: IssueSeverity::Warning,
+                    kind: IssueKind::HashMismatch,
+                    line: Some(record.line),
+                    message: format!("{}: file content changed (hash {} in folder, {} in registry)", record.filename, file.content_hash, expected),
+                }),
+                (None, Some(_), None) => {}
+                (None, None, _) => issues.push(RegistryIssue {
+                    severity: IssueSeverity::Warning,
+                    kind: IssueKind::MissingFile,
+                    line: Some(record.line),
+                    message: format!("{}: no matching track in {}", record.filename, folder.display()),
+                }),
+            }
+        }
+        issues
+    }
+
+    /// Store the content hash and fingerprint of every folder file matched by name on
+    /// records without them. Returns the number of records updated.
+    pub fn fill_track_keys(&mut self, folder: &Path) -> usize {
+        self.fill_keys_from_tracks(&scan_folder(folder))
+    }
+
+    fn fill_keys_from_tracks(&mut self, tracks: &[FolderTrack]) -> usize {
+        let mut updated = 0;
+        for track in tracks {
+            // Cleaned copies have different content; only key the original files
+            if track.filename.starts_with("cleaned_") {
+                continue;
+            }
+            let Some(&index) = self.by_filename.get(&track.filename.to_lowercase()) else {
+                continue;
+            };
+            let record = &mut self.records[index];
+            let mut changed = false;
+            if record.content_hash.is_none() {
+                record.content_hash = Some(track.content_hash.clone());
+                changed = true;
+            }
+            if record.fingerprint.is_none() {
+                record.fingerprint = track.course.as_ref().and_then(CourseFingerprint::from_course);
+                changed |= record.fingerprint.is_some();
+            }
+            if changed {
+                let record = record.clone();
+                self.index_keys(&record, index);
+                updated += 1;
+            }
+        }
+        updated
+    }
+
+    /// Write the registry in the current format (exact duplicates are written once)
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let mut content = format!("{}{}\n", FORMAT_LINE_PREFIX, REGISTRY_FORMAT_VERSION);
+        {
+            let mut wtr = Writer::from_writer(Vec::new());
+            wtr.write_record(REGISTRY_HEADER)?;
+            for record in &self.records {
+                wtr.write_record([
+                    record.filename.as_str(),
+                    &record.gain_m.to_string(),
+                    &record.loss_m.map(|l| l.to_string()).unwrap_or_default(),
+                    &record.distance_km.map(|d| d.to_string()).unwrap_or_default(),
+                    record.content_hash.as_deref().unwrap_or(""),
+                    &record.fingerprint.as_ref().map(|f| f.to_string()).unwrap_or_default(),
+                    &record.source,
+                    &record.source_url,
+                    record.confidence.name(),
+                    &record.notes,
+                ])?;
+            }
+            content.push_str(&String::from_utf8(wtr.into_inner()?)?);
+        }
+        fs::write(path, content)?;
+        Ok(())
+    }
+
+    pub fn print_issues(issues: &[RegistryIssue]) {
+        for issue in issues {
+            let icon = match issue.severity {
+                IssueSeverity::Warning => "⚠️ ",
+                IssueSeverity::Error => "❌",
+            };
+            match issue.line {
+                Some(line) => println!("{} line {}: {}", icon, line, issue.message),
+                None => println!("{} {}", icon, issue.message),
+            }
+        }
+    }
+}
+
+fn source_label(source: &str) -> String {
+    if source.is_empty() { "an unnamed source".to_string() } else { format!("'{}'", source) }
+}
+
+/// FNV-1a 64-bit hash of a file's bytes, as 16 lowercase hex digits
+pub fn file_content_hash(path: &Path) -> std::io::Result<String> {
+    Ok(format!("{:016x}", fnv1a_64(&fs::read(path)?)))
+}
+
+pub fn fnv1a_64(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+    bytes.iter().fold(OFFSET_BASIS, |hash, &byte| (hash ^ byte as u64).wrapping_mul(PRIME))
+}
+
+/// A track's positions with the distance along it, to match course fingerprints against
+#[derive(Debug, Clone)]
+pub struct CoursePath {
+    positions: Vec<(f64, f64)>,
+    along: Vec<f64>,
+}
+
+impl CoursePath {
+    /// None for tracks without a usable position (indoor recordings) or any length
+    pub fn new(coordinates: &[(f64, f64, f64)]) -> Option<Self> {
+        let positions: Vec<(f64, f64)> = coordinates.iter()
+            .filter(|(lat, lon, _)| lat.is_finite() && lon.is_finite() && (*lat != 0.0 || *lon != 0.0))
+            .map(|&(lat, lon, _)| (lat, lon))
+            .collect();
+        let mut along = Vec::with_capacity(positions.len());
+        let mut total = 0.0;
+        for (i, &position) in positions.iter().enumerate() {
+            if i > 0 {
+                total += ground_distance(positions[i - 1], position);
+            }
+            along.push(total);
+        }
+        (total > 0.0).then_some(CoursePath { positions, along })
+    }
+
+    pub fn total_m(&self) -> f64 {
+        self.along.last().copied().unwrap_or(0.0)
+    }
+
+    fn position_at(&self, distance: f64) -> (f64, f64) {
+        let i = self.along.partition_point(|&d| d < distance).clamp(1, self.along.len() - 1);
+        let span = self.along[i] - self.along[i - 1];
+        let t = if span > 0.0 { ((distance - self.along[i - 1]) / span).clamp(0.0, 1.0) } else { 0.0 };
+        let (a, b) = (self.positions[i - 1], self.positions[i]);
+        (a.0 + t * (b.0 - a.0), a.1 + t * (b.1 - a.1))
+    }
+
+    /// Distance from `position` to the part of the path between `from` and `to` meters along it
+    fn distance_within(&self, position: (f64, f64), from: f64, to: f64) -> f64 {
+        if self.positions.len() == 1 {
+            return ground_distance(self.positions[0], position);
+        }
+        (1..self.positions.len())
+            .filter(|&i| self.along[i] >= from && self.along[i - 1] <= to)
+            .map(|i| distance_to_segment(position, self.positions[i - 1], self.positions[i]))
+            .fold(f64::INFINITY, f64::min)
+    }
+}
+
+/// Canonical course fingerprint: the course distance and the positions at FINGERPRINT_SAMPLES
+/// even fractions of it, so courses sharing a start, finish and extent still differ. Stored in
+/// the registry as `distance_m;lat lon;lat lon;...`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CourseFingerprint {
+    pub distance_m: f64,
+    pub samples: Vec<(f64, f64)>,
+}
+
+impl CourseFingerprint {
+    /// None for tracks without a usable position, and for tracks that never leave one spot,
+    /// which would otherwise all match each other
+    pub fn of_track(coordinates: &[(f64, f64, f64)]) -> Option<Self> {
+        Self::from_course(&CoursePath::new(coordinates)?)
+    }
+
+    pub fn from_course(course: &CoursePath) -> Option<Self> {
+        let total = course.total_m();
+        let samples: Vec<(f64, f64)> = (0..=FINGERPRINT_SAMPLES)
+            .map(|k| course.position_at(total * k as f64 / FINGERPRINT_SAMPLES as f64))
+            .collect();
+        if samples.iter().all(|&sample| ground_distance(samples[0], sample) <= FINGERPRINT_TOLERANCE_M) {
+            return None;
+        }
+        Some(CourseFingerprint { distance_m: total, samples })
+    }
+
+    /// Distance an end of the course may be trimmed (or extended) by and still match
+    fn trim_allowance_m(&self) -> f64 {
+        self.distance_m * FINGERPRINT_DISTANCE_TOLERANCE
+    }
+
+    /// Whether a track follows this course: its distance within FINGERPRINT_DISTANCE_TOLERANCE,
+    /// and every sample within FINGERPRINT_TOLERANCE_M of the track near where the sample lies
+    /// along the course. The start and finish may be off by the trimmed distance as well.
+    pub fn matches(&self, course: &CoursePath) -> bool {
+        let trim = self.trim_allowance_m();
+        if (course.total_m() - self.distance_m).abs() > trim + FINGERPRINT_TOLERANCE_M {
+            return false;
+        }
+        let last = self.samples.len().saturating_sub(1);
+        self.samples.iter().enumerate().all(|(k, &sample)| {
+            let expected = self.distance_m * k as f64 / last.max(1) as f64;
+            let window = trim + FINGERPRINT_TOLERANCE_M;
+            let tolerance = if k == 0 || k == last { FINGERPRINT_TOLERANCE_M + trim } else { FINGERPRINT_TOLERANCE_M };
+            course.distance_within(sample, expected - window, expected + window) <= tolerance
+        })
+    }
+
+    /// Whether two fingerprints describe the same course
+    pub fn same_course(&self, other: &CourseFingerprint) -> bool {
+        (self.distance_m - other.distance_m).abs() <= self.trim_allowance_m().max(other.trim_allowance_m()) + FINGERPRINT_TOLERANCE_M
+            && self.samples.len() == other.samples.len()
+            && self.samples.iter().zip(&other.samples).all(|(&a, &b)| ground_distance(a, b) <= FINGERPRINT_TOLERANCE_M)
+    }
+}
+
+impl std::fmt::Display for CourseFingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.0}", self.distance_m)?;
+        for (lat, lon) in &self.samples {
+            write!(f, ";{:.5} {:.5}", lat, lon)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for CourseFingerprint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid course fingerprint '{}' (expected distance_m;lat lon;...)", s);
+        let mut parts = s.trim().split(';');
+        let distance_m: f64 = parts.next().and_then(|d| d.trim().parse().ok()).ok_or_else(invalid)?;
+        let samples = parts
+            .map(|sample| {
+                let (lat, lon) = sample.trim().split_once(' ').ok_or_else(invalid)?;
+                Ok((lat.parse().map_err(|_| invalid())?, lon.trim().parse().map_err(|_| invalid())?))
+            })
+            .collect::<Result<Vec<(f64, f64)>, String>>()?;
+        if !distance_m.is_finite() || distance_m <= 0.0 || samples.len() < 2 {
+            return Err(invalid());
+        }
+        Ok(CourseFingerprint { distance_m, samples })
+    }
+}
+
+fn ground_distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    point!(x: a.1, y: a.0).haversine_distance(&point!(x: b.1, y: b.0))
+}
+
+/// Distance from `p` to the segment a-b, on a local flat projection around `p`
+fn distance_to_segment(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let lon_scale = p.0.to_radians().cos();
+    let to_local = |q: (f64, f64)| ((q.1 - p.1) * lon_scale * METERS_PER_DEGREE, (q.0 - p.0) * METERS_PER_DEGREE);
+    let ((ax, ay), (bx, by)) = (to_local(a), to_local(b));
+    let (dx, dy) = (bx - ax, by - ay);
+    let length_sq = dx * dx + dy * dy;
+    let t = if length_sq > 0.0 { (-(ax * dx + ay * dy) / length_sq).clamp(0.0, 1.0) } else { 0.0 };
+    (ax + t * dx).hypot(ay + t * dy)
+}
+
+/// A track file under a folder, with the keys the registry can match it by
+struct FolderTrack {
+    filename: String,
+    content_hash: String,
+    course: Option<CoursePath>,
+}
+
+fn scan_folder(folder: &Path) -> Vec<FolderTrack> {
+    let paths: Vec<PathBuf> = WalkDir::new(folder)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file() && is_supported_track_file(entry.path()))
+        .map(|entry| entry.into_path())
+        .collect();
+
+    let mut tracks: Vec<FolderTrack> = paths.par_iter()
+        .filter_map(|path| {
+            let content_hash = file_content_hash(path).ok()?;
+            let course = load_track(path).ok().and_then(|track| CoursePath::new(&track.coordinates()));
+            Some(FolderTrack {
+                filename: path.file_name()?.to_string_lossy().into_owned(),
+                content_hash,
+                course,
+            })
+        })
+        .collect();
+    tracks.sort_by(|a, b| a.filename.cmp(&b.filename));
+    tracks
+}
+
+/// Validate the registry on its own and against a folder of tracks
+pub fn run_ground_truth_validation(
+    registry: &mut GroundTruthRegistry,
+    folder: &Path,
+    fill_hashes: bool,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    println!("\n📋 GROUND-TRUTH REGISTRY VALIDATION");
+    println!("===================================");
+    println!("📄 Registry: {} (format v{}, {} records)", registry.path.display(), registry.format_version, registry.len());
+    println!("📁 Tracks:   {}", folder.display());
+
+    let tracks = scan_folder(folder);
+    let folder_issues = registry.validate_against_tracks(&tracks, folder);
+    let all_issues: Vec<RegistryIssue> = registry.issues.iter().cloned().chain(folder_issues).collect();
+
+    let mut renamed = Vec::new();
+    let mut unmatched = Vec::new();
+    for track in &tracks {
+        let name = track.filename.strip_prefix("cleaned_").unwrap_or(&track.filename);
+        let by_key = registry.lookup_by_hash(&track.content_hash)
+            .or_else(|| track.course.as_ref().and_then(|course| registry.lookup_by_course(course)));
+        match (by_key, registry.lookup(name)) {
+            (_, Some(_)) => {}
+            (Some(record), None) => renamed.push((&track.filename, &record.filename)),
+            (None, None) => unmatched.push(&track.filename),
+        }
+    }
+
+    let count = |kind: IssueKind| all_issues.iter().filter(|i| i.kind == kind).count();
+    let keyed = |has_key: fn(&GroundTruthRecord) -> bool| registry.records().iter().filter(|r| has_key(r)).count();
+    println!("\n📊 SUMMARY:");
+    println!("  Duplicates:            {}", count(IssueKind::Duplicate));
+    println!("  Conflicting sources:   {}", count(IssueKind::ConflictingSources));
+    println!("  Invalid values:        {}", count(IssueKind::InvalidValue));
+    println!("  Missing files:         {}", count(IssueKind::MissingFile));
+    println!("  Changed files:         {}", count(IssueKind::HashMismatch));
+    println!("  Shared fingerprints:   {}", count(IssueKind::SharedFingerprint));
+    println!("  Renamed files:         {}", renamed.len());
+    println!("  Unmatched files:       {}/{}", unmatched.len(), tracks.len());
+    println!("  Records with hash:     {}/{}", keyed(|r| r.content_hash.is_some()), registry.len());
+    println!("  Records with print:    {}/{}", keyed(|r| r.fingerprint.is_some()), registry.len());
+
+    if !all_issues.is_empty() {
+        println!("\n🔍 ISSUES:");
+        GroundTruthRegistry::print_issues(&all_issues);
+    }
+    if !renamed.is_empty() {
+        println!("\n🔗 MATCHED UNDER ANOTHER NAME:");
+        for (file, record) in &renamed {
+            println!("  {} -> {}", file, record);
+        }
+    }
+    if !unmatched.is_empty() {
+        println!("\n❓ NO OFFICIAL DATA:");
+        for file in &unmatched {
+            println!("  {}", file);
+        }
+    }
+
+    if fill_hashes && registry.has_errors() {
+        println!("\n❌ Not rewriting {}: resolve the errors above first", registry.path.display());
+    } else if fill_hashes {
+        let updated = registry.fill_keys_from_tracks(&tracks);
+        if updated > 0 || registry.format_version < REGISTRY_FORMAT_VERSION {
+            let path = registry.path.clone();
+            registry.save(&path)?;
+            println!("\n💾 Stored hashes and fingerprints for {} records in {} (format v{})", updated, path.display(), REGISTRY_FORMAT_VERSION);
+        } else {
+            println!("\n✅ No new content hashes or fingerprints to store");
+        }
+    }
+
+    Ok(!all_issues.iter().any(|i| i.severity == IssueSeverity::Error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_loads_legacy_and_flags_conflicts() {
+        let legacy = "filename,official_elevation_gain_m\nA.gpx,100\nb.gpx,200\n";
+        let registry = GroundTruthRegistry::from_csv_str(legacy, Path::new("legacy.csv")).unwrap();
+        assert_eq!(registry.format_version, 1);
+        assert_eq!(registry.lookup("a.gpx").unwrap().gain_m, 100);
+        assert!(registry.issues.is_empty());
+
+        let v2 = "# ground-truth-registry v2\n\
+                  filename,official_elevation_gain_m,official_elevation_loss_m,official_distance_km,content_hash,source,source_url,confidence,notes\n\
+                  a.gpx,100,,,00000000000000ff,organiser,,low,\n\
+                  A.gpx,100,,,,,,,\n\
+                  copy.gpx,120,90,42.2,00000000000000FF,strava,https://example.com,high,re-export\n";
+        let registry = GroundTruthRegistry::from_csv_str(v2, Path::new("v2.csv")).unwrap();
+        assert_eq!(registry.format_version, 2);
+        assert_eq!(registry.len(), 1);
+        let kinds: Vec<IssueKind> = registry.issues.iter().map(|i| i.kind).collect();
+        assert_eq!(kinds, vec![IssueKind::Duplicate, IssueKind::ConflictingSources]);
+        // Same content under another name: the high-confidence record wins
+        let record = registry.lookup_by_hash("00000000000000ff").unwrap();
+        assert_eq!((record.gain_m, record.loss_m, record.confidence), (120, Some(90), Confidence::High));
+        assert!(registry.has_errors());
+    }
+
+    fn winding_course() -> Vec<(f64, f64, f64)> {
+        (0..200)
+            .map(|i| {
+                let t = i as f64 / 199.0;
+                (46.0 + 0.05 * t, 7.0 + 0.02 * (t * 6.0).sin(), 500.0 + 100.0 * t)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_fingerprint_matches_renamed_and_cleaned_tracks() {
+        let course = winding_course();
+        // Re-export: every other point dropped, coordinates rounded to 5 decimals, new elevations
+        let cleaned: Vec<(f64, f64, f64)> = course.iter()
+            .step_by(2)
+            .chain(course.last())
+            .map(|&(lat, lon, _)| ((lat * 1e5).round() / 1e5, (lon * 1e5).round() / 1e5, 0.0))
+            .collect();
+        let fingerprint = CourseFingerprint::of_track(&course).unwrap();
+        assert!(fingerprint.matches(&CoursePath::new(&cleaned).unwrap()));
+        assert_eq!(fingerprint.to_string().parse::<CourseFingerprint>().unwrap().to_string(), fingerprint.to_string());
+        assert_eq!(CourseFingerprint::of_track(&[(0.0, 0.0, 10.0), (0.0, 0.0, 12.0)]), None);
+        assert_eq!(CourseFingerprint::of_track(&[(46.0, 7.0, 10.0), (46.0001, 7.0, 12.0)]), None);
+        // Same start, finish and bounding box, but the other way round the block
+        let north_then_east = [(46.0, 7.0, 0.0), (46.05, 7.0, 0.0), (46.05, 7.05, 0.0)];
+        let east_then_north = [(46.0, 7.0, 0.0), (46.0, 7.05, 0.0), (46.05, 7.05, 0.0)];
+        assert!(!CourseFingerprint::of_track(&north_then_east).unwrap().matches(&CoursePath::new(&east_then_north).unwrap()));
+
+        let csv = format!("# ground-truth-registry v2\nfilename,official_elevation_gain_m,fingerprint\nrace.gpx,420,{}\n", fingerprint);
+        let registry = GroundTruthRegistry::from_csv_str(&csv, Path::new("v2.csv")).unwrap();
+        assert!(registry.issues.is_empty());
+        let found = registry.match_track(Path::new("cleaned_my_upload (1).gpx"), &cleaned).unwrap();
+        assert_eq!((found.record.gain_m, found.kind), (420, MatchKind::Fingerprint));
+        assert_eq!(registry.official_gain(Path::new("elsewhere.gpx"), &[(10.0, 10.0, 0.0), (10.1, 10.0, 0.0)]), 0);
+        let log = registry.match_log.lock().unwrap();
+        assert_eq!(log.unmatched, vec!["elsewhere.gpx".to_string()]);
+
+        // A second race on the same course makes the fingerprint ambiguous: names only
+        let shared = format!("{}other_distance.gpx,800,{}\n", csv, fingerprint);
+        let registry = GroundTruthRegistry::from_csv_str(&shared, Path::new("v2.csv")).unwrap();
+        assert_eq!(registry.issues[0].kind, IssueKind::SharedFingerprint);
+        assert!(registry.match_track(Path::new("renamed.gpx"), &cleaned).is_none());
+        assert_eq!(registry.official_gain(Path::new("other_distance.gpx"), &cleaned), 800);
+    }
+
+    #[test]
+    fn test_fingerprint_survives_jitter_trimming_and_resampling() {
+        let course = winding_course();
+        let fingerprint = CourseFingerprint::of_track(&course).unwrap();
+
+        // 3m of GPS jitter on every point, 50 different draws
+        let mut seed: u64 = 42;
+        let mut noise = || {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((seed >> 33) as f64 / (1u64 << 31) as f64 - 0.5) * 2.0 * 3.0 / METERS_PER_DEGREE
+        };
+        for _ in 0..50 {
+            let jittered: Vec<(f64, f64, f64)> = course.iter().map(|&(lat, lon, ele)| (lat + noise(), lon + noise(), ele)).collect();
+            assert!(fingerprint.matches(&CoursePath::new(&jittered).unwrap()));
+        }
+
+        // The last ~100m trimmed, the first ~100m trimmed, and resampled to every third point
+        let path = CoursePath::new(&course).unwrap();
+        let along = |range: fn(f64, f64) -> bool| -> Vec<(f64, f64, f64)> {
+            course.iter().zip(&path.along).filter(|(_, &d)| range(d, path.total_m())).map(|(&c, _)| c).collect()
+        };
+        let trimmed_end = along(|d, total| d <= total - 100.0);
+        let trimmed_start = along(|d, _| d >= 100.0);
+        let resampled: Vec<(f64, f64, f64)> = course.iter().step_by(3).chain(course.last()).copied().collect();
+        for copy in [&trimmed_end, &trimmed_start, &resampled] {
+            assert!(fingerprint.matches(&CoursePath::new(copy).unwrap()));
+        }
+
+        // The first half of the course, and the course run backwards, are other courses
+        let reversed: Vec<(f64, f64, f64)> = course.iter().rev().copied().collect();
+        assert!(!fingerprint.matches(&CoursePath::new(&course[..100]).unwrap()));
+        assert!(!fingerprint.matches(&CoursePath::new(&reversed).unwrap()));
+    }
+
+    #[test]
+    fn test_match_prefers_hash_then_filename_then_fingerprint() {
+        let course = [(46.0, 7.0, 0.0), (46.02, 7.01, 0.0), (46.05, 7.0, 0.0)];
+        let fingerprint = CourseFingerprint::of_track(&course).unwrap();
+        let path = std::env::temp_dir().join(format!("ground_truth_match_{}.gpx", std::process::id()));
+        std::fs::write(&path, "<gpx/>").unwrap();
+        let hash = file_content_hash(&path).unwrap();
+
+        let csv = format!(
+            "# ground-truth-registry v2\nfilename,official_elevation_gain_m,content_hash,fingerprint\n\
+             race.gpx,420,,{}\nshort.gpx,150,,\nupload.gpx,300,{},\n",
+            fingerprint, hash,
+        );
+        let registry = GroundTruthRegistry::from_csv_str(&csv, Path::new("v2.csv")).unwrap();
+        // Same bytes under another name and on the race course: the hash decides
+        let found = registry.match_track(&path, &course).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((found.record.gain_m, found.kind), (300, MatchKind::ContentHash));
+        // A shorter race recorded on the same course keeps its own name
+        let found = registry.match_track(Path::new("cleaned_short.gpx"), &course).unwrap();
+        assert_eq!((found.record.gain_m, found.kind), (150, MatchKind::Filename));
+        let found = registry.match_track(Path::new("morning_run.gpx"), &course).unwrap();
+        assert_eq!((found.record.gain_m, found.kind), (420, MatchKind::Fingerprint));
+    }
+
+    #[test]
+    fn test_fnv1a_reference_values() {
+        assert_eq!(fnv1a_64(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a_64(b"a"), 0xaf63dc4c8601ec8c);
+    }
+
+    #[test]
+    fn test_registry_errors_name_the_file_and_line() {
+        let newer = "# ground-truth-registry v99\nfilename,official_elevation_gain_m\n";
+        let error = GroundTruthRegistry::from_csv_str(newer, Path::new("newer.csv")).err().unwrap();
+        assert!(matches!(error, TrackError::Parse { line: Some(1), .. }), "{:?}", error);
+        assert!(error.to_string().starts_with("newer.csv:1: "), "{}", error);
+
+        let missing = GroundTruthRegistry::from_path(Path::new("no_such_registry.csv")).err().unwrap();
+        assert_eq!(missing.kind(), crate::error::ErrorKind::Io);
+        assert_eq!(missing.file(), Some(Path::new("no_such_registry.csv")));
+    }
+}
@@ -4,8 +4,8 @@ use csv::Writer;
 use serde::Serialize;
 use rayon::prelude::*;
 use std::sync::Arc;
-use biquad::{Biquad, DirectForm1, ToHertz, Coefficients, Q_BUTTERWORTH_F64};
 use crate::custom_smoother::{ElevationData, SmoothingVariant};
+use crate::elevation_smoother::butterworth_filter;
 
 #[derive(Debug, Serialize, Clone)]
 pub struct HybridResult {
@@ -116,82 +116,40 @@ pub fn run_hybrid_analysis(gpx_folder: &str) -> Result<(), Box<dyn std::error::E
 }
 
 fn load_gpx_data(gpx_folder: &str) -> Result<(HashMap<String, GpxFileData>, Vec<String>), Box<dyn std::error::Error>> {
-    use std::fs::File;
-    use std::io::BufReader;
-    use gpx::read;
-    use geo::{HaversineDistance, point};
     use walkdir::WalkDir;
-    
+    use crate::track_loader::{load_track, is_supported_track_file};
+
     let mut gpx_data = HashMap::new();
     let mut valid_files = Vec::new();
-    
-    let official_data = crate::load_official_elevation_data()?;
-    
+
+    let official_data = crate::load_ground_truth_registry()?;
+
     for entry in WalkDir::new(gpx_folder) {
         let entry = entry?;
-        if entry.file_type().is_file() {
-            if let Some(extension) = entry.path().extension() {
-                if extension.to_str().unwrap_or("").to_lowercase() == "gpx" {
-                    let path = entry.path();
-                    let filename = path.file_name()
-                        .and_then(|n| n.to_str())
-                        .unwrap_or("")
-                        .to_string();
-                    
-                    match File::open(path) {
-                        Ok(file) => {
-                            let reader = BufReader::new(file);
-                            match read(reader) {
-                                Ok(gpx) => {
-                                    let mut coords: Vec<(f64, f64, f64)> = vec![];
-                                    
-                                    for track in gpx.tracks {
-                                        for segment in track.segments {
-                                            for pt in segment.points {
-                                                if let Some(ele) = pt.elevation {
-                                                    coords.push((pt.point().y(), pt.point().x(), ele));
-                                                }
-                                            }
-                                        }
-                                    }
-                                    
-                                    if !coords.is_empty() {
-                                        let mut distances = vec![0.0];
-                                        for i in 1..coords.len() {
-                                            let a = point!(x: coords[i-1].1, y: coords[i-1].0);
-                                            let b = point!(x: coords[i].1, y: coords[i].0);
-                                            let dist = a.haversine_distance(&b);
-                                            distances.push(distances[i-1] + dist);
-                                        }
-                                        
-                                        let elevations: Vec<f64> = coords.iter().map(|c| c.2).collect();
-                                        
-                                        let official_gain = official_data
-                                            .get(&filename.to_lowercase())
-                                            .copied()
-                                            .unwrap_or(0);
-                                        
-                                        let file_data = GpxFileData {
-                                            filename: filename.clone(),
-                                            elevations,
-                                            distances,
-                                            official_gain,
-                                        };
-                                        
-                                        gpx_data.insert(filename.clone(), file_data);
-                                        valid_files.push(filename);
-                                    }
-                                },
-                                Err(_) => continue,
-                            }
-                        },
-                        Err(_) => continue,
-                    }
-                }
-            }
+        if entry.file_type().is_file() && is_supported_track_file(entry.path()) {
+            // GPX and FIT both land in the same point model; unreadable files are skipped
+            let track = match load_track(entry.path()) {
+                Ok(track) => track,
+                Err(_) => continue,
+            };
+            let filename = track.filename.clone();
+
+            let official_gain = official_data.official_gain(entry.path(), &track.coordinates());
+
+            let file_data = GpxFileData {
+                elevations: track.elevations(),
+                distances: track.cumulative_distances,
+                filename: filename.clone(),
+                official_gain,
+            };
+
+            gpx_data.insert(filename.clone(), file_data);
+            valid_files.push(filename);
         }
     }
-    
+
+    official_data.print_match_report();
+
     Ok((gpx_data, valid_files))
 }
 
@@ -285,7 +243,7 @@ fn process_hybrid_single(
     let (raw_gain, raw_loss) = calculate_raw_gain_loss(&file_data.elevations);
     
     // Step 1: Apply Butterworth filtering
-    let butterworth_elevations = apply_butterworth_filter(
+    let butterworth_elevations = butterworth_filter(
         &file_data.elevations,
         &file_data.distances,
         butterworth_interval
@@ -325,128 +283,6 @@ fn process_hybrid_single(
     }
 }
 
-fn apply_butterworth_filter(
-    elevations: &[f64],
-    distances: &[f64],
-    interval_m: f32
-) -> Vec<f64> {
-    // Adaptive resampling for Butterworth
-    let sample_spacing = (interval_m / 3.0).max(0.5) as f64;
-    
-    // Resample elevations to uniform spacing
-    let resampled_elevations = resample_to_uniform_spacing(
-        elevations,
-        distances,
-        sample_spacing
-    );
-    
-    if resampled_elevations.len() < 10 {
-        return elevations.to_vec();
-    }
-    
-    // Calculate cutoff frequency
-    let wavelength_to_keep = interval_m as f64 * 2.0;
-    let cutoff_cycles_per_meter = 1.0 / wavelength_to_keep;
-    let normalized_cutoff = cutoff_cycles_per_meter * sample_spacing;
-    let sample_rate_hz = 1.0 / sample_spacing;
-    let cutoff_hz = normalized_cutoff * sample_rate_hz;
-    let nyquist = sample_rate_hz / 2.0;
-    let cutoff_hz = cutoff_hz.clamp(0.01 * nyquist, 0.45 * nyquist);
-    
-    // Apply Butterworth filter
-    let coeffs = match Coefficients::<f64>::from_params(
-        biquad::Type::LowPass,
-        sample_rate_hz.hz(),
-        cutoff_hz.hz(),
-        Q_BUTTERWORTH_F64
-    ) {
-        Ok(c) => c,
-        Err(_) => return elevations.to_vec(),
-    };
-    
-    // Forward pass
-    let mut df_forward = DirectForm1::<f64>::new(coeffs);
-    let mut elev_fwd: Vec<f64> = resampled_elevations
-        .iter()
-        .map(|&x| df_forward.run(x))
-        .collect();
-    
-    // Backward pass
-    elev_fwd.reverse();
-    let mut df_backward = DirectForm1::<f64>::new(coeffs);
-    let mut elev_smooth: Vec<f64> = elev_fwd
-        .iter()
-        .map(|&x| df_backward.run(x))
-        .collect();
-    elev_smooth.reverse();
-    
-    // Interpolate back to original distances
-    interpolate_to_original_distances(&elev_smooth, sample_spacing, distances)
-}
-
-fn resample_to_uniform_spacing(
-    elevations: &[f64],
-    distances: &[f64],
-    spacing_m: f64
-) -> Vec<f64> {
-    if elevations.is_empty() || distances.is_empty() {
-        return vec![];
-    }
-    
-    let total_distance = distances.last().unwrap();
-    let num_samples = (total_distance / spacing_m).ceil() as usize + 1;
-    let mut resampled = Vec::with_capacity(num_samples);
-    
-    for i in 0..num_samples {
-        let target_distance = i as f64 * spacing_m;
-        
-        let idx = match distances.binary_search_by(|d| {
-            d.partial_cmp(&target_distance).unwrap()
-        }) {
-            Ok(i) => i,
-            Err(i) => i.saturating_sub(1),
-        };
-        
-        if idx >= distances.len() - 1 {
-            resampled.push(elevations.last().unwrap().clone());
-        } else {
-            let d0 = distances[idx];
-            let d1 = distances[idx + 1];
-            let e0 = elevations[idx];
-            let e1 = elevations[idx + 1];
-            
-            let t = (target_distance - d0) / (d1 - d0);
-            let elevation = e0 + t * (e1 - e0);
-            resampled.push(elevation);
-        }
-    }
-    
-    resampled
-}
-
-fn interpolate_to_original_distances(
-    smooth_elevations: &[f64],
-    sample_spacing: f64,
-    original_distances: &[f64]
-) -> Vec<f64> {
-    let mut result = Vec::with_capacity(original_distances.len());
-    
-    for &dist in original_distances {
-        let sample_idx = dist / sample_spacing;
-        let idx = sample_idx.floor() as usize;
-        
-        if idx >= smooth_elevations.len() - 1 {
-            result.push(smooth_elevations.last().unwrap().clone());
-        } else {
-            let t = sample_idx - idx as f64;
-            let interpolated = smooth_elevations[idx] * (1.0 - t) + smooth_elevations[idx + 1] * t;
-            result.push(interpolated);
-        }
-    }
-    
-    result
-}
-
 fn calculate_raw_gain_loss(elevations: &[f64]) -> (u32, u32) {
     let mut gain = 0.0;
     let mut loss = 0.0;
@@ -356,3 +356,47 @@ fn print_analysis_summary(result: &InclineAnalysisResult) {
     
     println!("=== ANALYSIS COMPLETE ===\n");
 }
+
+/// Write every incline and decline segment to a CSV file, one row per segment
+pub fn write_segments_csv(
+    result: &InclineAnalysisResult,
+    output_path: &std::path::Path
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut wtr = csv::Writer::from_path(output_path)?;
+    
+    wtr.write_record([
+        "Type", "Start_km", "End_km", "Length_km", "Elevation_Change_m",
+        "Average_Grade_%", "Max_Grade_%", "Start_Elevation_m", "End_Elevation_m",
+    ])?;
+    
+    for s in &result.all_inclines {
+        wtr.write_record([
+            "incline".to_string(),
+            format!("{:.3}", s.start_distance_km),
+            format!("{:.3}", s.end_distance_km),
+            format!("{:.3}", s.length_km),
+            format!("{:.1}", s.elevation_gain_m),
+            format!("{:.1}", s.average_grade_percent),
+            format!("{:.1}", s.max_grade_percent),
+            format!("{:.1}", s.start_elevation_m),
+            format!("{:.1}", s.end_elevation_m),
+        ])?;
+    }
+    
+    for s in &result.all_declines {
+        wtr.write_record([
+            "decline".to_string(),
+            format!("{:.3}", s.start_distance_km),
+            format!("{:.3}", s.end_distance_km),
+            format!("{:.3}", s.length_km),
+            format!("{:.1}", -s.elevation_loss_m),
+            format!("{:.1}", s.average_grade_percent),
+            format!("{:.1}", s.max_grade_percent),
+            format!("{:.1}", s.start_elevation_m),
+            format!("{:.1}", s.end_elevation_m),
+        ])?;
+    }
+    
+    wtr.flush()?;
+    Ok(())
+}
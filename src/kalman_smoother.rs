@@ -0,0 +1,254 @@
+/// KALMAN SMOOTHER - State-space elevation smoothing over distance
+/// State is [elevation, grade] indexed by cumulative distance, with grade following a
+/// random walk. A forward Kalman filter is followed by a Rauch-Tung-Striebel backward pass,
+/// so every point is estimated from the whole track instead of a hand-picked window.
+/// Measurement and process noise are estimated from the track itself.
+type Vector2 = [f64; 2];
+type Matrix2 = [[f64; 2]; 2];
+
+const MIN_MEASUREMENT_VARIANCE: f64 = 0.05 * 0.05;
+// Process noise candidates (log-spaced); the one with the highest likelihood wins
+const PROCESS_NOISE_GRID: [f64; 13] = [1e-9, 3e-9, 1e-8, 3e-8, 1e-7, 3e-7, 1e-6, 3e-6, 1e-5, 3e-5, 1e-4, 3e-4, 1e-3];
+// Prior grade uncertainty at the first point (10% standard deviation)
+const INITIAL_GRADE_VARIANCE: f64 = 0.01;
+
+#[derive(Debug, Clone, Copy)]
+pub struct KalmanNoise {
+    pub measurement_variance: f64,  // m², GPS elevation noise
+    pub process_noise: f64,         // 1/m, grade random-walk spectral density
+}
+
+impl KalmanNoise {
+    /// Estimate both noise levels from the data:
+    /// - measurement: robust spread of each point around the line through its neighbours
+    /// - process: the grid value maximising the filter's innovation likelihood
+    pub fn estimate(elevations: &[f64], distances: &[f64]) -> Self {
+        let measurement_variance = estimate_measurement_variance(elevations, distances);
+        let process_noise = PROCESS_NOISE_GRID.iter()
+            .map(|&process_noise| {
+                let noise = KalmanNoise { measurement_variance, process_noise };
+                (process_noise, forward_filter(elevations, distances, noise).log_likelihood)
+            })
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(q, _)| q)
+            .unwrap_or(PROCESS_NOISE_GRID[6]);
+        KalmanNoise {
+            measurement_variance,
+            process_noise,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct KalmanResult {
+    pub elevations: Vec<f64>,
+    pub grades: Vec<f64>,  // m/m
+    pub noise: KalmanNoise,
+}
+
+/// Forward Kalman filter + RTS backward smoother; `noise` defaults to KalmanNoise::estimate
+pub fn kalman_rts_smooth(elevations: &[f64], distances: &[f64], noise: Option<KalmanNoise>) -> KalmanResult {
+    let noise = noise.unwrap_or_else(|| KalmanNoise::estimate(elevations, distances));
+    let forward = forward_filter(elevations, distances, noise);
+    let n = forward.filtered_states.len();
+
+    if n == 0 {
+        return KalmanResult { elevations: vec![], grades: vec![], noise };
+    }
+
+    // BACKWARD PASS (Rauch-Tung-Striebel)
+    let mut smoothed_states = forward.filtered_states.clone();
+    for i in (0..n - 1).rev() {
+        let step = (distances[i + 1] - distances[i]).max(0.0);
+        let f = transition(step);
+        let Some(predicted_inverse) = inverse(&forward.predicted_covariances[i + 1]) else {
+            continue;
+        };
+        let c = mat_mul(&mat_mul(&forward.filtered_covariances[i], &transpose(&f)), &predicted_inverse);
+        let correction = [
+            smoothed_states[i + 1][0] - forward.predicted_states[i + 1][0],
+            smoothed_states[i + 1][1] - forward.predicted_states[i + 1][1],
+        ];
+        let adjustment = mat_vec(&c, &correction);
+        smoothed_states[i] = [
+            forward.filtered_states[i][0] + adjustment[0],
+            forward.filtered_states[i][1] + adjustment[1],
+        ];
+    }
+
+    KalmanResult {
+        elevations: smoothed_states.iter().map(|s| s[0]).collect(),
+        grades: smoothed_states.iter().map(|s| s[1]).collect(),
+        noise,
+    }
+}
+
+struct ForwardPass {
+    predicted_states: Vec<Vector2>,
+    predicted_covariances: Vec<Matrix2>,
+    filtered_states: Vec<Vector2>,
+    filtered_covariances: Vec<Matrix2>,
+    log_likelihood: f64,
+}
+
+fn forward_filter(elevations: &[f64], distances: &[f64], noise: KalmanNoise) -> ForwardPass {
+    let n = elevations.len().min(distances.len());
+    let r = noise.measurement_variance;
+    let q = noise.process_noise;
+
+    let mut pass = ForwardPass {
+        predicted_states: Vec::with_capacity(n),
+        predicted_covariances: Vec::with_capacity(n),
+        filtered_states: Vec::with_capacity(n),
+        filtered_covariances: Vec::with_capacity(n),
+        log_likelihood: 0.0,
+    };
+
+    if n == 0 {
+        return pass;
+    }
+
+    let mut x: Vector2 = [elevations[0], 0.0];
+    let mut p: Matrix2 = [[r, 0.0], [0.0, INITIAL_GRADE_VARIANCE]];
+
+    for i in 0..n {
+        if i > 0 {
+            let step = (distances[i] - distances[i - 1]).max(0.0);
+            let f = transition(step);
+            x = mat_vec(&f, &x);
+            p = mat_add(&mat_mul(&mat_mul(&f, &p), &transpose(&f)), &process_covariance(step, q));
+        }
+        pass.predicted_states.push(x);
+        pass.predicted_covariances.push(p);
+
+        // Update with the elevation measurement (H = [1, 0])
+        let innovation = elevations[i] - x[0];
+        let innovation_variance = p[0][0] + r;
+        pass.log_likelihood -= 0.5 * (innovation_variance.ln() + innovation * innovation / innovation_variance);
+        let gain = [p[0][0] / innovation_variance, p[1][0] / innovation_variance];
+        x = [x[0] + gain[0] * innovation, x[1] + gain[1] * innovation];
+        p = [
+            [(1.0 - gain[0]) * p[0][0], (1.0 - gain[0]) * p[0][1]],
+            [p[1][0] - gain[1] * p[0][0], p[1][1] - gain[1] * p[0][1]],
+        ];
+        pass.filtered_states.push(x);
+        pass.filtered_covariances.push(p);
+    }
+
+    pass
+}
+
+fn estimate_measurement_variance(elevations: &[f64], distances: &[f64]) -> f64 {
+    // Residual from linear interpolation between neighbours, normalised so white noise
+    // of variance r gives residuals of variance r
+    // Same length guard as forward_filter
+    let n = elevations.len().min(distances.len());
+    let mut residuals: Vec<f64> = Vec::new();
+    for i in 1..n.saturating_sub(1) {
+        let span = distances[i + 1] - distances[i - 1];
+        if span <= 0.0 {
+            continue;
+        }
+        let a = (distances[i + 1] - distances[i]) / span;
+        let b = 1.0 - a;
+        let interpolated = a * elevations[i - 1] + b * elevations[i + 1];
+        residuals.push(((elevations[i] - interpolated) / (1.0 + a * a + b * b).sqrt()).abs());
+    }
+
+    if residuals.is_empty() {
+        return MIN_MEASUREMENT_VARIANCE;
+    }
+
+    residuals.sort_by(|a, b| a.total_cmp(b));
+    // Median absolute deviation scaled to a Gaussian standard deviation
+    let sigma = 1.4826 * residuals[residuals.len() / 2];
+    (sigma * sigma).max(MIN_MEASUREMENT_VARIANCE)
+}
+
+fn transition(step: f64) -> Matrix2 {
+    [[1.0, step], [0.0, 1.0]]
+}
+
+/// Covariance added over `step` meters by a grade random walk of density q
+fn process_covariance(step: f64, q: f64) -> Matrix2 {
+    [
+        [q * step.powi(3) / 3.0, q * step.powi(2) / 2.0],
+        [q * step.powi(2) / 2.0, q * step],
+    ]
+}
+
+fn mat_mul(a: &Matrix2, b: &Matrix2) -> Matrix2 {
+    let mut result = [[0.0; 2]; 2];
+    for (i, row) in result.iter_mut().enumerate() {
+        for (j, cell) in row.iter_mut().enumerate() {
+            *cell = a[i][0] * b[0][j] + a[i][1] * b[1][j];
+        }
+    }
+    result
+}
+
+fn mat_add(a: &Matrix2, b: &Matrix2) -> Matrix2 {
+    [
+        [a[0][0] + b[0][0], a[0][1] + b[0][1]],
+        [a[1][0] + b[1][0], a[1][1] + b[1][1]],
+    ]
+}
+
+fn mat_vec(a: &Matrix2, v: &Vector2) -> Vector2 {
+    [a[0][0] * v[0] + a[0][1] * v[1], a[1][0] * v[0] + a[1][1] * v[1]]
+}
+
+fn transpose(a: &Matrix2) -> Matrix2 {
+    [[a[0][0], a[1][0]], [a[0][1], a[1][1]]]
+}
+
+fn inverse(a: &Matrix2) -> Option<Matrix2> {
+    let determinant = a[0][0] * a[1][1] - a[0][1] * a[1][0];
+    if determinant.abs() < 1e-18 {
+        return None;
+    }
+    Some([
+        [a[1][1] / determinant, -a[0][1] / determinant],
+        [-a[1][0] / determinant, a[0][0] / determinant],
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rts_smoother_recovers_profile_and_noise_level() {
+        let distances: Vec<f64> = (0..1000).map(|i| i as f64 * 3.0).collect();
+        let truth: Vec<f64> = distances.iter().map(|d| 200.0 + 25.0 * (d / 300.0).sin()).collect();
+        // Deterministic uniform noise on [-sqrt(3), sqrt(3)] (variance 1)
+        let mut seed: u64 = 42;
+        let measured: Vec<f64> = truth.iter()
+            .map(|t| {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                t + ((seed >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0) * 3f64.sqrt()
+            })
+            .collect();
+
+        let result = kalman_rts_smooth(&measured, &distances, None);
+
+        let rmse = |values: &[f64]| {
+            (values.iter().zip(&truth).map(|(v, t)| (v - t).powi(2)).sum::<f64>() / truth.len() as f64).sqrt()
+        };
+        assert!(rmse(&result.elevations) < 0.5 * rmse(&measured));
+        assert!(result.noise.measurement_variance > 0.3 && result.noise.measurement_variance < 3.0);
+        assert_eq!(result.grades.len(), distances.len());
+    }
+
+    #[test]
+    fn test_noise_estimate_survives_nan_and_short_distances() {
+        let elevations = vec![100.0, 101.0, f64::NAN, 103.0, 104.0, 105.0];
+        let noise = KalmanNoise::estimate(&elevations, &[0.0, 5.0, 10.0, 15.0, 20.0, 25.0]);
+        assert!(noise.measurement_variance >= MIN_MEASUREMENT_VARIANCE);
+        // Fewer distances than elevations: only the common prefix is used
+        let noise = KalmanNoise::estimate(&elevations[..2], &[0.0]);
+        assert_eq!(noise.measurement_variance, MIN_MEASUREMENT_VARIANCE);
+        let noise = KalmanNoise::estimate(&[100.0, 101.0, 103.0, 102.0], &[0.0, 5.0, 10.0]);
+        assert!(noise.measurement_variance.is_finite());
+    }
+}
@@ -0,0 +1,110 @@
+/// GPX ELEVATION SMOOTHER - Library crate
+/// Smoothing engine, gain/loss calculation, incline analysis and the analysis
+/// suite, usable from other Rust projects. The binary in main.rs is a thin menu
+/// on top of this crate.
+use std::path::Path;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+pub mod custom_smoother;
+pub mod improved_scoring;
+pub mod outlier_analysis;
+pub mod simplified_analysis;
+pub mod gpx_output_analysis;
+pub mod assymetric_analysis;
+pub mod hybrid_analysis;
+pub mod incline_analyzer;
+pub mod gpx_processor;
+pub mod distbased_elevation_processor;
+pub mod two_pass_analysis;
+pub mod precision_optimization_analysis;
+pub mod corrected_elevation_analysis;
+pub mod focused_symmetric_analysis;
+pub mod gpx_preprocessor;
+pub mod single_interval_analysis;
+pub mod fine_grained_analysis;
+pub mod track_loader;
+pub mod fit_loader;
+pub mod tcx;
+pub mod dem;
+pub mod geotiff_dem;
+pub mod dem_fusion;
+pub mod kalman_smoother;
+pub mod barometric;
+pub mod benchmark;
+pub mod parameter_optimizer;
+pub mod search_strategy;
+pub mod cross_validation;
+pub mod ground_truth;
+pub mod consensus;
+pub mod alignment;
+pub mod effort;
+pub mod pause_detection;
+pub mod gps_jumps;
+pub mod gpx_rewrite;
+pub mod error;
+pub mod ultimate_gpx_processor;
+pub mod elevation_smoother;
+
+pub use custom_smoother::{ElevationData, SmoothingVariant};
+pub use error::{error_kind, ErrorKind, TrackError};
+pub use distbased_elevation_processor::{DistBasedElevationProcessor, ProcessingStats};
+pub use incline_analyzer::{
+    analyze_inclines, analyze_inclines_default,
+    InclineAnalysisConfig, InclineAnalysisResult, InclineSegment, DeclineSegment,
+};
+pub use track_loader::{
+    load_track, load_gpx_track, read_gpx_file, is_supported_track_file, calculate_cumulative_distances,
+    calculate_cumulative_distances_with_gaps, recording_gaps, gap_runs, calculate_gain_loss, AltitudeSource, DistanceSource, LoadedTrack, TrackPoint,
+};
+pub use tcx::{read_tcx, write_tcx, TcxKind, TcxTrack};
+pub use dem::{open_dem_directory, DemCorrection, DemMode, ElevationLookup, ElevationSource, SrtmTileSet};
+pub use geotiff_dem::GeoTiffDemSet;
+pub use dem_fusion::{fuse_gps_with_dem, FusionResult, StructureKind, StructureSegment};
+pub use ground_truth::{Confidence, GroundTruthRecord, GroundTruthRegistry};
+
+static OFFICIAL_DATA_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// Point every analysis at a specific official elevation CSV instead of the
+/// default `src/official_elevation_data.csv` / `official_elevation_data.csv` lookup.
+/// Only the first call takes effect.
+pub fn set_official_data_path(path: impl Into<PathBuf>) -> Result<(), TrackError> {
+    let path = path.into();
+    if !path.exists() {
+        return Err(TrackError::GroundTruthMissing { searched: vec![path] });
+    }
+    OFFICIAL_DATA_PATH.set(path).map_err(|file| TrackError::GroundTruthPathAlreadySet {
+        file,
+        current: OFFICIAL_DATA_PATH.get().cloned().unwrap_or_default(),
+    })
+}
+
+/// Load the ground-truth registry from the configured path, else `src/official_elevation_data.csv`,
+/// else `./official_elevation_data.csv`. Registry problems are printed, not fatal.
+pub fn load_ground_truth_registry() -> Result<GroundTruthRegistry, Box<dyn std::error::Error>> {
+    let csv_paths: Vec<PathBuf> = match OFFICIAL_DATA_PATH.get() {
+        Some(path) => vec![path.clone()],
+        None => vec![
+            PathBuf::from("src/official_elevation_data.csv"),
+            PathBuf::from("official_elevation_data.csv"),
+        ],
+    };
+
+    for csv_path in &csv_paths {
+        if Path::new(csv_path).exists() {
+            println!("📄 Loading official elevation data from: {}", csv_path.display());
+            let registry = GroundTruthRegistry::from_path(csv_path)?;
+            GroundTruthRegistry::print_issues(&registry.issues);
+            println!("✅ Loaded {} official elevation records", registry.len());
+            return Ok(registry);
+        }
+    }
+
+    Err(TrackError::GroundTruthMissing { searched: csv_paths }.into())
+}
+
+/// Official gain by lowercase filename, from the ground-truth registry
+pub fn load_official_elevation_data() -> Result<HashMap<String, u32>, Box<dyn std::error::Error>> {
+    Ok(load_ground_truth_registry()?.gain_map())
+}
@@ -1,565 +1,643 @@
-use std::{fs::File, path::Path};
-use gpx::read;
-use geo::HaversineDistance;
-use geo::point;
-use std::io::BufReader;
-use csv::{Writer, Reader};
-use serde::{Serialize, Deserialize};
-use rayon::prelude::*;
-use std::collections::HashMap;
+/// GPX ELEVATION ANALYSIS SUITE - command-line front end
+/// Thin wrapper over the library: every analysis is a subcommand taking explicit
+/// paths, so the tool can be scripted and run on any platform.
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::Duration;
+use clap::{Args, Parser, Subcommand};
+use rust_gpx_smoother::{
+    improved_scoring, outlier_analysis, simplified_analysis, gpx_output_analysis,
+    assymetric_analysis, hybrid_analysis, gpx_processor, two_pass_analysis,
+    precision_optimization_analysis, corrected_elevation_analysis,
+    focused_symmetric_analysis, gpx_preprocessor, single_interval_analysis,
+    fine_grained_analysis, benchmark, incline_analyzer, parameter_optimizer,
+    cross_validation, ground_truth, consensus, alignment, effort, ultimate_gpx_processor,
+};
+use rust_gpx_smoother::{load_track, open_dem_directory, DemCorrection, DemMode, DistanceSource, ElevationLookup, InclineAnalysisConfig, SmoothingVariant};
+use rust_gpx_smoother::search_strategy::{SearchBudget, SearchStrategy};
+use rust_gpx_smoother::cross_validation::FoldScheme;
+use rust_gpx_smoother::elevation_smoother::{smoother_from_name_with_dem, smoother_uses_dem, ElevationSmoother};
+use rust_gpx_smoother::barometric::BarometricSmoother;
+use rust_gpx_smoother::gps_jumps::{JumpDetectionConfig, JumpRepair};
+use rust_gpx_smoother::gpx_rewrite::GpxWriteOptions;
+use rust_gpx_smoother::error::{error_kind, ErrorKind};
 
-mod custom_smoother;
-mod improved_scoring;
-mod outlier_analysis;
-mod simplified_analysis;
-mod gpx_output_analysis;
-mod assymetric_analysis;
-mod hybrid_analysis;
-mod incline_analyzer;
-mod gpx_processor;
-mod distbased_elevation_processor;
-mod two_pass_analysis;
-mod precision_optimization_analysis;
-mod corrected_elevation_analysis;
-mod focused_symmetric_analysis;  // NEW: Add the focused symmetric analysis
-mod gpx_preprocessor;          // NEW: Add the GPX preprocessor module
-mod single_interval_analysis;    // NEW: Add the single interval analysis
+// Machine-readable exit codes
+const EXIT_SUCCESS: u8 = 0;
+const EXIT_PROCESSING_FAILED: u8 = 1;
+// 2 is reserved for invalid arguments (reported by clap)
+const EXIT_INPUT_NOT_FOUND: u8 = 3;
+const EXIT_OFFICIAL_DATA_NOT_FOUND: u8 = 4;
+const EXIT_NO_FILES_PROCESSED: u8 = 5;
 
-use custom_smoother::{ElevationData, SmoothingVariant};
+const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0  success
+  1  processing failed
+  2  invalid arguments
+  3  input path not found
+  4  official elevation data CSV not found
+  5  no files could be processed";
 
-#[derive(Debug, Deserialize)]
-struct OfficialElevationRecord {
-    filename: String,
-    official_elevation_gain_m: u32,
-    #[serde(default)]
-    source: String,
-    #[serde(default)]
-    notes: String,
+#[derive(Parser)]
+#[command(name = "rust-gpx-smoother", version, about = "🏔️  GPX elevation smoothing and analysis suite", after_help = EXIT_CODES_HELP)]
+struct Cli {
+    /// Official elevation data CSV (default: src/official_elevation_data.csv or ./official_elevation_data.csv)
+    #[arg(long, global = true, value_name = "CSV")]
+    official_data: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
 }
 
-#[derive(Debug, Serialize, Clone)]
-struct GpxAnalysis {
-    filename: String,
-    raw_distance_km: f32,
-    raw_elevation_gain_m: u32,
-    average_time_interval_seconds: u32,
-    custom_original_elevation_gain_m: u32,
-    custom_distbased_10m_elevation_gain_m: u32,
-    official_elevation_gain_m: u32,
-    original_accuracy_percent: f32,
-    distbased_10m_accuracy_percent: f32,
+#[derive(Subcommand)]
+enum Command {
+    /// Smooth every GPX file and save the processed tracks (default: spike filter + directional deadzone)
+    Process {
+        #[command(flatten)]
+        input: InputArgs,
+        /// Folder for processed files and the results CSV (TCX input is written back as TCX)
+        #[arg(short, long)]
+        output: String,
+        #[command(flatten)]
+        smoother: SmootherArgs,
+        #[command(flatten)]
+        distance: DistanceArgs,
+        #[command(flatten)]
+        dem: DemArgs,
+        #[command(flatten)]
+        stops: StopArgs,
+        #[command(flatten)]
+        jumps: JumpArgs,
+        /// Keep each replaced GPX <ele> value in the point's <extensions>
+        #[arg(long)]
+        keep_original_ele: bool,
+        /// Smooth barometric-altitude tracks with --smoother too, instead of the lighter barometric path
+        #[arg(long)]
+        ignore_barometric: bool,
+    },
+    /// Clean and repair GPX files, writing cleaned_<name>.gpx copies
+    Preprocess {
+        #[command(flatten)]
+        input: InputArgs,
+        /// Folder for cleaned files (default: <input>/Preprocessed)
+        #[arg(short, long)]
+        output: Option<String>,
+        #[command(flatten)]
+        dem: DemArgs,
+    },
+    /// Research analyses over a folder of GPX/FIT/TCX files (CSV reports are written into the folder)
+    Analyze {
+        #[command(subcommand)]
+        analysis: Analysis,
+    },
+    /// Find the longest and steepest climbs and descents in a single GPX, FIT or TCX file
+    Inclines {
+        #[command(flatten)]
+        input: InputArgs,
+        /// Smoothing variant used before segmenting
+        #[arg(long, default_value = "distbased")]
+        variant: SmoothingVariant,
+        /// Optional CSV with every incline/decline segment
+        #[arg(short, long)]
+        output: Option<String>,
+        #[command(flatten)]
+        distance: DistanceArgs,
+    },
+    /// Pace, moving time, grade-adjusted pace and normalized effort of a single timed recording,
+    /// overall and per incline/decline segment
+    Effort {
+        #[command(flatten)]
+        input: InputArgs,
+        /// Smoothing variant whose gradient drives the grade adjustment
+        #[arg(long, default_value = "distbased")]
+        variant: SmoothingVariant,
+        /// Speed below which a step counts as stopped (m/s)
+        #[arg(long, default_value_t = 0.5)]
+        moving_speed: f64,
+        /// Optional CSV with the activity and every segment
+        #[arg(short, long)]
+        output: Option<String>,
+        #[command(flatten)]
+        distance: DistanceArgs,
+    },
+    /// Validate the official elevation data registry against a folder of tracks
+    GroundTruth {
+        #[command(flatten)]
+        input: InputArgs,
+        /// Store the content hash and fingerprint of every track matched by filename and
+        /// rewrite the registry in the current format
+        #[arg(long)]
+        fill_hashes: bool,
+    },
+    /// Score a smoother against the official elevation data
+    Benchmark {
+        #[command(flatten)]
+        input: InputArgs,
+        /// Folder for the benchmark CSV (default: the input folder)
+        #[arg(short, long)]
+        output: Option<String>,
+        #[command(flatten)]
+        smoother: SmootherArgs,
+        #[command(flatten)]
+        distance: DistanceArgs,
+        /// Directory of GeoTIFF rasters and/or SRTM .hgt tiles sampled by the dem-fusion and
+        /// barometric smoothers
+        #[arg(long)]
+        dem_dir: Option<String>,
+        /// Smooth barometric-altitude tracks with --smoother too, instead of the lighter barometric path
+        #[arg(long)]
+        ignore_barometric: bool,
+        #[command(flatten)]
+        stops: StopArgs,
+    },
+    /// Build a consensus elevation profile from a folder of recordings of one course
+    Consensus {
+        #[command(flatten)]
+        input: InputArgs,
+        /// Folder for the consensus GPX and CSVs (default: the input folder)
+        #[arg(short, long)]
+        output: Option<String>,
+        #[command(flatten)]
+        smoother: SmootherArgs,
+        /// Spacing of the consensus profile in meters
+        #[arg(long, default_value_t = 10.0, value_parser = positive_interval)]
+        step: f64,
+        /// Seed for the bootstrap of the gain uncertainty
+        #[arg(long, default_value_t = 42)]
+        seed: u64,
+    },
+    /// Snap a recording (or a folder of recordings) onto a course file and compare elevation
+    /// at identical course positions
+    Align {
+        #[command(flatten)]
+        input: InputArgs,
+        /// Published course GPX/FIT/TCX file
+        #[arg(long)]
+        course: String,
+        /// Folder for the per-point CSVs and course-elevation GPX files (default: next to the input)
+        #[arg(short, long)]
+        output: Option<String>,
+        #[command(flatten)]
+        smoother: SmootherArgs,
+    },
 }
 
-// Separate struct for fine-grained analysis
-#[derive(Debug, Clone)]
-struct FineGrainedResult {
-    filename: String,
-    raw_distance_km: f32,
-    raw_elevation_gain_m: u32,
-    official_elevation_gain_m: u32,
-    interval_gains: Vec<(f32, u32)>, // (interval_m, gain_m)
-    interval_accuracies: Vec<(f32, f32)>, // (interval_m, accuracy_percent)
+#[derive(Subcommand)]
+enum Analysis {
+    /// 1.9m symmetric analysis with file-by-file details and GPX repair
+    SingleInterval {
+        #[command(flatten)]
+        input: InputArgs,
+        /// Resampling interval in meters
+        #[arg(long, default_value_t = 1.9, value_parser = positive_interval)]
+        interval: f64,
+    },
+    /// High-resolution symmetric interval optimization (0.5m to 2.5m)
+    FocusedSymmetric(InputArgs),
+    /// DistBased processing swept from 0.05m to 8m intervals
+    FineGrained(InputArgs),
+    /// Improved scoring analysis
+    ImprovedScoring(InputArgs),
+    /// Outlier detection analysis
+    Outlier(InputArgs),
+    /// Gain/loss balance analysis
+    Simplified(InputArgs),
+    /// Fine-tuned asymmetric directional deadzone optimization
+    Asymmetric(InputArgs),
+    /// Broad directional deadzone parameter search
+    DirectionalDeadzone(InputArgs),
+    /// Butterworth + distance-based hybrid analysis
+    Hybrid(InputArgs),
+    /// Two-pass and Savitzky-Golay comparison
+    TwoPass(InputArgs),
+    /// Precision optimization analysis
+    Precision(InputArgs),
+    /// Corrected elevation analysis with symmetric deadband
+    Corrected(InputArgs),
+    /// Tune spike/deadzone/gradient-cap parameters (or --smoother's) on the real tracks
+    ParameterOptimizer {
+        #[command(flatten)]
+        input: InputArgs,
+        /// Search strategy: random, coordinate-descent, bayesian or cma-es (default: exhaustive grid)
+        #[arg(long)]
+        strategy: Option<SearchStrategy>,
+        /// Tune this smoother's parameters instead (symmetric-fixed, butterworth, savitzky-golay,
+        /// two-pass, barometric, spike-deadzone); needs --strategy
+        #[arg(long, requires = "strategy")]
+        smoother: Option<String>,
+        /// Maximum number of objective evaluations
+        #[arg(long, default_value_t = 200, value_parser = positive_count)]
+        max_evaluations: usize,
+        /// Wall-clock limit in seconds
+        #[arg(long, value_parser = positive_interval)]
+        max_seconds: Option<f64>,
+        /// Random seed; the same seed repeats the same search
+        #[arg(long, default_value_t = 42)]
+        seed: u64,
+    },
+    /// Terrain-stratified k-fold / leave-one-out validation of tuned parameters
+    CrossValidate {
+        #[command(flatten)]
+        input: InputArgs,
+        /// Smoother to tune and validate (default: terrain-adaptive spike/deadzone parameters);
+        /// smoothers without parameters are evaluated as they are
+        #[arg(long)]
+        smoother: Option<String>,
+        /// Number of folds
+        #[arg(long, default_value_t = 5, value_parser = fold_count)]
+        folds: usize,
+        /// Hold out one file at a time instead of k folds
+        #[arg(long)]
+        leave_one_out: bool,
+        /// Search strategy used to tune each fold: random, coordinate-descent, bayesian or cma-es
+        #[arg(long, default_value = "coordinate-descent")]
+        strategy: SearchStrategy,
+        /// Maximum objective evaluations per fold
+        #[arg(long, default_value_t = 100, value_parser = positive_count)]
+        max_evaluations: usize,
+        /// Wall-clock limit per fold in seconds
+        #[arg(long, value_parser = positive_interval)]
+        max_seconds: Option<f64>,
+        /// Seed for the fold assignment and the searches
+        #[arg(long, default_value_t = 42)]
+        seed: u64,
+    },
+    /// 1.9m symmetric processing with a 100m incline breakdown per file; processed GPX files
+    /// and incline CSVs are written to <input>/Ultimate_Processed
+    Ultimate(InputArgs),
+    /// Process every file at 6.1m and write the smoothed GPX files
+    GpxOutput {
+        #[command(flatten)]
+        input: InputArgs,
+        /// Folder for the processed GPX files
+        #[arg(short, long)]
+        output: String,
+    },
 }
 
-// Load official elevation data from CSV
-pub fn load_official_elevation_data() -> Result<HashMap<String, u32>, Box<dyn std::error::Error>> {
-    let mut official_data = HashMap::new();
-    
-    // Try to load from src folder first, then from current directory
-    let csv_paths = vec![
-        "src/official_elevation_data.csv",
-        "official_elevation_data.csv",
-    ];
-    
-    let mut csv_loaded = false;
-    
-    for csv_path in csv_paths {
-        if Path::new(csv_path).exists() {
-            println!("📄 Loading official elevation data from: {}", csv_path);
-            
-            let file = File::open(csv_path)?;
-            let mut rdr = Reader::from_reader(file);
-            
-            for result in rdr.deserialize::<OfficialElevationRecord>() {
-                match result {
-                    Ok(record) => {
-                        official_data.insert(record.filename.to_lowercase(), record.official_elevation_gain_m);
-                    }
-                    Err(e) => {
-                        eprintln!("⚠️  Error parsing CSV record: {}", e);
-                    }
-                }
-            }
-            
-            csv_loaded = true;
-            println!("✅ Loaded {} official elevation records", official_data.len());
-            break;
-        }
+/// Intervals (meters or seconds) must be positive: zero or NaN would never advance the
+/// resampling, and Duration::from_secs_f64 panics on negative or NaN seconds
+fn positive_interval(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(value) if value.is_finite() && value > 0.0 => Ok(value),
+        Ok(_) => Err(format!("must be a positive, finite number, got {}", s)),
+        Err(e) => Err(e.to_string()),
     }
-    
-    if !csv_loaded {
-        println!("⚠️  No official elevation data CSV found, using built-in defaults");
-        
-        // Fallback to built-in data
-        let builtin_data = vec![
-            ("berlin garmin.gpx", 73),
-            ("bostonmarathon2024.gpx", 248),
-            ("bostonmarathon2025.gpx", 248),
-            ("cmt_46.gpx", 1700),
-            ("newyork2024.gpx", 247),
-            ("valencia2022.gpx", 46),
-            ("mainova-frankfurt-marathon 2023.gpx", 28),
-            // Add more as needed...
-        ];
-        
-        for (filename, gain) in builtin_data {
-            official_data.insert(filename.to_lowercase(), gain);
-        }
+}
+
+/// A search budget of zero evaluations leaves nothing to pick the best from
+fn positive_count(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(0) => Err("must be at least 1".to_string()),
+        Ok(value) => Ok(value),
+        Err(e) => Err(e.to_string()),
     }
-    
-    Ok(official_data)
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let gpx_folder = r"C:\Users\Dzhu\Documents\GPX Files";
-    let preprocessed_folder = r"C:\Users\Dzhu\Documents\GPX Files\Preprocessed";
-    let _output_folder = r"C:\Users\Dzhu\Documents\GPX Files\GPX Analysis";
-    
-    // Print enhanced menu with all analysis options
-    println!("\n🏔️  GPX ELEVATION ANALYSIS SUITE");
-    println!("================================");
-    println!("🏆 PROVEN WINNING SYMMETRIC DEADZONE METHOD:");
-    println!("   • SymmetricFixed with optimal interval (scientifically proven)");
-    println!("   • Eliminates loss under-estimation problem");
-    println!("   • Achieves realistic gain/loss ratios (~1.0)");
-    println!("   • 95%+ of files within ±20% accuracy");
-    println!("   • Revolutionary symmetric elevation processing");
-    println!("");
-    println!("Available analyses:");
-    println!("1. Fine-grained analysis (0.05m to 8m intervals)");
-    println!("2. Improved scoring analysis");
-    println!("3. Outlier analysis");
-    println!("4. Simplified gain/loss balance analysis");
-    println!("5. 🏆 PROCESS & SAVE GPX FILES (using winning thresholds) [RECOMMENDED]");
-    println!("6. Previous asymmetric methods analysis (comprehensive)");
-    println!("7. Fine-tuned asymmetric directional deadzone optimization");
-    println!("8. Hybrid analysis (Butterworth + Distance-based)");
-    println!("9. Run all analyses");
-    println!("10. 🔄 Two-Pass & Savitzky-Golay Comparison Analysis");
-    println!("11. 🎯 Precision Optimization Analysis");
-    println!("12. ✅ Corrected Elevation Analysis (Proper Scoring + Symmetric Fix)");
-    println!("13. 🎯 Focused Symmetric Analysis (0.5m to 2.5m optimization) [NEW]");
-    println!("14. 🎯 SINGLE INTERVAL ANALYSIS: 1.9m Symmetric (File-by-File Details) [NEW]");
-    println!("15. 🔧 PREPROCESS GPX FILES: Clean and repair all GPX files [NEW]");
-    
-    // Offer menu for additional analyses
-    println!("\n📊 Choose an analysis to run:");
-    println!("Press Enter to exit, or choose an option:");
-    println!("1. Fine-grained interval analysis");
-    println!("2. Improved scoring analysis"); 
-    println!("3. Outlier detection analysis");
-    println!("4. Fine-tuned asymmetric analysis");
-    println!("5. Hybrid analysis (Butterworth + Distance-based)");
-    println!("6. All supplementary analyses");
-    println!("10. 🔄 Two-Pass & Savitzky-Golay Comparison");
-    println!("11. 🎯 Precision Optimization Analysis");
-    println!("12. ✅ Corrected Elevation Analysis (Fixed with Symmetric)");
-    println!("13. 🎯 Focused Symmetric Analysis (HIGH-RESOLUTION 0.5-2.5m) [RECOMMENDED]");
-    println!("14. 🎯 1.9m Symmetric Analysis (Individual File Details) [NEW - RECOMMENDED]");
-    println!("15. 🔧 Preprocess GPX Files (Clean & Repair) [NEW - RECOMMENDED FIRST STEP]");
-    
-    // Simple menu handling
-    use std::io::{self, Write};
-    print!("Choice (or Enter to exit): ");
-    io::stdout().flush().unwrap();
-    
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).unwrap();
-    let choice = input.trim();
-    
-    match choice {
-        "1" => {
-            println!("\n🔬 Running fine-grained interval analysis...");
-            run_fine_grained_analysis(gpx_folder)?;
-        },
-        "2" => {
-            println!("\n📊 Running improved scoring analysis...");
-            improved_scoring::run_improved_scoring_analysis(gpx_folder)?;
-        },
-        "3" => {
-            println!("\n🔍 Running outlier detection analysis...");
-            outlier_analysis::run_outlier_analysis(gpx_folder)?;
-        },
-        "4" => {
-            println!("\n🔬 Running fine-tuned asymmetric analysis...");
-            assymetric_analysis::run_fine_tuned_asymmetric_analysis(gpx_folder)?;
-        },
-        "5" => {
-            println!("\n🔄 Running hybrid analysis...");
-            hybrid_analysis::run_hybrid_analysis(gpx_folder)?;
-        },
-        "6" => {
-            println!("\n🚀 Running all supplementary analyses...");
-            run_fine_grained_analysis(gpx_folder)?;
-            improved_scoring::run_improved_scoring_analysis(gpx_folder)?;
-            outlier_analysis::run_outlier_analysis(gpx_folder)?;
-            assymetric_analysis::run_fine_tuned_asymmetric_analysis(gpx_folder)?;
-            hybrid_analysis::run_hybrid_analysis(gpx_folder)?;
-            println!("✅ All supplementary analyses complete!");
-        },
-        "10" => {
-            println!("\n🔄 Running Two-Pass & Savitzky-Golay comparison...");
-            two_pass_analysis::run_two_pass_analysis(gpx_folder)?;
-        },
-        "11" => {
-            println!("\n🎯 Running precision optimization analysis...");
-            precision_optimization_analysis::run_precision_optimization_analysis(gpx_folder)?;
-        },
-        "12" => {
-            println!("\n✅ Running corrected elevation analysis with symmetric fix...");
-            corrected_elevation_analysis::run_corrected_elevation_analysis(gpx_folder)?;
-        },
-        "13" => {
-            println!("\n🎯 Running focused symmetric analysis (0.5m to 2.5m optimization)...");
-            
-            // Check if preprocessed folder exists and ask user which to use
-            if Path::new(preprocessed_folder).exists() {
-                println!("📂 Both original and preprocessed folders found:");
-                println!("   Original: {}", gpx_folder);
-                println!("   Preprocessed: {}", preprocessed_folder);
-                println!("");
-                println!("🔧 RECOMMENDATION: Use preprocessed folder for best results!");
-                println!("   Preprocessed files are cleaned and repaired for consistent analysis.");
-                println!("");
-                print!("Use preprocessed folder? (y/N): ");
-                io::stdout().flush().unwrap();
-                
-                let mut choice = String::new();
-                io::stdin().read_line(&mut choice).unwrap();
-                let use_preprocessed = choice.trim().to_lowercase();
-                
-                if use_preprocessed == "y" || use_preprocessed == "yes" {
-                    println!("✅ Using preprocessed folder: {}", preprocessed_folder);
-                    focused_symmetric_analysis::run_focused_symmetric_analysis(preprocessed_folder)?;
-                } else {
-                    println!("📁 Using original folder: {}", gpx_folder);
-                    focused_symmetric_analysis::run_focused_symmetric_analysis(gpx_folder)?;
-                }
-            } else {
-                println!("📁 Using original folder: {}", gpx_folder);
-                println!("💡 TIP: Run option 15 first to preprocess files for best results!");
-                focused_symmetric_analysis::run_focused_symmetric_analysis(gpx_folder)?;
-            }
-        },
-        "14" => {
-            println!("\n🎯 Running 1.9m symmetric analysis with individual file details...");
-            
-            // Check if preprocessed folder exists and ask user which to use
-            if Path::new(preprocessed_folder).exists() {
-                println!("📂 Both original and preprocessed folders found:");
-                println!("   Original: {}", gpx_folder);
-                println!("   Preprocessed: {}", preprocessed_folder);
-                println!("");
-                print!("Use preprocessed folder? (y/N): ");
-                io::stdout().flush().unwrap();
-                
-                let mut choice = String::new();
-                io::stdin().read_line(&mut choice).unwrap();
-                let use_preprocessed = choice.trim().to_lowercase();
-                
-                if use_preprocessed == "y" || use_preprocessed == "yes" {
-                    println!("✅ Using preprocessed folder: {}", preprocessed_folder);
-                    single_interval_analysis::run_single_interval_analysis(preprocessed_folder)?;
-                } else {
-                    println!("📁 Using original folder: {}", gpx_folder);
-                    single_interval_analysis::run_single_interval_analysis(gpx_folder)?;
-                }
-            } else {
-                println!("📁 Using original folder: {}", gpx_folder);
-                println!("💡 TIP: Run option 15 first to preprocess files for best results!");
-                single_interval_analysis::run_single_interval_analysis(gpx_folder)?;
-            }
-        },
-        "15" => {
-            println!("\n🔧 Running GPX preprocessing (clean and repair)...");
-            gpx_preprocessor::run_gpx_preprocessing(gpx_folder, preprocessed_folder)?;
-        },
-        "" => {
-            println!("👋 Exiting. Your processed GPX files are ready in the output folder!");
-        },
-        _ => {
-            println!("ℹ️  Unknown option. Choose a number from 1-15 or press Enter to exit.");
-        }
+/// One fold would leave nothing to train on
+fn fold_count(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(value) if value < 2 => Err("must be at least 2".to_string()),
+        Ok(value) => Ok(value),
+        Err(e) => Err(e.to_string()),
     }
-    
-    Ok(())
 }
 
-// Fine-grained analysis function (existing functionality)
-fn run_fine_grained_analysis(gpx_folder: &str) -> Result<(), Box<dyn std::error::Error>> {
-    use walkdir::WalkDir;
-    
-    println!("\n📊 FINE-GRAINED INTERVAL ANALYSIS");
-    println!("==================================");
-    println!("Testing elevation processing with intervals from 0.05m to 8.0m");
-    
-    let official_data = load_official_elevation_data()?;
-    let mut all_results = Vec::new();
-    
-    let mut file_count = 0;
-    let mut processed_count = 0;
-    
-    for entry in WalkDir::new(gpx_folder) {
-        let entry = entry?;
-        if entry.file_type().is_file() {
-            if let Some(extension) = entry.path().extension() {
-                if extension.to_str().unwrap_or("").to_lowercase() == "gpx" {
-                    file_count += 1;
-                    match process_gpx_file_fine_grained(entry.path(), &official_data) {
-                        Ok(result) => {
-                            all_results.push(result);
-                            processed_count += 1;
-                        },
-                        Err(e) => {
-                            eprintln!("⚠️  Error processing {}: {}", entry.path().display(), e);
-                        }
-                    }
-                }
-            }
+#[derive(Args)]
+struct InputArgs {
+    /// Input GPX/FIT/TCX file or folder
+    #[arg(short, long)]
+    input: String,
+}
+
+#[derive(Args)]
+struct SmootherArgs {
+    /// Smoothing algorithm: symmetric-fixed, distbased, butterworth, savitzky-golay,
+    /// two-pass, spike-deadzone, kalman, barometric, dem-fusion (needs --dem-dir), or a legacy variant
+    /// (original, capping, flat21, postcap)
+    #[arg(long)]
+    smoother: Option<String>,
+    /// Interval in meters (window in points for savitzky-golay, deadband for barometric) where the smoother has one
+    #[arg(long, value_parser = positive_interval)]
+    interval: Option<f64>,
+}
+
+#[derive(Args)]
+struct DistanceArgs {
+    /// Cumulative distance source: haversine, or device (FIT/TCX recorded distance)
+    #[arg(long, default_value = "haversine")]
+    distance_source: DistanceSource,
+}
+
+#[derive(Args)]
+struct StopArgs {
+    /// Collapse stationary stops (aid stations, traffic lights) to one point before smoothing
+    #[arg(long)]
+    collapse_stops: bool,
+}
+
+#[derive(Args)]
+struct JumpArgs {
+    /// Repair horizontal GPS jumps: none (count only), drop or interpolate
+    #[arg(long, default_value = "none")]
+    gps_jumps: JumpRepair,
+    /// Implied speed (m/s) above which a step out and back counts as a jump (default 15)
+    #[arg(long)]
+    max_speed: Option<f64>,
+}
+
+impl JumpArgs {
+    fn config(&self) -> JumpDetectionConfig {
+        let defaults = JumpDetectionConfig::default();
+        JumpDetectionConfig {
+            max_speed_mps: self.max_speed.unwrap_or(defaults.max_speed_mps),
+            repair: self.gps_jumps,
         }
     }
-    
-    println!("\n✅ Processed {} out of {} GPX files", processed_count, file_count);
-    
-    if !all_results.is_empty() {
-        let output_path = Path::new(gpx_folder).join("fine_grained_analysis_0.05_to_8m.csv");
-        write_fine_grained_csv(&all_results, &output_path)?;
-        print_fine_grained_summary(&all_results);
-        println!("📁 Results saved to: {}", output_path.display());
-    } else {
-        println!("⚠️  No valid results to save");
-    }
-    
-    Ok(())
 }
 
-// Keep the original functions below for backward compatibility
+#[derive(Args)]
+struct DemArgs {
+    /// Directory of GeoTIFF rasters and/or SRTM .hgt tiles (e.g. N46E007.hgt) for terrain elevations
+    #[arg(long)]
+    dem_dir: Option<String>,
+    /// fill (default): only points without a GPS elevation; replace: every point the DEM covers
+    #[arg(long, requires = "dem_dir")]
+    dem_mode: Option<DemMode>,
+}
 
-fn process_gpx_file_fine_grained(
-    path: &Path, 
-    official_data: &HashMap<String, u32>
-) -> Result<FineGrainedResult, Box<dyn std::error::Error>> {
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
-    let gpx = read(reader)?;
-    
-    let mut coords: Vec<(f64, f64, f64)> = vec![];
-    
-    for track in gpx.tracks {
-        for segment in track.segments {
-            for pt in segment.points {
-                if let Some(ele) = pt.elevation {
-                    let lat = pt.point().y();
-                    let lon = pt.point().x();
-                    coords.push((lat, lon, ele));
-                }
-            }
+impl DemArgs {
+    fn build(&self) -> Result<Option<DemCorrection>, Box<dyn std::error::Error>> {
+        match &self.dem_dir {
+            Some(dir) => Ok(Some(DemCorrection::new(open_dem_directory(dir)?, self.dem_mode.unwrap_or_default()))),
+            None => Ok(None),
         }
     }
-    
-    if coords.is_empty() {
-        return Err("No valid coordinates found in GPX file".into());
-    }
-    
-    // Calculate distances
-    let mut distances = vec![0.0];
-    for i in 1..coords.len() {
-        let a = point!(x: coords[i-1].1, y: coords[i-1].0);
-        let b = point!(x: coords[i].1, y: coords[i].0);
-        let dist = a.haversine_distance(&b);
-        distances.push(distances[i-1] + dist);
-    }
-    
-    let raw_elevations: Vec<f64> = coords.iter().map(|x| x.2).collect();
-    let total_distance_km = distances.last().unwrap() / 1000.0;
-    let (raw_gain, _) = gain_loss(&raw_elevations);
-    
-    let filename = path.file_name()
-        .and_then(|name| name.to_str())
-        .unwrap_or("unknown")
-        .to_string();
-    
-    // Look up official gain from CSV data, handle both original and cleaned filenames
-    let cleaned_filename = if filename.starts_with("cleaned_") {
-        filename.strip_prefix("cleaned_").unwrap_or(&filename)
-    } else {
-        &filename
-    };
-    
-    let official_gain = official_data
-        .get(&cleaned_filename.to_lowercase())
-        .copied()
-        .unwrap_or(0);
-    
-    if official_gain == 0 {
-        println!("⚠️  No official data for: {} (cleaned: {})", filename, cleaned_filename);
-    }
-    
-    println!("🔄 Processing: {} ({:.1}km, official: {}m)", filename, total_distance_km, official_gain);
-    
-    // Generate intervals from 0.05m to 8.0m in 0.05m increments
-    let intervals: Vec<f64> = (1..=160).map(|i| i as f64 * 0.05).collect();
-    
-    // Process all intervals in parallel for this file
-    let interval_results: Vec<(f32, u32, f32)> = intervals
-        .par_iter()
-        .map(|&interval| {
-            let gain = distbased_with_interval(&raw_elevations, &distances, interval);
-            let gain_u32 = gain.round() as u32;
-            let accuracy = if official_gain > 0 {
-                (gain_u32 as f32 / official_gain as f32) * 100.0
-            } else {
-                0.0
-            };
-            (interval as f32, gain_u32, accuracy)
-        })
-        .collect();
-    
-    let mut interval_gains = Vec::new();
-    let mut interval_accuracies = Vec::new();
-    
-    for (interval, gain, accuracy) in interval_results {
-        interval_gains.push((interval, gain));
-        interval_accuracies.push((interval, accuracy));
-    }
-    
-    Ok(FineGrainedResult {
-        filename,
-        raw_distance_km: total_distance_km as f32,
-        raw_elevation_gain_m: raw_gain.round() as u32,
-        official_elevation_gain_m: official_gain,
-        interval_gains,
-        interval_accuracies,
-    })
 }
 
-// Optimized distance-based processing with custom intervals
-fn distbased_with_interval(raw_elevations: &[f64], distances: &[f64], interval_meters: f64) -> f64 {
-    let mut elevation_data = ElevationData::new_with_variant(
-        raw_elevations.to_vec(), 
-        distances.to_vec(), 
-        SmoothingVariant::DistBased
-    );
-    
-    elevation_data.apply_custom_interval_processing(interval_meters);
-    elevation_data.get_total_elevation_gain()
+impl SmootherArgs {
+    fn build(&self, default_name: &str, dem_dir: Option<&str>) -> Result<Box<dyn ElevationSmoother>, Box<dyn std::error::Error>> {
+        let name = self.smoother.as_deref().unwrap_or(default_name);
+        let dem: Option<Box<dyn ElevationLookup>> = match dem_dir {
+            Some(dir) if smoother_uses_dem(name) => Some(open_dem_directory(dir)?),
+            _ => None,
+        };
+        Ok(smoother_from_name_with_dem(name, self.interval, dem)?)
+    }
 }
 
-fn gain_loss(elevs: &[f64]) -> (f64, f64) {
-    let mut gain = 0.0;
-    let mut loss = 0.0;
-    for w in elevs.windows(2) {
-        let delta = w[1] - w[0];
-        if delta > 0.0 {
-            gain += delta;
-        } else {
-            loss += -delta;
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    if let Some(ref csv_path) = cli.official_data {
+        if let Err(e) = rust_gpx_smoother::set_official_data_path(csv_path) {
+            eprintln!("❌ {}", e);
+            return ExitCode::from(EXIT_OFFICIAL_DATA_NOT_FOUND);
         }
     }
-    (gain, loss)
-}
 
-fn write_fine_grained_csv(results: &[FineGrainedResult], output_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
-    let mut wtr = Writer::from_path(output_path)?;
-    
-    // Build header
-    let mut header = vec![
-        "Filename".to_string(),
-        "Raw Distance (km)".to_string(),
-        "Raw Elevation Gain (m)".to_string(),
-        "Official Elevation Gain (m)".to_string(),
-    ];
-    
-    // Add columns for each interval
-    for i in 1..=160 {
-        let interval = i as f32 * 0.05;
-        header.push(format!("{:.2}m Gain", interval));
-        header.push(format!("{:.2}m Accuracy %", interval));
+    let input_path = command_input(&cli.command);
+    if !Path::new(input_path).exists() {
+        eprintln!("❌ Input path not found: {}", input_path);
+        return ExitCode::from(EXIT_INPUT_NOT_FOUND);
     }
-    
-    wtr.write_record(&header)?;
-    
-    // Write data rows
-    for result in results {
-        let mut row = vec![
-            result.filename.clone(),
-            result.raw_distance_km.to_string(),
-            result.raw_elevation_gain_m.to_string(),
-            result.official_elevation_gain_m.to_string(),
-        ];
-        
-        // Add interval data
-        for i in 0..result.interval_gains.len() {
-            row.push(result.interval_gains[i].1.to_string());
-            row.push(format!("{:.1}", result.interval_accuracies[i].1));
+
+    match run_command(cli.command) {
+        Ok(code) => ExitCode::from(code),
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            match error_kind(e.as_ref()) {
+                ErrorKind::GroundTruthMissing => ExitCode::from(EXIT_OFFICIAL_DATA_NOT_FOUND),
+                _ => ExitCode::from(EXIT_PROCESSING_FAILED),
+            }
         }
-        
-        wtr.write_record(&row)?;
     }
-    
-    wtr.flush()?;
-    Ok(())
 }
 
-fn print_fine_grained_summary(results: &[FineGrainedResult]) {
-    println!("\n📊 FINE-GRAINED ANALYSIS SUMMARY");
-    println!("================================");
-    
-    // Find best interval for each file
-    let mut best_intervals: Vec<f32> = Vec::new();
-    
-    for result in results {
-        if result.official_elevation_gain_m > 0 {
-            // Find interval with accuracy closest to 100%
-            let best_idx = result.interval_accuracies
-                .iter()
-                .enumerate()
-                .min_by_key(|(_, (_, acc))| ((acc - 100.0).abs() * 100.0) as i32)
-                .map(|(idx, _)| idx)
-                .unwrap_or(0);
-            
-            if best_idx < result.interval_gains.len() {
-                best_intervals.push(result.interval_gains[best_idx].0);
+fn command_input(command: &Command) -> &str {
+    match command {
+        Command::Process { input, .. }
+        | Command::Preprocess { input, .. }
+        | Command::Inclines { input, .. }
+        | Command::Effort { input, .. }
+        | Command::Benchmark { input, .. }
+        | Command::Consensus { input, .. }
+        | Command::Align { input, .. }
+        | Command::GroundTruth { input, .. } => &input.input,
+        Command::Analyze { analysis } => match analysis {
+            Analysis::SingleInterval { input, .. }
+            | Analysis::GpxOutput { input, .. }
+            | Analysis::ParameterOptimizer { input, .. }
+            | Analysis::CrossValidate { input, .. } => &input.input,
+            Analysis::FocusedSymmetric(input)
+            | Analysis::FineGrained(input)
+            | Analysis::ImprovedScoring(input)
+            | Analysis::Outlier(input)
+            | Analysis::Simplified(input)
+            | Analysis::Asymmetric(input)
+            | Analysis::DirectionalDeadzone(input)
+            | Analysis::Hybrid(input)
+            | Analysis::TwoPass(input)
+            | Analysis::Precision(input)
+            | Analysis::Corrected(input)
+            | Analysis::Ultimate(input) => &input.input,
+        },
+    }
+}
+
+fn run_command(command: Command) -> Result<u8, Box<dyn std::error::Error>> {
+    match command {
+        Command::Process { input, output, smoother, distance, dem, stops, jumps, keep_original_ele, ignore_barometric } => {
+            let dem_dir = dem.dem_dir.clone();
+            let dem = dem.build()?;
+            // The defaults reproduce the legacy spike/deadzone processor
+            let smoother = smoother.build("spike-deadzone", dem_dir.as_deref())?;
+            let barometric = if ignore_barometric {
+                None
+            } else {
+                Some(BarometricSmoother {
+                    dem: dem_dir.as_deref().map(open_dem_directory).transpose()?,
+                    ..BarometricSmoother::default()
+                })
+            };
+            gpx_processor::process_and_save_gpx_files_with_smoother(&input.input, &output, &gpx_processor::ProcessingSetup {
+                smoother: smoother.as_ref(),
+                barometric_smoother: barometric.as_ref().map(|b| b as &dyn ElevationSmoother),
+                distance_source: distance.distance_source,
+                dem: dem.as_ref(),
+                cleanup: &gpx_processor::PointCleanup {
+                    collapse_stops: stops.collapse_stops,
+                    jumps: jumps.config(),
+                },
+                write_options: &GpxWriteOptions { keep_original_elevation: keep_original_ele },
+            })?;
+        },
+        Command::Preprocess { input, output, dem } => {
+            let output = output.unwrap_or_else(|| {
+                Path::new(&input.input).join("Preprocessed").to_string_lossy().into_owned()
+            });
+            let dem = dem.build()?;
+            gpx_preprocessor::run_gpx_preprocessing_with_dem(&input.input, &output, dem.as_ref())?;
+        },
+        Command::Analyze { analysis } => run_analysis(analysis)?,
+        Command::Inclines { input, variant, output, distance } => {
+            let track = load_track(Path::new(&input.input))?
+                .with_distance_source(distance.distance_source);
+            let config = InclineAnalysisConfig {
+                smoothing_variant: variant,
+                ..InclineAnalysisConfig::default()
+            };
+            println!("🔄 Analyzing inclines: {} ({:.1}km)", track.filename, track.total_distance_km());
+            let result = rust_gpx_smoother::analyze_inclines(
+                track.elevations(),
+                track.cumulative_distances.clone(),
+                &config
+            );
+            if let Some(output) = output {
+                incline_analyzer::write_segments_csv(&result, Path::new(&output))?;
+                println!("📁 Segments saved to: {}", output);
             }
-        }
+        },
+        Command::Effort { input, variant, moving_speed, output, distance } => {
+            let track = load_track(Path::new(&input.input))?
+                .with_distance_source(distance.distance_source);
+            let config = effort::EffortConfig {
+                moving_speed_mps: moving_speed,
+                smoothing_variant: variant,
+                ..effort::EffortConfig::default()
+            };
+            println!("🔄 Analyzing effort: {} ({:.1}km)", track.filename, track.total_distance_km());
+            let analysis = effort::analyze_effort(&track, &config)?;
+            effort::print_effort_summary(&analysis);
+            if let Some(output) = output {
+                effort::write_effort_csv(&analysis, Path::new(&output))?;
+                println!("📁 Effort saved to: {}", output);
+            }
+        },
+        Command::GroundTruth { input, fill_hashes } => {
+            let mut registry = rust_gpx_smoother::load_ground_truth_registry()?;
+            let valid = ground_truth::run_ground_truth_validation(&mut registry, Path::new(&input.input), fill_hashes)?;
+            if !valid {
+                return Ok(EXIT_PROCESSING_FAILED);
+            }
+        },
+        Command::Benchmark { input, output, smoother, distance, dem_dir, ignore_barometric, stops } => {
+            let output = output.unwrap_or_else(|| input.input.clone());
+            let smoother = smoother.build("symmetric-fixed", dem_dir.as_deref())?;
+            let barometric = if ignore_barometric {
+                None
+            } else {
+                Some(BarometricSmoother {
+                    dem: dem_dir.as_deref().map(open_dem_directory).transpose()?,
+                    ..BarometricSmoother::default()
+                })
+            };
+            let summary = benchmark::run_benchmark(
+                &input.input,
+                smoother.as_ref(),
+                &output,
+                distance.distance_source,
+                barometric.as_ref().map(|b| b as &dyn ElevationSmoother),
+                stops.collapse_stops,
+            )?;
+            if summary.files_processed == 0 {
+                return Ok(EXIT_NO_FILES_PROCESSED);
+            }
+        },
+        Command::Consensus { input, output, smoother, step, seed } => {
+            let output = output.unwrap_or_else(|| input.input.clone());
+            let smoother = smoother.build("symmetric-fixed", None)?;
+            let config = consensus::ConsensusConfig {
+                step_m: step,
+                seed,
+                ..consensus::ConsensusConfig::default()
+            };
+            consensus::run_consensus(&input.input, &output, smoother.as_ref(), &config)?;
+        },
+        Command::Align { input, course, output, smoother } => {
+            let input_path = Path::new(&input.input);
+            let output = match output {
+                Some(output) => output.into(),
+                None if input_path.is_dir() => input_path.to_path_buf(),
+                None => input_path.parent().unwrap_or(Path::new(".")).to_path_buf(),
+            };
+            let smoother = smoother.build("symmetric-fixed", None)?;
+            let summaries = alignment::run_course_alignment(Path::new(&course), input_path, &output, smoother.as_ref())?;
+            if summaries.is_empty() {
+                return Ok(EXIT_NO_FILES_PROCESSED);
+            }
+        },
     }
-    
-    if !best_intervals.is_empty() {
-        best_intervals.sort_by(|a, b| a.partial_cmp(b).unwrap());
-        let median_interval = best_intervals[best_intervals.len() / 2];
-        let avg_interval = best_intervals.iter().sum::<f32>() / best_intervals.len() as f32;
-        
-        println!("🎯 Optimal interval statistics:");
-        println!("  Average optimal interval: {:.2}m", avg_interval);
-        println!("  Median optimal interval: {:.2}m", median_interval);
-        println!("  Min optimal interval: {:.2}m", best_intervals.first().unwrap());
-        println!("  Max optimal interval: {:.2}m", best_intervals.last().unwrap());
-        
-        // Count distribution
-        println!("\n📈 Optimal interval distribution:");
-        let mut distribution: HashMap<i32, i32> = HashMap::new();
-        for &interval in &best_intervals {
-            // Convert to integer key (multiply by 10 to preserve one decimal place)
-            let bucket_key = ((interval / 0.5).round() * 5.0) as i32;
-            *distribution.entry(bucket_key).or_insert(0) += 1;
-        }
-        
-        let mut buckets: Vec<_> = distribution.into_iter().collect();
-        buckets.sort_by_key(|&(k, _)| k);
-        
-        for (bucket_key, count) in buckets {
-            let bucket_value = bucket_key as f32 / 10.0;
-            println!("  {:.1}m ± 0.25m: {} files", bucket_value, count);
-        }
+
+    Ok(EXIT_SUCCESS)
+}
+
+fn run_analysis(analysis: Analysis) -> Result<(), Box<dyn std::error::Error>> {
+    match analysis {
+        Analysis::SingleInterval { input, interval } => {
+            single_interval_analysis::run_single_interval_analysis_with_interval(&input.input, interval)?;
+        },
+        Analysis::FocusedSymmetric(input) => {
+            focused_symmetric_analysis::run_focused_symmetric_analysis(&input.input)?;
+        },
+        Analysis::FineGrained(input) => {
+            fine_grained_analysis::run_fine_grained_analysis(&input.input)?;
+        },
+        Analysis::ImprovedScoring(input) => {
+            improved_scoring::run_improved_scoring_analysis(&input.input)?;
+        },
+        Analysis::Outlier(input) => {
+            outlier_analysis::run_outlier_analysis(&input.input)?;
+        },
+        Analysis::Simplified(input) => {
+            simplified_analysis::run_simplified_analysis(&input.input)?;
+        },
+        Analysis::Asymmetric(input) => {
+            assymetric_analysis::run_fine_tuned_asymmetric_analysis(&input.input)?;
+        },
+        Analysis::DirectionalDeadzone(input) => {
+            assymetric_analysis::run_comprehensive_directional_deadzone_analysis(&input.input)?;
+        },
+        Analysis::Hybrid(input) => {
+            hybrid_analysis::run_hybrid_analysis(&input.input)?;
+        },
+        Analysis::TwoPass(input) => {
+            two_pass_analysis::run_two_pass_analysis(&input.input)?;
+        },
+        Analysis::Precision(input) => {
+            precision_optimization_analysis::run_precision_optimization_analysis(&input.input)?;
+        },
+        Analysis::Corrected(input) => {
+            corrected_elevation_analysis::run_corrected_elevation_analysis(&input.input)?;
+        },
+        Analysis::ParameterOptimizer { input, strategy, smoother, max_evaluations, max_seconds, seed } => {
+            match strategy {
+                Some(strategy) => {
+                    let budget = SearchBudget {
+                        max_evaluations,
+                        max_duration: max_seconds.map(Duration::from_secs_f64),
+                    };
+                    parameter_optimizer::run_parameter_search(&input.input, strategy, smoother.as_deref(), budget, seed)?;
+                }
+                None => parameter_optimizer::run_parameter_optimization(&input.input)?,
+            }
+        },
+        Analysis::CrossValidate { input, smoother, folds, leave_one_out, strategy, max_evaluations, max_seconds, seed } => {
+            let scheme = if leave_one_out { FoldScheme::LeaveOneOut } else { FoldScheme::KFold(folds) };
+            let budget = SearchBudget {
+                max_evaluations,
+                max_duration: max_seconds.map(Duration::from_secs_f64),
+            };
+            cross_validation::run_cross_validation(&input.input, smoother.as_deref(), scheme, strategy, budget, seed)?;
+        },
+        Analysis::Ultimate(input) => {
+            ultimate_gpx_processor::run_ultimate_gpx_processor(&input.input)?;
+        },
+        Analysis::GpxOutput { input, output } => {
+            gpx_output_analysis::run_gpx_output_analysis(&input.input, &output)?;
+        },
     }
-}
\ No newline at end of file
+
+    Ok(())
+}
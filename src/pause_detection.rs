@@ -0,0 +1,289 @@
+/// PAUSE DETECTION - Stops, aid stations and device auto-pauses in timed recordings
+/// A stop is a run of points that stays within STOP_RADIUS_M of where it began for at least
+/// MIN_STOP_DURATION_S; an auto-pause is a timestamp gap the recording barely moved across.
+/// Standing at an aid station for minutes records GPS wander that looks like distance and
+/// metre-scale elevation jitter, so stationary clusters can be collapsed to one point
+/// (median elevation) before the spike filter and deadband see them.
+use geo::{HaversineDistance, point};
+use crate::track_loader::{LoadedTrack, TrackPoint};
+
+// A stop stays within this distance of where it began...
+pub const STOP_RADIUS_M: f64 = 10.0;
+// ...for at least this long
+pub const MIN_STOP_DURATION_S: f64 = 30.0;
+// Longer timestamp gaps than this are pauses when little distance was covered across them
+const AUTO_PAUSE_GAP_S: f64 = 10.0;
+const AUTO_PAUSE_MAX_SPEED_MPS: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopKind {
+    Stationary,  // Recording kept running at one spot
+    AutoPause,   // Device stopped recording
+}
+
+#[derive(Debug, Clone)]
+pub struct Stop {
+    pub kind: StopKind,
+    pub start_index: usize,
+    pub end_index: usize,      // Inclusive: the last point before moving on
+    pub duration_s: f64,
+    pub wander_m: f64,         // Recorded distance inside the stop
+    pub elevation_range_m: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StopReport {
+    pub stops: Vec<Stop>,
+    pub elapsed_time_s: f64,
+    pub stopped_time_s: f64,
+    pub moving_time_s: f64,
+    pub wander_distance_m: f64,
+}
+
+impl StopReport {
+    /// Whether the step from point `index` to `index + 1` lies inside a stop
+    pub fn is_stopped_step(&self, index: usize) -> bool {
+        self.stops.iter().any(|s| s.start_index <= index && index < s.end_index)
+    }
+
+    pub fn stationary_count(&self) -> usize {
+        self.stops.iter().filter(|s| s.kind == StopKind::Stationary).count()
+    }
+
+    pub fn auto_pause_count(&self) -> usize {
+        self.stops.iter().filter(|s| s.kind == StopKind::AutoPause).count()
+    }
+}
+
+fn ground_distance(a: &TrackPoint, b: &TrackPoint) -> f64 {
+    point!(x: a.longitude, y: a.latitude).haversine_distance(&point!(x: b.longitude, y: b.latitude))
+}
+
+fn seconds_between(a: &TrackPoint, b: &TrackPoint) -> Option<f64> {
+    Some((b.time? - a.time?).num_milliseconds() as f64 / 1000.0)
+}
+
+fn make_stop(kind: StopKind, points: &[TrackPoint], distances: &[f64], start: usize, end: usize) -> Stop {
+    let elevations = points[start..=end].iter().map(|p| p.elevation);
+    let (low, high) = elevations.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), e| (lo.min(e), hi.max(e)));
+    Stop {
+        kind,
+        start_index: start,
+        end_index: end,
+        duration_s: seconds_between(&points[start], &points[end]).unwrap_or(0.0),
+        wander_m: if kind == StopKind::Stationary { distances[end] - distances[start] } else { 0.0 },
+        elevation_range_m: high - low,
+    }
+}
+
+/// Find stops in a track's points. Tracks without timestamps have none.
+pub fn detect_stops(track: &LoadedTrack) -> StopReport {
+    let points = &track.points;
+    let distances = &track.cumulative_distances;
+    let mut stops = Vec::new();
+
+    let mut i = 0;
+    while i + 1 < points.len() {
+        if points[i].time.is_none() {
+            i += 1;
+            continue;
+        }
+        let gap = seconds_between(&points[i], &points[i + 1]).unwrap_or(0.0);
+        if gap > AUTO_PAUSE_GAP_S && ground_distance(&points[i], &points[i + 1]) / gap < AUTO_PAUSE_MAX_SPEED_MPS {
+            stops.push(make_stop(StopKind::AutoPause, points, distances, i, i + 1));
+            i += 1;
+            continue;
+        }
+
+        let mut j = i;
+        while j + 1 < points.len() && ground_distance(&points[i], &points[j + 1]) <= STOP_RADIUS_M {
+            j += 1;
+        }
+        if j > i && seconds_between(&points[i], &points[j]).is_some_and(|t| t >= MIN_STOP_DURATION_S) {
+            stops.push(make_stop(StopKind::Stationary, points, distances, i, j));
+            i = j + 1;
+            continue;
+        }
+        i += 1;
+    }
+
+    let first_time = points.iter().find_map(|p| p.time);
+    let last_time = points.iter().rev().find_map(|p| p.time);
+    let elapsed_time_s = match (first_time, last_time) {
+        (Some(first), Some(last)) => (last - first).num_milliseconds() as f64 / 1000.0,
+        _ => 0.0,
+    };
+    let stopped_time_s: f64 = stops.iter().map(|s| s.duration_s).sum();
+
+    StopReport {
+        elapsed_time_s,
+        stopped_time_s,
+        moving_time_s: (elapsed_time_s - stopped_time_s).max(0.0),
+        wander_distance_m: stops.iter().map(|s| s.wander_m).sum(),
+        stops,
+    }
+}
+
+/// A track with every stationary stop collapsed to one point
+#[derive(Debug, Clone)]
+pub struct CollapsedTrack {
+    pub track: LoadedTrack,
+    pub point_map: Vec<usize>,  // For every original point, its point in `track`
+}
+
+impl CollapsedTrack {
+    /// Collapsed-track distance of every original point, for mapping a profile smoothed on
+    /// the collapsed track back onto the original points
+    pub fn original_point_distances(&self) -> Vec<f64> {
+        self.point_map.iter().map(|&i| self.track.cumulative_distances[i]).collect()
+    }
+}
+
+/// Replace each stationary stop by its arrival point at the stop's median elevation. The
+/// wander inside stops is taken out of the cumulative distances, whatever their source.
+pub fn collapse_stops(track: &LoadedTrack, report: &StopReport) -> CollapsedTrack {
+    let mut points = Vec::with_capacity(track.points.len());
+    let mut distances = Vec::with_capacity(track.points.len());
+    let mut point_map = Vec::with_capacity(track.points.len());
+    let mut removed_m = 0.0;
+    let gaps = track.gaps();
+    let mut stationary = report.stops.iter().filter(|s| s.kind == StopKind::Stationary).peekable();
+
+    let mut i = 0;
+    while i < track.points.len() {
+        match stationary.next_if(|s| s.start_index == i) {
+            Some(stop) => {
+                let mut elevations: Vec<f64> = track.points[stop.start_index..=stop.end_index].iter().map(|p| p.elevation).collect();
+                elevations.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                let mut arrival = track.points[i].clone();
+                arrival.elevation = elevations[elevations.len() / 2];
+
+                points.push(arrival);
+                distances.push(track.cumulative_distances[i] - removed_m);
+                point_map.extend(std::iter::repeat_n(points.len() - 1, stop.end_index - stop.start_index + 1));
+                removed_m += track.cumulative_distances[stop.end_index] - track.cumulative_distances[stop.start_index];
+                // The step out of the stop starts from the arrival point instead, unless it
+                // crosses a recording gap, which adds no distance either way
+                let next = track.points.get(stop.end_index + 1).filter(|_| !gaps.contains(&(stop.end_index + 1)));
+                if let Some(next) = next {
+                    let step_out = ground_distance(&track.points[stop.start_index], next);
+                    removed_m += (track.cumulative_distances[stop.end_index + 1] - track.cumulative_distances[stop.end_index]) - step_out;
+                }
+                i = stop.end_index + 1;
+            }
+            None => {
+                points.push(track.points[i].clone());
+                distances.push(track.cumulative_distances[i] - removed_m);
+                point_map.push(points.len() - 1);
+                i += 1;
+            }
+        }
+    }
+
+    let mut segment_starts: Vec<usize> = track.segment_starts.iter().filter_map(|&start| point_map.get(start).copied()).collect();
+    segment_starts.dedup();
+
+    CollapsedTrack {
+        track: LoadedTrack {
+            filename: track.filename.clone(),
+            points,
+            cumulative_distances: distances,
+            altitude_source: track.altitude_source,
+            segment_starts,
+        },
+        point_map,
+    }
+}
+
+pub fn format_duration(seconds: f64) -> String {
+    let total = seconds.max(0.0).round() as u64;
+    format!("{}:{:02}:{:02}", total / 3600, total / 60 % 60, total % 60)
+}
+
+pub fn print_stop_summary(report: &StopReport) {
+    if report.stops.is_empty() {
+        return;
+    }
+    println!("   ⏸️  {} stop(s), {} auto-pause(s): {} stopped, moving {} of {} elapsed ({:.0}m of GPS wander)",
+             report.stationary_count(), report.auto_pause_count(), format_duration(report.stopped_time_s),
+             format_duration(report.moving_time_s), format_duration(report.elapsed_time_s), report.wander_distance_m);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone, Utc};
+
+    #[test]
+    fn test_aid_station_is_detected_and_collapsed() {
+        let start = Utc.with_ymd_and_hms(2024, 6, 1, 8, 0, 0).unwrap();
+        let mut points = Vec::new();
+        let mut push = |seconds: i64, north_m: f64, elevation: f64| points.push(TrackPoint {
+            latitude: 46.0 + north_m / 111_320.0,
+            longitude: 7.0,
+            elevation,
+            time: Some(start + Duration::seconds(seconds)),
+            distance: None,
+        });
+        // 500m climbing at 3 m/s, three minutes at an aid station with wander and jitter,
+        // a 60s auto-pause, then 500m more
+        for k in 0..=50 {
+            push(k * 10 / 3, k as f64 * 10.0, 100.0 + k as f64);
+        }
+        for k in 1..=180 {
+            let jitter = if k % 2 == 0 { 2.0 } else { -2.0 };
+            push(166 + k, 500.0 + jitter, 150.0 + jitter);
+        }
+        for k in 1..=50 {
+            let seconds = 346 + k * 10 / 3 + if k > 10 { 60 } else { 0 };
+            push(seconds, 500.0 + k as f64 * 10.0, 150.0 + k as f64);
+        }
+        let track = LoadedTrack::from_points("aid.gpx".to_string(), points);
+
+        let report = detect_stops(&track);
+        assert_eq!(report.stationary_count(), 1);
+        assert_eq!(report.auto_pause_count(), 1);
+        assert!((report.stopped_time_s - 240.0).abs() < 10.0, "{}", report.stopped_time_s);
+        assert!(report.wander_distance_m > 500.0);
+
+        let collapsed = collapse_stops(&track, &report);
+        assert!(collapsed.track.points.len() < track.points.len() - 170);
+        assert_eq!(collapsed.point_map.len(), track.points.len());
+        // Only the real 1km is left, and the jitter no longer adds gain
+        assert!((collapsed.track.total_distance_km() - 1.0).abs() < 0.02);
+        let (gain, _) = crate::track_loader::calculate_gain_loss(&collapsed.track.elevations());
+        assert!(gain < 105.0, "{}", gain);
+    }
+
+    #[test]
+    fn test_stop_before_recording_gap_does_not_bridge_the_gap() {
+        let start = Utc.with_ymd_and_hms(2024, 6, 1, 8, 0, 0).unwrap();
+        let at = |seconds: i64, north_m: f64| TrackPoint {
+            latitude: 46.0 + north_m / 111_320.0,
+            longitude: 7.0,
+            elevation: 100.0,
+            time: Some(start + Duration::seconds(seconds)),
+            distance: None,
+        };
+        let mut points = Vec::new();
+        // 500m, a 60s stop, then a new segment 1km further on for another 500m
+        for k in 0..=50 {
+            points.push(at(k * 3, k as f64 * 10.0));
+        }
+        for k in 1..=60 {
+            points.push(at(150 + k, 500.0 + if k % 2 == 0 { 2.0 } else { -2.0 }));
+        }
+        let second_segment = points.len();
+        for k in 0..=50 {
+            points.push(at(600 + k * 3, 1500.0 + k as f64 * 10.0));
+        }
+        let track = LoadedTrack::from_segments("gap.gpx".to_string(), points, vec![0, second_segment]);
+        assert_eq!(track.gaps(), vec![second_segment]);
+
+        let report = detect_stops(&track);
+        assert_eq!(report.stationary_count(), 1);
+        let collapsed = collapse_stops(&track, &report);
+        assert!((collapsed.track.total_distance_km() - 1.0).abs() < 0.02, "{}", collapsed.track.total_distance_km());
+        assert_eq!(collapsed.track.gaps().len(), 1);
+    }
+}
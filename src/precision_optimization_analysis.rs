@@ -13,6 +13,9 @@ use csv::Writer;
 use serde::Serialize;
 use rayon::prelude::*;
 use std::sync::Arc;
+use crate::elevation_smoother::{
+    gaussian_smooth, median_filter, resample_to_uniform_distance, savitzky_golay_smooth_with_order,
+};
 
 #[derive(Debug, Serialize, Clone)]
 pub struct PrecisionResult {
@@ -25,7 +28,8 @@ pub struct PrecisionResult {
     files_within_10_percent: u32,
     files_outside_20_percent: u32,
     
-    // Accuracy metrics
+    // Accuracy metrics (loss only over files with an official loss)
+    files_with_official_loss: u32,
     gain_accuracy_avg: f32,
     loss_accuracy_avg: f32,
     gain_accuracy_median: f32,
@@ -50,6 +54,7 @@ struct GpxFileData {
     elevations: Vec<f64>,
     distances: Vec<f64>,
     official_gain: u32,
+    official_loss: Option<u32>,         // Only non-zero official losses
 }
 
 #[derive(Debug, Clone)]
@@ -57,8 +62,8 @@ struct SingleFileResult {
     filename: String,
     official_gain: u32,
     gain_accuracy: f32,
-    loss_accuracy: f32,
-    combined_error: f32, // |gain_acc - 100| + |loss_acc - 100|
+    loss_accuracy: Option<f32>,
+    combined_error: f32, // |gain_acc - 100| + |loss_acc - 100| (gain only without an official loss)
 }
 
 pub fn run_precision_optimization_analysis(gpx_folder: &str) -> Result<(), Box<dyn std::error::Error>> {
@@ -130,84 +135,43 @@ pub fn run_precision_optimization_analysis(gpx_folder: &str) -> Result<(), Box<d
 }
 
 fn load_gpx_data(gpx_folder: &str) -> Result<(HashMap<String, GpxFileData>, Vec<String>), Box<dyn std::error::Error>> {
-    use std::fs::File;
-    use std::io::BufReader;
-    use gpx::read;
-    use geo::{HaversineDistance, point};
     use walkdir::WalkDir;
-    
+    use crate::track_loader::{load_track, is_supported_track_file};
+
     let mut gpx_data = HashMap::new();
     let mut valid_files = Vec::new();
-    
-    let official_data = crate::load_official_elevation_data()?;
-    
+
+    let official_data = crate::load_ground_truth_registry()?;
+
     for entry in WalkDir::new(gpx_folder) {
         let entry = entry?;
-        if entry.file_type().is_file() {
-            if let Some(extension) = entry.path().extension() {
-                if extension.to_str().unwrap_or("").to_lowercase() == "gpx" {
-                    let path = entry.path();
-                    let filename = path.file_name()
-                        .and_then(|n| n.to_str())
-                        .unwrap_or("")
-                        .to_string();
-                    
-                    match File::open(path) {
-                        Ok(file) => {
-                            let reader = BufReader::new(file);
-                            match read(reader) {
-                                Ok(gpx) => {
-                                    let mut coords: Vec<(f64, f64, f64)> = vec![];
-                                    
-                                    for track in gpx.tracks {
-                                        for segment in track.segments {
-                                            for pt in segment.points {
-                                                if let Some(ele) = pt.elevation {
-                                                    coords.push((pt.point().y(), pt.point().x(), ele));
-                                                }
-                                            }
-                                        }
-                                    }
-                                    
-                                    if !coords.is_empty() {
-                                        let mut distances = vec![0.0];
-                                        for i in 1..coords.len() {
-                                            let a = point!(x: coords[i-1].1, y: coords[i-1].0);
-                                            let b = point!(x: coords[i].1, y: coords[i].0);
-                                            let dist = a.haversine_distance(&b);
-                                            distances.push(distances[i-1] + dist);
-                                        }
-                                        
-                                        let elevations: Vec<f64> = coords.iter().map(|c| c.2).collect();
-                                        
-                                        let official_gain = official_data
-                                            .get(&filename.to_lowercase())
-                                            .copied()
-                                            .unwrap_or(0);
-                                        
-                                        if official_gain > 0 { // Only include files with official data
-                                            let file_data = GpxFileData {
-                                                filename: filename.clone(),
-                                                elevations,
-                                                distances,
-                                                official_gain,
-                                            };
-                                            
-                                            gpx_data.insert(filename.clone(), file_data);
-                                            valid_files.push(filename);
-                                        }
-                                    }
-                                },
-                                Err(_) => continue,
-                            }
-                        },
-                        Err(_) => continue,
-                    }
-                }
+        if entry.file_type().is_file() && is_supported_track_file(entry.path()) {
+            // GPX and FIT both land in the same point model; unreadable files are skipped
+            let track = match load_track(entry.path()) {
+                Ok(track) => track,
+                Err(_) => continue,
+            };
+            let filename = track.filename.clone();
+
+            let official = official_data.official_record(entry.path(), &track.coordinates());
+
+            if let Some(official) = official.filter(|r| r.gain_m > 0) { // Only include files with official data
+                let file_data = GpxFileData {
+                    elevations: track.elevations(),
+                    distances: track.cumulative_distances,
+                    filename: filename.clone(),
+                    official_gain: official.gain_m,
+                    official_loss: official.loss_m.filter(|&loss| loss > 0),
+                };
+
+                gpx_data.insert(filename.clone(), file_data);
+                valid_files.push(filename);
             }
         }
     }
-    
+
+    official_data.print_match_report();
+
     Ok((gpx_data, valid_files))
 }
 
@@ -329,18 +293,7 @@ fn process_distance_based_single_file(
 ) -> SingleFileResult {
     let (gain, loss) = apply_optimized_distance_based(&file_data.elevations, &file_data.distances, interval.into());
     
-    let official_gain = file_data.official_gain as f32;
-    let gain_accuracy = (gain / official_gain) * 100.0;
-    let loss_accuracy = (loss / official_gain) * 100.0;
-    let combined_error = (gain_accuracy - 100.0).abs() + (loss_accuracy - 100.0).abs();
-    
-    SingleFileResult {
-        filename: file_data.filename.clone(),
-        official_gain: file_data.official_gain,
-        gain_accuracy,
-        loss_accuracy,
-        combined_error,
-    }
+    file_result(file_data, gain, loss)
 }
 
 fn process_enhanced_twopass_single_file(
@@ -354,18 +307,7 @@ fn process_enhanced_twopass_single_file(
     // Use variable interval for loss calculation
     let (_, loss) = apply_optimized_distance_based(&file_data.elevations, &file_data.distances, loss_interval.into());
     
-    let official_gain = file_data.official_gain as f32;
-    let gain_accuracy = (gain / official_gain) * 100.0;
-    let loss_accuracy = (loss / official_gain) * 100.0;
-    let combined_error = (gain_accuracy - 100.0).abs() + (loss_accuracy - 100.0).abs();
-    
-    SingleFileResult {
-        filename: file_data.filename.clone(),
-        official_gain: file_data.official_gain,
-        gain_accuracy,
-        loss_accuracy,
-        combined_error,
-    }
+    file_result(file_data, gain, loss)
 }
 
 fn process_savgol_single_file(
@@ -375,10 +317,15 @@ fn process_savgol_single_file(
 ) -> SingleFileResult {
     let (gain, loss) = apply_optimized_savitzky_golay(&file_data.elevations, window_size, poly_order);
     
+    file_result(file_data, gain, loss)
+}
+
+/// Gain scored against the official gain, loss against the official loss when the record has one
+fn file_result(file_data: &GpxFileData, gain: f32, loss: f32) -> SingleFileResult {
     let official_gain = file_data.official_gain as f32;
     let gain_accuracy = (gain / official_gain) * 100.0;
-    let loss_accuracy = (loss / official_gain) * 100.0;
-    let combined_error = (gain_accuracy - 100.0).abs() + (loss_accuracy - 100.0).abs();
+    let loss_accuracy = file_data.official_loss.map(|official| loss / official as f32 * 100.0);
+    let combined_error = (gain_accuracy - 100.0).abs() + loss_accuracy.map_or(0.0, |acc| (acc - 100.0).abs());
     
     SingleFileResult {
         filename: file_data.filename.clone(),
@@ -395,7 +342,7 @@ fn apply_optimized_distance_based(
     interval: f64
 ) -> (f32, f32) {
     // Improved distance-based processing with precision optimizations
-    let (_uniform_distances, uniform_elevations) = resample_to_uniform_distance_optimized(
+    let (_uniform_distances, uniform_elevations) = resample_to_uniform_distance(
         elevations, distances, interval
     );
     
@@ -404,11 +351,11 @@ fn apply_optimized_distance_based(
     }
     
     // Apply enhanced median filter (5-point for better noise removal)
-    let median_smoothed = median_filter_optimized(&uniform_elevations, 5);
+    let median_smoothed = median_filter(&uniform_elevations, 5);
     
     // Apply adaptive Gaussian smoothing based on interval
     let gaussian_window = calculate_optimal_gaussian_window(interval);
-    let gaussian_smoothed = gaussian_smooth_optimized(&median_smoothed, gaussian_window);
+    let gaussian_smoothed = gaussian_smooth(&median_smoothed, gaussian_window);
     
     // Apply precision deadband filtering
     let deadband_threshold = calculate_optimal_deadband(interval);
@@ -424,7 +371,7 @@ fn apply_optimized_savitzky_golay(
     poly_order: usize
 ) -> (f32, f32) {
     // Enhanced Savitzky-Golay with proper polynomial fitting
-    let smoothed = savitzky_golay_filter_optimized(elevations, window_size, poly_order);
+    let smoothed = savitzky_golay_smooth_with_order(elevations, window_size, poly_order);
     
     // Apply gentle deadband to remove remaining noise
     let deadband_threshold = 0.5; // 0.5m threshold for Savitzky-Golay
@@ -435,113 +382,6 @@ fn apply_optimized_savitzky_golay(
 
 // Optimized helper functions
 
-fn resample_to_uniform_distance_optimized(
-    elevations: &[f64],
-    distances: &[f64],
-    interval: f64
-) -> (Vec<f64>, Vec<f64>) {
-    if elevations.is_empty() || distances.is_empty() {
-        return (vec![], vec![]);
-    }
-    
-    let total_distance = distances.last().unwrap();
-    let num_points = (total_distance / interval).ceil() as usize + 1;
-    
-    let mut uniform_distances = Vec::with_capacity(num_points);
-    let mut uniform_elevations = Vec::with_capacity(num_points);
-    
-    for i in 0..num_points {
-        let target_distance = i as f64 * interval;
-        if target_distance > *total_distance {
-            break;
-        }
-        uniform_distances.push(target_distance);
-        
-        // Use cubic interpolation for smoother results
-        let elevation = interpolate_elevation_cubic(elevations, distances, target_distance);
-        uniform_elevations.push(elevation);
-    }
-    
-    (uniform_distances, uniform_elevations)
-}
-
-fn interpolate_elevation_cubic(
-    elevations: &[f64],
-    distances: &[f64],
-    target_distance: f64
-) -> f64 {
-    if target_distance <= 0.0 {
-        return elevations[0];
-    }
-    
-    // Find the segment containing target_distance
-    for i in 1..distances.len() {
-        if distances[i] >= target_distance {
-            // Use linear interpolation (cubic would be complex without external libs)
-            let d1 = distances[i - 1];
-            let d2 = distances[i];
-            let e1 = elevations[i - 1];
-            let e2 = elevations[i];
-            
-            if (d2 - d1).abs() < 1e-10 {
-                return e1;
-            }
-            
-            let t = (target_distance - d1) / (d2 - d1);
-            return e1 + t * (e2 - e1);
-        }
-    }
-    
-    *elevations.last().unwrap()
-}
-
-fn median_filter_optimized(data: &[f64], window: usize) -> Vec<f64> {
-    let mut result = Vec::with_capacity(data.len());
-    
-    for i in 0..data.len() {
-        let start = if i >= window / 2 { i - window / 2 } else { 0 };
-        let end = if i + window / 2 < data.len() { i + window / 2 } else { data.len() - 1 };
-        
-        let mut window_data: Vec<f64> = data[start..=end].to_vec();
-        window_data.sort_by(|a, b| a.partial_cmp(b).unwrap());
-        
-        let median = if window_data.len() % 2 == 0 {
-            (window_data[window_data.len() / 2 - 1] + window_data[window_data.len() / 2]) / 2.0
-        } else {
-            window_data[window_data.len() / 2]
-        };
-        
-        result.push(median);
-    }
-    
-    result
-}
-
-fn gaussian_smooth_optimized(data: &[f64], window: usize) -> Vec<f64> {
-    let mut result = Vec::with_capacity(data.len());
-    let sigma = window as f64 / 6.0;
-    
-    for i in 0..data.len() {
-        let start = if i >= window / 2 { i - window / 2 } else { 0 };
-        let end = if i + window / 2 < data.len() { i + window / 2 } else { data.len() - 1 };
-        
-        let mut weighted_sum = 0.0;
-        let mut weight_sum = 0.0;
-        
-        for j in start..=end {
-            let distance = (j as f64 - i as f64).abs();
-            let weight = (-0.5 * (distance / sigma).powi(2)).exp();
-            
-            weighted_sum += data[j] * weight;
-            weight_sum += weight;
-        }
-        
-        result.push(weighted_sum / weight_sum);
-    }
-    
-    result
-}
-
 fn calculate_optimal_gaussian_window(interval: f64) -> usize {
     // Adaptive window size based on interval
     let base_window = (120.0 / interval).round() as usize;
@@ -580,82 +420,6 @@ fn apply_precision_deadband(elevations: &[f64], threshold: f64) -> Vec<f64> {
     filtered
 }
 
-fn savitzky_golay_filter_optimized(
-    data: &[f64],
-    window_size: usize,
-    poly_order: usize
-) -> Vec<f64> {
-    if window_size < 5 || window_size >= data.len() || poly_order >= window_size {
-        return data.to_vec();
-    }
-    
-    let mut result = Vec::with_capacity(data.len());
-    let half_window = window_size / 2;
-    
-    // Generate Savitzky-Golay coefficients (simplified implementation)
-    let coeffs = generate_savgol_coefficients_optimized(window_size, poly_order);
-    
-    for i in 0..data.len() {
-        let start = if i >= half_window { i - half_window } else { 0 };
-        let end = if i + half_window < data.len() { i + half_window } else { data.len() - 1 };
-        
-        let mut smoothed_value = 0.0;
-        let mut weight_sum = 0.0;
-        
-        for (j, &value) in data[start..=end].iter().enumerate() {
-            let coeff = coeffs.get(j).copied().unwrap_or(1.0);
-            smoothed_value += value * coeff;
-            weight_sum += coeff;
-        }
-        
-        result.push(smoothed_value / weight_sum);
-    }
-    
-    result
-}
-
-fn generate_savgol_coefficients_optimized(window_size: usize, poly_order: usize) -> Vec<f64> {
-    // Simplified Savitzky-Golay coefficients generation
-    let mut coeffs = vec![1.0; window_size];
-    let center = window_size / 2;
-    
-    match poly_order {
-        2 => {
-            // 2nd order polynomial - parabolic weighting
-            for i in 0..window_size {
-                let distance = (i as f64 - center as f64).abs();
-                let normalized_dist = distance / center as f64;
-                coeffs[i] = 1.0 - normalized_dist * normalized_dist;
-            }
-        },
-        3 => {
-            // 3rd order polynomial - cubic weighting
-            for i in 0..window_size {
-                let distance = (i as f64 - center as f64).abs();
-                let normalized_dist = distance / center as f64;
-                coeffs[i] = 1.0 - normalized_dist * normalized_dist * normalized_dist;
-            }
-        },
-        4 => {
-            // 4th order polynomial - quartic weighting
-            for i in 0..window_size {
-                let distance = (i as f64 - center as f64).abs();
-                let normalized_dist = distance / center as f64;
-                coeffs[i] = 1.0 - normalized_dist.powi(4);
-            }
-        },
-        _ => {
-            // Default triangular weighting
-            for i in 0..window_size {
-                let distance = (i as f64 - center as f64).abs();
-                coeffs[i] = (window_size as f64 - distance) / window_size as f64;
-            }
-        }
-    }
-    
-    coeffs
-}
-
 fn calculate_precise_gain_loss(elevations: &[f64]) -> (f32, f32) {
     let mut gain = 0.0;
     let mut loss = 0.0;
@@ -685,6 +449,7 @@ fn create_precision_result(
             files_within_5_percent: 0,
             files_within_10_percent: 0,
             files_outside_20_percent: 0,
+            files_with_official_loss: 0,
             gain_accuracy_avg: 0.0,
             loss_accuracy_avg: 0.0,
             gain_accuracy_median: 0.0,
@@ -702,7 +467,8 @@ fn create_precision_result(
     
     // Extract accuracy vectors
     let gain_accuracies: Vec<f32> = file_results.iter().map(|r| r.gain_accuracy).collect();
-    let loss_accuracies: Vec<f32> = file_results.iter().map(|r| r.loss_accuracy).collect();
+    let loss_accuracies: Vec<f32> = file_results.iter().filter_map(|r| r.loss_accuracy).collect();
+    let files_with_official_loss = loss_accuracies.len() as u32;
     let combined_errors: Vec<f32> = file_results.iter().map(|r| r.combined_error).collect();
     
     // Count files in precision bands
@@ -713,7 +479,7 @@ fn create_precision_result(
     
     // Calculate accuracy statistics
     let gain_accuracy_avg = gain_accuracies.iter().sum::<f32>() / total_files as f32;
-    let loss_accuracy_avg = loss_accuracies.iter().sum::<f32>() / total_files as f32;
+    let loss_accuracy_avg = loss_accuracies.iter().sum::<f32>() / files_with_official_loss.max(1) as f32;
     
     let mut sorted_gain = gain_accuracies.clone();
     sorted_gain.sort_by(|a, b| a.partial_cmp(b).unwrap());
@@ -725,7 +491,9 @@ fn create_precision_result(
     
     let mut sorted_loss = loss_accuracies.clone();
     sorted_loss.sort_by(|a, b| a.partial_cmp(b).unwrap());
-    let loss_accuracy_median = if sorted_loss.len() % 2 == 0 {
+    let loss_accuracy_median = if sorted_loss.is_empty() {
+        0.0
+    } else if sorted_loss.len() % 2 == 0 {
         (sorted_loss[sorted_loss.len() / 2 - 1] + sorted_loss[sorted_loss.len() / 2]) / 2.0
     } else {
         sorted_loss[sorted_loss.len() / 2]
@@ -745,12 +513,13 @@ fn create_precision_result(
         .max_by_key(|&&acc| ((acc - 100.0).abs() * 1000.0) as i32)
         .copied().unwrap_or(100.0);
     
-    // Calculate precision score (higher is better)
+    // Calculate precision score (higher is better); without any official loss, gain alone counts
+    let loss_error_avg = if files_with_official_loss > 0 { (loss_accuracy_avg - 100.0).abs() } else { 0.0 };
     let precision_score = (files_within_2_percent as f32 * 20.0) +
                          (files_within_5_percent as f32 * 10.0) +
                          (files_within_10_percent as f32 * 5.0) -
                          (files_outside_20_percent as f32 * 10.0) +
-                         (200.0 - (gain_accuracy_avg - 100.0).abs() - (loss_accuracy_avg - 100.0).abs());
+                         (200.0 - (gain_accuracy_avg - 100.0).abs() - loss_error_avg);
     
     PrecisionResult {
         method_name,
@@ -759,6 +528,7 @@ fn create_precision_result(
         files_within_5_percent,
         files_within_10_percent,
         files_outside_20_percent,
+        files_with_official_loss,
         gain_accuracy_avg,
         loss_accuracy_avg,
         gain_accuracy_median,
@@ -782,7 +552,7 @@ fn write_precision_results(
     wtr.write_record(&[
         "Method", "Parameter", "Precision_Score",
         "Files_±2%", "Files_±5%", "Files_±10%", "Files_>20%",
-        "Gain_Avg_%", "Loss_Avg_%", "Gain_Median_%", "Loss_Median_%",
+        "Files_With_Official_Loss", "Gain_Avg_%", "Loss_Avg_%", "Gain_Median_%", "Loss_Median_%",
         "Best_Gain_%", "Worst_Gain_%", "Best_Loss_%", "Worst_Loss_%",
         "Total_Files"
     ])?;
@@ -801,6 +571,7 @@ fn write_precision_results(
             &result.files_within_5_percent.to_string(),
             &result.files_within_10_percent.to_string(),
             &result.files_outside_20_percent.to_string(),
+            &result.files_with_official_loss.to_string(),
             &format!("{:.2}", result.gain_accuracy_avg),
             &format!("{:.2}", result.loss_accuracy_avg),
             &format!("{:.2}", result.gain_accuracy_median),
@@ -843,8 +614,8 @@ fn print_precision_analysis(results: &[PrecisionResult]) {
              (best_overall.files_within_10_percent as f32 / best_overall.total_files as f32) * 100.0);
     println!("Gain accuracy: {:.2}% (median: {:.2}%)", 
              best_overall.gain_accuracy_avg, best_overall.gain_accuracy_median);
-    println!("Loss accuracy: {:.2}% (median: {:.2}%)", 
-             best_overall.loss_accuracy_avg, best_overall.loss_accuracy_median);
+    println!("Loss accuracy: {:.2}% (median: {:.2}%, {} files with official loss)", 
+             best_overall.loss_accuracy_avg, best_overall.loss_accuracy_median, best_overall.files_with_official_loss);
     
     // Find best in each category
     let best_distance = sorted_results.iter()
@@ -0,0 +1,575 @@
+/// SEARCH STRATEGY - Budgeted, seeded black-box minimisation over continuous parameters
+/// Replaces exhaustive grids when tuning smoothers: random search, coordinate descent,
+/// Bayesian optimisation with a Gaussian-process surrogate, and CMA-ES. Every strategy
+/// works in the unit cube and maps to the real ranges only to call the objective, stops
+/// at an evaluation count or wall-clock limit, and is fully reproducible from its seed.
+use std::time::{Duration, Instant};
+
+// Coordinate descent: first step (unit-cube fraction) and the step at which it restarts
+const COORDINATE_INITIAL_STEP: f64 = 0.25;
+const COORDINATE_MIN_STEP: f64 = 1e-3;
+// Gaussian process: length scales tried by marginal likelihood, nugget for flat objectives
+const GP_LENGTH_SCALES: [f64; 4] = [0.1, 0.2, 0.4, 0.8];
+const GP_NUGGET: f64 = 1e-4;
+const GP_MAX_POINTS: usize = 200;
+const GP_CANDIDATES: usize = 1000;
+const GP_LOCAL_CANDIDATES: usize = 200;
+// CMA-ES: initial step size and the size at which a run is restarted
+const CMA_INITIAL_SIGMA: f64 = 0.3;
+const CMA_MIN_SIGMA: f64 = 1e-4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchStrategy {
+    Random,
+    CoordinateDescent,
+    Bayesian,
+    CmaEs,
+}
+
+impl SearchStrategy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SearchStrategy::Random => "random",
+            SearchStrategy::CoordinateDescent => "coordinate-descent",
+            SearchStrategy::Bayesian => "bayesian",
+            SearchStrategy::CmaEs => "cma-es",
+        }
+    }
+}
+
+impl std::str::FromStr for SearchStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let key: String = s.chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_lowercase();
+
+        match key.as_str() {
+            "random" => Ok(SearchStrategy::Random),
+            "coordinatedescent" | "coordinate" => Ok(SearchStrategy::CoordinateDescent),
+            "bayesian" | "bayes" | "gp" => Ok(SearchStrategy::Bayesian),
+            "cmaes" | "cma" => Ok(SearchStrategy::CmaEs),
+            _ => Err(format!(
+                "unknown search strategy '{}' (expected random, coordinate-descent, bayesian or cma-es)",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ParameterRange {
+    pub name: String,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl ParameterRange {
+    pub fn new(name: &str, min: f64, max: f64) -> Self {
+        ParameterRange {
+            name: name.to_string(),
+            min,
+            max,
+        }
+    }
+
+    fn value_at(&self, unit: f64) -> f64 {
+        self.min + unit.clamp(0.0, 1.0) * (self.max - self.min)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SearchBudget {
+    pub max_evaluations: usize,
+    pub max_duration: Option<Duration>,
+}
+
+impl Default for SearchBudget {
+    fn default() -> Self {
+        SearchBudget {
+            max_evaluations: 200,
+            max_duration: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Evaluation {
+    pub values: Vec<f64>,
+    pub score: f64,
+    pub elapsed_s: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub strategy: SearchStrategy,
+    pub evaluations: Vec<Evaluation>,
+    pub elapsed: Duration,
+}
+
+impl SearchResult {
+    /// Lowest-scoring evaluation
+    pub fn best(&self) -> Option<&Evaluation> {
+        self.evaluations.iter()
+            .min_by(|a, b| a.score.partial_cmp(&b.score).unwrap_or(std::cmp::Ordering::Equal))
+    }
+}
+
+/// Deterministic generator (SplitMix64) so a seed reproduces a search exactly
+#[derive(Debug, Clone)]
+pub struct SearchRng {
+    state: u64,
+}
+
+impl SearchRng {
+    pub fn new(seed: u64) -> Self {
+        SearchRng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform on [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Standard normal (Box-Muller)
+    pub fn normal(&mut self) -> f64 {
+        let u1 = self.next_f64().max(f64::MIN_POSITIVE);
+        let u2 = self.next_f64();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+    }
+
+    fn unit_point(&mut self, dimensions: usize) -> Vec<f64> {
+        (0..dimensions).map(|_| self.next_f64()).collect()
+    }
+}
+
+/// Records every evaluation and enforces the budget
+struct Evaluator<'a> {
+    ranges: &'a [ParameterRange],
+    budget: SearchBudget,
+    start: Instant,
+    objective: &'a dyn Fn(&[f64]) -> f64,
+    evaluations: Vec<Evaluation>,
+    unit_points: Vec<Vec<f64>>,
+}
+
+impl Evaluator<'_> {
+    fn exhausted(&self) -> bool {
+        self.evaluations.len() >= self.budget.max_evaluations
+            || self.budget.max_duration.is_some_and(|limit| self.start.elapsed() >= limit)
+    }
+
+    /// Score a unit-cube point (clamped into the cube first)
+    fn evaluate(&mut self, unit: &[f64]) -> f64 {
+        let unit: Vec<f64> = unit.iter().map(|u| u.clamp(0.0, 1.0)).collect();
+        let values: Vec<f64> = self.ranges.iter().zip(&unit).map(|(r, &u)| r.value_at(u)).collect();
+        let score = (self.objective)(&values);
+        // A failed evaluation must never win
+        let score = if score.is_finite() { score } else { f64::MAX };
+        self.evaluations.push(Evaluation {
+            values,
+            score,
+            elapsed_s: self.start.elapsed().as_secs_f64(),
+        });
+        self.unit_points.push(unit);
+        score
+    }
+}
+
+/// Minimise `objective` over `ranges` with the given strategy, budget and seed.
+/// The objective receives real parameter values in range order; lower is better.
+pub fn run_search(
+    strategy: SearchStrategy,
+    ranges: &[ParameterRange],
+    budget: SearchBudget,
+    seed: u64,
+    objective: &dyn Fn(&[f64]) -> f64,
+) -> SearchResult {
+    let mut evaluator = Evaluator {
+        ranges,
+        budget,
+        start: Instant::now(),
+        objective,
+        evaluations: Vec::new(),
+        unit_points: Vec::new(),
+    };
+    let mut rng = SearchRng::new(seed);
+
+    if !ranges.is_empty() {
+        match strategy {
+            SearchStrategy::Random => random_search(&mut evaluator, &mut rng),
+            SearchStrategy::CoordinateDescent => coordinate_descent(&mut evaluator, &mut rng),
+            SearchStrategy::Bayesian => bayesian_search(&mut evaluator, &mut rng),
+            SearchStrategy::CmaEs => cma_es(&mut evaluator, &mut rng),
+        }
+    }
+
+    SearchResult {
+        strategy,
+        elapsed: evaluator.start.elapsed(),
+        evaluations: evaluator.evaluations,
+    }
+}
+
+fn random_search(evaluator: &mut Evaluator, rng: &mut SearchRng) {
+    let dimensions = evaluator.ranges.len();
+    while !evaluator.exhausted() {
+        let point = rng.unit_point(dimensions);
+        evaluator.evaluate(&point);
+    }
+}
+
+/// Pattern search along each axis, halving the step when no move helps;
+/// restarts from a random point once the step collapses
+fn coordinate_descent(evaluator: &mut Evaluator, rng: &mut SearchRng) {
+    let dimensions = evaluator.ranges.len();
+    let mut current = vec![0.5; dimensions];
+
+    while !evaluator.exhausted() {
+        let mut current_score = evaluator.evaluate(&current);
+        let mut step = COORDINATE_INITIAL_STEP;
+
+        while step >= COORDINATE_MIN_STEP && !evaluator.exhausted() {
+            let mut improved = false;
+            for dimension in 0..dimensions {
+                for direction in [1.0, -1.0] {
+                    let mut candidate = current.clone();
+                    candidate[dimension] = (current[dimension] + direction * step).clamp(0.0, 1.0);
+                    if candidate[dimension] == current[dimension] || evaluator.exhausted() {
+                        continue;
+                    }
+                    let score = evaluator.evaluate(&candidate);
+                    if score < current_score {
+                        current = candidate;
+                        current_score = score;
+                        improved = true;
+                        break;
+                    }
+                }
+            }
+            if !improved {
+                step *= 0.5;
+            }
+        }
+
+        current = rng.unit_point(dimensions);
+    }
+}
+
+/// Gaussian-process surrogate (RBF kernel) with expected improvement, maximised over
+/// random candidates plus perturbations of the best point so far
+fn bayesian_search(evaluator: &mut Evaluator, rng: &mut SearchRng) {
+    let dimensions = evaluator.ranges.len();
+    let initial_points = (2 * dimensions).max(5);
+
+    while !evaluator.exhausted() && evaluator.evaluations.len() < initial_points {
+        let point = rng.unit_point(dimensions);
+        evaluator.evaluate(&point);
+    }
+
+    while !evaluator.exhausted() {
+        // Fit on the best points only, so each step stays cheap on long runs
+        let mut order: Vec<usize> = (0..evaluator.evaluations.len()).collect();
+        order.sort_by(|&a, &b| evaluator.evaluations[a].score.partial_cmp(&evaluator.evaluations[b].score).unwrap());
+        order.truncate(GP_MAX_POINTS);
+        let points: Vec<Vec<f64>> = order.iter().map(|&i| evaluator.unit_points[i].clone()).collect();
+        let scores: Vec<f64> = order.iter().map(|&i| evaluator.evaluations[i].score).collect();
+
+        let next = match GaussianProcess::fit(&points, &scores) {
+            Some(gp) => {
+                let best = points[0].clone();
+                let mut candidates: Vec<Vec<f64>> = (0..GP_CANDIDATES).map(|_| rng.unit_point(dimensions)).collect();
+                for _ in 0..GP_LOCAL_CANDIDATES {
+                    candidates.push(best.iter().map(|&b| (b + 0.05 * rng.normal()).clamp(0.0, 1.0)).collect());
+                }
+                candidates.into_iter()
+                    .map(|c| (gp.expected_improvement(&c), c))
+                    .max_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal))
+                    .map(|(_, c)| c)
+                    .unwrap_or_else(|| rng.unit_point(dimensions))
+            }
+            None => rng.unit_point(dimensions),
+        };
+
+        evaluator.evaluate(&next);
+    }
+}
+
+struct GaussianProcess {
+    points: Vec<Vec<f64>>,
+    cholesky: Vec<Vec<f64>>,
+    alpha: Vec<f64>,
+    length_scale: f64,
+    mean: f64,
+    scale: f64,
+    best_score: f64,
+}
+
+impl GaussianProcess {
+    /// Fit on standardised scores, picking the length scale with the highest marginal likelihood
+    fn fit(points: &[Vec<f64>], scores: &[f64]) -> Option<Self> {
+        let n = scores.len() as f64;
+        let mean = scores.iter().sum::<f64>() / n;
+        let variance = scores.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / n;
+        let scale = if variance > 0.0 { variance.sqrt() } else { 1.0 };
+        let y: Vec<f64> = scores.iter().map(|s| (s - mean) / scale).collect();
+
+        let mut best_fit: Option<(f64, GaussianProcess)> = None;
+        for &length_scale in &GP_LENGTH_SCALES {
+            let mut kernel: Vec<Vec<f64>> = points.iter()
+                .map(|a| points.iter().map(|b| rbf(a, b, length_scale)).collect())
+                .collect();
+            for (i, row) in kernel.iter_mut().enumerate() {
+                row[i] += GP_NUGGET;
+            }
+            let Some(l) = cholesky(&kernel) else {
+                continue;
+            };
+            let alpha = cholesky_solve(&l, &y);
+            let log_likelihood = -0.5 * y.iter().zip(&alpha).map(|(a, b)| a * b).sum::<f64>()
+                - (0..l.len()).map(|i| l[i][i].ln()).sum::<f64>();
+
+            if best_fit.as_ref().is_none_or(|(ll, _)| log_likelihood > *ll) {
+                best_fit = Some((log_likelihood, GaussianProcess {
+                    points: points.to_vec(),
+                    cholesky: l,
+                    alpha,
+                    length_scale,
+                    mean,
+                    scale,
+                    best_score: scores.iter().cloned().fold(f64::INFINITY, f64::min),
+                }));
+            }
+        }
+
+        best_fit.map(|(_, gp)| gp)
+    }
+
+    /// Posterior mean and standard deviation of the score at `point`
+    fn predict(&self, point: &[f64]) -> (f64, f64) {
+        let k: Vec<f64> = self.points.iter().map(|p| rbf(p, point, self.length_scale)).collect();
+        let mu: f64 = k.iter().zip(&self.alpha).map(|(a, b)| a * b).sum();
+        let v = forward_substitute(&self.cholesky, &k);
+        let variance = (1.0 + GP_NUGGET - v.iter().map(|x| x * x).sum::<f64>()).max(1e-12);
+        (self.mean + self.scale * mu, self.scale * variance.sqrt())
+    }
+
+    fn expected_improvement(&self, point: &[f64]) -> f64 {
+        let (mean, sigma) = self.predict(point);
+        let improvement = self.best_score - mean;
+        let z = improvement / sigma;
+        improvement * normal_cdf(z) + sigma * normal_pdf(z)
+    }
+}
+
+fn rbf(a: &[f64], b: &[f64], length_scale: f64) -> f64 {
+    let distance_sq: f64 = a.iter().zip(b).map(|(x, y)| (x - y).powi(2)).sum();
+    (-0.5 * distance_sq / (length_scale * length_scale)).exp()
+}
+
+fn normal_pdf(z: f64) -> f64 {
+    (-0.5 * z * z).exp() / (2.0 * std::f64::consts::PI).sqrt()
+}
+
+fn normal_cdf(z: f64) -> f64 {
+    0.5 * (1.0 + erf(z / std::f64::consts::SQRT_2))
+}
+
+/// Abramowitz-Stegun 7.1.26 (max error 1.5e-7)
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs());
+    let poly = t * (0.254829592 + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    let value = 1.0 - poly * (-x * x).exp();
+    if x >= 0.0 { value } else { -value }
+}
+
+/// Lower-triangular L with L·Lᵀ = matrix, or None if not positive definite
+fn cholesky(matrix: &[Vec<f64>]) -> Option<Vec<Vec<f64>>> {
+    let n = matrix.len();
+    let mut l = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        for j in 0..=i {
+            let sum: f64 = (0..j).map(|k| l[i][k] * l[j][k]).sum();
+            if i == j {
+                let diagonal = matrix[i][i] - sum;
+                if diagonal <= 0.0 {
+                    return None;
+                }
+                l[i][j] = diagonal.sqrt();
+            } else {
+                l[i][j] = (matrix[i][j] - sum) / l[j][j];
+            }
+        }
+    }
+    Some(l)
+}
+
+fn forward_substitute(l: &[Vec<f64>], b: &[f64]) -> Vec<f64> {
+    let mut x = vec![0.0; b.len()];
+    for i in 0..b.len() {
+        let sum: f64 = (0..i).map(|k| l[i][k] * x[k]).sum();
+        x[i] = (b[i] - sum) / l[i][i];
+    }
+    x
+}
+
+fn cholesky_solve(l: &[Vec<f64>], b: &[f64]) -> Vec<f64> {
+    let y = forward_substitute(l, b);
+    let n = y.len();
+    let mut x = vec![0.0; n];
+    for i in (0..n).rev() {
+        let sum: f64 = (i + 1..n).map(|k| l[k][i] * x[k]).sum();
+        x[i] = (y[i] - sum) / l[i][i];
+    }
+    x
+}
+
+/// One CMA-ES sample: its score, standard-normal draw z and step y = A·z
+struct Offspring {
+    score: f64,
+    z: Vec<f64>,
+    y: Vec<f64>,
+}
+
+/// (mu/mu_w, lambda)-CMA-ES with rank-one and rank-mu covariance updates. Samples use the
+/// Cholesky factor of C; points outside the cube are resampled, then clamped. Restarts from
+/// a random mean when the step size collapses.
+fn cma_es(evaluator: &mut Evaluator, rng: &mut SearchRng) {
+    let n = evaluator.ranges.len();
+    let nf = n as f64;
+    let lambda = 4 + (3.0 * nf.ln()).floor() as usize;
+    let mu = lambda / 2;
+    let raw_weights: Vec<f64> = (0..mu).map(|i| (mu as f64 + 0.5).ln() - ((i + 1) as f64).ln()).collect();
+    let weight_sum: f64 = raw_weights.iter().sum();
+    let weights: Vec<f64> = raw_weights.iter().map(|w| w / weight_sum).collect();
+    let mueff = 1.0 / weights.iter().map(|w| w * w).sum::<f64>();
+
+    let cc = (4.0 + mueff / nf) / (nf + 4.0 + 2.0 * mueff / nf);
+    let cs = (mueff + 2.0) / (nf + mueff + 5.0);
+    let c1 = 2.0 / ((nf + 1.3).powi(2) + mueff);
+    let cmu = (1.0 - c1).min(2.0 * (mueff - 2.0 + 1.0 / mueff) / ((nf + 2.0).powi(2) + mueff));
+    let damps = 1.0 + 2.0 * (((mueff - 1.0) / (nf + 1.0)).sqrt() - 1.0).max(0.0) + cs;
+    let chi_n = nf.sqrt() * (1.0 - 1.0 / (4.0 * nf) + 1.0 / (21.0 * nf * nf));
+
+    let mut mean = vec![0.5; n];
+    while !evaluator.exhausted() {
+        let mut sigma = CMA_INITIAL_SIGMA;
+        let mut covariance = identity(n);
+        let mut path_c = vec![0.0; n];
+        let mut path_s = vec![0.0; n];
+        let mut generation = 0;
+
+        while sigma >= CMA_MIN_SIGMA && !evaluator.exhausted() {
+            let a = cholesky(&covariance).unwrap_or_else(|| {
+                covariance = identity(n);
+                identity(n)
+            });
+
+            let mut offspring: Vec<Offspring> = Vec::with_capacity(lambda);
+            for _ in 0..lambda {
+                if evaluator.exhausted() {
+                    return;
+                }
+                let mut sample = None;
+                for _ in 0..10 {
+                    let z: Vec<f64> = (0..n).map(|_| rng.normal()).collect();
+                    let y: Vec<f64> = (0..n).map(|i| (0..=i).map(|k| a[i][k] * z[k]).sum()).collect();
+                    let inside = (0..n).all(|i| (0.0..=1.0).contains(&(mean[i] + sigma * y[i])));
+                    sample = Some((z, y));
+                    if inside {
+                        break;
+                    }
+                }
+                let (z, y) = sample.unwrap();
+                let x: Vec<f64> = (0..n).map(|i| mean[i] + sigma * y[i]).collect();
+                let score = evaluator.evaluate(&x);
+                offspring.push(Offspring { score, z, y });
+            }
+            offspring.sort_by(|a, b| a.score.partial_cmp(&b.score).unwrap());
+
+            let weighted = |pick: fn(&Offspring) -> &Vec<f64>| -> Vec<f64> {
+                (0..n).map(|i| (0..mu).map(|k| weights[k] * pick(&offspring[k])[i]).sum()).collect()
+            };
+            let z_w = weighted(|o| &o.z);
+            let y_w = weighted(|o| &o.y);
+
+            for i in 0..n {
+                mean[i] = (mean[i] + sigma * y_w[i]).clamp(0.0, 1.0);
+                path_s[i] = (1.0 - cs) * path_s[i] + (cs * (2.0 - cs) * mueff).sqrt() * z_w[i];
+            }
+            generation += 1;
+            let path_s_norm = path_s.iter().map(|p| p * p).sum::<f64>().sqrt();
+            let hsig = path_s_norm / (1.0 - (1.0 - cs).powi(2 * generation)).sqrt() / chi_n < 1.4 + 2.0 / (nf + 1.0);
+            let hsig = if hsig { 1.0 } else { 0.0 };
+            for i in 0..n {
+                path_c[i] = (1.0 - cc) * path_c[i] + hsig * (cc * (2.0 - cc) * mueff).sqrt() * y_w[i];
+            }
+
+            for i in 0..n {
+                for j in 0..n {
+                    let rank_mu: f64 = (0..mu).map(|k| weights[k] * offspring[k].y[i] * offspring[k].y[j]).sum();
+                    covariance[i][j] = (1.0 - c1 - cmu) * covariance[i][j]
+                        + c1 * (path_c[i] * path_c[j] + (1.0 - hsig) * cc * (2.0 - cc) * covariance[i][j])
+                        + cmu * rank_mu;
+                }
+            }
+
+            sigma = (sigma * ((cs / damps) * (path_s_norm / chi_n - 1.0)).exp()).min(1.0);
+        }
+
+        mean = rng.unit_point(n);
+    }
+}
+
+fn identity(n: usize) -> Vec<Vec<f64>> {
+    (0..n).map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_strategy_finds_quadratic_minimum_reproducibly() {
+        let ranges = vec![
+            ParameterRange::new("a", -5.0, 5.0),
+            ParameterRange::new("b", 0.0, 10.0),
+            ParameterRange::new("c", 0.0, 1.0),
+        ];
+        let objective = |v: &[f64]| (v[0] - 1.0).powi(2) + (v[1] - 7.0).powi(2) + 10.0 * (v[2] - 0.3).powi(2);
+        for strategy in [SearchStrategy::Random, SearchStrategy::CoordinateDescent, SearchStrategy::Bayesian, SearchStrategy::CmaEs] {
+            // The surrogate is expensive per step but needs far fewer evaluations
+            let max_evaluations = if strategy == SearchStrategy::Bayesian { 40 } else { 150 };
+            let budget = SearchBudget { max_evaluations, max_duration: None };
+            let result = run_search(strategy, &ranges, budget, 7, &objective);
+            let repeat = run_search(strategy, &ranges, budget, 7, &objective);
+
+            assert_eq!(result.evaluations.len(), max_evaluations, "{} ignored the budget", strategy.name());
+            let best = result.best().unwrap();
+            assert_eq!(best.values, repeat.best().unwrap().values, "{} is not reproducible", strategy.name());
+            // Random search is the baseline; the model-based strategies must do much better
+            let tolerance = if strategy == SearchStrategy::Random { 2.0 } else { 0.05 };
+            assert!(best.score < tolerance, "{} only reached {}", strategy.name(), best.score);
+        }
+    }
+
+    #[test]
+    fn test_gaussian_process_interpolates_observations() {
+        let points: Vec<Vec<f64>> = (0..8).map(|i| vec![i as f64 / 7.0]).collect();
+        let scores: Vec<f64> = points.iter().map(|p| (p[0] * 3.0).sin()).collect();
+        let gp = GaussianProcess::fit(&points, &scores).unwrap();
+        for (point, score) in points.iter().zip(&scores) {
+            assert!((gp.predict(point).0 - score).abs() < 0.05);
+        }
+    }
+}
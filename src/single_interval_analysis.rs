@@ -14,6 +14,9 @@ use gpx::{read, Gpx};
 use geo::{HaversineDistance, point};
 use walkdir::WalkDir;
 use crate::custom_smoother::{ElevationData, SmoothingVariant};
+use crate::ground_truth::GroundTruthRegistry;
+use crate::error::{error_kind, TrackError};
+use crate::track_loader::{gpx_course_segments, gpx_geometry, read_gpx_file};
 
 // TARGET INTERVAL: Based on focused symmetric analysis results
 const TARGET_INTERVAL_M: f64 = 1.9;
@@ -37,6 +40,10 @@ pub struct SingleIntervalResult {
     official_elevation_gain_m: u32,
     accuracy_percent: f64,
     absolute_error_m: f64,
+    official_elevation_loss_m: Option<u32>,
+    loss_accuracy_percent: f64,      // 0 without an official loss
+    official_distance_km: Option<f64>,
+    distance_accuracy_percent: f64,  // 0 without an official distance
     
     // Gain/Loss balance metrics
     gain_loss_ratio: f64,
@@ -51,6 +58,8 @@ pub struct SingleIntervalResult {
     // Quality indicators
     similarity_to_official: String,
     accuracy_rating: String,
+    loss_accuracy_rating: String,
+    distance_accuracy_rating: String,
     balance_rating: String,
     
     // Error details (if any)
@@ -94,11 +103,19 @@ pub struct AnalysisSummary {
 }
 
 pub fn run_single_interval_analysis(gpx_folder: &str) -> Result<(), Box<dyn std::error::Error>> {
+    run_single_interval_analysis_with_interval(gpx_folder, TARGET_INTERVAL_M)
+}
+
+/// Same analysis with a caller-chosen interval instead of the 1.9m optimum
+pub fn run_single_interval_analysis_with_interval(
+    gpx_folder: &str,
+    interval_m: f64
+) -> Result<(), Box<dyn std::error::Error>> {
     let total_start = std::time::Instant::now();
     
-    println!("\n🎯 1.9M SYMMETRIC ANALYSIS WITH GPX REPAIR");
+    println!("\n🎯 {:.1}M SYMMETRIC ANALYSIS WITH GPX REPAIR", interval_m);
     println!("==========================================");
-    println!("🏆 OPTIMAL INTERVAL: {:.1}m with SymmetricFixed method", TARGET_INTERVAL_M);
+    println!("🏆 OPTIMAL INTERVAL: {:.1}m with SymmetricFixed method", interval_m);
     println!("   • Scientifically proven optimal from focused analysis");
     println!("   • Symmetric deadband filtering (fixes loss under-estimation)");
     println!("   • Advanced GPX file repair capabilities");
@@ -117,14 +134,14 @@ pub fn run_single_interval_analysis(gpx_folder: &str) -> Result<(), Box<dyn std:
         preprocessed_folder
     } else {
         println!("📂 No preprocessed folder found - processing raw GPX files with repair");
-        println!("💡 Tip: Run option 15 first to preprocess files for faster analysis");
+        println!("💡 Tip: Run the `preprocess` command first to preprocess files for faster analysis");
         println!("📂 Source: {}", gpx_folder);
         gpx_folder.to_string()
     };
     
     // Load official elevation data
     println!("📂 Loading official elevation data...");
-    let official_data = crate::load_official_elevation_data()?;
+    let official_data = crate::load_ground_truth_registry()?;
     println!("✅ Loaded {} official elevation records", official_data.len());
     
     // Collect all GPX files
@@ -135,30 +152,32 @@ pub fn run_single_interval_analysis(gpx_folder: &str) -> Result<(), Box<dyn std:
     // Process each file individually
     let processing_start = std::time::Instant::now();
     let (results, errors) = if use_preprocessed {
-        process_all_files_preprocessed(&gpx_files, &official_data)
+        process_all_files_preprocessed(&gpx_files, &official_data, interval_m)
     } else {
-        process_all_files(&gpx_files, &official_data)
+        process_all_files(&gpx_files, &official_data, interval_m)
     };
     println!("✅ Processing complete in {:.2}s", processing_start.elapsed().as_secs_f64());
+    official_data.print_match_report();
     
     // Calculate summary statistics
     let summary = calculate_analysis_summary(&results, &errors);
     
     // Write detailed results to CSV files
     let output_folder = Path::new(gpx_folder);
-    write_results_csv(&results, &output_folder.join("1.9m_symmetric_detailed_results.csv"))?;
-    write_errors_csv(&errors, &output_folder.join("1.9m_symmetric_processing_errors.csv"))?;
-    write_summary_csv(&summary, &output_folder.join("1.9m_symmetric_analysis_summary.csv"))?;
+    let prefix = format!("{:.1}m_symmetric", interval_m);
+    write_results_csv(&results, &output_folder.join(format!("{}_detailed_results.csv", prefix)))?;
+    write_errors_csv(&errors, &output_folder.join(format!("{}_processing_errors.csv", prefix)))?;
+    write_summary_csv(&summary, &output_folder.join(format!("{}_analysis_summary.csv", prefix)))?;
     
     // Print comprehensive analysis
-    print_detailed_analysis(&results, &errors, &summary);
+    print_detailed_analysis(&results, &errors, &summary, interval_m);
     
     let total_time = total_start.elapsed();
     println!("\n⏱️  TOTAL EXECUTION TIME: {:.1} seconds", total_time.as_secs_f64());
     println!("📁 Results saved to folder: {}", gpx_folder);
-    println!("   • 1.9m_symmetric_detailed_results.csv - Individual file results");
-    println!("   • 1.9m_symmetric_processing_errors.csv - Files that failed processing");
-    println!("   • 1.9m_symmetric_analysis_summary.csv - Summary statistics");
+    println!("   • {}_detailed_results.csv - Individual file results", prefix);
+    println!("   • {}_processing_errors.csv - Files that failed processing", prefix);
+    println!("   • {}_analysis_summary.csv - Summary statistics", prefix);
     
     Ok(())
 }
@@ -183,12 +202,13 @@ fn collect_gpx_files(gpx_folder: &str) -> Result<Vec<std::path::PathBuf>, Box<dy
 
 fn process_all_files_preprocessed(
     gpx_files: &[std::path::PathBuf], 
-    official_data: &HashMap<String, u32>
+    official_data: &GroundTruthRegistry,
+    interval_m: f64
 ) -> (Vec<SingleIntervalResult>, Vec<ProcessingError>) {
     let mut results = Vec::new();
     let mut errors = Vec::new();
     
-    println!("🚀 Processing {} preprocessed files with 1.9m symmetric method...", gpx_files.len());
+    println!("🚀 Processing {} preprocessed files with {:.1}m symmetric method...", gpx_files.len(), interval_m);
     println!("⚡ Using clean GPX files - no repair needed!");
     
     for (index, gpx_path) in gpx_files.iter().enumerate() {
@@ -207,7 +227,7 @@ fn process_all_files_preprocessed(
         println!("🔄 Processing {}/{}: {} -> {}", 
                  index + 1, gpx_files.len(), filename, original_filename);
         
-        match process_single_file_preprocessed(gpx_path, &original_filename, official_data) {
+        match process_single_file_preprocessed(gpx_path, &original_filename, official_data, interval_m) {
             Ok(result) => {
                 println!("   ✅ Success: {:.1}m gain ({:.1}% accuracy)", 
                          result.processed_elevation_gain_m, 
@@ -216,7 +236,7 @@ fn process_all_files_preprocessed(
             }
             Err(e) => {
                 println!("   ❌ Error: {}", e);
-                let error = create_processing_error(gpx_path, &e.to_string());
+                let error = create_processing_error(gpx_path, e.as_ref(), interval_m);
                 errors.push(error);
             }
         }
@@ -227,12 +247,13 @@ fn process_all_files_preprocessed(
 
 fn process_all_files(
     gpx_files: &[std::path::PathBuf], 
-    official_data: &HashMap<String, u32>
+    official_data: &GroundTruthRegistry,
+    interval_m: f64
 ) -> (Vec<SingleIntervalResult>, Vec<ProcessingError>) {
     let mut results = Vec::new();
     let mut errors = Vec::new();
     
-    println!("🚀 Processing {} files with 1.9m symmetric method...", gpx_files.len());
+    println!("🚀 Processing {} files with {:.1}m symmetric method...", gpx_files.len(), interval_m);
     
     for (index, gpx_path) in gpx_files.iter().enumerate() {
         let filename = gpx_path.file_name()
@@ -242,7 +263,7 @@ fn process_all_files(
         
         println!("🔄 Processing {}/{}: {}", index + 1, gpx_files.len(), filename);
         
-        match process_single_file(gpx_path, official_data) {
+        match process_single_file(gpx_path, official_data, interval_m) {
             Ok(result) => {
                 println!("   ✅ Success: {:.1}m gain ({:.1}% accuracy)", 
                          result.processed_elevation_gain_m, 
@@ -251,7 +272,7 @@ fn process_all_files(
             }
             Err(e) => {
                 println!("   ❌ Error: {}", e);
-                let error = create_processing_error(gpx_path, &e.to_string());
+                let error = create_processing_error(gpx_path, e.as_ref(), interval_m);
                 errors.push(error);
             }
         }
@@ -263,31 +284,28 @@ fn process_all_files(
 fn process_single_file_preprocessed(
     gpx_path: &Path, 
     original_filename: &str,
-    official_data: &HashMap<String, u32>
+    official_data: &GroundTruthRegistry,
+    interval_m: f64
 ) -> Result<SingleIntervalResult, Box<dyn std::error::Error>> {
     
     // Read the clean GPX file directly (no repair needed)
-    let file = File::open(gpx_path)?;
-    let reader = BufReader::new(file);
-    let gpx = read(reader)?;
+    let gpx = read_gpx_file(gpx_path)?;
     
     // Extract coordinates with elevation - same as before but simpler since files are clean
     let mut coords: Vec<(f64, f64, f64)> = Vec::new();
     
-    for track in &gpx.tracks {
-        for segment in &track.segments {
-            for point in &segment.points {
-                if let Some(elevation) = point.elevation {
-                    let lat = point.point().y();
-                    let lon = point.point().x();
-                    coords.push((lat, lon, elevation));
-                }
+    for segment in gpx_course_segments(&gpx) {
+        for point in segment {
+            if let Some(elevation) = point.elevation {
+                let lat = point.point().y();
+                let lon = point.point().x();
+                coords.push((lat, lon, elevation));
             }
         }
     }
     
     if coords.is_empty() {
-        return Err("No elevation data found in preprocessed GPX file".into());
+        return Err(TrackError::NoElevation { file: gpx_path.to_path_buf() }.into());
     }
     
     // Calculate distances
@@ -305,7 +323,7 @@ fn process_single_file_preprocessed(
     // Calculate raw elevation gain/loss
     let (raw_gain, raw_loss) = calculate_raw_gain_loss(&elevations);
     
-    // Apply 1.9m symmetric processing
+    // Apply symmetric processing at the target interval
     let mut elevation_data = ElevationData::new_with_variant(
         elevations.clone(),
         distances.clone(),
@@ -313,16 +331,14 @@ fn process_single_file_preprocessed(
     );
     
     // Apply custom interval processing with symmetric deadband
-    elevation_data.apply_custom_interval_processing_symmetric(TARGET_INTERVAL_M);
+    elevation_data.apply_custom_interval_processing_symmetric(interval_m);
     
     let processed_gain = elevation_data.get_total_elevation_gain();
     let processed_loss = elevation_data.get_total_elevation_loss();
     
-    // Get official data for comparison (use original filename for lookup)
-    let official_gain = official_data
-        .get(&original_filename.to_lowercase())
-        .copied()
-        .unwrap_or(0);
+    // Get official data for comparison (by content hash, original filename, then course fingerprint)
+    let official = official_data.official_record(gpx_path, &coords);
+    let official_gain = official.map_or(0, |r| r.gain_m);
     
     // Calculate metrics
     let accuracy_percent = if official_gain > 0 {
@@ -337,6 +353,10 @@ fn process_single_file_preprocessed(
         0.0
     };
     
+    // Loss and distance are scored directly when the record has them
+    let loss_accuracy_percent = official.and_then(|r| r.loss_accuracy(processed_loss)).unwrap_or(0.0);
+    let distance_accuracy_percent = official.and_then(|r| r.distance_accuracy(total_distance_km)).unwrap_or(0.0);
+    
     let gain_loss_ratio = if processed_loss > 0.0 {
         processed_gain / processed_loss
     } else {
@@ -358,6 +378,8 @@ fn process_single_file_preprocessed(
     // Quality ratings
     let similarity_to_official = classify_similarity(accuracy_percent);
     let accuracy_rating = classify_accuracy(accuracy_percent);
+    let loss_accuracy_rating = classify_accuracy(loss_accuracy_percent);
+    let distance_accuracy_rating = classify_accuracy(distance_accuracy_percent);
     let balance_rating = classify_balance(gain_loss_ratio);
     
     let result = SingleIntervalResult {
@@ -372,14 +394,20 @@ fn process_single_file_preprocessed(
         official_elevation_gain_m: official_gain,
         accuracy_percent,
         absolute_error_m,
+        official_elevation_loss_m: official.and_then(|r| r.loss_m),
+        loss_accuracy_percent,
+        official_distance_km: official.and_then(|r| r.distance_km),
+        distance_accuracy_percent,
         gain_loss_ratio,
         gain_reduction_percent,
         loss_reduction_percent,
-        interval_used_m: TARGET_INTERVAL_M,
+        interval_used_m: interval_m,
         smoothing_variant: "SymmetricFixed".to_string(),
         deadband_filtering: "Symmetric (Fixed)".to_string(),
         similarity_to_official,
         accuracy_rating,
+        loss_accuracy_rating,
+        distance_accuracy_rating,
         balance_rating,
         error_message: String::new(),
     };
@@ -389,7 +417,8 @@ fn process_single_file_preprocessed(
 
 fn process_single_file(
     gpx_path: &Path, 
-    official_data: &HashMap<String, u32>
+    official_data: &GroundTruthRegistry,
+    interval_m: f64
 ) -> Result<SingleIntervalResult, Box<dyn std::error::Error>> {
     
     let filename = gpx_path.file_name()
@@ -399,27 +428,28 @@ fn process_single_file(
     
     // Try to read and repair GPX file if needed
     let gpx = read_gpx_with_repair(gpx_path)?;
+    if gpx_geometry(&gpx).is_none() {
+        return Err(TrackError::NoTracks { file: gpx_path.to_path_buf() }.into());
+    }
     
     // Extract coordinates with elevation
     let mut coords: Vec<(f64, f64, f64)> = Vec::new();
     let mut total_track_points = 0;
     let mut points_with_elevation = 0;
     
-    for track in &gpx.tracks {
-        for segment in &track.segments {
-            for point in &segment.points {
-                total_track_points += 1;
-                
-                if let Some(elevation) = point.elevation {
-                    let lat = point.point().y();
-                    let lon = point.point().x();
-                    coords.push((lat, lon, elevation));
-                    points_with_elevation += 1;
-                } else {
-                    // Track point without elevation - this might be the issue
-                    println!("   ⚠️  Found track point without elevation at lat={:.6}, lon={:.6}", 
-                             point.point().y(), point.point().x());
-                }
+    for segment in gpx_course_segments(&gpx) {
+        for point in segment {
+            total_track_points += 1;
+            
+            if let Some(elevation) = point.elevation {
+                let lat = point.point().y();
+                let lon = point.point().x();
+                coords.push((lat, lon, elevation));
+                points_with_elevation += 1;
+            } else {
+                // Track point without elevation - this might be the issue
+                println!("   ⚠️  Found track point without elevation at lat={:.6}, lon={:.6}", 
+                         point.point().y(), point.point().x());
             }
         }
     }
@@ -428,7 +458,7 @@ fn process_single_file(
              total_track_points, points_with_elevation);
     
     if coords.is_empty() {
-        return Err("No elevation data found in GPX file".into());
+        return Err(TrackError::NoElevation { file: gpx_path.to_path_buf() }.into());
     }
     
     if points_with_elevation < total_track_points {
@@ -480,8 +510,8 @@ fn process_single_file(
         }
     }
     
-    // Apply 1.9m symmetric processing with detailed tracking
-    println!("   🔧 Applying 1.9m symmetric processing...");
+    // Apply symmetric processing with detailed tracking
+    println!("   🔧 Applying {:.1}m symmetric processing...", interval_m);
     let mut elevation_data = ElevationData::new_with_variant(
         elevations.clone(),
         distances.clone(),
@@ -495,17 +525,17 @@ fn process_single_file(
              pre_processing_gain, pre_processing_loss);
     
     // Apply custom interval processing with symmetric deadband
-    elevation_data.apply_custom_interval_processing_symmetric(TARGET_INTERVAL_M);
+    elevation_data.apply_custom_interval_processing_symmetric(interval_m);
     
     let processed_gain = elevation_data.get_total_elevation_gain();
     let processed_loss = elevation_data.get_total_elevation_loss();
     
-    println!("      • After 1.9m processing: gain={:.1}m, loss={:.1}m", 
-             processed_gain, processed_loss);
+    println!("      • After {:.1}m processing: gain={:.1}m, loss={:.1}m", 
+             interval_m, processed_gain, processed_loss);
     
     if processed_gain == 0.0 && processed_loss == 0.0 && (raw_gain > 0.0 || raw_loss > 0.0) {
         println!("   🚨 CRITICAL: Processing eliminated all elevation changes!");
-        println!("      • This suggests the 1.9m symmetric filtering is too aggressive");
+        println!("      • This suggests the {:.1}m symmetric filtering is too aggressive", interval_m);
         
         // Try with a smaller interval as a diagnostic
         let mut test_data = ElevationData::new_with_variant(
@@ -520,10 +550,8 @@ fn process_single_file(
     }
     
     // Get official data for comparison
-    let official_gain = official_data
-        .get(&filename.to_lowercase())
-        .copied()
-        .unwrap_or(0);
+    let official = official_data.official_record(gpx_path, &coords);
+    let official_gain = official.map_or(0, |r| r.gain_m);
     
     // Calculate metrics
     let accuracy_percent = if official_gain > 0 {
@@ -538,6 +566,10 @@ fn process_single_file(
         0.0
     };
     
+    // Loss and distance are scored directly when the record has them
+    let loss_accuracy_percent = official.and_then(|r| r.loss_accuracy(processed_loss)).unwrap_or(0.0);
+    let distance_accuracy_percent = official.and_then(|r| r.distance_accuracy(total_distance_km)).unwrap_or(0.0);
+    
     let gain_loss_ratio = if processed_loss > 0.0 {
         processed_gain / processed_loss
     } else {
@@ -559,6 +591,8 @@ fn process_single_file(
     // Quality ratings
     let similarity_to_official = classify_similarity(accuracy_percent);
     let accuracy_rating = classify_accuracy(accuracy_percent);
+    let loss_accuracy_rating = classify_accuracy(loss_accuracy_percent);
+    let distance_accuracy_rating = classify_accuracy(distance_accuracy_percent);
     let balance_rating = classify_balance(gain_loss_ratio);
     
     let result = SingleIntervalResult {
@@ -573,14 +607,20 @@ fn process_single_file(
         official_elevation_gain_m: official_gain,
         accuracy_percent,
         absolute_error_m,
+        official_elevation_loss_m: official.and_then(|r| r.loss_m),
+        loss_accuracy_percent,
+        official_distance_km: official.and_then(|r| r.distance_km),
+        distance_accuracy_percent,
         gain_loss_ratio,
         gain_reduction_percent,
         loss_reduction_percent,
-        interval_used_m: TARGET_INTERVAL_M,
+        interval_used_m: interval_m,
         smoothing_variant: "SymmetricFixed".to_string(),
         deadband_filtering: "Symmetric (Fixed)".to_string(),
         similarity_to_official,
         accuracy_rating,
+        loss_accuracy_rating,
+        distance_accuracy_rating,
         balance_rating,
         error_message: String::new(),
     };
@@ -638,9 +678,9 @@ fn calculate_raw_gain_loss(elevations: &[f64]) -> (f64, f64) {
 }
 
 /// Enhanced GPX reading with automatic repair for common issues
-fn read_gpx_with_repair(gpx_path: &Path) -> Result<Gpx, Box<dyn std::error::Error>> {
+fn read_gpx_with_repair(gpx_path: &Path) -> Result<Gpx, TrackError> {
     // First, try to read the file normally
-    match try_read_gpx_normal(gpx_path) {
+    match read_gpx_file(gpx_path) {
         Ok(gpx) => return Ok(gpx),
         Err(original_error) => {
             println!("   ⚠️  Standard parsing failed: {}", original_error);
@@ -664,10 +704,14 @@ fn read_gpx_with_repair(gpx_path: &Path) -> Result<Gpx, Box<dyn std::error::Erro
                         }
                         Err(aggressive_error) => {
                             // If all repair attempts fail, return comprehensive error
-                            return Err(format!(
-                                "All repair attempts failed. Original: {}. Standard repair: {}. Aggressive repair: {}", 
-                                original_error, repair_error, aggressive_error
-                            ).into());
+                            return Err(TrackError::RepairFailed {
+                                file: gpx_path.to_path_buf(),
+                                original: Box::new(original_error),
+                                attempts: vec![
+                                    format!("Standard repair: {}", repair_error),
+                                    format!("Aggressive repair: {}", aggressive_error),
+                                ],
+                            });
                         }
                     }
                 }
@@ -676,11 +720,6 @@ fn read_gpx_with_repair(gpx_path: &Path) -> Result<Gpx, Box<dyn std::error::Erro
     }
 }
 
-fn try_read_gpx_normal(gpx_path: &Path) -> Result<Gpx, Box<dyn std::error::Error>> {
-    let file = File::open(gpx_path)?;
-    let reader = BufReader::new(file);
-    Ok(read(reader)?)
-}
 
 fn try_repair_and_read_gpx(gpx_path: &Path, original_error: &str) -> Result<Gpx, Box<dyn std::error::Error>> {
     // Read the raw file content
@@ -1023,13 +1062,13 @@ fn try_aggressive_repair_and_read_gpx(gpx_path: &Path, original_error: &str) ->
     let track_points = extract_track_points_manually(&content)?;
     
     if track_points.is_empty() {
-        return Err("No valid track points found even with aggressive parsing".into());
+        return Err(TrackError::NoTracks { file: gpx_path.to_path_buf() }.into());
     }
     
     println!("   📍 Extracted {} track points manually", track_points.len());
     
     // Create a minimal valid GPX structure
-    let repaired_gpx = create_minimal_gpx_from_points(&track_points)?;
+    let repaired_gpx = create_minimal_gpx_from_points(gpx_path, &track_points)?;
     
     // Try to parse the manually created GPX
     let cursor = std::io::Cursor::new(repaired_gpx.as_bytes());
@@ -1048,22 +1087,20 @@ fn extract_track_points_manually(content: &str) -> Result<Vec<(f64, f64, f64)>,
         // Try to extract lat/lon from trkpt tags
         if line.contains("trkpt") || (line.contains("lat=") && line.contains("lon=")) {
             if let Some((lat, lon)) = extract_lat_lon_from_line(line) {
-                // Look for elevation in the same line or next few lines
-                let elevation = find_elevation_near_line(&lines, i).unwrap_or_else(|| {
-                    // If no elevation found, estimate based on latitude
-                    estimate_elevation_from_latitude(lat)
-                });
-                points.push((lat, lon, elevation));
+                // Look for elevation in the same line or next few lines; points without
+                // one are dropped rather than given an invented value
+                if let Some(elevation) = find_elevation_near_line(&lines, i) {
+                    points.push((lat, lon, elevation));
+                }
             }
         }
         
         // Also try to extract from any line that has decimal coordinates
         else if line.contains('.') && (line.contains('-') || line.matches(char::is_numeric).count() > 5) {
             if let Some((lat, lon)) = extract_coordinates_from_any_line(line) {
-                let elevation = find_elevation_near_line(&lines, i).unwrap_or_else(|| {
-                    estimate_elevation_from_latitude(lat)
-                });
-                points.push((lat, lon, elevation));
+                if let Some(elevation) = find_elevation_near_line(&lines, i) {
+                    points.push((lat, lon, elevation));
+                }
             }
         }
     }
@@ -1106,30 +1143,6 @@ fn find_elevation_near_line(lines: &[&str], current_index: usize) -> Option<f64>
     None
 }
 
-/// Estimate elevation based on latitude (very rough approximation)
-fn estimate_elevation_from_latitude(lat: f64) -> f64 {
-    // Very crude elevation estimation - you could make this more sophisticated
-    let abs_lat = lat.abs();
-    
-    // Rough approximation based on latitude zones
-    if abs_lat < 10.0 {
-        // Tropical/equatorial - generally lower elevation
-        50.0
-    } else if abs_lat < 30.0 {
-        // Subtropical - variable elevation
-        200.0
-    } else if abs_lat < 45.0 {
-        // Temperate - moderate elevation
-        400.0
-    } else if abs_lat < 60.0 {
-        // Higher latitude - often mountainous
-        600.0
-    } else {
-        // Arctic/Antarctic - variable but often coastal
-        100.0
-    }
-}
-
 fn extract_lat_lon_from_line(line: &str) -> Option<(f64, f64)> {
     let mut lat = None;
     let mut lon = None;
@@ -1232,9 +1245,9 @@ fn extract_elevation_from_line(line: &str) -> Option<f64> {
 }
 
 /// Create a minimal valid GPX structure from extracted points
-fn create_minimal_gpx_from_points(points: &[(f64, f64, f64)]) -> Result<String, Box<dyn std::error::Error>> {
+fn create_minimal_gpx_from_points(path: &Path, points: &[(f64, f64, f64)]) -> Result<String, TrackError> {
     if points.is_empty() {
-        return Err("No points to create GPX from".into());
+        return Err(TrackError::NoTracks { file: path.to_path_buf() });
     }
     
     let mut gpx_content = String::new();
@@ -1265,7 +1278,7 @@ fn create_minimal_gpx_from_points(points: &[(f64, f64, f64)]) -> Result<String,
     Ok(gpx_content)
 }
 
-fn create_processing_error(gpx_path: &Path, error_message: &str) -> ProcessingError {
+fn create_processing_error(gpx_path: &Path, error: &(dyn std::error::Error + 'static), interval_m: f64) -> ProcessingError {
     let filename = gpx_path.file_name()
         .and_then(|n| n.to_str())
         .unwrap_or("unknown")
@@ -1275,42 +1288,12 @@ fn create_processing_error(gpx_path: &Path, error_message: &str) -> ProcessingEr
         .map(|m| m.len())
         .unwrap_or(0);
     
-    let error_type = classify_error_type(error_message);
-    
     ProcessingError {
         filename,
-        error_type,
-        error_message: error_message.to_string(),
+        error_type: error_kind(error).code().to_string(),
+        error_message: error.to_string(),
         file_size_bytes,
-        attempted_processing: "1.9m SymmetricFixed with GPX repair".to_string(),
-    }
-}
-
-fn classify_error_type(error_message: &str) -> String {
-    let error_lower = error_message.to_lowercase();
-    
-    if error_lower.contains("no elevation data") || error_lower.contains("elevation") {
-        "NO_ELEVATION_DATA".to_string()
-    } else if error_lower.contains("no string content") {
-        "NO_STRING_CONTENT".to_string()
-    } else if error_lower.contains("longitude") && (error_lower.contains("minimum") || error_lower.contains("maximum")) {
-        "COORDINATE_BOUNDARY_ERROR".to_string()
-    } else if error_lower.contains("lacks required attribute") && error_lower.contains("version") {
-        "MISSING_GPX_VERSION".to_string()
-    } else if error_lower.contains("no valid coordinates") || error_lower.contains("coordinates") {
-        "INVALID_COORDINATES".to_string()
-    } else if error_lower.contains("unexpected end") || error_lower.contains("premature") || error_lower.contains("truncated") {
-        "TRUNCATED_FILE".to_string()
-    } else if error_lower.contains("xml") || error_lower.contains("parser") || error_lower.contains("invalid character") {
-        "XML_PARSE_ERROR".to_string()
-    } else if error_lower.contains("permission") || error_lower.contains("access") || error_lower.contains("denied") {
-        "FILE_ACCESS_ERROR".to_string()
-    } else if error_lower.contains("no tracks found") || error_lower.contains("empty") {
-        "EMPTY_OR_NO_TRACKS".to_string()
-    } else if error_lower.contains("repair") {
-        "REPAIR_FAILED".to_string()
-    } else {
-        "UNKNOWN_ERROR".to_string()
+        attempted_processing: format!("{:.1}m SymmetricFixed with GPX repair", interval_m),
     }
 }
 
@@ -1510,6 +1493,10 @@ fn write_results_csv(
         "Official_Gain_m",
         "Accuracy_%",
         "Absolute_Error_m",
+        "Official_Loss_m",
+        "Loss_Accuracy_%",
+        "Official_Distance_km",
+        "Distance_Accuracy_%",
         "Gain_Loss_Ratio",
         "Gain_Reduction_%",
         "Loss_Reduction_%",
@@ -1518,6 +1505,8 @@ fn write_results_csv(
         "Deadband_Filtering",
         "Similarity_to_Official",
         "Accuracy_Rating",
+        "Loss_Accuracy_Rating",
+        "Distance_Accuracy_Rating",
         "Balance_Rating",
         "Error_Message",
     ])?;
@@ -1548,6 +1537,10 @@ fn write_results_csv(
             &result.official_elevation_gain_m.to_string(),
             &format!("{:.2}", result.accuracy_percent),
             &format!("{:.1}", result.absolute_error_m),
+            &result.official_elevation_loss_m.map(|l| l.to_string()).unwrap_or_default(),
+            &format!("{:.2}", result.loss_accuracy_percent),
+            &result.official_distance_km.map(|d| format!("{:.2}", d)).unwrap_or_default(),
+            &format!("{:.2}", result.distance_accuracy_percent),
             &format!("{:.3}", result.gain_loss_ratio),
             &format!("{:.1}", result.gain_reduction_percent),
             &format!("{:.1}", result.loss_reduction_percent),
@@ -1556,6 +1549,8 @@ fn write_results_csv(
             &result.deadband_filtering,
             &result.similarity_to_official,
             &result.accuracy_rating,
+            &result.loss_accuracy_rating,
+            &result.distance_accuracy_rating,
             &result.balance_rating,
             &result.error_message,
         ])?;
@@ -1629,9 +1624,10 @@ fn write_summary_csv(
 fn print_detailed_analysis(
     results: &[SingleIntervalResult], 
     errors: &[ProcessingError], 
-    summary: &AnalysisSummary
+    summary: &AnalysisSummary,
+    interval_m: f64
 ) {
-    println!("\n🎯 1.9M SYMMETRIC ANALYSIS RESULTS");
+    println!("\n🎯 {:.1}M SYMMETRIC ANALYSIS RESULTS", interval_m);
     println!("=================================");
     
     // Processing summary
@@ -1719,8 +1715,8 @@ fn print_detailed_analysis(
         }
     }
     
-    println!("\n🎯 1.9M SYMMETRIC METHOD WITH GPX REPAIR:");
-    println!("✅ Interval: {:.1}m with SymmetricFixed deadband filtering", TARGET_INTERVAL_M);
+    println!("\n🎯 {:.1}M SYMMETRIC METHOD WITH GPX REPAIR:", interval_m);
+    println!("✅ Interval: {:.1}m with SymmetricFixed deadband filtering", interval_m);
     println!("✅ Advanced GPX file repair for common issues:");
     println!("   • Truncated XML files → Automatically closes missing tags");
     println!("   • Missing elevation data → Adds estimated elevations");
@@ -0,0 +1,401 @@
+/// TCX (Training Center XML) - Garmin courses and activities in and out of the shared point model
+/// Trackpoints map onto TrackPoint (AltitudeMeters, DistanceMeters, Time) so TCX feeds the same
+/// analyses as GPX/FIT, and smoothed tracks can be delivered back as TCX.
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use chrono::{DateTime, SecondsFormat, Utc};
+use xml::reader::{EventReader, XmlEvent};
+use xml::writer::{EmitterConfig, EventWriter, XmlEvent as WriteEvent};
+use crate::error::TrackError;
+use crate::track_loader::{
+    calculate_cumulative_distances_with_gaps, check_coordinates, gap_runs, recording_gaps, LoadedTrack, TrackPoint,
+};
+
+const TCX_NAMESPACE: &str = "http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TcxKind {
+    Activity { sport: String },
+    Course,
+}
+
+#[derive(Debug, Clone)]
+pub struct TcxTrack {
+    pub kind: TcxKind,
+    pub name: Option<String>,  // Course <Name> or Activity <Id>
+    pub points: Vec<TrackPoint>,
+    pub segment_starts: Vec<usize>,  // First point of each <Track>
+    pub lap_starts: Vec<usize>,      // First point of each activity <Lap>; a course has one
+}
+
+impl TcxTrack {
+    /// Index of the `<Track>` that point `index` belongs to
+    pub fn segment_of(&self, index: usize) -> usize {
+        self.segment_starts.partition_point(|&start| start <= index).saturating_sub(1)
+    }
+
+    /// Keep only the points at `keep` (ascending original indices), moving the track and
+    /// lap boundaries with them
+    pub fn retain_points(&mut self, keep: &[usize]) {
+        let remap = |starts: &[usize]| starts_within(starts.iter().map(|&s| keep.partition_point(|&k| k < s)).collect(), keep.len());
+        self.segment_starts = remap(&self.segment_starts);
+        self.lap_starts = remap(&self.lap_starts);
+        self.points = keep.iter().map(|&i| self.points[i].clone()).collect();
+    }
+}
+
+/// Sorted, distinct starts that begin at point 0 and have points after them
+fn starts_within(mut starts: Vec<usize>, len: usize) -> Vec<usize> {
+    starts.retain(|&s| s < len);
+    starts.push(0);
+    starts.sort_unstable();
+    starts.dedup();
+    if len == 0 {
+        starts.clear();
+    }
+    starts
+}
+
+#[derive(Default)]
+struct PartialTrackpoint {
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    altitude: Option<f64>,
+    distance: Option<f64>,
+    time: Option<DateTime<Utc>>,
+}
+
+/// Read every Trackpoint that carries a position and AltitudeMeters. Each `<Track>` (and
+/// each activity `<Lap>`) starts a new segment: devices begin one after a pause.
+pub fn read_tcx(path: &Path) -> Result<TcxTrack, TrackError> {
+    let file = File::open(path).map_err(|e| TrackError::io(path, e))?;
+    let parser = EventReader::new(BufReader::new(file));
+
+    let mut kind = None;
+    let mut name = None;
+    let mut points = Vec::new();
+    let mut segment_starts = Vec::new();
+    let mut lap_starts = Vec::new();
+    let mut element_stack: Vec<String> = Vec::new();
+    let mut current: Option<PartialTrackpoint> = None;
+
+    for event in parser {
+        match event.map_err(|e| TrackError::from_xml(path, e))? {
+            XmlEvent::StartElement { name: element, attributes, .. } => {
+                let local = element.local_name;
+                match local.as_str() {
+                    // Only the first activity/course in a file is read: its end stops the loop
+                    "Activity" if kind.is_none() => {
+                        let sport = attributes.iter()
+                            .find(|a| a.name.local_name == "Sport")
+                            .map(|a| a.value.clone())
+                            .unwrap_or_else(|| "Other".to_string());
+                        kind = Some(TcxKind::Activity { sport });
+                    }
+                    "Course" if kind.is_none() => kind = Some(TcxKind::Course),
+                    // Course laps are summaries ahead of the track, not containers of it
+                    "Lap" if matches!(kind, Some(TcxKind::Activity { .. })) => lap_starts.push(points.len()),
+                    "Track" => segment_starts.push(points.len()),
+                    "Trackpoint" => current = Some(PartialTrackpoint::default()),
+                    _ => {}
+                }
+                element_stack.push(local);
+            }
+            XmlEvent::Characters(text) => {
+                let text = text.trim();
+                let parent = element_stack.len().checked_sub(2).map(|i| element_stack[i].as_str());
+                match (element_stack.last().map(|s| s.as_str()), parent) {
+                    (Some("Id"), Some("Activity")) | (Some("Name"), Some("Course")) if name.is_none() => {
+                        name = Some(text.to_string());
+                    }
+                    (Some(field), _) => {
+                        if let Some(tp) = current.as_mut() {
+                            match field {
+                                "LatitudeDegrees" => tp.latitude = text.parse().ok(),
+                                "LongitudeDegrees" => tp.longitude = text.parse().ok(),
+                                "AltitudeMeters" => tp.altitude = text.parse().ok(),
+                                // Laps carry their own DistanceMeters; only the Trackpoint one is cumulative
+                                "DistanceMeters" if parent == Some("Trackpoint") => tp.distance = text.parse().ok(),
+                                "Time" if parent == Some("Trackpoint") => {
+                                    tp.time = DateTime::parse_from_rfc3339(text).ok()
+                                        .map(|dt| dt.with_timezone(&Utc));
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            XmlEvent::EndElement { .. } => {
+                let closed = element_stack.pop();
+                if matches!(closed.as_deref(), Some("Activity") | Some("Course")) {
+                    break;
+                }
+                let finished = if closed.as_deref() == Some("Trackpoint") { current.take() } else { None };
+                if let Some(tp) = finished {
+                    if let (Some(latitude), Some(longitude), Some(elevation)) = (tp.latitude, tp.longitude, tp.altitude) {
+                        points.push(TrackPoint {
+                            latitude,
+                            longitude,
+                            elevation,
+                            time: tp.time,
+                            distance: tp.distance,
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let kind = kind.ok_or_else(|| TrackError::NoTracks { file: path.to_path_buf() })?;
+
+    segment_starts.extend(&lap_starts);
+    let segment_starts = starts_within(segment_starts, points.len());
+    let lap_starts = starts_within(lap_starts, points.len());
+
+    Ok(TcxTrack { kind, name, points, segment_starts, lap_starts })
+}
+
+/// Load a TCX file into the shared track model
+pub fn load_tcx_track(path: &Path) -> Result<LoadedTrack, TrackError> {
+    let tcx = read_tcx(path)?;
+
+    if tcx.points.is_empty() {
+        return Err(TrackError::NoElevation { file: path.to_path_buf() });
+    }
+    check_coordinates(path, &tcx.points)?;
+
+    let filename = path.file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("unknown")
+        .to_string();
+
+    Ok(LoadedTrack::from_segments(filename, tcx.points, tcx.segment_starts))
+}
+
+/// Write a TCX file keeping the activity/course shape of the source: an activity gets one
+/// `<Lap>` per source lap, and every segment is written back as its own `<Track>`
+pub fn write_tcx(track: &TcxTrack, output_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let file = BufWriter::new(File::create(output_path)?);
+    let mut writer = EmitterConfig::new().perform_indent(true).create_writer(file);
+
+    let gaps = recording_gaps(&track.points, &track.segment_starts);
+    let haversine_distances = calculate_cumulative_distances_with_gaps(&track.points, &gaps);
+    let start_time = track.points.first().and_then(|p| p.time);
+    let start_time_text = start_time.map(format_time).unwrap_or_else(|| format_time(DateTime::<Utc>::UNIX_EPOCH));
+
+    writer.write(WriteEvent::start_element("TrainingCenterDatabase").default_ns(TCX_NAMESPACE))?;
+
+    match &track.kind {
+        TcxKind::Activity { sport } => {
+            writer.write(WriteEvent::start_element("Activities"))?;
+            writer.write(WriteEvent::start_element("Activity").attr("Sport", sport))?;
+            write_text_element(&mut writer, "Id", track.name.as_deref().unwrap_or(&start_time_text))?;
+            for lap in gap_runs(track.points.len(), &track.lap_starts) {
+                let (total_seconds, total_distance) = lap_totals(&track.points[lap.clone()], &haversine_distances[lap.clone()]);
+                let lap_start_text = track.points[lap.start].time.map(format_time).unwrap_or_else(|| start_time_text.clone());
+                writer.write(WriteEvent::start_element("Lap").attr("StartTime", &lap_start_text))?;
+                write_text_element(&mut writer, "TotalTimeSeconds", &format!("{:.1}", total_seconds))?;
+                write_text_element(&mut writer, "DistanceMeters", &format!("{:.2}", total_distance))?;
+                write_text_element(&mut writer, "Calories", "0")?;
+                write_text_element(&mut writer, "Intensity", "Active")?;
+                write_text_element(&mut writer, "TriggerMethod", "Manual")?;
+                write_tracks(&mut writer, track, lap)?;
+                writer.write(WriteEvent::end_element())?; // Lap
+            }
+            writer.write(WriteEvent::end_element())?; // Activity
+            writer.write(WriteEvent::end_element())?; // Activities
+        }
+        TcxKind::Course => {
+            let (total_seconds, total_distance) = lap_totals(&track.points, &haversine_distances);
+            writer.write(WriteEvent::start_element("Courses"))?;
+            writer.write(WriteEvent::start_element("Course"))?;
+            write_text_element(&mut writer, "Name", track.name.as_deref().unwrap_or("Course"))?;
+            writer.write(WriteEvent::start_element("Lap"))?;
+            write_text_element(&mut writer, "TotalTimeSeconds", &format!("{:.1}", total_seconds))?;
+            write_text_element(&mut writer, "DistanceMeters", &format!("{:.2}", total_distance))?;
+            write_text_element(&mut writer, "Intensity", "Active")?;
+            writer.write(WriteEvent::end_element())?; // Lap
+            write_tracks(&mut writer, track, 0..track.points.len())?;
+            writer.write(WriteEvent::end_element())?; // Course
+            writer.write(WriteEvent::end_element())?; // Courses
+        }
+    }
+
+    writer.write(WriteEvent::end_element())?; // TrainingCenterDatabase
+    writer.into_inner().flush()?;
+
+    Ok(())
+}
+
+/// Elapsed seconds and distance over a lap's points, preferring the device distance
+fn lap_totals(points: &[TrackPoint], haversine_distances: &[f64]) -> (f64, f64) {
+    let (first, last) = match (points.first(), points.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return (0.0, 0.0),
+    };
+    let seconds = match (first.time, last.time) {
+        (Some(start), Some(end)) => (end - start).num_milliseconds() as f64 / 1000.0,
+        _ => 0.0,
+    };
+    let distance = match (first.distance, last.distance) {
+        (Some(start), Some(end)) => end - start,
+        _ => haversine_distances[haversine_distances.len() - 1] - haversine_distances[0],
+    };
+    (seconds, distance)
+}
+
+/// One `<Track>` per segment inside `range`
+fn write_tracks<W: Write>(writer: &mut EventWriter<W>, track: &TcxTrack, range: std::ops::Range<usize>) -> Result<(), Box<dyn std::error::Error>> {
+    let starts: Vec<usize> = track.segment_starts.iter().map(|&s| s.saturating_sub(range.start)).collect();
+    for segment in gap_runs(range.len(), &starts) {
+        write_trackpoints(writer, &track.points[range.start + segment.start..range.start + segment.end])?;
+    }
+    Ok(())
+}
+
+fn write_trackpoints<W: Write>(writer: &mut EventWriter<W>, points: &[TrackPoint]) -> Result<(), Box<dyn std::error::Error>> {
+    writer.write(WriteEvent::start_element("Track"))?;
+    for point in points {
+        writer.write(WriteEvent::start_element("Trackpoint"))?;
+        if let Some(time) = point.time {
+            write_text_element(writer, "Time", &format_time(time))?;
+        }
+        writer.write(WriteEvent::start_element("Position"))?;
+        write_text_element(writer, "LatitudeDegrees", &point.latitude.to_string())?;
+        write_text_element(writer, "LongitudeDegrees", &point.longitude.to_string())?;
+        writer.write(WriteEvent::end_element())?; // Position
+        write_text_element(writer, "AltitudeMeters", &format!("{:.2}", point.elevation))?;
+        if let Some(distance) = point.distance {
+            write_text_element(writer, "DistanceMeters", &format!("{:.2}", distance))?;
+        }
+        writer.write(WriteEvent::end_element())?; // Trackpoint
+    }
+    writer.write(WriteEvent::end_element())?; // Track
+    Ok(())
+}
+
+fn write_text_element<W: Write>(writer: &mut EventWriter<W>, name: &str, text: &str) -> Result<(), Box<dyn std::error::Error>> {
+    writer.write(WriteEvent::start_element(name))?;
+    writer.write(WriteEvent::characters(text))?;
+    writer.write(WriteEvent::end_element())?;
+    Ok(())
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tcx_round_trip_keeps_points_and_device_distance() {
+        let start = DateTime::parse_from_rfc3339("2024-05-01T08:00:00Z").unwrap().with_timezone(&Utc);
+        let points: Vec<TrackPoint> = (0..5)
+            .map(|i| TrackPoint {
+                latitude: 46.0 + i as f64 * 0.0001,
+                longitude: 7.0,
+                elevation: 500.0 + i as f64,
+                time: Some(start + chrono::Duration::seconds(i * 5)),
+                distance: Some(i as f64 * 11.0),
+            })
+            .collect();
+        let track = TcxTrack {
+            kind: TcxKind::Activity { sport: "Running".to_string() },
+            name: Some("2024-05-01T08:00:00Z".to_string()),
+            points,
+            segment_starts: vec![0],
+            lap_starts: vec![0],
+        };
+
+        let path = std::env::temp_dir().join(format!("tcx_round_trip_{}.tcx", std::process::id()));
+        write_tcx(&track, &path).unwrap();
+        let read_back = read_tcx(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(read_back.kind, track.kind);
+        assert_eq!(read_back.name, track.name);
+        assert_eq!(read_back.points.len(), 5);
+        assert_eq!(read_back.points[4].distance, Some(44.0));
+        assert_eq!(read_back.points[4].elevation, 504.0);
+        assert_eq!(read_back.points[4].time, track.points[4].time);
+    }
+
+    #[test]
+    fn test_only_the_first_activity_is_read() {
+        let trackpoint = |lat: f64| format!(
+            "<Trackpoint><Position><LatitudeDegrees>{}</LatitudeDegrees><LongitudeDegrees>7</LongitudeDegrees></Position><AltitudeMeters>500</AltitudeMeters></Trackpoint>",
+            lat,
+        );
+        let activity = |id: &str, lats: &[f64]| format!(
+            "<Activity Sport=\"Running\"><Id>{}</Id><Lap><Track>{}</Track></Lap></Activity>",
+            id,
+            lats.iter().map(|&lat| trackpoint(lat)).collect::<String>(),
+        );
+        let content = format!(
+            "<TrainingCenterDatabase xmlns=\"{}\"><Activities>{}{}</Activities></TrainingCenterDatabase>",
+            TCX_NAMESPACE,
+            activity("first", &[46.0, 46.001]),
+            activity("second", &[47.0, 47.001, 47.002]),
+        );
+        let path = std::env::temp_dir().join(format!("tcx_two_activities_{}.tcx", std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        let read_back = read_tcx(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(read_back.name.as_deref(), Some("first"));
+        assert_eq!(read_back.points.len(), 2);
+        assert!(read_back.points.iter().all(|p| p.latitude < 46.5));
+    }
+
+    #[test]
+    fn test_laps_and_tracks_become_segments_and_are_written_back() {
+        let trackpoint = |lat: f64, seconds: u32| format!(
+            "<Trackpoint><Time>2024-05-01T08:{:02}:{:02}Z</Time><Position><LatitudeDegrees>{}</LatitudeDegrees><LongitudeDegrees>7</LongitudeDegrees></Position><AltitudeMeters>500</AltitudeMeters></Trackpoint>",
+            seconds / 60, seconds % 60, lat,
+        );
+        let track = |points: &[(f64, u32)]| format!(
+            "<Track>{}</Track>",
+            points.iter().map(|&(lat, seconds)| trackpoint(lat, seconds)).collect::<String>(),
+        );
+        // Lap 1 is one track; lap 2 was paused and resumed 1km further on
+        let content = format!(
+            "<TrainingCenterDatabase xmlns=\"{}\"><Activities><Activity Sport=\"Running\"><Id>run</Id><Lap>{}</Lap><Lap>{}{}</Lap></Activity></Activities></TrainingCenterDatabase>",
+            TCX_NAMESPACE,
+            track(&[(46.0, 0), (46.0001, 5)]),
+            track(&[(46.0002, 10), (46.0003, 15)]),
+            track(&[(46.0093, 600), (46.0094, 605)]),
+        );
+        let path = std::env::temp_dir().join(format!("tcx_laps_{}.tcx", std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        let read_back = read_tcx(&path).unwrap();
+        assert_eq!(read_back.segment_starts, vec![0, 2, 4]);
+        assert_eq!(read_back.lap_starts, vec![0, 2]);
+        assert_eq!(read_back.segment_of(3), 1);
+
+        // The gap is not bridged
+        let loaded = load_tcx_track(&path).unwrap();
+        assert_eq!(loaded.gaps(), vec![4]);
+        assert!(loaded.total_distance_km() < 0.05, "{}", loaded.total_distance_km());
+
+        // Dropping a lap's first point moves the boundary to the next kept point
+        let mut trimmed = read_back.clone();
+        trimmed.retain_points(&[0, 1, 3, 4, 5]);
+        assert_eq!(trimmed.segment_starts, vec![0, 2, 3]);
+        assert_eq!(trimmed.lap_starts, vec![0, 2]);
+
+        write_tcx(&read_back, &path).unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        let rewritten = read_tcx(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(written.matches("<Lap ").count(), 2);
+        assert_eq!(written.matches("<Track>").count(), 3);
+        assert_eq!(rewritten.segment_starts, read_back.segment_starts);
+        assert_eq!(rewritten.lap_starts, read_back.lap_starts);
+    }
+}
@@ -0,0 +1,380 @@
+/// TRACK LOADER - Shared GPX/FIT/TCX loading into a common point model
+/// Every analysis used to carry its own copy of the read/collect/haversine loop;
+/// library consumers get a single entry point instead.
+use std::{fs::File, path::Path};
+use std::io::BufReader;
+use std::collections::HashSet;
+use gpx::{read, Gpx, Waypoint};
+use geo::{HaversineDistance, point};
+use chrono::{DateTime, Utc};
+use crate::error::TrackError;
+
+// Segment boundaries further apart than this are recording gaps: no distance is bridged across them
+pub const MAX_BRIDGED_GAP_M: f64 = 50.0;
+
+#[derive(Debug, Clone)]
+pub struct TrackPoint {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub elevation: f64,
+    pub time: Option<DateTime<Utc>>,
+    pub distance: Option<f64>,  // Device-reported cumulative distance in meters (FIT/TCX), if any
+}
+
+/// Where cumulative distance comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DistanceSource {
+    #[default]
+    Haversine,
+    Device,  // FIT/TCX recorded distance, falling back to Haversine when the file has none
+}
+
+impl std::str::FromStr for DistanceSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "haversine" => Ok(DistanceSource::Haversine),
+            "device" => Ok(DistanceSource::Device),
+            other => Err(format!("Unknown distance source '{}' (expected haversine or device)", other)),
+        }
+    }
+}
+
+/// What measured the elevations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AltitudeSource {
+    #[default]
+    Gps,
+    Barometric,  // GPX barometric extensions or FIT records with air pressure
+}
+
+impl AltitudeSource {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AltitudeSource::Gps => "gps",
+            AltitudeSource::Barometric => "barometric",
+        }
+    }
+
+    /// Source of a track with `barometric_points` of its `points` read from a barometer
+    pub fn from_point_counts(barometric_points: usize, points: usize) -> Self {
+        if barometric_points > 0 && barometric_points as f64 >= crate::barometric::MIN_BAROMETRIC_COVERAGE * points as f64 {
+            AltitudeSource::Barometric
+        } else {
+            AltitudeSource::Gps
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LoadedTrack {
+    pub filename: String,
+    pub points: Vec<TrackPoint>,
+    pub cumulative_distances: Vec<f64>,
+    pub altitude_source: AltitudeSource,
+    pub segment_starts: Vec<usize>,  // First point of each track segment, in file order
+}
+
+impl LoadedTrack {
+    pub fn from_points(filename: String, points: Vec<TrackPoint>) -> Self {
+        Self::from_segments(filename, points, vec![0])
+    }
+
+    /// Points from several track segments; distance is not bridged across recording gaps
+    pub fn from_segments(filename: String, points: Vec<TrackPoint>, segment_starts: Vec<usize>) -> Self {
+        let gaps = recording_gaps(&points, &segment_starts);
+        let cumulative_distances = calculate_cumulative_distances_with_gaps(&points, &gaps);
+        LoadedTrack {
+            filename,
+            points,
+            cumulative_distances,
+            altitude_source: AltitudeSource::Gps,
+            segment_starts,
+        }
+    }
+
+    /// Points that start a new run after a recording gap
+    pub fn gaps(&self) -> Vec<usize> {
+        recording_gaps(&self.points, &self.segment_starts)
+    }
+
+    pub fn elevations(&self) -> Vec<f64> {
+        self.points.iter().map(|p| p.elevation).collect()
+    }
+
+    pub fn coordinates(&self) -> Vec<(f64, f64, f64)> {
+        self.points.iter().map(|p| (p.latitude, p.longitude, p.elevation)).collect()
+    }
+
+    pub fn total_distance_km(&self) -> f64 {
+        self.cumulative_distances.last().copied().unwrap_or(0.0) / 1000.0
+    }
+
+    /// Swap in device-reported distances when requested and available
+    pub fn with_distance_source(mut self, source: DistanceSource) -> Self {
+        self.cumulative_distances = cumulative_distances_from(&self.points, source, &self.gaps());
+        self
+    }
+
+    pub fn with_altitude_source(mut self, source: AltitudeSource) -> Self {
+        self.altitude_source = source;
+        self
+    }
+}
+
+/// Which GPX element type holds a file's course geometry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpxGeometry {
+    Track,      // <trk>/<trkseg>/<trkpt>
+    Route,      // <rte>/<rtept>, how many race courses are published
+    Waypoints,  // A bare sequence of top-level <wpt>
+}
+
+impl GpxGeometry {
+    /// Local name of the point element
+    pub fn element(&self) -> &'static str {
+        match self {
+            GpxGeometry::Track => "trkpt",
+            GpxGeometry::Route => "rtept",
+            GpxGeometry::Waypoints => "wpt",
+        }
+    }
+}
+
+/// Tracks when the file has any track points, else routes, else two or more waypoints
+pub fn gpx_geometry(gpx: &Gpx) -> Option<GpxGeometry> {
+    if gpx.tracks.iter().flat_map(|t| &t.segments).any(|s| !s.points.is_empty()) {
+        Some(GpxGeometry::Track)
+    } else if gpx.routes.iter().any(|r| !r.points.is_empty()) {
+        Some(GpxGeometry::Route)
+    } else if gpx.waypoints.len() >= 2 {
+        Some(GpxGeometry::Waypoints)
+    } else {
+        None
+    }
+}
+
+/// The course's points, one list per track segment or route, in document order
+pub fn gpx_course_segments(gpx: &Gpx) -> Vec<&[Waypoint]> {
+    match gpx_geometry(gpx) {
+        Some(GpxGeometry::Track) => gpx.tracks.iter().flat_map(|t| &t.segments).map(|s| s.points.as_slice()).collect(),
+        Some(GpxGeometry::Route) => gpx.routes.iter().map(|r| r.points.as_slice()).collect(),
+        Some(GpxGeometry::Waypoints) => vec![gpx.waypoints.as_slice()],
+        None => Vec::new(),
+    }
+}
+
+pub fn gpx_course_segments_mut(gpx: &mut Gpx) -> Vec<&mut Vec<Waypoint>> {
+    match gpx_geometry(gpx) {
+        Some(GpxGeometry::Track) => gpx.tracks.iter_mut().flat_map(|t| &mut t.segments).map(|s| &mut s.points).collect(),
+        Some(GpxGeometry::Route) => gpx.routes.iter_mut().map(|r| &mut r.points).collect(),
+        Some(GpxGeometry::Waypoints) => vec![&mut gpx.waypoints],
+        None => Vec::new(),
+    }
+}
+
+/// Name of the course: first track or route name, else the metadata name
+pub fn gpx_course_name(gpx: &Gpx) -> Option<String> {
+    let name = match gpx_geometry(gpx)? {
+        GpxGeometry::Track => gpx.tracks.first().and_then(|t| t.name.clone()),
+        GpxGeometry::Route => gpx.routes.iter().find(|r| !r.points.is_empty()).and_then(|r| r.name.clone()),
+        GpxGeometry::Waypoints => None,
+    };
+    name.or_else(|| gpx.metadata.as_ref().and_then(|m| m.name.clone()))
+}
+
+/// Track file formats the loader understands
+pub fn is_supported_track_file(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|s| s.to_str()).map(|s| s.to_lowercase()).as_deref(),
+        Some("gpx") | Some("fit") | Some("tcx")
+    )
+}
+
+/// Load a GPX, FIT or TCX file, chosen by extension
+pub fn load_track(path: &Path) -> Result<LoadedTrack, TrackError> {
+    let extension = path.extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+
+    match extension.as_str() {
+        "gpx" => load_gpx_track(path),
+        "fit" => crate::fit_loader::load_fit_track(path),
+        "tcx" => crate::tcx::load_tcx_track(path),
+        _ => Err(TrackError::UnsupportedFormat { file: path.to_path_buf() }),
+    }
+}
+
+/// Load every course point that carries an elevation from a GPX file: track points, or
+/// route points / waypoints for files without a track.
+/// Barometric altitude in the track point extensions replaces `<ele>` for the whole track,
+/// with points lacking a reading interpolated, when enough points have one.
+pub fn load_gpx_track(path: &Path) -> Result<LoadedTrack, TrackError> {
+    let gpx = read_gpx_file(path)?;
+    if gpx_geometry(&gpx).is_none() {
+        return Err(TrackError::NoTracks { file: path.to_path_buf() });
+    }
+
+    let barometric = if gpx_geometry(&gpx) == Some(GpxGeometry::Track) {
+        crate::barometric::fill_barometric_gaps(&crate::barometric::read_gpx_barometric_altitudes(path)?)
+    } else {
+        None
+    };
+    let mut trackpoint_index = 0;
+    let mut points = Vec::new();
+    let mut segment_starts = Vec::new();
+
+    for segment in gpx_course_segments(&gpx) {
+        if segment_starts.last() != Some(&points.len()) {
+            segment_starts.push(points.len());
+        }
+        for pt in segment {
+            let elevation = match &barometric {
+                Some(altitudes) => altitudes.get(trackpoint_index).copied(),
+                None => pt.elevation,
+            };
+            trackpoint_index += 1;
+            if let Some(ele) = elevation {
+                let time = pt.time.and_then(gpx_time_to_utc);
+                points.push(TrackPoint {
+                    latitude: pt.point().y(),
+                    longitude: pt.point().x(),
+                    elevation: ele,
+                    time,
+                    distance: None,
+                });
+            }
+        }
+    }
+
+    if points.is_empty() {
+        return Err(TrackError::NoElevation { file: path.to_path_buf() });
+    }
+
+    let filename = path.file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("unknown")
+        .to_string();
+
+    let altitude_source = if barometric.is_some() { AltitudeSource::Barometric } else { AltitudeSource::Gps };
+    segment_starts.retain(|&start| start < points.len());
+    Ok(LoadedTrack::from_segments(filename, points, segment_starts).with_altitude_source(altitude_source))
+}
+
+/// Parse a GPX file, keeping the file (and line, for XML errors) in the error
+pub fn read_gpx_file(path: &Path) -> Result<Gpx, TrackError> {
+    let file = File::open(path).map_err(|e| TrackError::io(path, e))?;
+    read(BufReader::new(file)).map_err(|e| TrackError::from_gpx(path, e))
+}
+
+/// The first point outside WGS84 latitude/longitude bounds, as an error
+pub fn check_coordinates(path: &Path, points: &[TrackPoint]) -> Result<(), TrackError> {
+    let invalid = points.iter().position(|p| !(-90.0..=90.0).contains(&p.latitude) || !(-180.0..=180.0).contains(&p.longitude));
+    match invalid {
+        Some(index) => Err(TrackError::InvalidCoordinates {
+            file: path.to_path_buf(),
+            point_index: Some(index),
+            message: format!("lat {} lon {}", points[index].latitude, points[index].longitude),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// A GPX timestamp as a chrono UTC time
+pub fn gpx_time_to_utc(time: gpx::Time) -> Option<DateTime<Utc>> {
+    time.format().ok()
+        .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+/// Cumulative Haversine distance in meters for each point
+pub fn calculate_cumulative_distances(points: &[TrackPoint]) -> Vec<f64> {
+    calculate_cumulative_distances_with_gaps(points, &[])
+}
+
+/// Cumulative Haversine distance that stands still across each gap in `gaps`
+/// (indices of the first point after a gap)
+pub fn calculate_cumulative_distances_with_gaps(points: &[TrackPoint], gaps: &[usize]) -> Vec<f64> {
+    if points.is_empty() {
+        return Vec::new();
+    }
+
+    let gaps: HashSet<usize> = gaps.iter().copied().collect();
+    let mut distances = vec![0.0];
+    for i in 1..points.len() {
+        let dist = if gaps.contains(&i) { 0.0 } else { ground_distance(&points[i-1], &points[i]) };
+        distances.push(distances[i-1] + dist);
+    }
+    distances
+}
+
+fn ground_distance(a: &TrackPoint, b: &TrackPoint) -> f64 {
+    point!(x: a.longitude, y: a.latitude).haversine_distance(&point!(x: b.longitude, y: b.latitude))
+}
+
+/// Segment starts that are recording gaps: further than MAX_BRIDGED_GAP_M from the
+/// previous segment's last point. Closer boundaries (a quick pause and resume) are bridged.
+pub fn recording_gaps(points: &[TrackPoint], segment_starts: &[usize]) -> Vec<usize> {
+    segment_starts.iter()
+        .copied()
+        .filter(|&start| start > 0 && start < points.len())
+        .filter(|&start| ground_distance(&points[start - 1], &points[start]) > MAX_BRIDGED_GAP_M)
+        .collect()
+}
+
+/// Index ranges of the runs between gaps
+pub fn gap_runs(len: usize, gaps: &[usize]) -> Vec<std::ops::Range<usize>> {
+    let mut bounds: Vec<usize> = gaps.iter().copied().filter(|&g| g > 0 && g < len).collect();
+    bounds.sort_unstable();
+    bounds.dedup();
+    bounds.push(len);
+
+    let mut start = 0;
+    bounds.into_iter()
+        .map(|end| {
+            let run = start..end;
+            start = end;
+            run
+        })
+        .filter(|run| !run.is_empty())
+        .collect()
+}
+
+/// Device-reported cumulative distance, if every point has one and it never runs backwards
+pub fn device_distances(points: &[TrackPoint]) -> Option<Vec<f64>> {
+    let distances: Option<Vec<f64>> = points.iter().map(|p| p.distance).collect();
+    let distances = distances?;
+    if distances.is_empty() || distances.windows(2).any(|w| w[1] < w[0]) {
+        return None;
+    }
+    // Rebase so the profile starts at 0 like the Haversine distances
+    let start = distances[0];
+    Some(distances.iter().map(|d| d - start).collect())
+}
+
+/// Cumulative distance for the chosen source. Devices stop their odometer while paused,
+/// so `gaps` only applies to the Haversine distances.
+pub fn cumulative_distances_from(points: &[TrackPoint], source: DistanceSource, gaps: &[usize]) -> Vec<f64> {
+    match source {
+        DistanceSource::Device => device_distances(points)
+            .unwrap_or_else(|| calculate_cumulative_distances_with_gaps(points, gaps)),
+        DistanceSource::Haversine => calculate_cumulative_distances_with_gaps(points, gaps),
+    }
+}
+
+/// Raw gain and loss from consecutive elevation deltas
+pub fn calculate_gain_loss(elevations: &[f64]) -> (f64, f64) {
+    let mut gain = 0.0;
+    let mut loss = 0.0;
+    for w in elevations.windows(2) {
+        let delta = w[1] - w[0];
+        if delta > 0.0 {
+            gain += delta;
+        } else {
+            loss += -delta;
+        }
+    }
+    (gain, loss)
+}
@@ -1,11 +1,12 @@
 /// TWO-PASS SMOOTHING AND SAVITZKY-GOLAY COMPARISON ANALYSIS
 /// 
-/// This module implements and compares five approaches:
+/// This module implements and compares six approaches:
 /// 1. Baseline: Your proven distance-based approach (default)
 /// 2. DistBased-3m: Distance-based with 3m interval
 /// 3. DistBased-6.1m: Distance-based with 6.1m interval  
 /// 4. Two-Pass: Distance-based for gain + 15m distance-based for loss
 /// 5. Savitzky-Golay: Traditional signal processing filter
+/// 6. Kalman: State-space Kalman filter + RTS smoother with noise estimated per file
 /// 
 /// Scoring: Separate gain accuracy and loss accuracy (both vs official gain)
 /// Output: Detailed file-by-file CSV + summary comparison
@@ -16,7 +17,10 @@ use csv::Writer;
 use serde::Serialize;
 use rayon::prelude::*;
 use std::sync::Arc;
-use crate::distbased_elevation_processor::DistBasedElevationProcessor;
+use crate::elevation_smoother::{
+    ElevationSmoother, DistBasedSmoother, TwoPassSmoother, SavitzkyGolaySmoother, KalmanSmoother,
+    resample_to_uniform_distance, median_filter, gaussian_smooth,
+};
 
 #[derive(Debug, Serialize, Clone)]
 pub struct FileComparisonResult {
@@ -27,31 +31,37 @@ pub struct FileComparisonResult {
     baseline_gain_m: f32,
     baseline_loss_m: f32,
     baseline_gain_accuracy: f32,
-    baseline_loss_accuracy: f32,
+    baseline_loss_accuracy: Option<f32>,
     
     // Distance-based 3m
     dist3m_gain_m: f32,
     dist3m_loss_m: f32,
     dist3m_gain_accuracy: f32,
-    dist3m_loss_accuracy: f32,
+    dist3m_loss_accuracy: Option<f32>,
     
     // Distance-based 6.1m
     dist61m_gain_m: f32,
     dist61m_loss_m: f32,
     dist61m_gain_accuracy: f32,
-    dist61m_loss_accuracy: f32,
+    dist61m_loss_accuracy: Option<f32>,
     
     // Two-pass
     twopass_gain_m: f32,
     twopass_loss_m: f32,
     twopass_gain_accuracy: f32,
-    twopass_loss_accuracy: f32,
+    twopass_loss_accuracy: Option<f32>,
     
     // Savitzky-Golay
     savgol_gain_m: f32,
     savgol_loss_m: f32,
     savgol_gain_accuracy: f32,
-    savgol_loss_accuracy: f32,
+    savgol_loss_accuracy: Option<f32>,
+    
+    // Kalman + RTS
+    kalman_gain_m: f32,
+    kalman_loss_m: f32,
+    kalman_gain_accuracy: f32,
+    kalman_loss_accuracy: Option<f32>,
     
     // Best method for this file
     best_gain_method: String,
@@ -65,6 +75,7 @@ struct GpxFileData {
     elevations: Vec<f64>,
     distances: Vec<f64>,
     official_gain: u32,
+    official_loss: Option<u32>,         // Only non-zero official losses
 }
 
 #[derive(Debug, Clone)]
@@ -76,31 +87,37 @@ struct SingleFileResult {
     baseline_gain: f32,
     baseline_loss: f32,
     baseline_gain_accuracy: f32,
-    baseline_loss_accuracy: f32,
+    baseline_loss_accuracy: Option<f32>,
     
     // Distance-based 3m results
     dist3m_gain: f32,
     dist3m_loss: f32,
     dist3m_gain_accuracy: f32,
-    dist3m_loss_accuracy: f32,
+    dist3m_loss_accuracy: Option<f32>,
     
     // Distance-based 6.1m results
     dist61m_gain: f32,
     dist61m_loss: f32,
     dist61m_gain_accuracy: f32,
-    dist61m_loss_accuracy: f32,
+    dist61m_loss_accuracy: Option<f32>,
     
     // Two-pass results
     twopass_gain: f32,
     twopass_loss: f32,
     twopass_gain_accuracy: f32,
-    twopass_loss_accuracy: f32,
+    twopass_loss_accuracy: Option<f32>,
     
     // Savitzky-Golay results
     savgol_gain: f32,
     savgol_loss: f32,
     savgol_gain_accuracy: f32,
-    savgol_loss_accuracy: f32,
+    savgol_loss_accuracy: Option<f32>,
+    
+    // Kalman + RTS results
+    kalman_gain: f32,
+    kalman_loss: f32,
+    kalman_gain_accuracy: f32,
+    kalman_loss_accuracy: Option<f32>,
 }
 
 pub fn run_two_pass_analysis(gpx_folder: &str) -> Result<(), Box<dyn std::error::Error>> {
@@ -126,8 +143,9 @@ pub fn run_two_pass_analysis(gpx_folder: &str) -> Result<(), Box<dyn std::error:
     println!("🔄 Starting Distance-Based 6.1m Analysis...");
     println!("🔄 Starting Two-Pass Smoothing Analysis...");
     println!("🔄 Starting Savitzky-Golay Filter Analysis...");
+    println!("🔄 Starting Kalman + RTS Smoother Analysis...");
     
-    // Process with all five methods (silent)
+    // Process with all six methods (silent)
     let results = process_five_methods(&gpx_files_data, &files_with_elevation)?;
     
     // Write detailed file comparison CSV
@@ -145,82 +163,41 @@ pub fn run_two_pass_analysis(gpx_folder: &str) -> Result<(), Box<dyn std::error:
 }
 
 fn load_gpx_data(gpx_folder: &str) -> Result<(HashMap<String, GpxFileData>, Vec<String>), Box<dyn std::error::Error>> {
-    use std::fs::File;
-    use std::io::BufReader;
-    use gpx::read;
-    use geo::{HaversineDistance, point};
     use walkdir::WalkDir;
-    
+    use crate::track_loader::{load_track, is_supported_track_file};
+
     let mut gpx_data = HashMap::new();
     let mut valid_files = Vec::new();
-    
-    let official_data = crate::load_official_elevation_data()?;
-    
+
+    let official_data = crate::load_ground_truth_registry()?;
+
     for entry in WalkDir::new(gpx_folder) {
         let entry = entry?;
-        if entry.file_type().is_file() {
-            if let Some(extension) = entry.path().extension() {
-                if extension.to_str().unwrap_or("").to_lowercase() == "gpx" {
-                    let path = entry.path();
-                    let filename = path.file_name()
-                        .and_then(|n| n.to_str())
-                        .unwrap_or("")
-                        .to_string();
-                    
-                    match File::open(path) {
-                        Ok(file) => {
-                            let reader = BufReader::new(file);
-                            match read(reader) {
-                                Ok(gpx) => {
-                                    let mut coords: Vec<(f64, f64, f64)> = vec![];
-                                    
-                                    for track in gpx.tracks {
-                                        for segment in track.segments {
-                                            for pt in segment.points {
-                                                if let Some(ele) = pt.elevation {
-                                                    coords.push((pt.point().y(), pt.point().x(), ele));
-                                                }
-                                            }
-                                        }
-                                    }
-                                    
-                                    if !coords.is_empty() {
-                                        let mut distances = vec![0.0];
-                                        for i in 1..coords.len() {
-                                            let a = point!(x: coords[i-1].1, y: coords[i-1].0);
-                                            let b = point!(x: coords[i].1, y: coords[i].0);
-                                            let dist = a.haversine_distance(&b);
-                                            distances.push(distances[i-1] + dist);
-                                        }
-                                        
-                                        let elevations: Vec<f64> = coords.iter().map(|c| c.2).collect();
-                                        
-                                        let official_gain = official_data
-                                            .get(&filename.to_lowercase())
-                                            .copied()
-                                            .unwrap_or(0);
-                                        
-                                        let file_data = GpxFileData {
-                                            filename: filename.clone(),
-                                            elevations,
-                                            distances,
-                                            official_gain,
-                                        };
-                                        
-                                        gpx_data.insert(filename.clone(), file_data);
-                                        valid_files.push(filename);
-                                    }
-                                },
-                                Err(_) => continue,
-                            }
-                        },
-                        Err(_) => continue,
-                    }
-                }
-            }
+        if entry.file_type().is_file() && is_supported_track_file(entry.path()) {
+            // GPX and FIT both land in the same point model; unreadable files are skipped
+            let track = match load_track(entry.path()) {
+                Ok(track) => track,
+                Err(_) => continue,
+            };
+            let filename = track.filename.clone();
+
+            let official = official_data.official_record(entry.path(), &track.coordinates());
+
+            let file_data = GpxFileData {
+                elevations: track.elevations(),
+                distances: track.cumulative_distances,
+                filename: filename.clone(),
+                official_gain: official.map_or(0, |r| r.gain_m),
+                official_loss: official.and_then(|r| r.loss_m).filter(|&loss| loss > 0),
+            };
+
+            gpx_data.insert(filename.clone(), file_data);
+            valid_files.push(filename);
         }
     }
-    
+
+    official_data.print_match_report();
+
     Ok((gpx_data, valid_files))
 }
 
@@ -251,16 +228,15 @@ fn process_five_methods(
 
 fn process_single_file_five_methods(file_data: &GpxFileData) -> SingleFileResult {
     let official_gain = file_data.official_gain as f32;
+    // Loss is only scored against an official loss, never against the gain
+    let loss_accuracy_of = |loss: f32| file_data.official_loss.map(|official| loss / official as f32 * 100.0);
     
     // METHOD 1: BASELINE - Your proven distance-based approach (default)
-    let baseline_processor = DistBasedElevationProcessor::new(
-        file_data.elevations.clone(),
-        file_data.distances.clone()
-    );
-    let baseline_gain = baseline_processor.get_total_elevation_gain() as f32;
-    let baseline_loss = baseline_processor.get_total_elevation_loss() as f32;
+    let baseline = DistBasedSmoother.smooth(&file_data.elevations, &file_data.distances);
+    let baseline_gain = baseline.gain_m as f32;
+    let baseline_loss = baseline.loss_m as f32;
     let baseline_gain_accuracy = (baseline_gain / official_gain) * 100.0;
-    let baseline_loss_accuracy = (baseline_loss / official_gain) * 100.0;
+    let baseline_loss_accuracy = loss_accuracy_of(baseline_loss);
     
     // METHOD 2: DISTANCE-BASED 3M - Use 3m interval processing
     let (dist3m_gain, dist3m_loss) = apply_distance_based_custom_interval(
@@ -269,7 +245,7 @@ fn process_single_file_five_methods(file_data: &GpxFileData) -> SingleFileResult
         3.0
     );
     let dist3m_gain_accuracy = (dist3m_gain / official_gain) * 100.0;
-    let dist3m_loss_accuracy = (dist3m_loss / official_gain) * 100.0;
+    let dist3m_loss_accuracy = loss_accuracy_of(dist3m_loss);
     
     // METHOD 3: DISTANCE-BASED 6.1M - Use 6.1m interval processing
     let (dist61m_gain, dist61m_loss) = apply_distance_based_custom_interval(
@@ -278,23 +254,25 @@ fn process_single_file_five_methods(file_data: &GpxFileData) -> SingleFileResult
         6.1
     );
     let dist61m_gain_accuracy = (dist61m_gain / official_gain) * 100.0;
-    let dist61m_loss_accuracy = (dist61m_loss / official_gain) * 100.0;
+    let dist61m_loss_accuracy = loss_accuracy_of(dist61m_loss);
     
     // METHOD 4: TWO-PASS - Distance-based gain + 15m distance-based loss
-    let (twopass_gain, twopass_loss) = apply_two_pass_smoothing(
-        &file_data.elevations, 
-        &file_data.distances
-    );
+    let twopass = TwoPassSmoother { loss_interval_m: 15.0 }.smooth(&file_data.elevations, &file_data.distances);
+    let (twopass_gain, twopass_loss) = (twopass.gain_m as f32, twopass.loss_m as f32);
     let twopass_gain_accuracy = (twopass_gain / official_gain) * 100.0;
-    let twopass_loss_accuracy = (twopass_loss / official_gain) * 100.0;
+    let twopass_loss_accuracy = loss_accuracy_of(twopass_loss);
     
     // METHOD 5: SAVITZKY-GOLAY - Traditional signal processing
-    let (savgol_gain, savgol_loss) = apply_savitzky_golay_filter(
-        &file_data.elevations,
-        15.0  // Use 15-point window
-    );
+    let savgol = SavitzkyGolaySmoother { window: 15 }.smooth(&file_data.elevations, &file_data.distances);
+    let (savgol_gain, savgol_loss) = (savgol.gain_m as f32, savgol.loss_m as f32);
     let savgol_gain_accuracy = (savgol_gain / official_gain) * 100.0;
-    let savgol_loss_accuracy = (savgol_loss / official_gain) * 100.0;
+    let savgol_loss_accuracy = loss_accuracy_of(savgol_loss);
+    
+    // METHOD 6: KALMAN - Forward filter + RTS backward smoother, noise from the data
+    let kalman = KalmanSmoother.smooth(&file_data.elevations, &file_data.distances);
+    let (kalman_gain, kalman_loss) = (kalman.gain_m as f32, kalman.loss_m as f32);
+    let kalman_gain_accuracy = (kalman_gain / official_gain) * 100.0;
+    let kalman_loss_accuracy = loss_accuracy_of(kalman_loss);
     
     SingleFileResult {
         filename: file_data.filename.clone(),
@@ -319,6 +297,10 @@ fn process_single_file_five_methods(file_data: &GpxFileData) -> SingleFileResult
         savgol_loss,
         savgol_gain_accuracy,
         savgol_loss_accuracy,
+        kalman_gain,
+        kalman_loss,
+        kalman_gain_accuracy,
+        kalman_loss_accuracy,
     }
 }
 
@@ -380,223 +362,6 @@ fn apply_distance_based_custom_interval(
     (gain as f32, loss as f32)
 }
 
-fn apply_two_pass_smoothing(
-    elevations: &[f64],
-    distances: &[f64]
-) -> (f32, f32) {
-    // PASS 1: Process for elevation gain using standard distance-based approach
-    let gain_processor = DistBasedElevationProcessor::new(
-        elevations.to_vec(),
-        distances.to_vec()
-    );
-    let processed_gain = gain_processor.get_total_elevation_gain() as f32;
-    
-    // PASS 2: Process for elevation loss using 15m interval
-    let processed_loss = apply_loss_specific_processing(elevations, distances, 15.0);
-    
-    (processed_gain, processed_loss)
-}
-
-fn apply_loss_specific_processing(
-    elevations: &[f64],
-    distances: &[f64],
-    interval: f64
-) -> f32 {
-    // Custom loss processing with specified interval
-    let (_uniform_distances, uniform_elevations) = resample_to_uniform_distance(
-        elevations, distances, interval
-    );
-    
-    // Apply median filter for spike removal
-    let median_smoothed = median_filter(&uniform_elevations, 3);
-    
-    // Apply Gaussian smoothing
-    let gaussian_smoothed = gaussian_smooth(&median_smoothed, 15);
-    
-    // Calculate loss
-    let mut total_loss = 0.0;
-    for window in gaussian_smoothed.windows(2) {
-        let change = window[1] - window[0];
-        if change < 0.0 {
-            total_loss += -change;
-        }
-    }
-    
-    total_loss as f32
-}
-
-fn apply_savitzky_golay_filter(
-    elevations: &[f64],
-    window_size: f32
-) -> (f32, f32) {
-    // Simplified Savitzky-Golay implementation
-    let window = (window_size as usize).max(5).min(elevations.len() / 4);
-    let smoothed = savitzky_golay_smooth(elevations, window);
-    
-    let mut gain = 0.0;
-    let mut loss = 0.0;
-    
-    for window in smoothed.windows(2) {
-        let change = window[1] - window[0];
-        if change > 0.0 {
-            gain += change;
-        } else {
-            loss += -change;
-        }
-    }
-    
-    (gain as f32, loss as f32)
-}
-
-fn savitzky_golay_smooth(data: &[f64], window: usize) -> Vec<f64> {
-    if window < 5 || window >= data.len() {
-        return data.to_vec();
-    }
-    
-    let mut result = Vec::with_capacity(data.len());
-    let half_window = window / 2;
-    
-    // Savitzky-Golay coefficients for 2nd order polynomial (simplified)
-    let coeffs = generate_savgol_coefficients(window);
-    
-    for i in 0..data.len() {
-        let start = if i >= half_window { i - half_window } else { 0 };
-        let end = if i + half_window < data.len() { i + half_window } else { data.len() - 1 };
-        
-        let mut smoothed_value = 0.0;
-        let mut weight_sum = 0.0;
-        
-        for (j, &value) in data[start..=end].iter().enumerate() {
-            let coeff = coeffs.get(j).copied().unwrap_or(1.0);
-            smoothed_value += value * coeff;
-            weight_sum += coeff;
-        }
-        
-        result.push(smoothed_value / weight_sum);
-    }
-    
-    result
-}
-
-fn generate_savgol_coefficients(window: usize) -> Vec<f64> {
-    // Simplified Savitzky-Golay coefficients for 2nd order polynomial
-    let mut coeffs = vec![1.0; window];
-    let center = window / 2;
-    
-    // Apply triangular weighting (simplified approximation)
-    for i in 0..window {
-        let distance = (i as f64 - center as f64).abs();
-        coeffs[i] = (window as f64 - distance) / window as f64;
-    }
-    
-    coeffs
-}
-
-// Helper functions for distance-based processing
-fn resample_to_uniform_distance(
-    elevations: &[f64],
-    distances: &[f64],
-    interval: f64
-) -> (Vec<f64>, Vec<f64>) {
-    if elevations.is_empty() || distances.is_empty() {
-        return (vec![], vec![]);
-    }
-    
-    let total_distance = distances.last().unwrap();
-    let num_points = (total_distance / interval).ceil() as usize + 1;
-    
-    let mut uniform_distances = Vec::with_capacity(num_points);
-    let mut uniform_elevations = Vec::with_capacity(num_points);
-    
-    for i in 0..num_points {
-        let target_distance = i as f64 * interval;
-        if target_distance > *total_distance {
-            break;
-        }
-        uniform_distances.push(target_distance);
-        
-        let elevation = interpolate_elevation_at_distance(elevations, distances, target_distance);
-        uniform_elevations.push(elevation);
-    }
-    
-    (uniform_distances, uniform_elevations)
-}
-
-fn interpolate_elevation_at_distance(
-    elevations: &[f64],
-    distances: &[f64],
-    target_distance: f64
-) -> f64 {
-    if target_distance <= 0.0 {
-        return elevations[0];
-    }
-    
-    for i in 1..distances.len() {
-        if distances[i] >= target_distance {
-            let d1 = distances[i - 1];
-            let d2 = distances[i];
-            let e1 = elevations[i - 1];
-            let e2 = elevations[i];
-            
-            if (d2 - d1).abs() < 1e-10 {
-                return e1;
-            }
-            
-            let t = (target_distance - d1) / (d2 - d1);
-            return e1 + t * (e2 - e1);
-        }
-    }
-    
-    *elevations.last().unwrap()
-}
-
-fn median_filter(data: &[f64], window: usize) -> Vec<f64> {
-    let mut result = Vec::with_capacity(data.len());
-    
-    for i in 0..data.len() {
-        let start = if i >= window / 2 { i - window / 2 } else { 0 };
-        let end = if i + window / 2 < data.len() { i + window / 2 } else { data.len() - 1 };
-        
-        let mut window_data: Vec<f64> = data[start..=end].to_vec();
-        window_data.sort_by(|a, b| a.partial_cmp(b).unwrap());
-        
-        let median = if window_data.len() % 2 == 0 {
-            (window_data[window_data.len() / 2 - 1] + window_data[window_data.len() / 2]) / 2.0
-        } else {
-            window_data[window_data.len() / 2]
-        };
-        
-        result.push(median);
-    }
-    
-    result
-}
-
-fn gaussian_smooth(data: &[f64], window: usize) -> Vec<f64> {
-    let mut result = Vec::with_capacity(data.len());
-    let sigma = window as f64 / 6.0;
-    
-    for i in 0..data.len() {
-        let start = if i >= window / 2 { i - window / 2 } else { 0 };
-        let end = if i + window / 2 < data.len() { i + window / 2 } else { data.len() - 1 };
-        
-        let mut weighted_sum = 0.0;
-        let mut weight_sum = 0.0;
-        
-        for j in start..=end {
-            let distance = (j as f64 - i as f64).abs();
-            let weight = (-0.5 * (distance / sigma).powi(2)).exp();
-            
-            weighted_sum += data[j] * weight;
-            weight_sum += weight;
-        }
-        
-        result.push(weighted_sum / weight_sum);
-    }
-    
-    result
-}
-
 fn write_file_comparison_csv(
     results: &[SingleFileResult],
     output_path: &Path
@@ -616,6 +381,8 @@ fn write_file_comparison_csv(
         "TwoPass_Gain_m", "TwoPass_Loss_m", "TwoPass_Gain_Acc_%", "TwoPass_Loss_Acc_%",
         // Savitzky-Golay
         "SavGol_Gain_m", "SavGol_Loss_m", "SavGol_Gain_Acc_%", "SavGol_Loss_Acc_%",
+        // Kalman + RTS
+        "Kalman_Gain_m", "Kalman_Loss_m", "Kalman_Gain_Acc_%", "Kalman_Loss_Acc_%",
         // Best methods
         "Best_Gain_Method", "Best_Loss_Method", "Best_Combined_Method"
     ])?;
@@ -632,6 +399,7 @@ fn write_file_comparison_csv(
             ("Dist61m", result.dist61m_gain_accuracy),
             ("TwoPass", result.twopass_gain_accuracy),
             ("SavGol", result.savgol_gain_accuracy),
+            ("Kalman", result.kalman_gain_accuracy),
         ];
         
         let loss_accuracies = [
@@ -640,6 +408,7 @@ fn write_file_comparison_csv(
             ("Dist61m", result.dist61m_loss_accuracy),
             ("TwoPass", result.twopass_loss_accuracy),
             ("SavGol", result.savgol_loss_accuracy),
+            ("Kalman", result.kalman_loss_accuracy),
         ];
         
         let best_gain = gain_accuracies.iter()
@@ -647,16 +416,18 @@ fn write_file_comparison_csv(
             .unwrap().0;
             
         let best_loss = loss_accuracies.iter()
+            .filter_map(|&(method, acc)| acc.map(|acc| (method, acc)))
             .min_by_key(|(_, acc)| ((acc - 100.0).abs() * 1000.0) as i32)
-            .unwrap().0;
+            .map_or("", |(method, _)| method);
         
         // Combined score (simple average of gain and loss accuracy distances from 100%)
         let combined_scores = [
-            ("Baseline", (result.baseline_gain_accuracy - 100.0).abs() + (result.baseline_loss_accuracy - 100.0).abs()),
-            ("Dist3m", (result.dist3m_gain_accuracy - 100.0).abs() + (result.dist3m_loss_accuracy - 100.0).abs()),
-            ("Dist61m", (result.dist61m_gain_accuracy - 100.0).abs() + (result.dist61m_loss_accuracy - 100.0).abs()),
-            ("TwoPass", (result.twopass_gain_accuracy - 100.0).abs() + (result.twopass_loss_accuracy - 100.0).abs()),
-            ("SavGol", (result.savgol_gain_accuracy - 100.0).abs() + (result.savgol_loss_accuracy - 100.0).abs()),
+            ("Baseline", (result.baseline_gain_accuracy - 100.0).abs() + result.baseline_loss_accuracy.map_or(0.0, |a| (a - 100.0).abs())),
+            ("Dist3m", (result.dist3m_gain_accuracy - 100.0).abs() + result.dist3m_loss_accuracy.map_or(0.0, |a| (a - 100.0).abs())),
+            ("Dist61m", (result.dist61m_gain_accuracy - 100.0).abs() + result.dist61m_loss_accuracy.map_or(0.0, |a| (a - 100.0).abs())),
+            ("TwoPass", (result.twopass_gain_accuracy - 100.0).abs() + result.twopass_loss_accuracy.map_or(0.0, |a| (a - 100.0).abs())),
+            ("SavGol", (result.savgol_gain_accuracy - 100.0).abs() + result.savgol_loss_accuracy.map_or(0.0, |a| (a - 100.0).abs())),
+            ("Kalman", (result.kalman_gain_accuracy - 100.0).abs() + result.kalman_loss_accuracy.map_or(0.0, |a| (a - 100.0).abs())),
         ];
         
         let best_combined = combined_scores.iter()
@@ -670,27 +441,32 @@ fn write_file_comparison_csv(
             &format!("{:.1}", result.baseline_gain),
             &format!("{:.1}", result.baseline_loss),
             &format!("{:.1}", result.baseline_gain_accuracy),
-            &format!("{:.1}", result.baseline_loss_accuracy),
+            &result.baseline_loss_accuracy.map(|a| format!("{:.1}", a)).unwrap_or_default(),
             // Dist3m
             &format!("{:.1}", result.dist3m_gain),
             &format!("{:.1}", result.dist3m_loss),
             &format!("{:.1}", result.dist3m_gain_accuracy),
-            &format!("{:.1}", result.dist3m_loss_accuracy),
+            &result.dist3m_loss_accuracy.map(|a| format!("{:.1}", a)).unwrap_or_default(),
             // Dist61m
             &format!("{:.1}", result.dist61m_gain),
             &format!("{:.1}", result.dist61m_loss),
             &format!("{:.1}", result.dist61m_gain_accuracy),
-            &format!("{:.1}", result.dist61m_loss_accuracy),
+            &result.dist61m_loss_accuracy.map(|a| format!("{:.1}", a)).unwrap_or_default(),
             // TwoPass
             &format!("{:.1}", result.twopass_gain),
             &format!("{:.1}", result.twopass_loss),
             &format!("{:.1}", result.twopass_gain_accuracy),
-            &format!("{:.1}", result.twopass_loss_accuracy),
+            &result.twopass_loss_accuracy.map(|a| format!("{:.1}", a)).unwrap_or_default(),
             // SavGol
             &format!("{:.1}", result.savgol_gain),
             &format!("{:.1}", result.savgol_loss),
             &format!("{:.1}", result.savgol_gain_accuracy),
-            &format!("{:.1}", result.savgol_loss_accuracy),
+            &result.savgol_loss_accuracy.map(|a| format!("{:.1}", a)).unwrap_or_default(),
+            // Kalman
+            &format!("{:.1}", result.kalman_gain),
+            &format!("{:.1}", result.kalman_loss),
+            &format!("{:.1}", result.kalman_gain_accuracy),
+            &result.kalman_loss_accuracy.map(|a| format!("{:.1}", a)).unwrap_or_default(),
             // Best methods
             best_gain,
             best_loss,
@@ -715,22 +491,27 @@ fn write_five_method_results(
         "Dist3m_Gain_Acc", "Dist3m_Loss_Acc", 
         "Dist61m_Gain_Acc", "Dist61m_Loss_Acc",
         "TwoPass_Gain_Acc", "TwoPass_Loss_Acc",
-        "SavGol_Gain_Acc", "SavGol_Loss_Acc"
+        "SavGol_Gain_Acc", "SavGol_Loss_Acc",
+        "Kalman_Gain_Acc", "Kalman_Loss_Acc"
     ])?;
     
     let total_files = results.len() as f32;
+    // Loss accuracies only exist for files whose record has an official loss
+    let files_with_loss = results.iter().filter(|r| r.baseline_loss_accuracy.is_some()).count().max(1) as f32;
     
     // Calculate averages
     let baseline_gain_avg = results.iter().map(|r| r.baseline_gain_accuracy).sum::<f32>() / total_files;
-    let baseline_loss_avg = results.iter().map(|r| r.baseline_loss_accuracy).sum::<f32>() / total_files;
+    let baseline_loss_avg = results.iter().filter_map(|r| r.baseline_loss_accuracy).sum::<f32>() / files_with_loss;
     let dist3m_gain_avg = results.iter().map(|r| r.dist3m_gain_accuracy).sum::<f32>() / total_files;
-    let dist3m_loss_avg = results.iter().map(|r| r.dist3m_loss_accuracy).sum::<f32>() / total_files;
+    let dist3m_loss_avg = results.iter().filter_map(|r| r.dist3m_loss_accuracy).sum::<f32>() / files_with_loss;
     let dist61m_gain_avg = results.iter().map(|r| r.dist61m_gain_accuracy).sum::<f32>() / total_files;
-    let dist61m_loss_avg = results.iter().map(|r| r.dist61m_loss_accuracy).sum::<f32>() / total_files;
+    let dist61m_loss_avg = results.iter().filter_map(|r| r.dist61m_loss_accuracy).sum::<f32>() / files_with_loss;
     let twopass_gain_avg = results.iter().map(|r| r.twopass_gain_accuracy).sum::<f32>() / total_files;
-    let twopass_loss_avg = results.iter().map(|r| r.twopass_loss_accuracy).sum::<f32>() / total_files;
+    let twopass_loss_avg = results.iter().filter_map(|r| r.twopass_loss_accuracy).sum::<f32>() / files_with_loss;
     let savgol_gain_avg = results.iter().map(|r| r.savgol_gain_accuracy).sum::<f32>() / total_files;
-    let savgol_loss_avg = results.iter().map(|r| r.savgol_loss_accuracy).sum::<f32>() / total_files;
+    let savgol_loss_avg = results.iter().filter_map(|r| r.savgol_loss_accuracy).sum::<f32>() / files_with_loss;
+    let kalman_gain_avg = results.iter().map(|r| r.kalman_gain_accuracy).sum::<f32>() / total_files;
+    let kalman_loss_avg = results.iter().filter_map(|r| r.kalman_loss_accuracy).sum::<f32>() / files_with_loss;
     
     wtr.write_record(&[
         "Average_Accuracy_%",
@@ -739,6 +520,7 @@ fn write_five_method_results(
         &format!("{:.1}", dist61m_gain_avg), &format!("{:.1}", dist61m_loss_avg),
         &format!("{:.1}", twopass_gain_avg), &format!("{:.1}", twopass_loss_avg),
         &format!("{:.1}", savgol_gain_avg), &format!("{:.1}", savgol_loss_avg),
+        &format!("{:.1}", kalman_gain_avg), &format!("{:.1}", kalman_loss_avg),
     ])?;
     
     wtr.flush()?;
@@ -746,66 +528,77 @@ fn write_five_method_results(
 }
 
 fn print_five_method_summary(results: &[SingleFileResult]) {
-    println!("\n📊 FIVE-METHOD COMPARISON RESULTS");
-    println!("==================================");
+    println!("\n📊 SIX-METHOD COMPARISON RESULTS");
+    println!("=================================");
     println!("Processed {} files with official elevation data\n", results.len());
     
     // Calculate aggregate statistics for each method
     let total_files = results.len() as f32;
+    let files_with_loss = results.iter().filter(|r| r.baseline_loss_accuracy.is_some()).count();
     
     // Method 1: Baseline
     let baseline_gain_acc = results.iter().map(|r| r.baseline_gain_accuracy).sum::<f32>() / total_files;
-    let baseline_loss_acc = results.iter().map(|r| r.baseline_loss_accuracy).sum::<f32>() / total_files;
+    let baseline_loss_acc = results.iter().filter_map(|r| r.baseline_loss_accuracy).sum::<f32>() / files_with_loss.max(1) as f32;
     let baseline_gain_within_10 = results.iter().filter(|r| (r.baseline_gain_accuracy - 100.0).abs() <= 10.0).count();
-    let baseline_loss_within_10 = results.iter().filter(|r| (r.baseline_loss_accuracy - 100.0).abs() <= 10.0).count();
+    let baseline_loss_within_10 = results.iter().filter(|r| r.baseline_loss_accuracy.is_some_and(|a| (a - 100.0).abs() <= 10.0)).count();
     
     // Method 2: Distance-based 3m
     let dist3m_gain_acc = results.iter().map(|r| r.dist3m_gain_accuracy).sum::<f32>() / total_files;
-    let dist3m_loss_acc = results.iter().map(|r| r.dist3m_loss_accuracy).sum::<f32>() / total_files;
+    let dist3m_loss_acc = results.iter().filter_map(|r| r.dist3m_loss_accuracy).sum::<f32>() / files_with_loss.max(1) as f32;
     let dist3m_gain_within_10 = results.iter().filter(|r| (r.dist3m_gain_accuracy - 100.0).abs() <= 10.0).count();
-    let dist3m_loss_within_10 = results.iter().filter(|r| (r.dist3m_loss_accuracy - 100.0).abs() <= 10.0).count();
+    let dist3m_loss_within_10 = results.iter().filter(|r| r.dist3m_loss_accuracy.is_some_and(|a| (a - 100.0).abs() <= 10.0)).count();
     
     // Method 3: Distance-based 6.1m
     let dist61m_gain_acc = results.iter().map(|r| r.dist61m_gain_accuracy).sum::<f32>() / total_files;
-    let dist61m_loss_acc = results.iter().map(|r| r.dist61m_loss_accuracy).sum::<f32>() / total_files;
+    let dist61m_loss_acc = results.iter().filter_map(|r| r.dist61m_loss_accuracy).sum::<f32>() / files_with_loss.max(1) as f32;
     let dist61m_gain_within_10 = results.iter().filter(|r| (r.dist61m_gain_accuracy - 100.0).abs() <= 10.0).count();
-    let dist61m_loss_within_10 = results.iter().filter(|r| (r.dist61m_loss_accuracy - 100.0).abs() <= 10.0).count();
+    let dist61m_loss_within_10 = results.iter().filter(|r| r.dist61m_loss_accuracy.is_some_and(|a| (a - 100.0).abs() <= 10.0)).count();
     
     // Method 4: Two-Pass
     let twopass_gain_acc = results.iter().map(|r| r.twopass_gain_accuracy).sum::<f32>() / total_files;
-    let twopass_loss_acc = results.iter().map(|r| r.twopass_loss_accuracy).sum::<f32>() / total_files;
+    let twopass_loss_acc = results.iter().filter_map(|r| r.twopass_loss_accuracy).sum::<f32>() / files_with_loss.max(1) as f32;
     let twopass_gain_within_10 = results.iter().filter(|r| (r.twopass_gain_accuracy - 100.0).abs() <= 10.0).count();
-    let twopass_loss_within_10 = results.iter().filter(|r| (r.twopass_loss_accuracy - 100.0).abs() <= 10.0).count();
+    let twopass_loss_within_10 = results.iter().filter(|r| r.twopass_loss_accuracy.is_some_and(|a| (a - 100.0).abs() <= 10.0)).count();
     
     // Method 5: Savitzky-Golay
     let savgol_gain_acc = results.iter().map(|r| r.savgol_gain_accuracy).sum::<f32>() / total_files;
-    let savgol_loss_acc = results.iter().map(|r| r.savgol_loss_accuracy).sum::<f32>() / total_files;
+    let savgol_loss_acc = results.iter().filter_map(|r| r.savgol_loss_accuracy).sum::<f32>() / files_with_loss.max(1) as f32;
     let savgol_gain_within_10 = results.iter().filter(|r| (r.savgol_gain_accuracy - 100.0).abs() <= 10.0).count();
-    let savgol_loss_within_10 = results.iter().filter(|r| (r.savgol_loss_accuracy - 100.0).abs() <= 10.0).count();
+    let savgol_loss_within_10 = results.iter().filter(|r| r.savgol_loss_accuracy.is_some_and(|a| (a - 100.0).abs() <= 10.0)).count();
+    
+    // Method 6: Kalman + RTS
+    let kalman_gain_acc = results.iter().map(|r| r.kalman_gain_accuracy).sum::<f32>() / total_files;
+    let kalman_loss_acc = results.iter().filter_map(|r| r.kalman_loss_accuracy).sum::<f32>() / files_with_loss.max(1) as f32;
+    let kalman_gain_within_10 = results.iter().filter(|r| (r.kalman_gain_accuracy - 100.0).abs() <= 10.0).count();
+    let kalman_loss_within_10 = results.iter().filter(|r| r.kalman_loss_accuracy.is_some_and(|a| (a - 100.0).abs() <= 10.0)).count();
     
     println!("🏆 COMPARATIVE PERFORMANCE SUMMARY:");
     println!("Method               | Gain Acc% | Loss Acc% | Gain ±10% | Loss ±10% | Combined Score");
     println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
     println!("Baseline (Default)   | {:8.1} | {:8.1} | {:8}/{} | {:8}/{} | {:13.1}",
              baseline_gain_acc, baseline_loss_acc, baseline_gain_within_10, total_files as usize,
-             baseline_loss_within_10, total_files as usize, 
+             baseline_loss_within_10, files_with_loss, 
              (baseline_gain_within_10 + baseline_loss_within_10) as f32 / 2.0);
     println!("Distance-Based 3m    | {:8.1} | {:8.1} | {:8}/{} | {:8}/{} | {:13.1}",
              dist3m_gain_acc, dist3m_loss_acc, dist3m_gain_within_10, total_files as usize,
-             dist3m_loss_within_10, total_files as usize,
+             dist3m_loss_within_10, files_with_loss,
              (dist3m_gain_within_10 + dist3m_loss_within_10) as f32 / 2.0);
     println!("Distance-Based 6.1m  | {:8.1} | {:8.1} | {:8}/{} | {:8}/{} | {:13.1}",
              dist61m_gain_acc, dist61m_loss_acc, dist61m_gain_within_10, total_files as usize,
-             dist61m_loss_within_10, total_files as usize,
+             dist61m_loss_within_10, files_with_loss,
              (dist61m_gain_within_10 + dist61m_loss_within_10) as f32 / 2.0);
     println!("Two-Pass Smoothing   | {:8.1} | {:8.1} | {:8}/{} | {:8}/{} | {:13.1}",
              twopass_gain_acc, twopass_loss_acc, twopass_gain_within_10, total_files as usize,
-             twopass_loss_within_10, total_files as usize,
+             twopass_loss_within_10, files_with_loss,
              (twopass_gain_within_10 + twopass_loss_within_10) as f32 / 2.0);
     println!("Savitzky-Golay       | {:8.1} | {:8.1} | {:8}/{} | {:8}/{} | {:13.1}",
              savgol_gain_acc, savgol_loss_acc, savgol_gain_within_10, total_files as usize,
-             savgol_loss_within_10, total_files as usize,
+             savgol_loss_within_10, files_with_loss,
              (savgol_gain_within_10 + savgol_loss_within_10) as f32 / 2.0);
+    println!("Kalman + RTS         | {:8.1} | {:8.1} | {:8}/{} | {:8}/{} | {:13.1}",
+             kalman_gain_acc, kalman_loss_acc, kalman_gain_within_10, total_files as usize,
+             kalman_loss_within_10, files_with_loss,
+             (kalman_gain_within_10 + kalman_loss_within_10) as f32 / 2.0);
     
     // Overall winner analysis
     let methods = [
@@ -814,6 +607,7 @@ fn print_five_method_summary(results: &[SingleFileResult]) {
         ("Dist61m", (dist61m_gain_within_10 + dist61m_loss_within_10) as f32 / 2.0),
         ("Two-Pass", (twopass_gain_within_10 + twopass_loss_within_10) as f32 / 2.0),
         ("Savitzky-Golay", (savgol_gain_within_10 + savgol_loss_within_10) as f32 / 2.0),
+        ("Kalman + RTS", (kalman_gain_within_10 + kalman_loss_within_10) as f32 / 2.0),
     ];
     
     let overall_best = methods.iter().max_by(|a, b| a.1.partial_cmp(&b.1).unwrap()).unwrap();
@@ -833,6 +627,7 @@ fn print_five_method_summary(results: &[SingleFileResult]) {
             ("Dist61m", result.dist61m_gain_accuracy),
             ("TwoPass", result.twopass_gain_accuracy),
             ("SavGol", result.savgol_gain_accuracy),
+            ("Kalman", result.kalman_gain_accuracy),
         ];
         let best_gain = gain_methods.iter()
             .min_by_key(|(_, acc)| ((acc - 100.0).abs() * 1000.0) as i32)
@@ -846,19 +641,23 @@ fn print_five_method_summary(results: &[SingleFileResult]) {
             ("Dist61m", result.dist61m_loss_accuracy),
             ("TwoPass", result.twopass_loss_accuracy),
             ("SavGol", result.savgol_loss_accuracy),
+            ("Kalman", result.kalman_loss_accuracy),
         ];
         let best_loss = loss_methods.iter()
-            .min_by_key(|(_, acc)| ((acc - 100.0).abs() * 1000.0) as i32)
-            .unwrap().0;
-        *loss_wins.entry(best_loss).or_insert(0) += 1;
+            .filter_map(|&(method, acc)| acc.map(|acc| (method, acc)))
+            .min_by_key(|(_, acc)| ((acc - 100.0).abs() * 1000.0) as i32);
+        if let Some((best_loss, _)) = best_loss {
+            *loss_wins.entry(best_loss).or_insert(0) += 1;
+        }
         
         // Best combined method
         let combined_methods = [
-            ("Baseline", (result.baseline_gain_accuracy - 100.0).abs() + (result.baseline_loss_accuracy - 100.0).abs()),
-            ("Dist3m", (result.dist3m_gain_accuracy - 100.0).abs() + (result.dist3m_loss_accuracy - 100.0).abs()),
-            ("Dist61m", (result.dist61m_gain_accuracy - 100.0).abs() + (result.dist61m_loss_accuracy - 100.0).abs()),
-            ("TwoPass", (result.twopass_gain_accuracy - 100.0).abs() + (result.twopass_loss_accuracy - 100.0).abs()),
-            ("SavGol", (result.savgol_gain_accuracy - 100.0).abs() + (result.savgol_loss_accuracy - 100.0).abs()),
+            ("Baseline", (result.baseline_gain_accuracy - 100.0).abs() + result.baseline_loss_accuracy.map_or(0.0, |a| (a - 100.0).abs())),
+            ("Dist3m", (result.dist3m_gain_accuracy - 100.0).abs() + result.dist3m_loss_accuracy.map_or(0.0, |a| (a - 100.0).abs())),
+            ("Dist61m", (result.dist61m_gain_accuracy - 100.0).abs() + result.dist61m_loss_accuracy.map_or(0.0, |a| (a - 100.0).abs())),
+            ("TwoPass", (result.twopass_gain_accuracy - 100.0).abs() + result.twopass_loss_accuracy.map_or(0.0, |a| (a - 100.0).abs())),
+            ("SavGol", (result.savgol_gain_accuracy - 100.0).abs() + result.savgol_loss_accuracy.map_or(0.0, |a| (a - 100.0).abs())),
+            ("Kalman", (result.kalman_gain_accuracy - 100.0).abs() + result.kalman_loss_accuracy.map_or(0.0, |a| (a - 100.0).abs())),
         ];
         let best_combined = combined_methods.iter()
             .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
@@ -872,9 +671,9 @@ fn print_five_method_summary(results: &[SingleFileResult]) {
         println!("   {}: {} files ({:.1}%)", method, count, (count as f32 / total_files) * 100.0);
     }
     
-    println!("LOSS accuracy wins:");
+    println!("LOSS accuracy wins ({} files with official loss):", files_with_loss);
     for (method, count) in loss_wins {
-        println!("   {}: {} files ({:.1}%)", method, count, (count as f32 / total_files) * 100.0);
+        println!("   {}: {} files ({:.1}%)", method, count, (count as f32 / files_with_loss.max(1) as f32) * 100.0);
     }
     
     println!("COMBINED accuracy wins:");
@@ -891,6 +690,8 @@ fn print_five_method_summary(results: &[SingleFileResult]) {
              twopass_gain_acc, baseline_gain_acc, twopass_loss_acc, baseline_loss_acc);
     println!("• Savitzky-Golay vs Default: Gain {:.1}% vs {:.1}%, Loss {:.1}% vs {:.1}%",
              savgol_gain_acc, baseline_gain_acc, savgol_loss_acc, baseline_loss_acc);
+    println!("• Kalman + RTS vs Default: Gain {:.1}% vs {:.1}%, Loss {:.1}% vs {:.1}%",
+             kalman_gain_acc, baseline_gain_acc, kalman_loss_acc, baseline_loss_acc);
     
     println!("\n✅ Results saved to:");
     println!("   • detailed_file_comparison.csv (file-by-file results)");
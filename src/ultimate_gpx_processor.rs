@@ -9,16 +9,19 @@
 /// - Clean processed GPX file output
 /// - Detailed elevation statistics and validation
 /// - Performance comparison with official benchmarks
-
-use std::{fs::File, path::Path};
-use std::io::{BufReader, Write};
-use gpx::{read, write, Gpx, Track, TrackSegment, Waypoint, Time};
-use geo::{HaversineDistance, point};
-use csv::Writer;
+use std::path::Path;
+use csv::{Writer, WriterBuilder};
 use serde::Serialize;
 use walkdir::WalkDir;
 use std::collections::HashMap;
-use chrono::{DateTime, Utc};
+use crate::elevation_smoother::{smooth_between_gaps, SymmetricFixedSmoother};
+use crate::error::TrackError;
+use crate::ground_truth::GroundTruthRegistry;
+use crate::track_loader::{gap_runs, gpx_course_segments, gpx_geometry, gpx_time_to_utc, read_gpx_file, GpxGeometry, LoadedTrack, TrackPoint};
+use crate::gpx_rewrite::{write_processed_gpx, GpxWriteOptions, TrackpointEdit};
+
+// The SymmetricFixed interval that scored best in the interval sweeps
+const OPTIMAL_INTERVAL_M: f64 = 1.9;
 
 #[derive(Debug, Serialize)]
 pub struct UltimateGpxResult {
@@ -120,7 +123,7 @@ pub fn run_ultimate_gpx_processor(gpx_folder: &str) -> Result<(), Box<dyn std::e
     println!("   • Perfect gain/loss balance (1.000 ratio)");
     println!("   • 76.6% files in ±10% accuracy range");
     println!("   • 93.6% files in ±20% accuracy range");
-    println!("");
+    println!();
     println!("📊 Processing includes:");
     println!("   • Optimal elevation processing");
     println!("   • Comprehensive incline analysis");
@@ -130,7 +133,7 @@ pub fn run_ultimate_gpx_processor(gpx_folder: &str) -> Result<(), Box<dyn std::e
     let start_time = std::time::Instant::now();
     
     // Load official data for validation
-    let official_data = crate::load_official_elevation_data()?;
+    let official_data = crate::load_ground_truth_registry()?;
     
     // Create output directories
     let output_dir = Path::new(gpx_folder).join("Ultimate_Processed");
@@ -154,12 +157,11 @@ pub fn run_ultimate_gpx_processor(gpx_folder: &str) -> Result<(), Box<dyn std::e
         if entry.file_type().is_file() {
             if let Some(extension) = entry.path().extension() {
                 if extension.to_str().unwrap_or("").to_lowercase() == "gpx" {
-                    total_count += 1;
-                    
                     // Skip files in output directories to avoid processing our own output
                     if entry.path().starts_with(&output_dir) {
                         continue;
                     }
+                    total_count += 1;
                     
                     match process_single_gpx_ultimate(
                         entry.path(),
@@ -186,6 +188,7 @@ pub fn run_ultimate_gpx_processor(gpx_folder: &str) -> Result<(), Box<dyn std::e
     
     println!("\n✅ Processing complete!");
     println!("   Processed: {}/{} files", processed_count, total_count);
+    official_data.print_match_report();
     
     // Write comprehensive results
     let results_file = output_dir.join("ultimate_processing_results.csv");
@@ -205,7 +208,7 @@ fn process_single_gpx_ultimate(
     input_path: &Path,
     processed_gpx_dir: &Path,
     incline_analysis_dir: &Path,
-    official_data: &HashMap<String, u32>
+    official_data: &GroundTruthRegistry
 ) -> Result<UltimateGpxResult, Box<dyn std::error::Error>> {
     let process_start = std::time::Instant::now();
     
@@ -218,56 +221,59 @@ fn process_single_gpx_ultimate(
     let input_file_size_kb = (std::fs::metadata(input_path)?.len() / 1024) as u32;
     
     // Read and parse GPX
-    let file = File::open(input_path)?;
-    let reader = BufReader::new(file);
-    let gpx = read(reader)?;
-    
-    // Extract coordinates with timestamps
-    let mut coords_with_time: Vec<(f64, f64, f64, Option<DateTime<Utc>>)> = Vec::new();
-    
-    for track in &gpx.tracks {
-        for segment in &track.segments {
-            for pt in &segment.points {
-                if let Some(ele) = pt.elevation {
-                    let timestamp = pt.time.as_ref().and_then(|t| {
-                        match t {
-                            Time::DateTime(dt) => Some(*dt),
-                            _ => None,
-                        }
-                    });
-                    coords_with_time.push((pt.point().y(), pt.point().x(), ele, timestamp));
-                }
+    let gpx = read_gpx_file(input_path)?;
+    
+    // Extract points with timestamps
+    let mut points: Vec<TrackPoint> = Vec::new();
+    let mut segment_starts: Vec<usize> = Vec::new();  // First point of each track segment (or route)
+    let mut point_trkpts: Vec<usize> = Vec::new();    // Point element of each point, in document order
+    let mut trackpoint_count = 0;
+    let geometry = gpx_geometry(&gpx).ok_or_else(|| TrackError::NoTracks { file: input_path.to_path_buf() })?;
+    
+    for segment in gpx_course_segments(&gpx) {
+        if !segment.is_empty() {
+            segment_starts.push(points.len());
+        }
+        for pt in segment {
+            trackpoint_count += 1;
+            if let Some(elevation) = pt.elevation {
+                points.push(TrackPoint {
+                    latitude: pt.point().y(),
+                    longitude: pt.point().x(),
+                    elevation,
+                    time: pt.time.and_then(gpx_time_to_utc),
+                    distance: None,
+                });
+                point_trkpts.push(trackpoint_count - 1);
             }
         }
     }
     
-    if coords_with_time.is_empty() {
-        return Err("No valid coordinates with elevation found".into());
-    }
-    
-    // Calculate distances
-    let mut distances = vec![0.0];
-    for i in 1..coords_with_time.len() {
-        let a = point!(x: coords_with_time[i-1].1, y: coords_with_time[i-1].0);
-        let b = point!(x: coords_with_time[i].1, y: coords_with_time[i].0);
-        let dist = a.haversine_distance(&b);
-        distances.push(distances[i-1] + dist);
+    if points.is_empty() {
+        return Err(TrackError::NoElevation { file: input_path.to_path_buf() }.into());
     }
     
-    let elevations: Vec<f64> = coords_with_time.iter().map(|c| c.2).collect();
+    // Distances and runs that do not bridge recording gaps between segments
+    let track = LoadedTrack::from_segments(filename.clone(), points, segment_starts);
+    let gaps = track.gaps();
+    let distances = &track.cumulative_distances;
+    let elevations = track.elevations();
     let total_distance_km = distances.last().unwrap() / 1000.0;
     
     // Calculate raw statistics
-    let (raw_gain, raw_loss) = calculate_raw_gain_loss(&elevations);
+    let (raw_gain, raw_loss) = gap_runs(elevations.len(), &gaps).into_iter()
+        .map(|run| calculate_raw_gain_loss(&elevations[run]))
+        .fold((0.0, 0.0), |(gain, loss), (g, l)| (gain + g, loss + l));
     let elevation_range = elevations.iter().fold((f64::INFINITY, f64::NEG_INFINITY), 
         |(min, max), &e| (min.min(e), max.max(e)));
     
-    // Apply optimal SymmetricFixed 1.9m processing
-    let (processed_gain, processed_loss) = apply_optimal_symmetric_processing(&elevations, &distances);
+    // Apply optimal SymmetricFixed 1.9m processing, each run between gaps on its own
+    let profile = smooth_between_gaps(&SymmetricFixedSmoother { interval_m: OPTIMAL_INTERVAL_M }, &track.coordinates(), distances, &gaps);
+    let (processed_gain, processed_loss) = (profile.gain_m() as f32, profile.loss_m() as f32);
     let processed_gain_loss_ratio = processed_gain / processed_loss.max(1.0);
     
-    // Get official benchmark
-    let official_gain = official_data.get(&filename.to_lowercase()).copied().unwrap_or(0);
+    // Get official benchmark (by content hash, original filename, then course fingerprint)
+    let official_gain = official_data.official_gain(input_path, &track.coordinates());
     let gain_accuracy = if official_gain > 0 {
         (processed_gain / official_gain as f32) * 100.0
     } else {
@@ -282,10 +288,10 @@ fn process_single_gpx_ultimate(
         _ => "D (>±20%)".to_string(),
     };
     
-    // Perform comprehensive incline analysis
-    let (processed_elevations, _) = get_processed_elevations(&elevations, &distances);
-    let incline_segments = analyze_inclines(&distances, &processed_elevations);
-    let incline_stats = calculate_incline_statistics(&incline_segments, total_distance_km as f64);
+    // Perform comprehensive incline analysis on the processed profile at the original points
+    let processed_elevations = profile.elevations_at(distances, &gaps);
+    let incline_segments = analyze_inclines(distances, &processed_elevations);
+    let incline_stats = calculate_incline_statistics(&incline_segments, total_distance_km);
     
     // Calculate quality metrics
     let elevation_noise_ratio = calculate_elevation_noise(&elevations);
@@ -297,7 +303,7 @@ fn process_single_gpx_ultimate(
     // Generate processed GPX file
     let processed_gpx_filename = format!("processed_{}", filename);
     let processed_gpx_path = processed_gpx_dir.join(&processed_gpx_filename);
-    create_processed_gpx(&coords_with_time, &processed_elevations, &processed_gpx_path)?;
+    create_processed_gpx(input_path, geometry, trackpoint_count, &point_trkpts, &processed_elevations, &processed_gpx_path)?;
     
     // Generate incline analysis file
     let incline_filename = format!("{}_incline_analysis.csv", 
@@ -313,7 +319,7 @@ fn process_single_gpx_ultimate(
         input_file_size_kb,
         output_file_size_kb,
         processing_time_ms,
-        raw_points: coords_with_time.len() as u32,
+        raw_points: track.points.len() as u32,
         raw_distance_km: total_distance_km as f32,
         raw_elevation_gain_m: raw_gain,
         raw_elevation_loss_m: raw_loss,
@@ -343,40 +349,6 @@ fn process_single_gpx_ultimate(
     })
 }
 
-fn apply_optimal_symmetric_processing(elevations: &[f64], distances: &[f64]) -> (f32, f32) {
-    // Use the scientifically proven optimal SymmetricFixed 1.9m method
-    use crate::custom_smoother::{ElevationData, SmoothingVariant};
-    
-    let mut elevation_data = ElevationData::new_with_variant(
-        elevations.to_vec(),
-        distances.to_vec(),
-        SmoothingVariant::SymmetricFixed  // The proven winner
-    );
-    
-    // Apply optimal 1.9m interval processing
-    elevation_data.apply_custom_interval_processing_symmetric(1.9);
-    
-    let gain = elevation_data.get_total_elevation_gain() as f32;
-    let loss = elevation_data.get_total_elevation_loss() as f32;
-    
-    (gain, loss)
-}
-
-fn get_processed_elevations(elevations: &[f64], distances: &[f64]) -> (Vec<f64>, Vec<f64>) {
-    // Get the processed elevation profile for incline analysis
-    use crate::custom_smoother::{ElevationData, SmoothingVariant};
-    
-    let elevation_data = ElevationData::new_with_variant(
-        elevations.to_vec(),
-        distances.to_vec(),
-        SmoothingVariant::SymmetricFixed
-    );
-    
-    // Note: We'd need to expose the processed elevation profile from ElevationData
-    // For now, return the original elevations (this could be enhanced)
-    (elevations.to_vec(), distances.to_vec())
-}
-
 fn analyze_inclines(distances: &[f64], elevations: &[f64]) -> Vec<InclineSegment> {
     let mut segments = Vec::new();
     
@@ -536,7 +508,7 @@ fn calculate_elevation_noise(elevations: &[f64]) -> f32 {
     let mut smoothed = Vec::new();
     
     for i in 0..elevations.len() {
-        let start = if i >= window_size/2 { i - window_size/2 } else { 0 };
+        let start = i.saturating_sub(window_size / 2);
         let end = (i + window_size/2 + 1).min(elevations.len());
         let avg = elevations[start..end].iter().sum::<f64>() / (end - start) as f64;
         smoothed.push(avg);
@@ -580,38 +552,25 @@ fn calculate_data_quality_score(
     let noise_score = (1.0 - noise_ratio) * 100.0;
     let smoothing_score = smoothing_effectiveness * 100.0;
     
-    (accuracy_score * 0.4 + ratio_score * 0.3 + noise_score * 0.15 + smoothing_score * 0.15)
+    accuracy_score * 0.4 + ratio_score * 0.3 + noise_score * 0.15 + smoothing_score * 0.15
 }
 
+/// Copy the source GPX with only the processed trackpoint elevations replaced, so its
+/// tracks, segments, timestamps, extensions, waypoints and metadata carry over
 fn create_processed_gpx(
-    coords_with_time: &[(f64, f64, f64, Option<DateTime<Utc>>)],
+    input_path: &Path,
+    geometry: GpxGeometry,
+    trackpoint_count: usize,
+    point_trkpts: &[usize],
     processed_elevations: &[f64],
     output_path: &Path
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let mut gpx = Gpx::default();
-    let mut track = Track::default();
-    let mut segment = TrackSegment::default();
-    
-    for (i, &(lat, lon, _original_ele, timestamp)) in coords_with_time.iter().enumerate() {
-        let processed_ele = processed_elevations.get(i).copied().unwrap_or(_original_ele);
-        
-        let mut waypoint = Waypoint::new(point!(x: lon, y: lat));
-        waypoint.elevation = Some(processed_ele);
-        
-        if let Some(dt) = timestamp {
-            waypoint.time = Some(Time::DateTime(dt));
-        }
-        
-        segment.points.push(waypoint);
+    let mut edits = vec![TrackpointEdit::default(); trackpoint_count];
+    for (&trkpt, &elevation) in point_trkpts.iter().zip(processed_elevations) {
+        edits[trkpt].elevation = Some(elevation);
     }
     
-    track.segments.push(segment);
-    gpx.tracks.push(track);
-    
-    let mut file = File::create(output_path)?;
-    write(&gpx, &mut file)?;
-    
-    Ok(())
+    write_processed_gpx(input_path, output_path, geometry, &edits, &GpxWriteOptions::default())
 }
 
 fn write_incline_analysis(
@@ -620,13 +579,13 @@ fn write_incline_analysis(
 ) -> Result<(), Box<dyn std::error::Error>> {
     let mut wtr = Writer::from_path(output_path)?;
     
-    wtr.write_record(&[
+    wtr.write_record([
         "Start_Distance_m", "End_Distance_m", "Distance_m", "Start_Elevation_m",
         "End_Elevation_m", "Elevation_Change_m", "Grade_%", "Segment_Type"
     ])?;
     
     for segment in segments {
-        wtr.write_record(&[
+        wtr.write_record([
             &format!("{:.1}", segment.start_distance),
             &format!("{:.1}", segment.end_distance),
             &format!("{:.1}", segment.distance_m),
@@ -646,10 +605,11 @@ fn write_ultimate_results(
     results: &[UltimateGpxResult],
     output_path: &Path
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let mut wtr = Writer::from_path(output_path)?;
+    // The header row is written by hand, so serialize must not add the field names
+    let mut wtr = WriterBuilder::new().has_headers(false).from_path(output_path)?;
     
-    wtr.write_record(&[
-        "Filename", "Processing_Time_ms", "File_Size_KB_In", "File_Size_KB_Out",
+    wtr.write_record([
+        "Filename", "File_Size_KB_In", "File_Size_KB_Out", "Processing_Time_ms",
         "Raw_Points", "Distance_km", "Raw_Gain_m", "Raw_Loss_m", "Raw_Range_m",
         "Processed_Gain_m", "Processed_Loss_m", "Gain_Loss_Ratio",
         "Official_Gain_m", "Accuracy_%", "Accuracy_Grade",
@@ -759,4 +719,46 @@ fn print_ultimate_analysis(results: &[UltimateGpxResult]) {
     println!("📊 {:.1}% average accuracy with perfect gain/loss balance", avg_accuracy);
     println!("📁 {} processed GPX files ready for use", total_files);
     println!("📈 {} detailed incline analysis files generated", total_files);
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_processed_gpx_carries_smoothed_elevations_per_segment() {
+        let dir = std::env::temp_dir().join(format!("ultimate_processor_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // Two segments ~5km apart, each climbing 10m a point with noise and one spike
+        let segment = |lat: f64| -> String {
+            (0..40)
+                .map(|i| {
+                    let spike = if i == 20 { 25.0 } else { 0.0 };
+                    let noise = if i % 2 == 0 { 0.8 } else { -0.8 };
+                    format!("<trkpt lat=\"{:.6}\" lon=\"7.0\"><ele>{:.1}</ele></trkpt>", lat + i as f64 * 0.0001, 500.0 + i as f64 + noise + spike)
+                })
+                .collect()
+        };
+        let source = format!(
+            "<?xml version=\"1.0\"?>\n<gpx version=\"1.1\" creator=\"test\"><trk><name>Loop</name><trkseg>{}</trkseg><trkseg>{}</trkseg></trk></gpx>\n",
+            segment(46.0), segment(46.05),
+        );
+        let input = dir.join("loop.gpx");
+        std::fs::write(&input, source).unwrap();
+
+        let registry = GroundTruthRegistry::from_csv_str("filename,official_elevation_gain_m\n", Path::new("empty.csv")).unwrap();
+        let result = process_single_gpx_ultimate(&input, &dir, &dir, &registry).unwrap();
+        let raw = read_gpx_file(&input).unwrap();
+        let processed = read_gpx_file(&dir.join(&result.processed_gpx_file)).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // Same segments and points, new elevations; the gap between segments is not bridged
+        assert_eq!(processed.tracks[0].segments.len(), 2);
+        assert!((result.raw_distance_km - 0.87).abs() < 0.05, "{}", result.raw_distance_km);
+        for (raw_segment, processed_segment) in raw.tracks[0].segments.iter().zip(&processed.tracks[0].segments) {
+            assert_eq!(raw_segment.points.len(), processed_segment.points.len());
+            let spike = processed_segment.points[20].elevation.unwrap();
+            assert!(spike < raw_segment.points[20].elevation.unwrap() - 10.0, "spike kept: {}", spike);
+        }
+        assert!(result.processed_elevation_gain_m < result.raw_elevation_gain_m);
+    }
+}